@@ -0,0 +1,110 @@
+use std::{fmt, ops::RangeInclusive};
+
+use thiserror::Error;
+
+use crate::VarInt;
+
+/// An application protocol error code, checked against the codes the protocol reserves
+///
+/// QUIC leaves the meaning of the error codes used when closing connections or resetting and
+/// stopping streams up to the application protocol, which may set some of them aside. For example,
+/// HTTP/3 reserves codes of the form `0x1f * N + 0x21` for greasing. Constructing codes through
+/// this type prevents such values from being used by accident.
+///
+/// Converts into the [`VarInt`] expected by APIs such as `Connection::close`.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct AppErrorCode(VarInt);
+
+impl AppErrorCode {
+    /// Construct a code, failing if it lies within any of the `reserved` ranges
+    pub fn new(code: u64, reserved: &[RangeInclusive<u64>]) -> Result<Self, InvalidAppErrorCode> {
+        if reserved.iter().any(|range| range.contains(&code)) {
+            return Err(InvalidAppErrorCode::Reserved(code));
+        }
+        Self::unreserved(code)
+    }
+
+    /// Construct a code, failing if HTTP/3 reserves it for greasing
+    pub fn http3(code: u64) -> Result<Self, InvalidAppErrorCode> {
+        if code >= 0x21 && (code - 0x21) % 0x1f == 0 {
+            return Err(InvalidAppErrorCode::Reserved(code));
+        }
+        Self::unreserved(code)
+    }
+
+    fn unreserved(code: u64) -> Result<Self, InvalidAppErrorCode> {
+        VarInt::from_u64(code)
+            .map(Self)
+            .map_err(|_| InvalidAppErrorCode::TooLarge(code))
+    }
+
+    /// Extract the integer value
+    pub fn into_inner(self) -> u64 {
+        self.0.into_inner()
+    }
+}
+
+impl From<AppErrorCode> for VarInt {
+    fn from(x: AppErrorCode) -> Self {
+        x.0
+    }
+}
+
+impl fmt::Debug for AppErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for AppErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Reasons an [`AppErrorCode`] could not be constructed
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Error)]
+pub enum InvalidAppErrorCode {
+    /// The code is reserved by the application protocol
+    #[error("error code {0} is reserved")]
+    Reserved(u64),
+    /// The code is too large to be encoded, i.e. at least 2^62
+    #[error("error code {0} is too large")]
+    TooLarge(u64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_ranges() {
+        let reserved = [0x100..=0x1ff];
+        assert_eq!(
+            AppErrorCode::new(0xff, &reserved).unwrap().into_inner(),
+            0xff
+        );
+        assert_eq!(
+            AppErrorCode::new(0x100, &reserved),
+            Err(InvalidAppErrorCode::Reserved(0x100))
+        );
+        assert_eq!(
+            AppErrorCode::new(1 << 62, &[]),
+            Err(InvalidAppErrorCode::TooLarge(1 << 62))
+        );
+    }
+
+    #[test]
+    fn http3_grease() {
+        assert!(AppErrorCode::http3(0x100).is_ok());
+        assert_eq!(
+            AppErrorCode::http3(0x21),
+            Err(InvalidAppErrorCode::Reserved(0x21))
+        );
+        assert_eq!(
+            AppErrorCode::http3(0x21 + 3 * 0x1f),
+            Err(InvalidAppErrorCode::Reserved(0x21 + 3 * 0x1f))
+        );
+        assert!(AppErrorCode::http3(0x21 + 0x1e).is_ok());
+    }
+}
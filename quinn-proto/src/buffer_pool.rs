@@ -0,0 +1,18 @@
+/// Allocates the buffers used for datagram I/O
+///
+/// Supplied through [`EndpointConfig::buffer_pool()`](crate::EndpointConfig::buffer_pool) to
+/// control where the hot receive and transmit buffers live, e.g. to recycle them rather than
+/// returning them to the allocator, or to first touch them on the NUMA node which will use them.
+/// Buffers are ordinary `Vec`s, so they must be obtained from the global allocator.
+pub trait BufferPool: Send + Sync {
+    /// Obtain an empty buffer with a capacity of at least `capacity` bytes
+    fn allocate(&self, capacity: usize) -> Vec<u8>;
+
+    /// Accept a buffer which is no longer in use, e.g. to hand it out again later
+    ///
+    /// Not every allocated buffer is guaranteed to be released; some are simply dropped. The
+    /// default implementation drops `buf`.
+    fn release(&self, buf: Vec<u8>) {
+        drop(buf);
+    }
+}
@@ -20,6 +20,12 @@ pub trait ConnectionIdGenerator: Send {
     ///
     /// Connection IDs will be retired after the returned `Duration`, if any. Assumed to be constant.
     fn cid_lifetime(&self) -> Option<Duration>;
+    /// Generates the destination CID for the first Initial packet of an outgoing connection
+    ///
+    /// Must be at least 8 bytes long. Defaults to a random CID of `MAX_CID_SIZE` bytes.
+    fn generate_initial_dcid(&mut self) -> ConnectionId {
+        RandomConnectionIdGenerator::new(MAX_CID_SIZE).generate_cid()
+    }
 }
 
 /// Generates purely random connection IDs of a certain length
@@ -74,3 +80,87 @@ impl ConnectionIdGenerator for RandomConnectionIdGenerator {
         self.lifetime
     }
 }
+
+/// Generates connection IDs by encoding an incrementing counter
+///
+/// Intended for tests which need byte-stable wire output across runs. The generated CIDs are
+/// trivially linkable, so this must not be used in production.
+#[derive(Debug, Clone, Copy)]
+pub struct SequentialConnectionIdGenerator {
+    cid_len: usize,
+    lifetime: Option<Duration>,
+    next: u64,
+}
+
+impl Default for SequentialConnectionIdGenerator {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl SequentialConnectionIdGenerator {
+    /// Initialize sequential CID generator with a fixed CID length
+    ///
+    /// The given length must be less than or equal to MAX_CID_SIZE. The counter is encoded
+    /// big-endian in the trailing bytes of each CID, truncated if `cid_len` is less than 8.
+    pub fn new(cid_len: usize) -> Self {
+        debug_assert!(cid_len <= MAX_CID_SIZE);
+        Self {
+            cid_len,
+            lifetime: None,
+            next: 0,
+        }
+    }
+
+    /// Set the lifetime of CIDs created by this generator
+    pub fn set_lifetime(&mut self, d: Duration) -> &mut Self {
+        self.lifetime = Some(d);
+        self
+    }
+
+    fn encode(&mut self, len: usize) -> ConnectionId {
+        let mut bytes_arr = [0; MAX_CID_SIZE];
+        let counter = self.next.to_be_bytes();
+        self.next = self.next.wrapping_add(1);
+        let n = len.min(counter.len());
+        bytes_arr[len - n..len].copy_from_slice(&counter[counter.len() - n..]);
+        ConnectionId::new(&bytes_arr[..len])
+    }
+}
+
+impl ConnectionIdGenerator for SequentialConnectionIdGenerator {
+    fn generate_cid(&mut self) -> ConnectionId {
+        self.encode(self.cid_len)
+    }
+
+    fn cid_len(&self) -> usize {
+        self.cid_len
+    }
+
+    fn cid_lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+
+    fn generate_initial_dcid(&mut self) -> ConnectionId {
+        self.encode(MAX_CID_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequential_cids() {
+        let mut gen = SequentialConnectionIdGenerator::new(4);
+        assert_eq!(&gen.generate_cid()[..], &[0, 0, 0, 0]);
+        assert_eq!(&gen.generate_cid()[..], &[0, 0, 0, 1]);
+        let dcid = gen.generate_initial_dcid();
+        assert_eq!(dcid.len(), MAX_CID_SIZE);
+        assert_eq!(dcid[MAX_CID_SIZE - 1], 2);
+        assert_eq!(
+            SequentialConnectionIdGenerator::new(0).generate_cid().len(),
+            0
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Minimal parsing of the TLS ClientHello carried in a client's first Initial packet
+
+use bytes::{Bytes, BytesMut};
+
+use crate::frame::{self, Frame};
+
+/// TLS handshake message type of a ClientHello
+const CLIENT_HELLO: u8 = 1;
+/// TLS extension type of application-layer protocol negotiation
+const ALPN_EXTENSION: [u8; 2] = [0, 16];
+/// TLS alert sent when none of the client's ALPN protocols is supported
+pub(crate) const NO_APPLICATION_PROTOCOL: u8 = 120;
+
+/// Find the body of the ClientHello in a decrypted Initial packet payload
+///
+/// Returns `None` if the packet doesn't contain a complete ClientHello, e.g. because it spans
+/// multiple packets, in which case it's left to the TLS implementation to judge.
+pub(crate) fn initial_client_hello(payload: Bytes) -> Option<Bytes> {
+    let mut frames = frame::Iter::new(payload)
+        .filter_map(|frame| match frame {
+            Frame::Crypto(frame) => Some(frame),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    frames.sort_unstable_by_key(|frame| frame.offset);
+
+    // CRYPTO frames may be split and reordered within the packet
+    let mut data = BytesMut::new();
+    for frame in frames {
+        let end = frame.offset + frame.data.len() as u64;
+        if frame.offset > data.len() as u64 {
+            break;
+        } else if end > data.len() as u64 {
+            data.extend_from_slice(&frame.data[(data.len() as u64 - frame.offset) as usize..]);
+        }
+    }
+
+    if *data.first()? != CLIENT_HELLO {
+        return None;
+    }
+    let len = take_prefixed(&mut &data[1..], 3)?.len();
+    Some(data.freeze().slice(4..4 + len))
+}
+
+/// Extract the ALPN protocols from the body of a TLS ClientHello message
+///
+/// Returns `None` if the client didn't offer any protocols using ALPN, including if the message
+/// is malformed.
+pub(crate) fn offered_alpn_protocols(mut body: &[u8]) -> Option<Vec<Vec<u8>>> {
+    // Skip legacy_version and random
+    body = body.get(34..)?;
+    let _session_id = take_prefixed(&mut body, 1)?;
+    let _cipher_suites = take_prefixed(&mut body, 2)?;
+    let _compression_methods = take_prefixed(&mut body, 1)?;
+    let mut extensions = take_prefixed(&mut body, 2)?;
+    while !extensions.is_empty() {
+        let ty = extensions.get(..2)?;
+        let is_alpn = ty == ALPN_EXTENSION;
+        extensions = &extensions[2..];
+        let mut data = take_prefixed(&mut extensions, 2)?;
+        if is_alpn {
+            let mut list = take_prefixed(&mut data, 2)?;
+            let mut protocols = Vec::new();
+            while !list.is_empty() {
+                protocols.push(take_prefixed(&mut list, 1)?.to_vec());
+            }
+            return Some(protocols);
+        }
+    }
+    None
+}
+
+/// Split a field prefixed by its `prefix_len`-byte big-endian length off the front of `buf`
+pub(crate) fn take_prefixed<'a>(buf: &mut &'a [u8], prefix_len: usize) -> Option<&'a [u8]> {
+    let len = buf
+        .get(..prefix_len)?
+        .iter()
+        .fold(0, |acc, &b| (acc << 8) | usize::from(b));
+    let data = buf.get(prefix_len..prefix_len + len)?;
+    *buf = &buf[prefix_len + len..];
+    Some(data)
+}
@@ -5,11 +5,16 @@ use thiserror::Error;
 #[cfg(feature = "ring")]
 use rand::RngCore;
 
+#[cfg(feature = "rustls")]
+use crate::ConnectError;
+
 use crate::{
     cid_generator::{ConnectionIdGenerator, RandomConnectionIdGenerator},
     congestion,
     crypto::{self, HandshakeTokenKey, HmacKey},
-    VarInt, VarIntBoundsExceeded, DEFAULT_SUPPORTED_VERSIONS, INITIAL_MAX_UDP_PAYLOAD_SIZE,
+    AcceptFilter, BufferPool, DatagramDropPolicy, EcnValidationPolicy, OversizedDatagramPolicy,
+    RetryTokenHandler, StreamAdmission, VarInt, VarIntBoundsExceeded, DEFAULT_SUPPORTED_VERSIONS,
+    INITIAL_MAX_UDP_PAYLOAD_SIZE,
 };
 
 /// Parameters governing the core QUIC state machine
@@ -27,8 +32,13 @@ use crate::{
 pub struct TransportConfig {
     pub(crate) max_concurrent_bidi_streams: VarInt,
     pub(crate) max_concurrent_uni_streams: VarInt,
+    pub(crate) max_remote_stream_rate: Option<(u32, Duration)>,
     pub(crate) max_idle_timeout: Option<VarInt>,
+    pub(crate) idle_timeout_policy: Option<IdleTimeoutPolicy>,
     pub(crate) stream_receive_window: VarInt,
+    pub(crate) stream_receive_window_bidi_local: Option<VarInt>,
+    pub(crate) stream_receive_window_bidi_remote: Option<VarInt>,
+    pub(crate) stream_receive_window_uni: Option<VarInt>,
     pub(crate) receive_window: VarInt,
     pub(crate) send_window: u64,
 
@@ -39,15 +49,31 @@ pub struct TransportConfig {
     pub(crate) initial_max_udp_payload_size: u16,
 
     pub(crate) persistent_congestion_threshold: u32,
+    pub(crate) max_pto_backoff: u32,
+    pub(crate) max_ack_ranges: usize,
+    pub(crate) max_ack_delay: Duration,
     pub(crate) keep_alive_interval: Option<Duration>,
     pub(crate) crypto_buffer_size: usize,
     pub(crate) allow_spin: bool,
+    pub(crate) retransmit_rejected_0rtt: bool,
+    pub(crate) max_pending_path_responses: usize,
+    pub(crate) max_concurrent_paths: u32,
     pub(crate) datagram_receive_buffer_size: Option<usize>,
+    pub(crate) datagram_receive_buffer_capacity: Option<usize>,
+    pub(crate) datagram_drop_policy: DatagramDropPolicy,
+    pub(crate) oversized_datagram_policy: OversizedDatagramPolicy,
+    pub(crate) ecn_validation_policy: EcnValidationPolicy,
     pub(crate) datagram_send_buffer_size: usize,
+    pub(crate) datagram_frames_per_transmit: Option<usize>,
 
     pub(crate) congestion_controller_factory: Box<dyn congestion::ControllerFactory + Send + Sync>,
+    pub(crate) stream_admission: Option<(StreamAdmissionFactory, VarInt)>,
 }
 
+type StreamAdmissionFactory = Box<dyn Fn() -> Box<dyn StreamAdmission> + Send + Sync>;
+type IdleTimeoutPolicy =
+    Box<dyn Fn(Option<Duration>, Option<Duration>) -> Option<Duration> + Send + Sync>;
+
 impl TransportConfig {
     /// Maximum number of incoming bidirectional streams that may be open concurrently
     ///
@@ -66,6 +92,23 @@ impl TransportConfig {
         self
     }
 
+    /// Maximum rate at which the peer may open streams, as a number of streams per interval
+    ///
+    /// The concurrency limits bound how many streams are open at once, but not how quickly a peer
+    /// may churn through them by opening and immediately finishing or resetting streams, which
+    /// costs state and processing for each. With `Some((count, interval))`, the peer may open up
+    /// to `count` streams at once and `count` per `interval` on average; a peer opening streams
+    /// faster causes the connection to be closed with a `PROTOCOL_VIOLATION` error. Streams
+    /// opened implicitly, by using a higher stream ID, count as well.
+    ///
+    /// The number of streams opened by the peer so far is reported in
+    /// [`ConnectionStats::remote_streams_opened`](crate::ConnectionStats::remote_streams_opened).
+    /// Defaults to `None`, which doesn't limit the rate.
+    pub fn max_remote_stream_rate(&mut self, value: Option<(u32, Duration)>) -> &mut Self {
+        self.max_remote_stream_rate = value;
+        self
+    }
+
     /// Maximum duration of inactivity to accept before timing out the connection.
     ///
     /// The true idle timeout is the minimum of this and the peer's own max idle timeout. `None`
@@ -93,6 +136,25 @@ impl TransportConfig {
         self
     }
 
+    /// Custom rule deriving the effective idle timeout from the values advertised by both peers
+    ///
+    /// `policy` is called with the local [`max_idle_timeout()`](Self::max_idle_timeout) and the
+    /// peer's, once the peer's transport parameters are received, with `None` standing for a
+    /// disabled timeout. Its result replaces the minimum of the two non-disabled values which
+    /// RFC 9000 prescribes, and which is used by default. Returning `None` disables the idle
+    /// timeout on this side. For example, `|local, _| local` always applies the local value.
+    ///
+    /// The peer applies its own rule independently, so a longer effective timeout doesn't keep a
+    /// connection alive past the peer's timeout unless
+    /// [`keep_alive_interval()`](Self::keep_alive_interval) is also configured.
+    pub fn idle_timeout_policy<F>(&mut self, policy: F) -> &mut Self
+    where
+        F: Fn(Option<Duration>, Option<Duration>) -> Option<Duration> + Send + Sync + 'static,
+    {
+        self.idle_timeout_policy = Some(Box::new(policy));
+        self
+    }
+
     /// Maximum number of bytes the peer may transmit without acknowledgement on any one stream
     /// before becoming blocked.
     ///
@@ -106,6 +168,42 @@ impl TransportConfig {
         self
     }
 
+    /// Override [`stream_receive_window()`](Self::stream_receive_window) for bidirectional
+    /// streams opened locally
+    ///
+    /// Advertised to the peer as the `initial_max_stream_data_bidi_local` transport parameter, and
+    /// maintained as the stream is read, like `stream_receive_window`. Useful when the peer sends
+    /// large responses on streams opened by this side, e.g. to let a single large download
+    /// proceed at full speed without raising the window of every other stream. Data on all
+    /// streams together is still limited by [`receive_window()`](Self::receive_window), so a
+    /// per-stream window larger than that can't be used in full. Windows aren't tuned
+    /// automatically; `Connection::set_receive_window()` only affects the connection-level
+    /// window. `None`, the default, uses `stream_receive_window`.
+    pub fn stream_receive_window_bidi_local(&mut self, value: Option<VarInt>) -> &mut Self {
+        self.stream_receive_window_bidi_local = value;
+        self
+    }
+
+    /// Override [`stream_receive_window()`](Self::stream_receive_window) for bidirectional
+    /// streams opened by the peer
+    ///
+    /// Advertised as the `initial_max_stream_data_bidi_remote` transport parameter. See
+    /// [`stream_receive_window_bidi_local()`](Self::stream_receive_window_bidi_local).
+    pub fn stream_receive_window_bidi_remote(&mut self, value: Option<VarInt>) -> &mut Self {
+        self.stream_receive_window_bidi_remote = value;
+        self
+    }
+
+    /// Override [`stream_receive_window()`](Self::stream_receive_window) for unidirectional
+    /// streams, which are always opened by the peer when receiving
+    ///
+    /// Advertised as the `initial_max_stream_data_uni` transport parameter. See
+    /// [`stream_receive_window_bidi_local()`](Self::stream_receive_window_bidi_local).
+    pub fn stream_receive_window_uni(&mut self, value: Option<VarInt>) -> &mut Self {
+        self.stream_receive_window_uni = value;
+        self
+    }
+
     /// Maximum number of bytes the peer may transmit across all streams of a connection before
     /// becoming blocked.
     ///
@@ -177,6 +275,45 @@ impl TransportConfig {
         self
     }
 
+    /// Maximum number of times the probe timeout (PTO) is doubled while probes go unacknowledged
+    ///
+    /// Each consecutive PTO without an acknowledgement doubles the time before the next probe,
+    /// which can grow to many seconds on a dead path. Lowering this keeps probing frequent, so
+    /// that combined with a short [`max_idle_timeout()`](Self::max_idle_timeout) a connection
+    /// fails fast when its path stops working, at the cost of sending more probes when the path
+    /// is merely congested. Defaults to 16; values above 16 have no further effect.
+    pub fn max_pto_backoff(&mut self, value: u32) -> &mut Self {
+        self.max_pto_backoff = value;
+        self
+    }
+
+    /// Maximum number of ranges of received packets tracked for acknowledgement
+    ///
+    /// Heavy loss or reordering fragments the set of received packets, growing ACK frames and the
+    /// memory used to track them. Once the limit is reached, the ranges covering the oldest
+    /// packets are forgotten, and those packets will not be acknowledged again. The current count
+    /// is reported in [`ConnectionStats::ack_ranges`](crate::ConnectionStats::ack_ranges).
+    /// Defaults to 64, which is also the maximum, ensuring ACK frames always fit in a single
+    /// minimum-size packet; larger values are treated as 64, and 0 as 1.
+    pub fn max_ack_ranges(&mut self, value: usize) -> &mut Self {
+        self.max_ack_ranges = value;
+        self
+    }
+
+    /// Maximum amount of time by which this endpoint may delay acknowledgements
+    ///
+    /// Advertised to the peer in the `max_ack_delay` transport parameter during the handshake. The
+    /// peer adds it to its probe timeout, so a small value lets it detect tail losses sooner, at
+    /// the cost of spurious retransmissions if acknowledgements are in fact delayed longer, e.g.
+    /// by a busy host. This implementation acknowledges ack-eliciting packets as soon as it's able
+    /// to send, so values down to the host's scheduling latency are safe. Truncated to whole
+    /// milliseconds and capped at 2^14 - 1 ms. Defaults to 25ms, the value assumed by peers when
+    /// the parameter is absent.
+    pub fn max_ack_delay(&mut self, value: Duration) -> &mut Self {
+        self.max_ack_delay = value;
+        self
+    }
+
     /// Period of inactivity before sending a keep-alive packet
     ///
     /// Keep-alive packets prevent an inactive but otherwise healthy connection from timing out.
@@ -204,6 +341,51 @@ impl TransportConfig {
         self
     }
 
+    /// Whether stream data sent in 0-RTT is sent again at 1-RTT if the server rejects 0-RTT
+    ///
+    /// By default, a client's streams opened during 0-RTT are discarded on rejection, and must be
+    /// reopened and written again by the application after learning of the rejection. When
+    /// enabled, such streams instead remain usable, and everything written to them is
+    /// retransmitted once the handshake completes, provided the server's new transport parameters
+    /// still admit the streams and data. Otherwise, the streams are discarded as usual.
+    ///
+    /// Only stream data is retransmitted: application datagrams sent in 0-RTT are lost on
+    /// rejection. Since stream data may be replayed either way, only idempotent operations should
+    /// be performed in 0-RTT. Defaults to `false`.
+    pub fn retransmit_rejected_0rtt(&mut self, value: bool) -> &mut Self {
+        self.retransmit_rejected_0rtt = value;
+        self
+    }
+
+    /// Maximum number of PATH_RESPONSE frames to queue in reply to the peer's PATH_CHALLENGEs
+    ///
+    /// Bounds the memory a peer can consume by sending many path challenges in quick succession.
+    /// When a challenge arrives while the queue is full, the oldest queued response is discarded
+    /// and counted in the `path_challenges_dropped` field of
+    /// [`ConnectionStats::path`](crate::ConnectionStats::path); a peer validating a path will
+    /// retransmit its challenge. Values below 1 are treated as 1.
+    pub fn max_pending_path_responses(&mut self, value: usize) -> &mut Self {
+        self.max_pending_path_responses = value;
+        self
+    }
+
+    /// Maximum number of paths a connection maintains at once while the peer migrates
+    ///
+    /// When the peer moves to a new address, the previous path is normally retained until the new
+    /// one is validated, so that the connection can fall back to it if validation fails. With a
+    /// limit of 1, the previous path is discarded immediately instead, so a migrating peer can
+    /// never cause more than one path's worth of state to be held. This disables the fallback: a
+    /// single packet with a spoofed source address moves the connection to that address even if
+    /// validation fails, stalling it until the real peer sends again (see RFC 9000 §9.3.3). At
+    /// most one previous path is currently retained, so values above 2 have the same effect as 2.
+    /// Values below 1 are treated as 1.
+    ///
+    /// See [`Connection::active_paths()`](crate::Connection::active_paths).
+    pub fn max_concurrent_paths(&mut self, value: u32) -> &mut Self {
+        self.max_concurrent_paths = value;
+        self
+    }
+
     /// Maximum number of incoming application datagram bytes to buffer, or None to disable
     /// incoming datagrams
     ///
@@ -215,6 +397,48 @@ impl TransportConfig {
         self
     }
 
+    /// Maximum number of incoming application datagrams to buffer, or `None` for no limit beyond
+    /// [`datagram_receive_buffer_size()`](Self::datagram_receive_buffer_size)
+    ///
+    /// A small capacity suits real-time applications which only care about the most recent
+    /// datagrams. A capacity of 0 is treated as 1. Defaults to `None`. Can be changed for an
+    /// individual connection with [`Datagrams::set_recv_buffer()`](crate::Datagrams::set_recv_buffer).
+    pub fn datagram_receive_buffer_capacity(&mut self, value: Option<usize>) -> &mut Self {
+        self.datagram_receive_buffer_capacity = value;
+        self
+    }
+
+    /// Which incoming application datagrams to drop when the receive buffer is full
+    ///
+    /// Defaults to [`DatagramDropPolicy::DropOldest`]. Dropped datagrams are counted in
+    /// [`ConnectionStats`](crate::ConnectionStats)' `datagrams.recv_dropped`.
+    pub fn datagram_drop_policy(&mut self, value: DatagramDropPolicy) -> &mut Self {
+        self.datagram_drop_policy = value;
+        self
+    }
+
+    /// How to handle outgoing application datagrams larger than the connection can currently send
+    ///
+    /// Defaults to [`OversizedDatagramPolicy::Reject`]. Can be changed for an individual
+    /// connection with [`Datagrams::set_oversized_policy()`](crate::Datagrams::set_oversized_policy).
+    pub fn oversized_datagram_policy(&mut self, value: OversizedDatagramPolicy) -> &mut Self {
+        self.oversized_datagram_policy = value;
+        self
+    }
+
+    /// How to react when the peer's ECN counts are inconsistent with the packets marked
+    ///
+    /// Such inconsistencies indicate that the path mangles or strips ECN marks, so by default
+    /// ([`EcnValidationPolicy::Disable`]) the connection stops marking packets on the path, as
+    /// required by RFC 9000. On networks where this happens only intermittently,
+    /// [`EcnValidationPolicy::Retry`] allows the benefits of ECN to be regained later. Failures
+    /// are counted in [`ConnectionStats`](crate::ConnectionStats)'
+    /// `path.ecn_validation_failures`.
+    pub fn ecn_validation_policy(&mut self, value: EcnValidationPolicy) -> &mut Self {
+        self.ecn_validation_policy = value;
+        self
+    }
+
     /// Maximum number of outgoing application datagram bytes to buffer
     ///
     /// While datagrams are sent ASAP, it is possible for an application to generate data faster
@@ -226,6 +450,18 @@ impl TransportConfig {
         self
     }
 
+    /// Maximum number of application datagrams to send in a single transmit while stream data is
+    /// waiting, or `None` for no limit
+    ///
+    /// Queued datagrams are normally sent ahead of stream data, so an application which sends
+    /// datagrams faster than the link can carry them will starve its streams. Once this many
+    /// datagrams have been packed into a transmit, the remaining packets of that transmit are
+    /// filled with stream data first. Datagrams may still use any space left over.
+    pub fn datagram_frames_per_transmit(&mut self, value: Option<usize>) -> &mut Self {
+        self.datagram_frames_per_transmit = value;
+        self
+    }
+
     /// How to construct new `congestion::Controller`s
     ///
     /// Typically the refcounted configuration of a `congestion::Controller`,
@@ -244,6 +480,52 @@ impl TransportConfig {
         self.congestion_controller_factory = Box::new(factory);
         self
     }
+
+    /// Policy deciding whether to admit each stream opened by the peer
+    ///
+    /// `factory` is called once per connection to construct that connection's policy, which is
+    /// consulted as soon as the peer opens each stream. This allows admission rules which can't be
+    /// expressed by stream limits alone, e.g. refusing bidirectional streams or rate-limiting
+    /// particular peers.
+    ///
+    /// Rejected streams are never yielded by `Streams::accept()`. Instead, `STOP_SENDING` and, for
+    /// bidirectional streams, `RESET_STREAM` frames carrying `error_code` are sent. A rejected
+    /// stream continues to count against the concurrency limits until the peer finishes or resets
+    /// it.
+    pub fn stream_admission<F>(&mut self, factory: F, error_code: VarInt) -> &mut Self
+    where
+        F: Fn() -> Box<dyn StreamAdmission> + Send + Sync + 'static,
+    {
+        self.stream_admission = Some((Box::new(factory), error_code));
+        self
+    }
+}
+
+impl TransportConfig {
+    /// Per-stream receive windows for bidirectional streams opened locally, bidirectional streams
+    /// opened by the peer, and unidirectional streams
+    pub(crate) fn stream_receive_windows(&self) -> (VarInt, VarInt, VarInt) {
+        (
+            self.stream_receive_window_bidi_local
+                .unwrap_or(self.stream_receive_window),
+            self.stream_receive_window_bidi_remote
+                .unwrap_or(self.stream_receive_window),
+            self.stream_receive_window_uni
+                .unwrap_or(self.stream_receive_window),
+        )
+    }
+
+    /// Check for settings which contradict each other
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        if let (Some(interval), Some(timeout)) = (self.keep_alive_interval, self.max_idle_timeout) {
+            if interval >= Duration::from_millis(timeout.0) {
+                return Err(ConfigError::InconsistentTransport(
+                    "keep-alive interval is not shorter than the idle timeout",
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for TransportConfig {
@@ -257,8 +539,13 @@ impl Default for TransportConfig {
         TransportConfig {
             max_concurrent_bidi_streams: 100u32.into(),
             max_concurrent_uni_streams: 100u32.into(),
+            max_remote_stream_rate: None,
             max_idle_timeout: Some(VarInt(10_000)),
+            idle_timeout_policy: None,
             stream_receive_window: STREAM_RWND.into(),
+            stream_receive_window_bidi_local: None,
+            stream_receive_window_bidi_remote: None,
+            stream_receive_window_uni: None,
             receive_window: VarInt::MAX,
             send_window: (8 * STREAM_RWND).into(),
 
@@ -269,13 +556,25 @@ impl Default for TransportConfig {
             initial_max_udp_payload_size: INITIAL_MAX_UDP_PAYLOAD_SIZE,
 
             persistent_congestion_threshold: 3,
+            max_pto_backoff: 16,
+            max_ack_ranges: 64,
+            max_ack_delay: Duration::from_millis(25),
             keep_alive_interval: None,
             crypto_buffer_size: 16 * 1024,
             allow_spin: true,
+            retransmit_rejected_0rtt: false,
+            max_pending_path_responses: 4,
+            max_concurrent_paths: 2,
             datagram_receive_buffer_size: Some(STREAM_RWND as usize),
+            datagram_receive_buffer_capacity: None,
+            datagram_drop_policy: DatagramDropPolicy::default(),
+            oversized_datagram_policy: OversizedDatagramPolicy::default(),
+            ecn_validation_policy: EcnValidationPolicy::default(),
             datagram_send_buffer_size: 1024 * 1024,
+            datagram_frames_per_transmit: None,
 
             congestion_controller_factory: Box::new(Arc::new(congestion::CubicConfig::default())),
+            stream_admission: None,
         }
     }
 }
@@ -291,8 +590,22 @@ impl fmt::Debug for TransportConfig {
                 "max_concurrent_uni_streams",
                 &self.max_concurrent_uni_streams,
             )
+            .field("max_remote_stream_rate", &self.max_remote_stream_rate)
             .field("max_idle_timeout", &self.max_idle_timeout)
+            .field(
+                "idle_timeout_policy",
+                &self.idle_timeout_policy.as_ref().map(|_| "[ opaque ]"),
+            )
             .field("stream_receive_window", &self.stream_receive_window)
+            .field(
+                "stream_receive_window_bidi_local",
+                &self.stream_receive_window_bidi_local,
+            )
+            .field(
+                "stream_receive_window_bidi_remote",
+                &self.stream_receive_window_bidi_remote,
+            )
+            .field("stream_receive_window_uni", &self.stream_receive_window_uni)
             .field("receive_window", &self.receive_window)
             .field("send_window", &self.send_window)
             .field("max_tlps", &self.max_tlps)
@@ -303,15 +616,39 @@ impl fmt::Debug for TransportConfig {
                 "persistent_congestion_threshold",
                 &self.persistent_congestion_threshold,
             )
+            .field("max_pto_backoff", &self.max_pto_backoff)
+            .field("max_ack_ranges", &self.max_ack_ranges)
+            .field("max_ack_delay", &self.max_ack_delay)
             .field("keep_alive_interval", &self.keep_alive_interval)
             .field("crypto_buffer_size", &self.crypto_buffer_size)
             .field("allow_spin", &self.allow_spin)
+            .field("retransmit_rejected_0rtt", &self.retransmit_rejected_0rtt)
+            .field(
+                "max_pending_path_responses",
+                &self.max_pending_path_responses,
+            )
+            .field("max_concurrent_paths", &self.max_concurrent_paths)
             .field(
                 "datagram_receive_buffer_size",
                 &self.datagram_receive_buffer_size,
             )
+            .field(
+                "datagram_receive_buffer_capacity",
+                &self.datagram_receive_buffer_capacity,
+            )
+            .field("datagram_drop_policy", &self.datagram_drop_policy)
+            .field("oversized_datagram_policy", &self.oversized_datagram_policy)
+            .field("ecn_validation_policy", &self.ecn_validation_policy)
             .field("datagram_send_buffer_size", &self.datagram_send_buffer_size)
+            .field(
+                "datagram_frames_per_transmit",
+                &self.datagram_frames_per_transmit,
+            )
             .field("congestion_controller_factory", &"[ opaque ]")
+            .field(
+                "stream_admission",
+                &self.stream_admission.as_ref().map(|(_, code)| code),
+            )
             .finish()
     }
 }
@@ -330,6 +667,20 @@ pub struct EndpointConfig {
         Arc<dyn Fn() -> Box<dyn ConnectionIdGenerator> + Send + Sync>,
     pub(crate) supported_versions: Vec<u32>,
     pub(crate) grease_quic_bit: bool,
+    pub(crate) stateless_reset: bool,
+    pub(crate) version_negotiation: bool,
+    pub(crate) socket_send_buffer_size: Option<usize>,
+    pub(crate) socket_recv_buffer_size: Option<usize>,
+    pub(crate) max_concurrent_outgoing_handshakes: Option<usize>,
+    pub(crate) max_buffered_datagrams: Option<usize>,
+    pub(crate) expected_connections: usize,
+    pub(crate) transmit_scheduling: TransmitScheduling,
+    pub(crate) slow_poll_threshold: Option<Duration>,
+    pub(crate) per_datagram_recv_limit: bool,
+    pub(crate) max_handshake_rate_per_ip: Option<(u32, Duration)>,
+    pub(crate) handshake_rate_table_size: usize,
+    pub(crate) congestion_controller_selector: Option<Arc<congestion::ControllerSelector>>,
+    pub(crate) buffer_pool: Option<Arc<dyn BufferPool>>,
 }
 
 impl EndpointConfig {
@@ -343,6 +694,20 @@ impl EndpointConfig {
             connection_id_generator_factory: Arc::new(cid_factory),
             supported_versions: DEFAULT_SUPPORTED_VERSIONS.to_vec(),
             grease_quic_bit: true,
+            stateless_reset: true,
+            version_negotiation: true,
+            socket_send_buffer_size: None,
+            socket_recv_buffer_size: None,
+            max_concurrent_outgoing_handshakes: None,
+            max_buffered_datagrams: None,
+            expected_connections: 0,
+            transmit_scheduling: TransmitScheduling::default(),
+            slow_poll_threshold: None,
+            per_datagram_recv_limit: false,
+            max_handshake_rate_per_ip: None,
+            handshake_rate_table_size: 4096,
+            congestion_controller_selector: None,
+            buffer_pool: None,
         }
     }
 
@@ -355,7 +720,8 @@ impl EndpointConfig {
     ///
     /// `EndpointConfig::new()` applies a default random CID generator factory. This functions
     /// accepts any customized CID generator to reset CID generator factory that implements
-    /// the `ConnectionIdGenerator` trait.
+    /// the `ConnectionIdGenerator` trait. For reproducible wire output in tests, a
+    /// [`SequentialConnectionIdGenerator`](crate::SequentialConnectionIdGenerator) can be used.
     pub fn cid_generator<F: Fn() -> Box<dyn ConnectionIdGenerator> + Send + Sync + 'static>(
         &mut self,
         factory: F,
@@ -364,6 +730,25 @@ impl EndpointConfig {
         self
     }
 
+    /// Supply a function choosing the congestion controller of each new connection
+    ///
+    /// Called as each connection is created, before its handshake, with the connection's side,
+    /// peer address, and, for clients, the server name. Returning `None` uses the controller
+    /// configured by [`TransportConfig::congestion_controller_factory()`]. Allows e.g. rolling out
+    /// a new congestion control algorithm to a fraction of connections and comparing the results.
+    pub fn congestion_controller_selector<F>(&mut self, selector: F) -> &mut Self
+    where
+        F: Fn(
+                &congestion::ConnectionProperties,
+            ) -> Option<Arc<dyn congestion::ControllerFactory + Send + Sync>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.congestion_controller_selector = Some(Arc::new(selector));
+        self
+    }
+
     /// Private key used to send authenticated connection resets to peers who were
     /// communicating with a previous instance of this endpoint.
     pub fn reset_key(&mut self, key: Arc<dyn HmacKey>) -> &mut Self {
@@ -408,6 +793,220 @@ impl EndpointConfig {
         self.grease_quic_bit = value;
         self
     }
+
+    /// Whether to send stateless resets in response to packets for unknown connections
+    ///
+    /// Enabled by default, allowing peers which lost state, e.g. due to a restart of this
+    /// endpoint, to promptly learn that their connection is gone. Disable to avoid revealing the
+    /// endpoint's presence to scanners, at the cost of such peers waiting out their idle timeout.
+    pub fn stateless_reset(&mut self, value: bool) -> &mut Self {
+        self.stateless_reset = value;
+        self
+    }
+
+    /// Whether servers respond to packets of unsupported QUIC versions with Version Negotiation
+    ///
+    /// Enabled by default, as required by RFC 9000. Disable to silently drop such packets instead,
+    /// which avoids revealing the endpoint to scanners probing with unknown versions and removes a
+    /// response that could be elicited with spoofed source addresses. However, clients offering a
+    /// version this endpoint doesn't support will then fail only once their handshake times out,
+    /// rather than promptly learning which versions to use, so this is best reserved for
+    /// deployments whose clients are known to use a supported version.
+    pub fn version_negotiation(&mut self, value: bool) -> &mut Self {
+        self.version_negotiation = value;
+        self
+    }
+
+    /// Size of the UDP socket's kernel send buffer (`SO_SNDBUF`), or `None` to keep the platform
+    /// default
+    ///
+    /// Applied by higher-level layers, e.g. the `quinn` crate, to every socket they configure for
+    /// an endpoint, including sockets passed to `rebind`. The operating system may round or clamp
+    /// the requested size.
+    pub fn socket_send_buffer_size(&mut self, value: Option<usize>) -> &mut Self {
+        self.socket_send_buffer_size = value;
+        self
+    }
+
+    /// Size of the UDP socket's kernel receive buffer (`SO_RCVBUF`), or `None` to keep the
+    /// platform default
+    ///
+    /// See [`socket_send_buffer_size()`](Self::socket_send_buffer_size) for details.
+    pub fn socket_recv_buffer_size(&mut self, value: Option<usize>) -> &mut Self {
+        self.socket_recv_buffer_size = value;
+        self
+    }
+
+    /// Maximum number of outgoing connections that may be performing a handshake at once, or
+    /// `None` for no limit, which is the default
+    ///
+    /// Enforced by higher-level layers, e.g. the `quinn` crate, which defer starting further
+    /// handshakes until an in-progress one completes or fails. Helps clients that open many
+    /// connections at once avoid saturating the network with handshake traffic.
+    pub fn max_concurrent_outgoing_handshakes(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_concurrent_outgoing_handshakes = value;
+        self
+    }
+
+    /// Maximum number of received datagrams buffered by all of the endpoint's connections together
+    ///
+    /// Enforced by higher-level layers, e.g. the `quinn` crate, in addition to each connection's
+    /// own [`TransportConfig::datagram_receive_buffer_capacity()`] and
+    /// [`TransportConfig::datagram_receive_buffer_size()`]. When a datagram arrives at a full
+    /// endpoint, the oldest datagram buffered by any connection is dropped, bounding the memory
+    /// used by datagrams regardless of the number of connections. `None`, the default, imposes no
+    /// endpoint-wide limit.
+    pub fn max_buffered_datagrams(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_buffered_datagrams = value;
+        self
+    }
+
+    /// Number of concurrent connections to allocate space for up front
+    ///
+    /// Connection tables grow as needed regardless, but sizing them ahead of time avoids repeated
+    /// reallocation, and the resulting latency spikes, when many connections are established at
+    /// once, e.g. as clients reconnect after a server restarts. Defaults to 0.
+    pub fn expected_connections(&mut self, value: usize) -> &mut Self {
+        self.expected_connections = value;
+        self
+    }
+
+    /// Order in which connections' outgoing datagrams are written to the socket
+    ///
+    /// Enforced by higher-level layers, e.g. the `quinn` crate. Only matters when connections
+    /// produce data faster than the socket accepts it, in which case a strategy other than the
+    /// default [`TransmitScheduling::Fifo`] prevents a few busy connections from starving the rest.
+    pub fn transmit_scheduling(&mut self, value: TransmitScheduling) -> &mut Self {
+        self.transmit_scheduling = value;
+        self
+    }
+
+    /// Wall time a single pass of the endpoint's I/O driver may take before a warning is logged,
+    /// or `None` to never warn, which is the default
+    ///
+    /// Enforced by higher-level layers, e.g. the `quinn` crate, which log the time spent receiving,
+    /// processing connection events, and sending alongside the warning. Helps identify latency
+    /// regressions and pathological connections in production.
+    pub fn slow_poll_threshold(&mut self, value: Option<Duration>) -> &mut Self {
+        self.slow_poll_threshold = value;
+        self
+    }
+
+    /// Whether the time spent receiving is bounded after each datagram, rather than only after
+    /// each batch of datagrams read from a socket
+    ///
+    /// Enforced by higher-level layers, e.g. the `quinn` crate, whose I/O driver stops receiving
+    /// once a pass has taken long enough, to leave time for sending and handling timers. By
+    /// default this is only checked between batches, so a single large batch, e.g. one coalesced
+    /// by generic receive offload, is always processed in full. Enabling this lets
+    /// latency-sensitive deployments yield more promptly, deferring the rest of a batch to the next
+    /// pass, at the cost of checking the clock more often. Disabled by default.
+    pub fn per_datagram_recv_limit(&mut self, value: bool) -> &mut Self {
+        self.per_datagram_recv_limit = value;
+        self
+    }
+
+    /// Maximum rate at which each source IP address may attempt to establish connections, as a
+    /// number of attempts per interval, or `None` for no limit, which is the default
+    ///
+    /// Protects servers from a single source flooding them with handshakes. Checked for every
+    /// Initial packet which would start a new connection, before any state is allocated for it;
+    /// attempts exceeding the limit are dropped silently. A source may make up to the full number
+    /// of attempts in a burst, after which its allowance is replenished gradually over the
+    /// interval. Unlike [`ServerConfig::concurrent_connections()`], this doesn't limit sources
+    /// which stay within their rate.
+    ///
+    /// Initial packets aren't authenticated, so their source address can be spoofed. When clients
+    /// must complete a Retry, e.g. due to [`ServerConfig::use_retry()`], only Initials carrying a
+    /// valid retry token, which proves the client can receive at its address, are counted.
+    /// Otherwise every attempt is counted, and an off-path attacker spoofing a victim's address
+    /// can exhaust the victim's allowance.
+    ///
+    /// Rejected attempts are counted by [`Endpoint::handshake_rate_rejections()`].
+    ///
+    /// [`Endpoint::handshake_rate_rejections()`]: crate::Endpoint::handshake_rate_rejections
+    pub fn max_handshake_rate_per_ip(&mut self, value: Option<(u32, Duration)>) -> &mut Self {
+        self.max_handshake_rate_per_ip = value;
+        self
+    }
+
+    /// Number of source IP addresses tracked to enforce
+    /// [`max_handshake_rate_per_ip()`](Self::max_handshake_rate_per_ip)
+    ///
+    /// When more sources attempt to connect, the least recently seen are forgotten, allowing them
+    /// a full burst of attempts again. Larger tables resist attackers cycling through many
+    /// addresses better, at the cost of memory. Defaults to 4096.
+    pub fn handshake_rate_table_size(&mut self, value: usize) -> &mut Self {
+        self.handshake_rate_table_size = value;
+        self
+    }
+
+    /// Supply the allocator of buffers for datagram I/O
+    ///
+    /// Used for the endpoint's receive buffer and for the datagrams assembled by its connections,
+    /// which are released to the pool once they've been passed to the socket. By default, buffers
+    /// are allocated and freed normally. The receive buffer and releasing sent datagrams are
+    /// handled by higher-level layers, e.g. the `quinn` crate.
+    pub fn buffer_pool(&mut self, pool: Arc<dyn BufferPool>) -> &mut Self {
+        self.buffer_pool = Some(pool);
+        self
+    }
+
+    /// Get the current value of `socket_send_buffer_size`
+    ///
+    /// Exposed for the same reason as [`get_max_udp_payload_size()`](Self::get_max_udp_payload_size).
+    #[doc(hidden)]
+    pub fn get_socket_send_buffer_size(&self) -> Option<usize> {
+        self.socket_send_buffer_size
+    }
+
+    /// Get the current value of `socket_recv_buffer_size`
+    #[doc(hidden)]
+    pub fn get_socket_recv_buffer_size(&self) -> Option<usize> {
+        self.socket_recv_buffer_size
+    }
+
+    /// Get the current value of `max_concurrent_outgoing_handshakes`
+    #[doc(hidden)]
+    pub fn get_max_concurrent_outgoing_handshakes(&self) -> Option<usize> {
+        self.max_concurrent_outgoing_handshakes
+    }
+
+    /// Get the current value of `max_buffered_datagrams`
+    #[doc(hidden)]
+    pub fn get_max_buffered_datagrams(&self) -> Option<usize> {
+        self.max_buffered_datagrams
+    }
+
+    /// Get the current value of `transmit_scheduling`
+    #[doc(hidden)]
+    pub fn get_transmit_scheduling(&self) -> TransmitScheduling {
+        self.transmit_scheduling
+    }
+
+    /// Get the current value of `expected_connections`
+    #[doc(hidden)]
+    pub fn get_expected_connections(&self) -> usize {
+        self.expected_connections
+    }
+
+    /// Get the current value of `slow_poll_threshold`
+    #[doc(hidden)]
+    pub fn get_slow_poll_threshold(&self) -> Option<Duration> {
+        self.slow_poll_threshold
+    }
+
+    /// Get the current value of `per_datagram_recv_limit`
+    #[doc(hidden)]
+    pub fn get_per_datagram_recv_limit(&self) -> bool {
+        self.per_datagram_recv_limit
+    }
+
+    /// Get the current value of `buffer_pool`
+    #[doc(hidden)]
+    pub fn get_buffer_pool(&self) -> Option<&Arc<dyn BufferPool>> {
+        self.buffer_pool.as_ref()
+    }
 }
 
 impl fmt::Debug for EndpointConfig {
@@ -418,6 +1017,32 @@ impl fmt::Debug for EndpointConfig {
             .field("cid_generator_factory", &"[ elided ]")
             .field("supported_versions", &self.supported_versions)
             .field("grease_quic_bit", &self.grease_quic_bit)
+            .field("stateless_reset", &self.stateless_reset)
+            .field("version_negotiation", &self.version_negotiation)
+            .field("socket_send_buffer_size", &self.socket_send_buffer_size)
+            .field("socket_recv_buffer_size", &self.socket_recv_buffer_size)
+            .field(
+                "max_concurrent_outgoing_handshakes",
+                &self.max_concurrent_outgoing_handshakes,
+            )
+            .field("max_buffered_datagrams", &self.max_buffered_datagrams)
+            .field("expected_connections", &self.expected_connections)
+            .field("transmit_scheduling", &self.transmit_scheduling)
+            .field("slow_poll_threshold", &self.slow_poll_threshold)
+            .field("per_datagram_recv_limit", &self.per_datagram_recv_limit)
+            .field("max_handshake_rate_per_ip", &self.max_handshake_rate_per_ip)
+            .field("handshake_rate_table_size", &self.handshake_rate_table_size)
+            .field(
+                "congestion_controller_selector",
+                &self
+                    .congestion_controller_selector
+                    .as_ref()
+                    .map(|_| "[ elided ]"),
+            )
+            .field(
+                "buffer_pool",
+                &self.buffer_pool.as_ref().map(|_| "[ elided ]"),
+            )
             .finish()
     }
 }
@@ -457,6 +1082,16 @@ pub struct ServerConfig {
     pub(crate) use_retry: bool,
     /// Microseconds after a stateless retry token was issued for which it's considered valid.
     pub(crate) retry_token_lifetime: Duration,
+    /// Custom generation and validation of stateless retry tokens, replacing `token_key`
+    pub(crate) retry_token_handler: Option<Arc<dyn RetryTokenHandler>>,
+    /// Number of address validation tokens sent in NEW_TOKEN frames on each connection
+    pub(crate) new_tokens: u32,
+    /// Duration after a NEW_TOKEN token was issued for which it's considered valid
+    pub(crate) new_token_lifetime: Duration,
+    /// Decides how to respond to each connection attempt, overriding `use_retry`
+    pub(crate) accept_filter: Option<Arc<dyn AcceptFilter>>,
+    /// ALPN protocols of which clients must offer at least one, if nonempty
+    pub(crate) required_alpn_protocols: Vec<Vec<u8>>,
 
     /// Maximum number of concurrent connections
     pub(crate) concurrent_connections: u32,
@@ -466,6 +1101,11 @@ pub struct ServerConfig {
     /// Improves behavior for clients that move between different internet connections or suffer NAT
     /// rebinding. Enabled by default.
     pub(crate) migration: bool,
+    /// Whether to close connections which migrate despite migration being disabled
+    pub(crate) close_on_migration: bool,
+
+    /// Whether the application may send data before the handshake completes
+    pub(crate) half_rtt: bool,
 }
 
 impl ServerConfig {
@@ -481,10 +1121,18 @@ impl ServerConfig {
             token_key,
             use_retry: false,
             retry_token_lifetime: Duration::from_secs(15),
+            retry_token_handler: None,
+            new_tokens: 0,
+            new_token_lifetime: Duration::from_secs(14 * 24 * 60 * 60),
+            accept_filter: None,
+            required_alpn_protocols: Vec::new(),
 
             concurrent_connections: 100_000,
 
             migration: true,
+            close_on_migration: false,
+
+            half_rtt: true,
         }
     }
 
@@ -514,6 +1162,71 @@ impl ServerConfig {
         self
     }
 
+    /// Custom generation and validation of the tokens sent in Retry packets
+    ///
+    /// Only used when [`use_retry()`](Self::use_retry) is enabled. `None`, the default, seals
+    /// tokens with the [`token_key()`](Self::token_key).
+    pub fn retry_token_handler(&mut self, value: Option<Arc<dyn RetryTokenHandler>>) -> &mut Self {
+        self.retry_token_handler = value;
+        self
+    }
+
+    /// Number of address validation tokens to issue in NEW_TOKEN frames on each connection
+    ///
+    /// Tokens are sent once the handshake completes, sealed with the
+    /// [`token_key()`](Self::token_key) and bound to the client's IP address. Capping their number
+    /// keeps a client from harvesting tokens to hand out to others. A token lost in transit is
+    /// replaced by a fresh one, so a client never receives more than this many from a single
+    /// connection. 0, the default, disables NEW_TOKEN frames.
+    ///
+    /// A client presenting a valid token in a later connection has its address validated, so it
+    /// isn't sent a Retry even if [`use_retry()`](Self::use_retry) is enabled. Invalid tokens are
+    /// treated as if none was presented.
+    pub fn new_tokens(&mut self, value: u32) -> &mut Self {
+        self.new_tokens = value;
+        self
+    }
+
+    /// Duration after a token was issued in a NEW_TOKEN frame for which it's considered valid
+    ///
+    /// Defaults to two weeks.
+    pub fn new_token_lifetime(&mut self, value: Duration) -> &mut Self {
+        self.new_token_lifetime = value;
+        self
+    }
+
+    /// Decide per connection attempt whether to accept it, require address validation through a
+    /// Retry, or refuse it
+    ///
+    /// The filter is consulted for every Initial packet which would otherwise create a
+    /// connection, before any connection state is allocated, allowing graduated responses based on
+    /// e.g. external address reputation data. [`AcceptDecision::Retry`] requires validation even
+    /// if [`use_retry()`](Self::use_retry) is disabled, while [`AcceptDecision::Accept`] defers to
+    /// it. Valid tokens presented in response to an earlier Retry are always honored, so a client
+    /// isn't refused for having been challenged, and a valid token issued in a NEW_TOKEN frame
+    /// takes the place of a Retry. `None`, the default, accepts every attempt.
+    ///
+    /// [`AcceptDecision::Retry`]: crate::AcceptDecision::Retry
+    /// [`AcceptDecision::Accept`]: crate::AcceptDecision::Accept
+    pub fn accept_filter(&mut self, value: Option<Arc<dyn AcceptFilter>>) -> &mut Self {
+        self.accept_filter = value;
+        self
+    }
+
+    /// Refuse connection attempts whose ClientHello doesn't offer any of `protocols` for ALPN
+    ///
+    /// The check is made on the client's first Initial packet, before any connection state is
+    /// allocated, and fails the attempt with a TLS `no_application_protocol` alert. This saves the
+    /// cost of a handshake which the TLS configuration would otherwise only reject later. When the
+    /// ClientHello doesn't fit in the first packet, the check is skipped, so the TLS configuration
+    /// should still enforce the same protocols. Empty, the default, disables the check.
+    ///
+    /// See [`Endpoint::alpn_rejections()`](crate::Endpoint::alpn_rejections).
+    pub fn required_alpn_protocols(&mut self, protocols: Vec<Vec<u8>>) -> &mut Self {
+        self.required_alpn_protocols = protocols;
+        self
+    }
+
     /// Maximum number of simultaneous connections to accept.
     ///
     /// New incoming connections are only accepted if the total number of incoming or outgoing
@@ -531,6 +1244,37 @@ impl ServerConfig {
         self.migration = value;
         self
     }
+
+    /// Whether to close connections whose clients migrate while migration is disabled
+    ///
+    /// Only has an effect if [`migration()`](Self::migration) is disabled. By default, packets
+    /// arriving from a new client address are silently discarded. If this is set, an authenticated
+    /// packet from a new address instead closes the connection with a `PROTOCOL_VIOLATION` error,
+    /// which suits servers that must pin connections to their original path and treat any address
+    /// change as hostile. Note that this also closes connections affected by NAT rebinding.
+    /// Disabled by default.
+    pub fn close_on_migration(&mut self, value: bool) -> &mut Self {
+        self.close_on_migration = value;
+        self
+    }
+
+    /// Whether the application may send 0.5-RTT data on incoming connections
+    ///
+    /// 0.5-RTT data is sent by the server after it has derived 1-RTT keys but before the
+    /// handshake completes, saving a round trip for protocols where the server speaks first or can
+    /// respond to 0-RTT requests immediately. Enabled by default; see e.g. quinn's
+    /// `Connecting::into_0rtt()`.
+    ///
+    /// # Security
+    ///
+    /// The client has not yet been authenticated when 0.5-RTT data is sent, so it must not depend
+    /// on client certificates or anything else established by the client's half of the handshake.
+    /// Data may also be sent to a client whose address has not been validated, subject to the
+    /// usual anti-amplification limits.
+    pub fn half_rtt(&mut self, value: bool) -> &mut Self {
+        self.half_rtt = value;
+        self
+    }
 }
 
 #[cfg(feature = "rustls")]
@@ -570,8 +1314,21 @@ impl fmt::Debug for ServerConfig {
             .field("token_key", &"[ elided ]")
             .field("use_retry", &self.use_retry)
             .field("retry_token_lifetime", &self.retry_token_lifetime)
+            .field(
+                "retry_token_handler",
+                &self.retry_token_handler.as_ref().map(|_| "[ elided ]"),
+            )
+            .field("new_tokens", &self.new_tokens)
+            .field("new_token_lifetime", &self.new_token_lifetime)
+            .field(
+                "accept_filter",
+                &self.accept_filter.as_ref().map(|_| "[ elided ]"),
+            )
+            .field("required_alpn_protocols", &self.required_alpn_protocols)
             .field("concurrent_connections", &self.concurrent_connections)
             .field("migration", &self.migration)
+            .field("close_on_migration", &self.close_on_migration)
+            .field("half_rtt", &self.half_rtt)
             .finish()
     }
 }
@@ -588,6 +1345,10 @@ pub struct ClientConfig {
     /// Cryptographic configuration to use
     pub(crate) crypto: Arc<dyn crypto::ClientConfig>,
 
+    /// The rustls configuration behind `crypto`, if known, allowing it to be adjusted
+    #[cfg(feature = "rustls")]
+    pub(crate) rustls: Option<Arc<rustls::ClientConfig>>,
+
     /// QUIC protocol version to use
     pub(crate) version: u32,
 }
@@ -598,6 +1359,8 @@ impl ClientConfig {
         Self {
             transport: Default::default(),
             crypto,
+            #[cfg(feature = "rustls")]
+            rustls: None,
             version: 1,
         }
     }
@@ -613,6 +1376,34 @@ impl ClientConfig {
         self.version = version;
         self
     }
+
+    /// Check for obvious misconfiguration without attempting a connection
+    ///
+    /// Useful for surfacing errors at startup rather than on the first connection attempt.
+    /// Detects transport settings which contradict each other and, for configurations backed by
+    /// rustls, a QUIC version or TLS protocol version which can't be used and malformed ALPN
+    /// protocol names. Trust anchors can't be inspected once they've been handed to rustls, so an
+    /// empty root certificate store is not detected.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.transport.validate()?;
+        #[cfg(feature = "rustls")]
+        if let Some(ref rustls) = self.rustls {
+            if crypto::rustls::interpret_version(self.version).is_err() {
+                return Err(ConfigError::UnsupportedVersion);
+            }
+            if !rustls.supports_version(rustls::ProtocolVersion::TLSv1_3) {
+                return Err(ConfigError::Tls13Unsupported);
+            }
+            if rustls
+                .alpn_protocols
+                .iter()
+                .any(|p| p.is_empty() || p.len() > 255)
+            {
+                return Err(ConfigError::InvalidAlpn);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "rustls")]
@@ -639,7 +1430,39 @@ impl ClientConfig {
 
     /// Create a client configuration that trusts specified trust anchors
     pub fn with_root_certificates(roots: rustls::RootCertStore) -> Self {
-        Self::new(Arc::new(crypto::rustls::client_config(roots)))
+        Self::with_rustls(Arc::new(crypto::rustls::client_config(roots)))
+    }
+
+    /// Create a client configuration from a rustls configuration
+    ///
+    /// Unlike [`ClientConfig::new()`], this allows the rustls configuration to be adjusted later
+    /// using [`map_rustls()`](Self::map_rustls).
+    pub fn with_rustls(crypto: Arc<rustls::ClientConfig>) -> Self {
+        Self {
+            rustls: Some(crypto.clone()),
+            ..Self::new(crypto)
+        }
+    }
+
+    /// Copy this configuration, applying `f` to a copy of its rustls configuration
+    ///
+    /// Fails with [`ConnectError::OpaqueCryptoConfig`] if the configuration was created with
+    /// [`ClientConfig::new()`], since its cryptographic configuration can't be inspected.
+    pub fn map_rustls(
+        &self,
+        f: impl FnOnce(&mut rustls::ClientConfig),
+    ) -> Result<Self, ConnectError> {
+        let mut crypto = match self.rustls {
+            Some(ref crypto) => (**crypto).clone(),
+            None => return Err(ConnectError::OpaqueCryptoConfig),
+        };
+        f(&mut crypto);
+        let crypto = Arc::new(crypto);
+        Ok(Self {
+            crypto: crypto.clone(),
+            rustls: Some(crypto),
+            ..self.clone()
+        })
     }
 }
 
@@ -653,6 +1476,30 @@ impl fmt::Debug for ClientConfig {
     }
 }
 
+/// Strategy for ordering the outgoing datagrams of an endpoint's connections
+///
+/// See [`EndpointConfig::transmit_scheduling()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransmitScheduling {
+    /// Send datagrams in the order connections produce them
+    Fifo,
+    /// Take one datagram from each connection with data to send in turn
+    RoundRobin,
+    /// Take up to as many datagrams from each connection in turn as its weight, so that a
+    /// connection's share of the socket is proportional to its weight
+    WeightedFair,
+    /// Always send the datagrams of the highest-priority connection with data to send first,
+    /// taking turns between connections of equal priority
+    StrictPriority,
+}
+
+impl Default for TransmitScheduling {
+    fn default() -> Self {
+        Self::Fifo
+    }
+}
+
 /// Errors in the configuration of an endpoint
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -660,6 +1507,18 @@ pub enum ConfigError {
     /// Value exceeds supported bounds
     #[error("value exceeds supported bounds")]
     OutOfBounds,
+    /// The cryptographic configuration does not support the specified QUIC version
+    #[error("unsupported QUIC version")]
+    UnsupportedVersion,
+    /// The TLS configuration does not enable TLS 1.3, which QUIC requires
+    #[error("TLS 1.3 not enabled")]
+    Tls13Unsupported,
+    /// An ALPN protocol name is empty or longer than 255 bytes
+    #[error("invalid ALPN protocol")]
+    InvalidAlpn,
+    /// Transport settings contradict each other
+    #[error("inconsistent transport configuration: {0}")]
+    InconsistentTransport(&'static str),
 }
 
 impl From<TryFromIntError> for ConfigError {
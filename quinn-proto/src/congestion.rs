@@ -1,7 +1,10 @@
 //! Logic for controlling the rate at which data is sent
 
 use crate::connection::RttEstimator;
+use crate::Side;
 use std::any::Any;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Instant;
 
 mod bbr;
@@ -67,12 +70,67 @@ pub trait Controller: Send {
     /// Initial congestion window
     fn initial_window(&self) -> u64;
 
+    /// Smallest congestion window the controller will fall back to, however much loss occurs
+    ///
+    /// Raising the minimum can keep throughput from collapsing on links with heavy non-congestive
+    /// loss, such as some wireless networks, but makes the connection less responsive to real
+    /// congestion and so unfair to other flows sharing a bottleneck. Controllers which don't
+    /// enforce a minimum return 0.
+    fn minimum_window(&self) -> u64 {
+        0
+    }
+
+    /// The phase of congestion control the controller is in
+    ///
+    /// Controllers which don't distinguish phases return [`CongestionPhase::Unknown`].
+    fn phase(&self) -> CongestionPhase {
+        CongestionPhase::Unknown
+    }
+
     /// Returns Self for use in down-casting to extract implementation details
     fn into_any(self: Box<Self>) -> Box<dyn Any>;
 }
 
+/// Phase of a congestion controller, as reported by [`Controller::phase()`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CongestionPhase {
+    /// The window grows exponentially to quickly find the available capacity
+    ///
+    /// Reported by BBR while in its startup mode.
+    SlowStart,
+    /// The window grows slowly, or is held steady, after the available capacity has been found
+    ///
+    /// Reported by BBR in all modes after startup, unless in recovery.
+    CongestionAvoidance,
+    /// The window was reduced in response to loss or congestion signals, and won't grow until a
+    /// packet sent since is acknowledged
+    Recovery,
+    /// The controller doesn't report its phase
+    Unknown,
+}
+
 /// Constructs controllers on demand
 pub trait ControllerFactory {
     /// Construct a fresh `Controller`
     fn build(&self, now: Instant) -> Box<dyn Controller>;
 }
+
+/// Chooses the controller factory for a new connection, or `None` to use the one from its
+/// transport configuration
+///
+/// See [`EndpointConfig::congestion_controller_selector()`](crate::EndpointConfig::congestion_controller_selector).
+pub type ControllerSelector =
+    dyn Fn(&ConnectionProperties) -> Option<Arc<dyn ControllerFactory + Send + Sync>> + Send + Sync;
+
+/// What is known about a connection when its congestion controller is chosen
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ConnectionProperties<'a> {
+    /// Whether the connection was initiated locally or by the peer
+    pub side: Side,
+    /// The peer's address
+    pub remote: SocketAddr,
+    /// The server name passed when connecting, for client connections
+    pub server_name: Option<&'a str>,
+}
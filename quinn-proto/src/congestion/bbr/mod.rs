@@ -9,7 +9,7 @@ use crate::congestion::bbr::bw_estimation::BandwidthEstimation;
 use crate::congestion::bbr::min_max::MinMax;
 use crate::connection::RttEstimator;
 
-use super::{Controller, ControllerFactory};
+use super::{CongestionPhase, Controller, ControllerFactory};
 
 mod bw_estimation;
 mod min_max;
@@ -485,6 +485,20 @@ impl Controller for Bbr {
         self.config.initial_window
     }
 
+    fn minimum_window(&self) -> u64 {
+        self.config.minimum_window
+    }
+
+    fn phase(&self) -> CongestionPhase {
+        if self.recovery_state.in_recovery() {
+            CongestionPhase::Recovery
+        } else if self.mode == Mode::Startup {
+            CongestionPhase::SlowStart
+        } else {
+            CongestionPhase::CongestionAvoidance
+        }
+    }
+
     fn into_any(self: Box<Self>) -> Box<dyn Any> {
         self
     }
@@ -517,11 +531,22 @@ impl BbrConfig {
 
     /// Default minimum congestion window.
     ///
+    /// The window never drops below this, even under persistent congestion. See
+    /// [`Controller::minimum_window()`] for the tradeoffs of raising it.
+    ///
     /// Recommended value: `2 * max_datagram_size`.
     pub fn minimum_window(&mut self, value: u64) -> &mut Self {
         self.minimum_window = value;
         self
     }
+
+    /// Set [`minimum_window`](Self::minimum_window) to a number of packets
+    ///
+    /// Uses the current [`max_datagram_size`](Self::max_datagram_size), so set that first.
+    pub fn minimum_window_packets(&mut self, packets: u64) -> &mut Self {
+        self.minimum_window = packets * self.max_datagram_size;
+        self
+    }
 }
 
 impl Default for BbrConfig {
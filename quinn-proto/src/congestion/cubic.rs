@@ -2,7 +2,7 @@ use std::any::Any;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use super::{Controller, ControllerFactory};
+use super::{CongestionPhase, Controller, ControllerFactory};
 use crate::connection::RttEstimator;
 use std::cmp;
 
@@ -68,6 +68,8 @@ pub struct Cubic {
     /// The time when QUIC first detects a loss, causing it to enter recovery. When a packet sent
     /// after this time is acknowledged, QUIC exits recovery.
     recovery_start_time: Option<Instant>,
+    /// Whether the window was reduced and no packet sent since has been acknowledged yet
+    in_recovery: bool,
     cubic_state: State,
 }
 
@@ -78,6 +80,7 @@ impl Cubic {
             window: config.initial_window,
             ssthresh: u64::MAX,
             recovery_start_time: None,
+            in_recovery: false,
             config,
             cubic_state: Default::default(),
         }
@@ -93,6 +96,12 @@ impl Controller for Cubic {
         app_limited: bool,
         rtt: &RttEstimator,
     ) {
+        if self
+            .recovery_start_time
+            .map_or(true, |recovery_start_time| sent > recovery_start_time)
+        {
+            self.in_recovery = false;
+        }
         if app_limited
             || self
                 .recovery_start_time
@@ -176,6 +185,7 @@ impl Controller for Cubic {
         }
 
         self.recovery_start_time = Some(now);
+        self.in_recovery = true;
 
         // Fast convergence
         #[allow(clippy::branches_sharing_code)]
@@ -197,6 +207,7 @@ impl Controller for Cubic {
 
         if is_persistent_congestion {
             self.recovery_start_time = None;
+            self.in_recovery = false;
             self.cubic_state.w_max = self.window as f64;
 
             // 4.7 Timeout - reduce ssthresh based on BETA_CUBIC
@@ -223,6 +234,20 @@ impl Controller for Cubic {
         self.config.initial_window
     }
 
+    fn minimum_window(&self) -> u64 {
+        self.config.minimum_window
+    }
+
+    fn phase(&self) -> CongestionPhase {
+        if self.in_recovery {
+            CongestionPhase::Recovery
+        } else if self.window < self.ssthresh {
+            CongestionPhase::SlowStart
+        } else {
+            CongestionPhase::CongestionAvoidance
+        }
+    }
+
     fn into_any(self: Box<Self>) -> Box<dyn Any> {
         self
     }
@@ -255,11 +280,22 @@ impl CubicConfig {
 
     /// Default minimum congestion window.
     ///
+    /// The window never drops below this, even under persistent congestion. See
+    /// [`Controller::minimum_window()`] for the tradeoffs of raising it.
+    ///
     /// Recommended value: `2 * max_datagram_size`.
     pub fn minimum_window(&mut self, value: u64) -> &mut Self {
         self.minimum_window = value;
         self
     }
+
+    /// Set [`minimum_window`](Self::minimum_window) to a number of packets
+    ///
+    /// Uses the current [`max_datagram_size`](Self::max_datagram_size), so set that first.
+    pub fn minimum_window_packets(&mut self, packets: u64) -> &mut Self {
+        self.minimum_window = packets * self.max_datagram_size;
+        self
+    }
 }
 
 impl Default for CubicConfig {
@@ -2,7 +2,7 @@ use std::any::Any;
 use std::sync::Arc;
 use std::time::Instant;
 
-use super::{Controller, ControllerFactory};
+use super::{CongestionPhase, Controller, ControllerFactory};
 use crate::connection::RttEstimator;
 
 /// A simple, standard congestion controller
@@ -17,6 +17,8 @@ pub struct NewReno {
     /// The time when QUIC first detects a loss, causing it to enter recovery. When a packet sent
     /// after this time is acknowledged, QUIC exits recovery.
     recovery_start_time: Instant,
+    /// Whether no packet sent after `recovery_start_time` has been acknowledged yet
+    in_recovery: bool,
     /// Bytes which had been acked by the peer since leaving slow start
     bytes_acked: u64,
 }
@@ -28,6 +30,7 @@ impl NewReno {
             window: config.initial_window,
             ssthresh: u64::max_value(),
             recovery_start_time: now,
+            in_recovery: false,
             config,
             bytes_acked: 0,
         }
@@ -43,6 +46,9 @@ impl Controller for NewReno {
         app_limited: bool,
         _rtt: &RttEstimator,
     ) {
+        if sent > self.recovery_start_time {
+            self.in_recovery = false;
+        }
         if app_limited || sent <= self.recovery_start_time {
             return;
         }
@@ -88,6 +94,7 @@ impl Controller for NewReno {
         }
 
         self.recovery_start_time = now;
+        self.in_recovery = true;
         self.window = (self.window as f32 * self.config.loss_reduction_factor) as u64;
         self.window = self.window.max(self.config.minimum_window);
         self.ssthresh = self.window;
@@ -109,6 +116,20 @@ impl Controller for NewReno {
         self.config.initial_window
     }
 
+    fn minimum_window(&self) -> u64 {
+        self.config.minimum_window
+    }
+
+    fn phase(&self) -> CongestionPhase {
+        if self.in_recovery {
+            CongestionPhase::Recovery
+        } else if self.window < self.ssthresh {
+            CongestionPhase::SlowStart
+        } else {
+            CongestionPhase::CongestionAvoidance
+        }
+    }
+
     fn into_any(self: Box<Self>) -> Box<dyn Any> {
         self
     }
@@ -142,12 +163,23 @@ impl NewRenoConfig {
 
     /// Default minimum congestion window.
     ///
+    /// The window never drops below this, even under persistent congestion. See
+    /// [`Controller::minimum_window()`] for the tradeoffs of raising it.
+    ///
     /// Recommended value: `2 * max_datagram_size`.
     pub fn minimum_window(&mut self, value: u64) -> &mut Self {
         self.minimum_window = value;
         self
     }
 
+    /// Set [`minimum_window`](Self::minimum_window) to a number of packets
+    ///
+    /// Uses the current [`max_datagram_size`](Self::max_datagram_size), so set that first.
+    pub fn minimum_window_packets(&mut self, packets: u64) -> &mut Self {
+        self.minimum_window = packets * self.max_datagram_size;
+        self
+    }
+
     /// Reduction in congestion window when a new loss event is detected.
     pub fn loss_reduction_factor(&mut self, value: f32) -> &mut Self {
         self.loss_reduction_factor = value;
@@ -209,6 +209,30 @@ impl Assembler {
         self.bytes_read
     }
 
+    /// Number of buffered bytes which can be read immediately
+    ///
+    /// In ordered mode, this is the length of the contiguous data following the read offset. In
+    /// unordered mode, any buffered data can be read, and duplicates have already been discarded.
+    pub(crate) fn readable(&self) -> u64 {
+        if !self.state.is_ordered() {
+            return self.buffered as u64;
+        }
+        let mut ranges = self
+            .data
+            .iter()
+            .map(|chunk| (chunk.offset, chunk.offset + chunk.bytes.len() as u64))
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+        let mut end = self.bytes_read;
+        for (start, stop) in ranges {
+            if start > end {
+                break;
+            }
+            end = end.max(stop);
+        }
+        end - self.bytes_read
+    }
+
     /// Discard all buffered data
     pub(crate) fn clear(&mut self) {
         self.data.clear();
@@ -369,6 +393,27 @@ mod test {
         assert_matches!(next(&mut x, 32), None);
     }
 
+    #[test]
+    fn readable() {
+        let mut x = Assembler::new();
+        x.insert(3, Bytes::from_static(b"456"), 3);
+        assert_eq!(x.readable(), 0);
+        x.insert(0, Bytes::from_static(b"123"), 3);
+        assert_eq!(x.readable(), 6);
+        x.insert(1, Bytes::from_static(b"234"), 3);
+        x.insert(8, Bytes::from_static(b"9"), 1);
+        assert_eq!(x.readable(), 6);
+        assert_matches!(next(&mut x, 2), Some(ref y) if &y[..] == b"12");
+        assert_eq!(x.readable(), 4);
+
+        let mut x = Assembler::new();
+        x.ensure_ordering(false).unwrap();
+        x.insert(3, Bytes::from_static(b"456"), 3);
+        assert_eq!(x.readable(), 3);
+        x.insert(2, Bytes::from_static(b"34"), 2);
+        assert_eq!(x.readable(), 4);
+    }
+
     #[test]
     fn assemble_duplicate() {
         let mut x = Assembler::new();
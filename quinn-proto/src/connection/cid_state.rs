@@ -4,10 +4,10 @@ use std::{
     time::{Duration, Instant},
 };
 
-use rustc_hash::FxHashSet;
+use rustc_hash::FxHashMap;
 use tracing::{debug, trace};
 
-use crate::{shared::IssuedCid, TransportError};
+use crate::{shared::IssuedCid, ConnectionId, TransportError};
 
 /// Local connection ID management
 pub struct CidState {
@@ -15,8 +15,8 @@ pub struct CidState {
     retire_timestamp: VecDeque<CidTimestamp>,
     /// Number of local connection IDs that have been issued in NEW_CONNECTION_ID frames.
     issued: u64,
-    /// Sequence numbers of local connection IDs not yet retired by the peer
-    active_seq: FxHashSet<u64>,
+    /// Local connection IDs not yet retired by the peer, by sequence number
+    active_seq: FxHashMap<u64, ConnectionId>,
     /// Sequence number the peer has already retired all CIDs below at our request via `retire_prior_to`
     prev_retire_seq: u64,
     /// Sequence number to set in retire_prior_to field in NEW_CONNECTION_ID frame
@@ -28,10 +28,15 @@ pub struct CidState {
 }
 
 impl CidState {
-    pub(crate) fn new(cid_len: usize, cid_lifetime: Option<Duration>, now: Instant) -> Self {
-        let mut active_seq = FxHashSet::default();
-        // Add sequence number of CID used in handshaking into tracking set
-        active_seq.insert(0);
+    pub(crate) fn new(
+        initial_cid: ConnectionId,
+        cid_len: usize,
+        cid_lifetime: Option<Duration>,
+        now: Instant,
+    ) -> Self {
+        let mut active_seq = FxHashMap::default();
+        // Add CID used in handshaking into tracking set
+        active_seq.insert(0, initial_cid);
         let mut this = CidState {
             retire_timestamp: VecDeque::new(),
             issued: 1, // One CID is already supplied during handshaking
@@ -90,7 +95,7 @@ impl CidState {
     pub(crate) fn on_cid_timeout(&mut self) -> bool {
         // Whether the peer hasn't retired all the CIDs we asked it to yet
         let unretired_ids_found =
-            (self.prev_retire_seq..self.retire_seq).any(|seq| self.active_seq.contains(&seq));
+            (self.prev_retire_seq..self.retire_seq).any(|seq| self.active_seq.contains_key(&seq));
 
         let current_retire_prior_to = self.retire_seq;
         let next_retire_sequence = self
@@ -122,7 +127,7 @@ impl CidState {
         //
         // If yes (return true), a new CID must be pushed with updated `retire_prior_to` field to remote peer.
         // If no (return false), it means CIDs that reach the end of lifetime have been retired already. Do not push a new CID in order to avoid violating above RFC.
-        (current_retire_prior_to..self.retire_seq).any(|seq| self.active_seq.contains(&seq))
+        (current_retire_prior_to..self.retire_seq).any(|seq| self.active_seq.contains_key(&seq))
     }
 
     /// Update cid state when `NewIdentifiers` event is received
@@ -136,7 +141,7 @@ impl CidState {
         // Record the timestamp of CID with the largest seq number
         let sequence = last_cid.sequence;
         ids.iter().for_each(|frame| {
-            self.active_seq.insert(frame.sequence);
+            self.active_seq.insert(frame.sequence, frame.id);
         });
         self.track_lifetime(sequence, now);
     }
@@ -172,6 +177,13 @@ impl CidState {
         Ok(limit > self.active_seq.len() as u64)
     }
 
+    /// Local connection IDs not yet retired by the peer, in the order they were issued
+    pub(crate) fn active_cids(&self) -> Vec<ConnectionId> {
+        let mut cids = self.active_seq.iter().collect::<Vec<_>>();
+        cids.sort_unstable_by_key(|&(&seq, _)| seq);
+        cids.into_iter().map(|(_, &cid)| cid).collect()
+    }
+
     /// Length of local Connection IDs
     pub(crate) fn cid_len(&self) -> usize {
         self.cid_len
@@ -186,7 +198,7 @@ impl CidState {
     pub(crate) fn active_seq(&self) -> (u64, u64) {
         let mut min = u64::MAX;
         let mut max = u64::MIN;
-        for n in self.active_seq.iter() {
+        for n in self.active_seq.keys() {
             if n < &min {
                 min = *n;
             }
@@ -200,7 +212,7 @@ impl CidState {
     #[cfg(test)]
     pub(crate) fn assign_retire_seq(&mut self, v: u64) -> u64 {
         // Cannot retire more CIDs than what have been issued
-        debug_assert!(v <= *self.active_seq.iter().max().unwrap() + 1);
+        debug_assert!(v <= *self.active_seq.keys().max().unwrap() + 1);
         let n = v.checked_sub(self.retire_seq).unwrap();
         self.retire_seq = v;
         n
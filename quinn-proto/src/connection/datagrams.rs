@@ -19,8 +19,10 @@ pub struct Datagrams<'a> {
 impl<'a> Datagrams<'a> {
     /// Queue an unreliable, unordered datagram for immediate transmission
     ///
-    /// Returns `Err` iff a `len`-byte datagram cannot currently be sent
-    pub fn send(&mut self, data: Bytes) -> Result<(), SendDatagramError> {
+    /// Returns `Err` iff a `len`-byte datagram cannot currently be sent. Datagrams larger than
+    /// [`max_size()`](Self::max_size) are handled according to the connection's
+    /// [`OversizedDatagramPolicy`].
+    pub fn send(&mut self, mut data: Bytes) -> Result<(), SendDatagramError> {
         if self.conn.config.datagram_receive_buffer_size.is_none() {
             return Err(SendDatagramError::Disabled);
         }
@@ -36,9 +38,21 @@ impl<'a> Datagrams<'a> {
                 .expect("datagrams.outgoing_total desynchronized");
             trace!(len = prev.data.len(), "dropping outgoing datagram");
             self.conn.datagrams.outgoing_total -= prev.data.len();
+            self.conn.datagrams.send_dropped += 1;
         }
         if data.len() > max {
-            return Err(SendDatagramError::TooLarge);
+            self.conn.datagrams.too_large += 1;
+            match self.conn.datagrams.oversized_policy {
+                OversizedDatagramPolicy::Reject => return Err(SendDatagramError::TooLarge),
+                OversizedDatagramPolicy::Drop => {
+                    trace!(len = data.len(), max, "dropping oversized datagram");
+                    return Ok(());
+                }
+                OversizedDatagramPolicy::Truncate => {
+                    trace!(len = data.len(), max, "truncating oversized datagram");
+                    data.truncate(max);
+                }
+            }
         }
         self.conn.datagrams.outgoing_total += data.len();
         self.conn.datagrams.outgoing.push_back(Datagram { data });
@@ -55,6 +69,13 @@ impl<'a> Datagrams<'a> {
     ///
     /// Not necessarily the maximum size of received datagrams.
     pub fn max_size(&self) -> Option<usize> {
+        // Datagrams may be disabled locally
+        self.conn.config.datagram_receive_buffer_size?;
+        let peer_limit = self.conn.peer_params.max_datagram_frame_size?.into_inner();
+        if peer_limit == 0 {
+            // A zero limit is equivalent to omitting the transport parameter
+            return None;
+        }
         // This is usually 1162 bytes, but we shouldn't document that without a doctest.
         let max_size = self.conn.path.max_udp_payload_size as usize
             - 1                 // flags byte
@@ -62,20 +83,74 @@ impl<'a> Datagrams<'a> {
             - 4                 // worst-case packet number size
             - self.conn.spaces[SpaceId::Data].crypto.as_ref().map_or_else(|| &self.conn.zero_rtt_crypto.as_ref().unwrap().packet, |x| &x.packet.local).tag_len()
             - Datagram::SIZE_BOUND;
-        let limit = self
-            .conn
-            .peer_params
-            .max_datagram_frame_size?
-            .into_inner()
-            .saturating_sub(Datagram::SIZE_BOUND as u64);
+        let limit = peer_limit.saturating_sub(Datagram::SIZE_BOUND as u64);
         Some(limit.min(max_size as u64) as usize)
     }
 
+    /// Whether datagrams may be sent to the peer
+    ///
+    /// Requires the peer to have advertised a nonzero `max_datagram_frame_size`, so this is `false`
+    /// until its transport parameters are known. Also `false` if datagrams are disabled locally,
+    /// since [`send()`](Self::send) then fails regardless of the peer's support.
+    pub fn can_send(&self) -> bool {
+        self.max_size().is_some()
+    }
+
+    /// Whether the peer may send datagrams to us
+    ///
+    /// Determined by the nonzero `max_datagram_frame_size` we advertise, per
+    /// [`TransportConfig::datagram_receive_buffer_size()`], independent of the peer's own support.
+    ///
+    /// [`TransportConfig::datagram_receive_buffer_size()`]: crate::TransportConfig::datagram_receive_buffer_size
+    pub fn can_receive(&self) -> bool {
+        self.conn
+            .config
+            .datagram_receive_buffer_size
+            .map_or(false, |x| x > 0)
+    }
+
     /// Receive an unreliable, unordered datagram
     pub fn recv(&mut self) -> Option<Bytes> {
         self.conn.datagrams.recv()
     }
 
+    /// Number of received datagrams buffered but not yet read by the application
+    pub fn recv_queue_len(&self) -> usize {
+        self.conn.datagrams.incoming.len()
+    }
+
+    /// Limit the number of received datagrams buffered for the application, overriding
+    /// [`TransportConfig::datagram_receive_buffer_capacity()`] and
+    /// [`TransportConfig::datagram_drop_policy()`]
+    ///
+    /// `None` leaves the number of datagrams unlimited, subject only to the receive buffer size;
+    /// a capacity of 0 is treated as 1. Datagrams already buffered in excess of `capacity` are
+    /// dropped immediately according to `policy`.
+    ///
+    /// [`TransportConfig::datagram_receive_buffer_capacity()`]: crate::TransportConfig::datagram_receive_buffer_capacity
+    /// [`TransportConfig::datagram_drop_policy()`]: crate::TransportConfig::datagram_drop_policy
+    pub fn set_recv_buffer(&mut self, capacity: Option<usize>, policy: DatagramDropPolicy) {
+        let datagrams = &mut self.conn.datagrams;
+        datagrams.recv_capacity = capacity;
+        datagrams.drop_policy = policy;
+        while datagrams.incoming.len() > datagrams.capacity() {
+            let dropped = match policy {
+                DatagramDropPolicy::DropOldest => datagrams.incoming.pop_front(),
+                DatagramDropPolicy::DropNewest => datagrams.incoming.pop_back(),
+            };
+            datagrams.recv_buffered -= dropped.unwrap().data.len();
+            datagrams.recv_dropped += 1;
+        }
+    }
+
+    /// Choose how datagrams larger than [`max_size()`](Self::max_size) are handled, overriding
+    /// [`TransportConfig::oversized_datagram_policy()`]
+    ///
+    /// [`TransportConfig::oversized_datagram_policy()`]: crate::TransportConfig::oversized_datagram_policy
+    pub fn set_oversized_policy(&mut self, policy: OversizedDatagramPolicy) {
+        self.conn.datagrams.oversized_policy = policy;
+    }
+
     /// Bytes available in the outgoing datagram buffer
     ///
     /// When greater than zero, [`send`](Self::send)ing a datagram of at most this size is
@@ -96,6 +171,24 @@ pub(super) struct DatagramState {
     pub(super) incoming: VecDeque<Datagram>,
     pub(super) outgoing: VecDeque<Datagram>,
     pub(super) outgoing_total: usize,
+    /// Number of received datagrams dropped because the receive buffer was full
+    pub(super) recv_dropped: u64,
+    /// Number of datagrams received from the peer
+    pub(super) received: u64,
+    /// Number of datagrams transmitted to the peer
+    pub(super) sent: u64,
+    /// Number of queued outgoing datagrams dropped because the send buffer was full
+    pub(super) send_dropped: u64,
+    /// Number of datagrams the application failed to send because they were too large
+    pub(super) too_large: u64,
+    /// Maximum number of received datagrams to buffer, if limited
+    pub(super) recv_capacity: Option<usize>,
+    /// Which datagrams to drop when the receive buffer is full
+    pub(super) drop_policy: DatagramDropPolicy,
+    /// What to do with outgoing datagrams which are too large to send
+    pub(super) oversized_policy: OversizedDatagramPolicy,
+    /// Number of datagrams written in the transmit currently being assembled
+    pub(super) sent_in_transmit: usize,
 }
 
 impl DatagramState {
@@ -116,11 +209,22 @@ impl DatagramState {
         if datagram.data.len() > window {
             return Err(TransportError::PROTOCOL_VIOLATION("oversized datagram"));
         }
+        self.received += 1;
 
         let was_empty = self.recv_buffered == 0;
-        while datagram.data.len() + self.recv_buffered > window {
+        let capacity = self.capacity();
+        if self.drop_policy == DatagramDropPolicy::DropNewest
+            && (datagram.data.len() + self.recv_buffered > window
+                || self.incoming.len() >= capacity)
+        {
+            debug!("dropping new datagram");
+            self.recv_dropped += 1;
+            return Ok(false);
+        }
+        while datagram.data.len() + self.recv_buffered > window || self.incoming.len() >= capacity {
             debug!("dropping stale datagram");
             self.recv();
+            self.recv_dropped += 1;
         }
 
         self.recv_buffered += datagram.data.len();
@@ -142,10 +246,17 @@ impl DatagramState {
         }
 
         self.outgoing_total -= datagram.data.len();
+        self.sent_in_transmit += 1;
+        self.sent += 1;
         datagram.encode(true, buf);
         true
     }
 
+    /// Maximum number of received datagrams to buffer
+    fn capacity(&self) -> usize {
+        self.recv_capacity.map_or(usize::MAX, |x| x.max(1))
+    }
+
     pub fn recv(&mut self) -> Option<Bytes> {
         let x = self.incoming.pop_front()?.data;
         self.recv_buffered -= x.len();
@@ -153,6 +264,51 @@ impl DatagramState {
     }
 }
 
+/// Which received datagrams to drop when the receive buffer is full
+///
+/// See [`TransportConfig::datagram_drop_policy()`](crate::TransportConfig::datagram_drop_policy).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DatagramDropPolicy {
+    /// Drop the oldest buffered datagrams to make room for new ones
+    ///
+    /// Suits real-time applications which only care about the most recent data.
+    DropOldest,
+    /// Drop newly received datagrams, preserving those already buffered
+    DropNewest,
+}
+
+impl Default for DatagramDropPolicy {
+    fn default() -> Self {
+        Self::DropOldest
+    }
+}
+
+/// How to handle outgoing application datagrams larger than the connection can currently send
+///
+/// See [`TransportConfig::oversized_datagram_policy()`](crate::TransportConfig::oversized_datagram_policy).
+/// Oversized datagrams are counted in [`ConnectionStats`](crate::ConnectionStats)'
+/// `datagrams.too_large` regardless of the policy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OversizedDatagramPolicy {
+    /// Fail with [`SendDatagramError::TooLarge`]
+    ///
+    /// Lets the application split the data according to
+    /// [`Datagrams::max_size()`](crate::Datagrams::max_size) and try again.
+    Reject,
+    /// Discard the datagram, reporting success
+    ///
+    /// Suits applications which treat datagrams as lossy anyway.
+    Drop,
+    /// Send as much of the start of the datagram as fits
+    Truncate,
+}
+
+impl Default for OversizedDatagramPolicy {
+    fn default() -> Self {
+        Self::Reject
+    }
+}
+
 /// Errors that can arise when sending a datagram
 #[derive(Debug, Error, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum SendDatagramError {
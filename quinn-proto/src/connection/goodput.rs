@@ -0,0 +1,74 @@
+//! Tracking of recently acknowledged application data
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Period over which goodput is averaged
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Granularity at which acknowledged bytes are recorded
+const BUCKET: Duration = Duration::from_millis(100);
+
+/// Stream data newly acknowledged by the peer within the last [`WINDOW`]
+///
+/// Unlike cumulative byte counters, this reflects the rate at which the application's data is
+/// currently getting through.
+#[derive(Debug, Default)]
+pub(super) struct GoodputWindow {
+    /// Bytes acknowledged since each bucket's start time, oldest first
+    buckets: VecDeque<(Instant, u64)>,
+}
+
+impl GoodputWindow {
+    /// Record that `bytes` of stream data were acknowledged for the first time
+    pub(super) fn record(&mut self, now: Instant, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        while self
+            .buckets
+            .front()
+            .map_or(false, |&(start, _)| start + WINDOW <= now)
+        {
+            self.buckets.pop_front();
+        }
+        match self.buckets.back_mut() {
+            Some((start, acked)) if now < *start + BUCKET => *acked += bytes,
+            _ => self.buckets.push_back((now, bytes)),
+        }
+    }
+
+    /// Bytes acknowledged per second, averaged over the window ending at `now`
+    pub(super) fn rate(&self, now: Instant) -> u64 {
+        let acked = self
+            .buckets
+            .iter()
+            .filter(|&&(start, _)| start + WINDOW > now)
+            .map(|&(_, acked)| acked)
+            .sum::<u64>();
+        (acked as f64 / WINDOW.as_secs_f64()) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sliding() {
+        let start = Instant::now();
+        let mut window = GoodputWindow::default();
+        assert_eq!(window.rate(start), 0);
+        window.record(start, 1000);
+        window.record(start + Duration::from_millis(50), 1000);
+        window.record(start + Duration::from_millis(500), 1000);
+        assert_eq!(window.rate(start + Duration::from_millis(500)), 3000);
+        // Older data ages out of the window
+        assert_eq!(window.rate(start + WINDOW), 1000);
+        assert_eq!(window.rate(start + WINDOW * 2), 0);
+        window.record(start + WINDOW * 2, 500);
+        assert_eq!(window.buckets.len(), 1);
+    }
+}
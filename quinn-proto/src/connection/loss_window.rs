@@ -0,0 +1,55 @@
+//! Tracking of recent packet loss
+
+use std::collections::VecDeque;
+
+/// Number of packets whose fate is remembered
+const WINDOW_SIZE: usize = 256;
+
+/// The outcomes of the most recent packets whose delivery has been determined
+///
+/// Unlike cumulative loss counters, this reflects current network conditions on long-lived
+/// connections.
+#[derive(Debug, Default)]
+pub(super) struct LossWindow {
+    /// Whether each packet was lost, oldest first
+    outcomes: VecDeque<bool>,
+    /// Number of `true` entries in `outcomes`
+    lost: usize,
+}
+
+impl LossWindow {
+    /// Record that a packet was either acknowledged or declared lost
+    pub(super) fn record(&mut self, lost: bool) {
+        if self.outcomes.len() == WINDOW_SIZE && self.outcomes.pop_front() == Some(true) {
+            self.lost -= 1;
+        }
+        self.outcomes.push_back(lost);
+        self.lost += lost as usize;
+    }
+
+    /// Fraction of recent packets which were lost, or 0 if none have been recorded
+    pub(super) fn rate(&self) -> f32 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+        self.lost as f32 / self.outcomes.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sliding() {
+        let mut window = LossWindow::default();
+        assert_eq!(window.rate(), 0.0);
+        window.record(true);
+        window.record(false);
+        assert_eq!(window.rate(), 0.5);
+        for _ in 0..WINDOW_SIZE {
+            window.record(false);
+        }
+        assert_eq!(window.rate(), 0.0);
+    }
+}
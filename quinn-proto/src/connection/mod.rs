@@ -5,7 +5,7 @@ use std::{
     fmt, io, mem,
     net::{IpAddr, SocketAddr},
     sync::Arc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
 use bytes::{Bytes, BytesMut};
@@ -19,6 +19,7 @@ use crate::{
     cid_queue::CidQueue,
     coding::BufMutExt,
     config::{ServerConfig, TransportConfig},
+    congestion,
     crypto::{self, HeaderKey, KeyPair, Keys, PacketKey},
     frame,
     frame::{Close, Datagram, FrameStruct},
@@ -28,7 +29,7 @@ use crate::{
         ConnectionEvent, ConnectionEventInner, ConnectionId, EcnCodepoint, EndpointEvent,
         EndpointEventInner,
     },
-    token::ResetToken,
+    token::{NewToken, ResetToken, RetryToken},
     transport_parameters::TransportParameters,
     Dir, EndpointConfig, Frame, Side, StreamId, Transmit, TransportError, TransportErrorCode,
     VarInt, MAX_STREAM_COUNT, MIN_INITIAL_SIZE, RESET_TOKEN_SIZE, TIMER_GRANULARITY,
@@ -42,7 +43,13 @@ use cid_state::CidState;
 
 mod datagrams;
 use datagrams::DatagramState;
-pub use datagrams::{Datagrams, SendDatagramError};
+pub use datagrams::{DatagramDropPolicy, Datagrams, OversizedDatagramPolicy, SendDatagramError};
+
+mod goodput;
+use goodput::GoodputWindow;
+
+mod loss_window;
+use loss_window::LossWindow;
 
 mod pacing;
 
@@ -51,7 +58,7 @@ use packet_builder::PacketBuilder;
 
 mod paths;
 use paths::PathData;
-pub use paths::RttEstimator;
+pub use paths::{EcnState, EcnValidationPolicy, RttEstimator};
 
 mod send_buffer;
 
@@ -63,7 +70,10 @@ use spaces::Retransmits;
 use spaces::{PacketSpace, SendableFrames, SentPacket, ThinRetransmits};
 
 mod stats;
-pub use stats::ConnectionStats;
+pub use stats::{ConnectionStats, FlowControlStats, StreamDataStats};
+
+mod stream_rate;
+use stream_rate::StreamRateLimiter;
 
 mod streams;
 #[cfg(fuzzing)]
@@ -72,11 +82,12 @@ pub use streams::StreamsState;
 use streams::StreamsState;
 pub use streams::{
     ByteSlice, BytesArray, BytesSource, Chunks, FinishError, ReadError, ReadableError, RecvStream,
-    SendStream, ShouldTransmit, StreamEvent, Streams, UnknownStream, WriteError, Written,
+    SendStream, ShouldTransmit, StreamAdmission, StreamEvent, StreamLimits, StreamProgress,
+    Streams, UnknownStream, WriteError, Written,
 };
 
 mod timer;
-use crate::congestion::Controller;
+use crate::congestion::{CongestionPhase, Controller};
 use timer::{Timer, TimerTable};
 
 /// Protocol state and logic for a single QUIC connection
@@ -122,6 +133,8 @@ pub struct Connection {
     endpoint_config: Arc<EndpointConfig>,
     server_config: Option<Arc<ServerConfig>>,
     config: Arc<TransportConfig>,
+    /// Overrides the factory in `config`, as chosen by the endpoint's controller selector
+    congestion_controller_factory: Option<Arc<dyn congestion::ControllerFactory + Send + Sync>>,
     rng: StdRng,
     crypto: Box<dyn crypto::Session>,
     /// The CID we initially chose, for use during the handshake
@@ -131,6 +144,15 @@ pub struct Connection {
     /// The "real" local IP address which was was used to receive the initial packet.
     /// This is only populated for the server case, and if known
     local_ip: Option<IpAddr>,
+    /// Attached to every outgoing `Transmit` for interpretation by the socket
+    transmit_metadata: Option<Bytes>,
+    /// Attached to every outgoing `Transmit` as the datagram's priority
+    socket_priority: Option<u32>,
+    /// Bytes that couldn't be sent due to the anti-amplification limit
+    amplification_withheld: u64,
+    /// End of the handshake data already counted in `amplification_withheld` for each space, so
+    /// that data held back more than once, or retransmitted, isn't counted again
+    amplification_counted: [u64; 3],
     path: PathData,
     prev_path: Option<PathData>,
     state: State,
@@ -140,6 +162,8 @@ pub struct Connection {
     /// Set if 0-RTT is supported, then cleared when no longer needed.
     zero_rtt_crypto: Option<ZeroRttCrypto>,
     key_phase: bool,
+    /// Number of 1-RTT key updates, whether initiated locally or by the peer
+    key_updates: u64,
     /// Transport parameters set by the peer
     peer_params: TransportParameters,
     /// Source ConnectionId of the first packet received from the peer
@@ -151,6 +175,12 @@ pub struct Connection {
     retry_src_cid: Option<ConnectionId>,
     /// Total number of outgoing packets that have been deemed lost
     lost_packets: u64,
+    /// Outcomes of recently sent packets
+    recent_losses: LossWindow,
+    /// Stream data recently acknowledged by the peer
+    goodput: GoodputWindow,
+    /// Number of migrations to a new path which were validated
+    migrations: u64,
     events: VecDeque<Event>,
     endpoint_events: VecDeque<EndpointEventInner>,
     /// Whether the spin bit is in use for this connection
@@ -161,6 +191,14 @@ pub struct Connection {
     spaces: [PacketSpace; 3],
     /// Highest usable packet number space
     highest_space: SpaceId,
+    /// The most recently observed encryption level, and when it was reached
+    encryption_level: (EncryptionLevel, Instant),
+    /// When a packet was last authenticated, or when the connection was created
+    last_packet_received: Instant,
+    /// Bound on how long this endpoint may delay acknowledgements
+    max_ack_delay: Duration,
+    /// Fixed congestion window used in place of the congestion controller's, if any
+    congestion_window_override: Option<u64>,
     /// 1-RTT keys used prior to a key update
     prev_crypto: Option<PrevCrypto>,
     /// 1-RTT keys to be used for the next key update
@@ -169,6 +207,8 @@ pub struct Connection {
     /// spoofing key updates.
     next_crypto: Option<KeyPair<Box<dyn PacketKey>>>,
     accepted_0rtt: bool,
+    /// Whether 0-RTT was rejected but its stream data is being sent again at 1-RTT
+    retransmitted_0rtt: bool,
     /// Whether the idle timer should be reset the next time an ack-eliciting packet is transmitted.
     permit_idle_reset: bool,
     /// Negotiated idle timeout
@@ -178,6 +218,8 @@ pub struct Connection {
     authentication_failures: u64,
     /// Why the connection was lost, if it has been
     error: Option<ConnectionError>,
+    /// The most recent transport errors detected locally, oldest first
+    recent_errors: VecDeque<TransportError>,
     /// Sent in every outgoing Initial packet. Always empty for servers and after Initial keys are
     /// discarded.
     retry_token: Bytes,
@@ -185,7 +227,7 @@ pub struct Connection {
     //
     // Queued non-retransmittable 1-RTT data
     //
-    path_response: Option<PathResponse>,
+    path_responses: VecDeque<PathResponse>,
     close: bool,
 
     //
@@ -206,8 +248,17 @@ pub struct Connection {
     /// Whether the last `poll_transmit` call yielded no data because there was
     /// no outgoing application data.
     app_limited: bool,
+    /// Whether the last `poll_transmit` call was prevented from sending more by congestion
+    /// control or pacing
+    congestion_blocked: bool,
 
     streams: StreamsState,
+    /// Interval at which to send keep-alives, initialized from the `TransportConfig`
+    keep_alive_interval: Option<Duration>,
+    /// Policy for streams opened by the peer, and the error code used to refuse them
+    stream_admission: Option<(Box<dyn StreamAdmission>, VarInt)>,
+    /// Limit on the rate at which the peer opens streams
+    stream_rate: Option<StreamRateLimiter>,
     /// Surplus remote CIDs for future use on new paths
     rem_cids: CidQueue,
     // Attributes of CIDs generated by local peer
@@ -234,7 +285,12 @@ impl Connection {
         cid_gen: &dyn ConnectionIdGenerator,
         now: Instant,
         version: u32,
+        congestion_controller_factory: Option<Arc<dyn congestion::ControllerFactory + Send + Sync>>,
     ) -> Self {
+        let controller = match congestion_controller_factory {
+            Some(ref factory) => factory.build(now),
+            None => config.congestion_controller_factory.build(now),
+        };
         let side = if server_config.is_some() {
             Side::Server
         } else {
@@ -254,52 +310,68 @@ impl Connection {
         let mut this = Self {
             endpoint_config,
             server_config,
+            congestion_controller_factory,
             crypto,
             handshake_cid: loc_cid,
             rem_handshake_cid: rem_cid,
-            local_cid_state: CidState::new(cid_gen.cid_len(), cid_gen.cid_lifetime(), now),
+            local_cid_state: CidState::new(loc_cid, cid_gen.cid_len(), cid_gen.cid_lifetime(), now),
             path: PathData::new(
                 remote,
                 config.initial_rtt,
-                config.congestion_controller_factory.build(now),
+                controller,
                 config.initial_max_udp_payload_size,
                 now,
                 path_validated,
             ),
             local_ip,
+            transmit_metadata: None,
+            socket_priority: None,
+            amplification_withheld: 0,
+            amplification_counted: [0; 3],
             prev_path: None,
             side,
             state,
             zero_rtt_enabled: false,
             zero_rtt_crypto: None,
             key_phase: false,
+            key_updates: 0,
             peer_params: TransportParameters::default(),
             orig_rem_cid: rem_cid,
             initial_dst_cid: init_cid,
             retry_src_cid: None,
             lost_packets: 0,
+            recent_losses: LossWindow::default(),
+            goodput: GoodputWindow::default(),
+            migrations: 0,
             events: VecDeque::new(),
             endpoint_events: VecDeque::new(),
             spin_enabled: config.allow_spin && rng.gen_ratio(7, 8),
             spin: false,
             spaces: [initial_space, PacketSpace::new(now), PacketSpace::new(now)],
             highest_space: SpaceId::Initial,
+            encryption_level: (EncryptionLevel::Initial, now),
+            last_packet_received: now,
+            max_ack_delay: config.max_ack_delay,
+            congestion_window_override: None,
             prev_crypto: None,
             next_crypto: None,
             accepted_0rtt: false,
+            retransmitted_0rtt: false,
             permit_idle_reset: true,
             idle_timeout: config.max_idle_timeout,
             timers: TimerTable::default(),
             authentication_failures: 0,
+            recent_errors: VecDeque::new(),
             error: None,
             retry_token: Bytes::new(),
 
-            path_response: None,
+            path_responses: VecDeque::new(),
             close: false,
 
             pto_count: 0,
 
             app_limited: false,
+            congestion_blocked: false,
             in_flight: InFlight::new(),
             receiving_ecn: false,
             total_authed_packets: 0,
@@ -310,9 +382,22 @@ impl Connection {
                 config.max_concurrent_bidi_streams,
                 config.send_window,
                 config.receive_window,
-                config.stream_receive_window,
+                config.stream_receive_windows(),
             ),
-            datagrams: DatagramState::default(),
+            datagrams: DatagramState {
+                recv_capacity: config.datagram_receive_buffer_capacity,
+                drop_policy: config.datagram_drop_policy,
+                oversized_policy: config.oversized_datagram_policy,
+                ..DatagramState::default()
+            },
+            keep_alive_interval: config.keep_alive_interval,
+            stream_admission: config
+                .stream_admission
+                .as_ref()
+                .map(|(factory, code)| (factory(), *code)),
+            stream_rate: config
+                .max_remote_stream_rate
+                .map(|(count, interval)| StreamRateLimiter::new(count, interval)),
             config,
             rem_cids: CidQueue::new(rem_cid),
             rng,
@@ -323,6 +408,7 @@ impl Connection {
             // Kick off the connection
             this.write_crypto();
             this.init_0rtt();
+            this.update_encryption_level(now);
         }
         this
     }
@@ -414,6 +500,15 @@ impl Connection {
         let max_datagrams = max_datagrams.min(MAX_TRANSMIT_SEGMENTS);
 
         let mut num_datagrams = 0;
+        self.datagrams.sent_in_transmit = 0;
+
+        if self.path.ecn_retry_at.map_or(false, |t| t <= now) {
+            debug!("resuming ECN");
+            self.path.ecn_retry_at = None;
+            self.path.sending_ecn = true;
+            self.path.ecn_validated = false;
+            self.path.first_ecn_packet = self.spaces[SpaceId::Data].next_packet_number;
+        }
 
         // Send PATH_CHALLENGE for a previous path if necessary
         if let Some(ref mut prev_path) = self.prev_path {
@@ -462,6 +557,8 @@ impl Connection {
                     ecn: None,
                     segment_size: None,
                     src_ip: self.local_ip,
+                    metadata: self.transmit_metadata.clone(),
+                    priority: self.socket_priority,
                 });
             }
         }
@@ -475,6 +572,7 @@ impl Connection {
         let close = match self.state {
             State::Drained => {
                 self.app_limited = true;
+                self.congestion_blocked = false;
                 return None;
             }
             State::Draining | State::Closed(_) => {
@@ -482,6 +580,7 @@ impl Connection {
                 // encoded successfully
                 if !self.close {
                     self.app_limited = true;
+                    self.congestion_blocked = false;
                     return None;
                 }
                 true
@@ -557,6 +656,7 @@ impl Connection {
                     self.path.max_udp_payload_size as u64 * num_datagrams as u64 + 1,
                 ) {
                     trace!("blocked by anti-amplification");
+                    self.count_amplification_withheld();
                     break;
                 }
 
@@ -572,7 +672,7 @@ impl Connection {
                     debug_assert!(untracked_bytes <= self.path.max_udp_payload_size as u64);
 
                     let bytes_to_send = u64::from(self.path.max_udp_payload_size) + untracked_bytes;
-                    if self.in_flight.bytes + bytes_to_send >= self.path.congestion.window() {
+                    if self.in_flight.bytes + bytes_to_send >= self.congestion_window_limit() {
                         space_idx += 1;
                         congestion_blocked = true;
                         // We continue instead of breaking here in order to avoid
@@ -586,7 +686,7 @@ impl Connection {
                         smoothed_rtt,
                         bytes_to_send,
                         self.path.max_udp_payload_size,
-                        self.path.congestion.window(),
+                        self.congestion_window_limit(),
                         now,
                     ) {
                         self.timers.set(Timer::Pacing, delay);
@@ -619,9 +719,15 @@ impl Connection {
                     // (e.g. purely containing ACKs), modern memory allocators
                     // (e.g. mimalloc and jemalloc) will pool certain allocation sizes
                     // and therefore this is still rather efficient.
-                    buf.reserve(
-                        max_datagrams * self.path.max_udp_payload_size as usize - buf.capacity(),
-                    );
+                    let capacity = max_datagrams * self.path.max_udp_payload_size as usize;
+                    match self.endpoint_config.buffer_pool {
+                        // The first datagram's buffer comes from the pool, if any
+                        Some(ref pool) if buf.capacity() == 0 => {
+                            buf = pool.allocate(capacity);
+                            buf.clear();
+                        }
+                        _ => buf.reserve(capacity - buf.capacity()),
+                    }
                 }
                 num_datagrams += 1;
                 coalesce = true;
@@ -773,6 +879,10 @@ impl Connection {
         }
 
         self.app_limited = buf.is_empty() && !congestion_blocked;
+        if congestion_blocked && !self.congestion_blocked {
+            self.stats.path.congestion_blocked += 1;
+        }
+        self.congestion_blocked = congestion_blocked;
 
         if buf.is_empty() {
             return None;
@@ -798,9 +908,26 @@ impl Connection {
                 _ => Some(self.path.max_udp_payload_size as usize),
             },
             src_ip: self.local_ip,
+            metadata: self.transmit_metadata.clone(),
+            priority: self.socket_priority,
         })
     }
 
+    /// Add the handshake data held back by the anti-amplification limit to
+    /// `amplification_withheld`, skipping whatever was counted before
+    fn count_amplification_withheld(&mut self) {
+        for space_id in SpaceId::iter() {
+            let counted = &mut self.amplification_counted[space_id as usize];
+            for frame in &self.spaces[space_id].pending.crypto {
+                let end = frame.offset + frame.data.len() as u64;
+                if end > *counted {
+                    self.amplification_withheld += end - frame.offset.max(*counted);
+                    *counted = end;
+                }
+            }
+        }
+    }
+
     /// Indicate what types of frames are ready to send for the given space
     fn space_can_send(&self, space_id: SpaceId) -> SendableFrames {
         if self.spaces[space_id].crypto.is_some() {
@@ -851,9 +978,13 @@ impl Connection {
                 // forbids migration, drop the datagram. This could be relaxed to heuristically
                 // permit NAT-rebinding-like migration.
                 if remote != self.path.remote
-                    && self.server_config.as_ref().map_or(true, |x| !x.migration)
+                    && self
+                        .server_config
+                        .as_ref()
+                        .map_or(true, |x| !x.migration && !x.close_on_migration)
                 {
                     trace!("discarding packet from unrecognized peer {}", remote);
+                    self.stats.path.migrations_refused += 1;
                     return;
                 }
 
@@ -881,6 +1012,7 @@ impl Connection {
                     // the server's first flight is lost.
                     self.set_loss_detection_timer(now);
                 }
+                self.update_encryption_level(now);
             }
             NewIdentifiers(ids, now) => {
                 self.local_cid_state.new_cids(&ids, now);
@@ -996,11 +1128,115 @@ impl Connection {
     pub fn stats(&self) -> ConnectionStats {
         let mut stats = self.stats;
         stats.path.rtt = self.path.rtt.get();
-        stats.path.cwnd = self.path.congestion.window();
+        stats.path.cwnd = self.congestion_window_limit();
+        stats.datagrams.recv_dropped = self.datagrams.recv_dropped;
+        stats.datagrams.received = self.datagrams.received;
+        stats.datagrams.sent = self.datagrams.sent;
+        stats.datagrams.send_dropped = self.datagrams.send_dropped;
+        stats.datagrams.too_large = self.datagrams.too_large;
+        stats.remote_streams_opened = self.streams.remote_opened();
+        stats.ack_ranges = self
+            .spaces
+            .iter()
+            .map(|space| space.pending_acks.ranges().len() as u64)
+            .sum();
 
         stats
     }
 
+    /// The total amount of bytes sent inside UDP datagrams, as in [`ConnectionStats::udp_tx`]
+    ///
+    /// Cheaper than [`stats()`](Self::stats) when polled frequently.
+    pub fn udp_tx_bytes(&self) -> u64 {
+        self.stats.udp_tx.bytes
+    }
+
+    /// The total amount of bytes received inside UDP datagrams, as in [`ConnectionStats::udp_rx`]
+    ///
+    /// Cheaper than [`stats()`](Self::stats) when polled frequently.
+    pub fn udp_rx_bytes(&self) -> u64 {
+        self.stats.udp_rx.bytes
+    }
+
+    /// The amount of packets sent, as in [`ConnectionStats::path`]
+    ///
+    /// Cheaper than [`stats()`](Self::stats) when polled frequently.
+    pub fn sent_packets(&self) -> u64 {
+        self.stats.path.sent_packets
+    }
+
+    /// The amount of stream data bytes transmitted for the first time, as in
+    /// [`ConnectionStats::stream_data`]
+    ///
+    /// Cheaper than [`stats()`](Self::stats) when polled frequently.
+    pub fn original_bytes(&self) -> u64 {
+        self.stats.stream_data.original_bytes
+    }
+
+    /// The amount of stream data bytes transmitted again after being declared lost, as in
+    /// [`ConnectionStats::stream_data`]
+    ///
+    /// Cheaper than [`stats()`](Self::stats) when polled frequently.
+    pub fn retransmitted_bytes(&self) -> u64 {
+        self.stats.stream_data.retransmitted_bytes
+    }
+
+    /// Apply the `StreamAdmission` policy, if any, to streams newly opened by the peer
+    fn admit_streams(&mut self) {
+        let (policy, error_code) = match self.stream_admission {
+            Some((ref mut policy, error_code)) => (policy, error_code),
+            None => return,
+        };
+        for dir in Dir::iter() {
+            while let Some(id) = self.streams.next_unadmitted(dir) {
+                if policy.admit(id) {
+                    continue;
+                }
+                debug!(stream = %id, "rejecting stream");
+                self.streams.reject_remote(id);
+                let pending = &mut self.spaces[SpaceId::Data].pending;
+                let _ = RecvStream {
+                    id,
+                    state: &mut self.streams,
+                    pending,
+                }
+                .stop(error_code);
+                if dir == Dir::Bi {
+                    let _ = SendStream {
+                        id,
+                        state: &mut self.streams,
+                        pending,
+                        conn_state: &self.state,
+                    }
+                    .reset(error_code);
+                }
+            }
+        }
+    }
+
+    /// Fraction of recently sent packets which were lost
+    ///
+    /// Computed over the most recent packets whose fate is known, so it reflects current network
+    /// conditions rather than the connection's entire history. Returns 0 if no packets have been
+    /// acknowledged or lost yet.
+    pub fn recent_loss_rate(&self) -> f32 {
+        self.recent_losses.rate()
+    }
+
+    /// Rate at which application data is being delivered, in bytes per second
+    ///
+    /// Counts stream data newly acknowledged by the peer over the second preceding `now`, so it
+    /// reflects recent progress rather than the connection's entire history. Retransmissions,
+    /// packet and frame overhead, and data on streams which were reset are excluded.
+    pub fn goodput(&self, now: Instant) -> u64 {
+        self.goodput.rate(now)
+    }
+
+    /// Returns the current connection-level flow control state
+    pub fn flow_control_stats(&self) -> FlowControlStats {
+        self.streams.flow_control_stats()
+    }
+
     /// Ping the remote endpoint
     ///
     /// Causes an ACK-eliciting packet to be transmitted.
@@ -1008,9 +1244,41 @@ impl Connection {
         self.spaces[self.highest_space].ping_pending = true;
     }
 
-    #[doc(hidden)]
-    pub fn initiate_key_update(&mut self) {
+    /// Switch to new 1-RTT packet protection keys
+    ///
+    /// Key updates happen automatically before the keys have been used for too many packets, so
+    /// this is only needed to rotate keys more often, e.g. to satisfy a security policy. Returns
+    /// `false`, doing nothing, until the handshake is confirmed, and while the keys replaced by
+    /// the previous update are still retained for packets delayed in the network, which takes
+    /// about three probe timeouts after the peer has responded to that update.
+    pub fn initiate_key_update(&mut self) -> bool {
+        if !self.state.is_established()
+            || self.spaces[SpaceId::Handshake].crypto.is_some()
+            || self.prev_crypto.is_some()
+        {
+            return false;
+        }
         self.update_keys(None, false);
+        true
+    }
+
+    /// Number of times the 1-RTT packet protection keys have been updated
+    ///
+    /// Counts both updates initiated locally, including automatic ones, and by the peer.
+    pub fn key_update_count(&self) -> u64 {
+        self.key_updates
+    }
+
+    /// The most recent transport errors detected on this connection, oldest first
+    ///
+    /// Includes both errors which closed the connection and protocol violations by the peer which
+    /// were tolerated, such as invalid ECN feedback, which halts the use of ECN, or an Initial
+    /// packet with the wrong retry token, which is discarded. As Initial packets can be spoofed,
+    /// only the first of those is recorded. Unlike
+    /// [`Event::ConnectionLost`], this reveals the sequence of problems leading up to a
+    /// connection's failure. At most the last 8 errors are retained.
+    pub fn recent_errors(&self) -> impl Iterator<Item = &TransportError> {
+        self.recent_errors.iter()
     }
 
     /// Get a session reference
@@ -1026,6 +1294,15 @@ impl Connection {
         self.state.is_handshake()
     }
 
+    /// Whether the handshake has been confirmed
+    ///
+    /// Servers confirm the handshake as soon as it completes, while clients must additionally
+    /// receive a HANDSHAKE_DONE frame from the server. Once confirmed, both peers are guaranteed to
+    /// have derived 1-RTT keys and data sent can no longer be replayed by an attacker.
+    pub fn is_handshake_confirmed(&self) -> bool {
+        self.highest_space == SpaceId::Data && self.spaces[SpaceId::Handshake].crypto.is_none()
+    }
+
     /// Whether the connection is closed
     ///
     /// Closed connections cannot transport any further data. A connection becomes closed when
@@ -1045,6 +1322,54 @@ impl Connection {
         self.state.is_drained()
     }
 
+    /// The most advanced keys available to the connection
+    ///
+    /// Together with [`encryption_level_since()`](Self::encryption_level_since), helps to tell
+    /// which step of a stalled handshake the connection is waiting on.
+    pub fn encryption_level(&self) -> EncryptionLevel {
+        self.encryption_level.0
+    }
+
+    /// When the connection reached its current [`encryption_level()`](Self::encryption_level)
+    ///
+    /// Changes are observed when received packets are processed, so this is the time of the
+    /// packet which supplied the keys.
+    pub fn encryption_level_since(&self) -> Instant {
+        self.encryption_level.1
+    }
+
+    /// When a packet was most recently received from the peer
+    ///
+    /// Every authenticated packet counts, including those carrying only acknowledgements or
+    /// keep-alives, while packets which fail to decrypt don't. Before any packet has been
+    /// received, this is when the connection was created. Unlike the idle timeout, this can tell
+    /// a silent peer from one that's merely quiet.
+    pub fn last_packet_received(&self) -> Instant {
+        self.last_packet_received
+    }
+
+    /// How far the connection's teardown has progressed
+    pub fn close_state(&self) -> CloseState {
+        match self.state {
+            State::Handshake(_) | State::Established => CloseState::Open,
+            State::Closed(_) => CloseState::Closing,
+            State::Draining => CloseState::Draining,
+            State::Drained => CloseState::Drained,
+        }
+    }
+
+    /// Whether the peer initiated the connection's teardown by sending CONNECTION_CLOSE
+    ///
+    /// Remains `true` once the connection has drained. `false` if the connection was closed locally
+    /// first, even once the peer's CONNECTION_CLOSE answering ours has arrived, or if it was lost
+    /// for another reason such as a stateless reset or an idle timeout.
+    pub fn peer_closing(&self) -> bool {
+        matches!(
+            self.error,
+            Some(ConnectionError::ApplicationClosed(_) | ConnectionError::ConnectionClosed(_))
+        )
+    }
+
     /// For clients, if the peer accepted the 0-RTT data packets
     ///
     /// The value is meaningless until after the handshake completes.
@@ -1052,11 +1377,36 @@ impl Connection {
         self.accepted_0rtt
     }
 
+    /// For clients, if stream data rejected by the peer in 0-RTT is being retransmitted at 1-RTT
+    ///
+    /// If so, streams opened in 0-RTT remain usable despite the rejection. See
+    /// [`TransportConfig::retransmit_rejected_0rtt()`].
+    pub fn retransmitted_0rtt(&self) -> bool {
+        self.retransmitted_0rtt
+    }
+
+    /// Whether the handshake resumed a previous session rather than being a full handshake
+    ///
+    /// Only resumed connections can use 0-RTT, so together with
+    /// [`accepted_0rtt()`](Self::accepted_0rtt) this distinguishes fresh handshakes, resumptions
+    /// whose 0-RTT data was accepted, and resumptions which didn't use 0-RTT or had it rejected.
+    /// The value is meaningless until after the handshake completes.
+    pub fn was_resumed(&self) -> bool {
+        self.crypto.is_resumed()
+    }
+
     /// Whether 0-RTT is/was possible during the handshake
     pub fn has_0rtt(&self) -> bool {
         self.zero_rtt_enabled
     }
 
+    /// Whether the application may send 0.5-RTT data, per [`ServerConfig::half_rtt()`]
+    ///
+    /// Always `false` for outgoing connections.
+    pub fn allows_half_rtt(&self) -> bool {
+        self.server_config.as_ref().map_or(false, |x| x.half_rtt)
+    }
+
     /// Whether there are any pending retransmits
     pub fn has_pending_retransmits(&self) -> bool {
         !self.spaces[SpaceId::Data].pending.is_empty(&self.streams)
@@ -1072,6 +1422,26 @@ impl Connection {
         self.path.remote
     }
 
+    /// Number of network paths currently maintained for this connection
+    ///
+    /// This is 1, plus 1 while the peer's previous address is retained during validation of a new
+    /// one. Bounded by [`TransportConfig::max_concurrent_paths()`].
+    pub fn active_paths(&self) -> usize {
+        1 + self.prev_path.is_some() as usize
+    }
+
+    /// Connection IDs issued by this endpoint which the peer hasn't retired yet, oldest first
+    ///
+    /// These are the IDs the peer may currently address packets for this connection to. Useful to
+    /// verify that IDs are rotated, e.g. after a network change, and that old ones get retired.
+    /// Empty if the endpoint uses zero-length connection IDs.
+    pub fn active_connection_ids(&self) -> Vec<ConnectionId> {
+        if self.local_cid_state.cid_len() == 0 {
+            return Vec::new();
+        }
+        self.local_cid_state.active_cids()
+    }
+
     /// The local IP address which was used when the peer established
     /// the connection
     ///
@@ -1090,16 +1460,127 @@ impl Connection {
         self.local_ip
     }
 
+    /// The QUIC version in use by the connection
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     /// Current best estimate of this connection's latency (round-trip-time)
     pub fn rtt(&self) -> Duration {
         self.path.rtt.get()
     }
 
+    /// Whether explicit congestion notification is in use on the current path
+    pub fn ecn_state(&self) -> EcnState {
+        self.path.ecn_state()
+    }
+
+    /// Size of the largest packet sent on the current path that the peer acknowledged
+    ///
+    /// A lower bound on the path MTU which has been demonstrated to work end to end, unlike the
+    /// estimate used to size outgoing packets. Zero until a packet is acknowledged.
+    pub fn max_sent_packet_size(&self) -> u16 {
+        self.path.max_acked_packet_size
+    }
+
+    /// Whether the peer's address on the current path has been validated
+    ///
+    /// Until then, data sent on the path is subject to the anti-amplification limit.
+    pub fn path_validated(&self) -> bool {
+        self.path.validated
+    }
+
+    /// Number of times the peer has successfully migrated to a new address
+    ///
+    /// Counted when the new path is validated, so migrations which fall back to the previous path
+    /// aren't included. Only servers observe migrations; clients always report 0.
+    pub fn migration_count(&self) -> u64 {
+        self.migrations
+    }
+
+    /// Number of bytes withheld because of the anti-amplification limit
+    ///
+    /// Counts the handshake data which was ready to send when the limit on unvalidated paths was
+    /// hit, with each byte counted at most once over the connection's lifetime. A large value for a
+    /// server explains slow handshakes with unvalidated clients, which
+    /// [`ServerConfig::use_retry`] can avoid by validating client addresses earlier.
+    pub fn amplification_withheld(&self) -> u64 {
+        self.amplification_withheld
+    }
+
+    /// Number of bytes which may still be sent before hitting the anti-amplification limit
+    ///
+    /// `None` once the peer's address has been validated, which is always the case for clients.
+    /// Complements [`amplification_withheld()`](Self::amplification_withheld) by allowing large
+    /// sends to be deferred until the limit no longer applies, rather than observing them stall.
+    pub fn amplification_budget(&self) -> Option<u64> {
+        self.path.amplification_budget()
+    }
+
+    /// Current probe timeout (PTO), including any backoff due to unacknowledged probes
+    ///
+    /// Approximately how long the connection waits for an acknowledgement before sending a probe.
+    /// See [`TransportConfig::max_pto_backoff()`].
+    pub fn current_pto(&self) -> Duration {
+        let space = match self.is_handshaking() {
+            true => SpaceId::Handshake,
+            false => SpaceId::Data,
+        };
+        self.pto(space) * self.pto_backoff()
+    }
+
+    /// Average time between packets imposed by pacing at the current congestion window and RTT
+    ///
+    /// Zero if pacing is effectively disabled due to an extremely large congestion window.
+    pub fn pacing_delay(&self) -> Duration {
+        pacing::interval(
+            self.path.rtt.get(),
+            self.congestion_window_limit(),
+            self.path.max_udp_payload_size,
+        )
+    }
+
+    /// Number of bytes the pacer would currently let through without delay
+    ///
+    /// Writing data in chunks no larger than this avoids it being held back by pacing. `u64::MAX`
+    /// if pacing is effectively disabled, like for [`pacing_delay()`](Self::pacing_delay).
+    /// Congestion control and flow control may impose further limits.
+    pub fn pacing_tokens(&self, now: Instant) -> u64 {
+        self.path.pacing.available(
+            self.path.rtt.get(),
+            self.path.max_udp_payload_size,
+            self.congestion_window_limit(),
+            now,
+        )
+    }
+
+    /// What's preventing the connection from sending data faster
+    ///
+    /// Computed from the connection's current state: whether the most recent
+    /// [`poll_transmit()`](Self::poll_transmit) call was held back by congestion control or pacing,
+    /// and otherwise whether a stream write is blocked on flow control.
+    pub fn send_limit_reason(&self) -> SendLimit {
+        if self.congestion_blocked {
+            SendLimit::Congestion
+        } else if self.streams.is_write_blocked() {
+            SendLimit::FlowControl
+        } else {
+            SendLimit::Application
+        }
+    }
+
     /// Current state of this connection's congestion controller, for debugging purposes
     pub fn congestion_state(&self) -> &dyn Controller {
         self.path.congestion.as_ref()
     }
 
+    /// Phase of this connection's congestion controller
+    ///
+    /// See [`Controller::phase()`].
+    pub fn congestion_phase(&self) -> CongestionPhase {
+        self.path.congestion.phase()
+    }
+
     /// Modify the number of remotely initiated streams that may be concurrently open
     ///
     /// No streams may be opened by the peer unless fewer than `count` are already open. Large
@@ -1108,7 +1589,72 @@ impl Connection {
         self.streams.set_max_concurrent(dir, count);
     }
 
-    /// See [`TransportConfig::receive_window()`]
+    /// Limits on the number of streams each endpoint may open
+    ///
+    /// Until the handshake completes, the limits on locally-initiated streams are those the peer
+    /// advertised in a previous session when 0-RTT is in use, and zero otherwise.
+    pub fn stream_limits(&self) -> StreamLimits {
+        self.streams.limits()
+    }
+
+    /// Change the bound on how long this endpoint may delay acknowledgements
+    ///
+    /// Initially [`TransportConfig::max_ack_delay()`]. The peer only learns the value during the
+    /// handshake: transport parameters can't be renegotiated, and the ACK frequency extension,
+    /// which would allow adjusting acknowledgement behavior mid-connection, isn't supported, so
+    /// the peer's loss detection keeps using the advertised value. Since acknowledgements are
+    /// currently sent without intentional delay, the bound doesn't otherwise affect behavior.
+    pub fn set_max_ack_delay(&mut self, value: Duration) {
+        self.max_ack_delay = value;
+    }
+
+    /// The current bound on how long this endpoint may delay acknowledgements
+    ///
+    /// See [`set_max_ack_delay()`](Self::set_max_ack_delay).
+    pub fn max_ack_delay(&self) -> Duration {
+        self.max_ack_delay
+    }
+
+    /// The idle timeout in effect for this connection
+    ///
+    /// Once the peer's transport parameters are known, this is the minimum of both peers'
+    /// [`TransportConfig::max_idle_timeout()`], unless overridden by
+    /// [`TransportConfig::idle_timeout_policy()`]; until then it's the local value. `None` if idle
+    /// timeouts are disabled on both sides.
+    pub fn max_idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout.map(|x| Duration::from_millis(x.0))
+    }
+
+    /// Override [`TransportConfig::keep_alive_interval()`] for this connection
+    ///
+    /// Takes effect immediately; `None` stops sending keep-alives.
+    pub fn set_keep_alive_interval(&mut self, now: Instant, interval: Option<Duration>) {
+        self.keep_alive_interval = interval;
+        match interval {
+            Some(_) => self.reset_keep_alive(now),
+            None => self.timers.stop(Timer::KeepAlive),
+        }
+    }
+
+    /// Set opaque data to be attached to every datagram this connection sends
+    ///
+    /// Copied into [`Transmit::metadata`] for interpretation by a custom socket implementation,
+    /// e.g. as a routing hint. Applies to datagrams produced after this call.
+    pub fn set_transmit_metadata(&mut self, metadata: Option<Bytes>) {
+        self.transmit_metadata = metadata;
+    }
+
+    /// Set the priority for the OS to queue datagrams this connection sends with
+    ///
+    /// Copied into [`Transmit::priority`]. Applies to datagrams produced after this call.
+    pub fn set_socket_priority(&mut self, priority: Option<u32>) {
+        self.socket_priority = priority;
+    }
+
+    /// Change the connection-level receive window, overriding [`TransportConfig::receive_window()`]
+    ///
+    /// Growing the window issues a `MAX_DATA` frame immediately. Because credit already granted
+    /// to the peer can't be revoked, shrinking only takes effect as the peer consumes it.
     pub fn set_receive_window(&mut self, receive_window: VarInt) {
         if self.streams.set_receive_window(receive_window) {
             self.spaces[SpaceId::Data].pending.max_data = true;
@@ -1155,6 +1701,8 @@ impl Connection {
             return Ok(());
         }
 
+        let pto_fired_at = self.spaces[space].pto_fired_at.take();
+        let mut spurious_pto = false;
         let mut ack_eliciting_acked = false;
         for packet in newly_acked.elts() {
             if let Some(info) = self.spaces[space].sent_packets.remove(&packet) {
@@ -1167,9 +1715,13 @@ impl Connection {
                     self.spaces[space].pending_acks.subtract_below(acked);
                 }
                 ack_eliciting_acked |= info.ack_eliciting;
+                spurious_pto |= info.ack_eliciting && pto_fired_at.map_or(false, |pn| packet < pn);
                 self.on_packet_acked(now, space, info);
             }
         }
+        if spurious_pto {
+            self.stats.path.spurious_ptos += 1;
+        }
 
         self.path.congestion.on_end_acks(
             now,
@@ -1183,7 +1735,7 @@ impl Connection {
                 Duration::from_micros(0)
             } else {
                 cmp::min(
-                    self.max_ack_delay(),
+                    self.peer_max_ack_delay(),
                     Duration::from_micros(ack.delay << self.peer_params.ack_delay_exponent.0),
                 )
             };
@@ -1211,12 +1763,23 @@ impl Connection {
                 // reordering.
                 if new_largest {
                     let sent = self.spaces[space].largest_acked_packet_sent;
-                    self.process_ecn(now, space, newly_acked.len() as u64, ecn, sent);
+                    let first_marked = match space {
+                        SpaceId::Data => self.path.first_ecn_packet,
+                        _ => 0,
+                    };
+                    let marked = newly_acked.elts().filter(|&pn| pn >= first_marked).count();
+                    self.process_ecn(now, space, marked as u64, ecn, sent);
                 }
             } else {
                 // We always start out sending ECN, so any ack that doesn't acknowledge it disables it.
                 debug!("ECN not acknowledged by peer");
                 self.path.sending_ecn = false;
+                self.path.ecn_unreported = true;
+            }
+        } else if let (Some(ecn), true) = (ack.ecn, new_largest) {
+            if self.path.ecn_retry_at.is_some() {
+                // Keep track of the peer's counts so they can be validated once ECN is resumed
+                self.spaces[space].ecn_feedback = ecn;
             }
         }
 
@@ -1236,13 +1799,21 @@ impl Connection {
         match self.spaces[space].detect_ecn(newly_acked, ecn) {
             Err(e) => {
                 debug!("halting ECN due to verification failure: {}", e);
+                self.record_error(TransportError::PROTOCOL_VIOLATION(e));
+                self.stats.path.ecn_validation_failures += 1;
                 self.path.sending_ecn = false;
+                if let EcnValidationPolicy::Retry(delay) = self.config.ecn_validation_policy {
+                    self.path.ecn_retry_at = Some(now + delay);
+                }
                 // Wipe out the existing value because it might be garbage and could interfere with
                 // future attempts to use ECN on new paths.
                 self.spaces[space].ecn_feedback = frame::EcnCounts::ZERO;
             }
-            Ok(false) => {}
+            Ok(false) => {
+                self.path.ecn_validated = true;
+            }
             Ok(true) => {
+                self.path.ecn_validated = true;
                 self.stats.path.congestion_events += 1;
                 self.path
                     .congestion
@@ -1255,6 +1826,7 @@ impl Connection {
     // high-latency handshakes
     fn on_packet_acked(&mut self, now: Instant, space: SpaceId, info: SentPacket) {
         self.remove_in_flight(space, &info);
+        self.recent_losses.record(false);
         if info.ack_eliciting && self.path.challenge.is_none() {
             // Only pass ACKs to the congestion controller if we are not validating the current
             // path, so as to ignore any ACKs from older paths still coming in.
@@ -1266,6 +1838,10 @@ impl Connection {
                 &self.path.rtt,
             );
         }
+        if self.path.challenge.is_none() {
+            // As above, ignore ACKs which may be for packets sent on older paths
+            self.path.max_acked_packet_size = self.path.max_acked_packet_size.max(info.size);
+        }
 
         // Update state for confirmed delivery of frames
         if let Some(retransmits) = info.retransmits.get() {
@@ -1274,9 +1850,11 @@ impl Connection {
             }
         }
 
+        let mut acked = 0;
         for frame in info.stream_frames {
-            self.streams.received_ack_of(frame);
+            acked += self.streams.received_ack_of(frame);
         }
+        self.goodput.record(now, acked);
     }
 
     fn set_key_discard_timer(&mut self, now: Instant, space: SpaceId) {
@@ -1328,7 +1906,10 @@ impl Connection {
             _ => 2,
         };
         self.spaces[space].loss_probes = self.spaces[space].loss_probes.saturating_add(count);
+        let space = &mut self.spaces[space];
+        space.pto_fired_at.get_or_insert(space.next_packet_number);
         self.pto_count = self.pto_count.saturating_add(1);
+        self.stats.path.ptos += 1;
         self.set_loss_detection_timer(now);
     }
 
@@ -1401,6 +1982,9 @@ impl Connection {
             let old_bytes_in_flight = self.in_flight.bytes;
             let largest_lost_sent = self.spaces[pn_space].sent_packets[&largest_lost].time_sent;
             self.lost_packets += lost_packets.len() as u64;
+            for _ in &lost_packets {
+                self.recent_losses.record(true);
+            }
             self.stats.path.lost_packets += lost_packets.len() as u64;
             self.stats.path.lost_bytes += size_of_lost_packets as u64;
             trace!(
@@ -1411,6 +1995,9 @@ impl Connection {
             for packet in &lost_packets {
                 let info = self.spaces[pn_space].sent_packets.remove(packet).unwrap(); // safe: lost_packets is populated just above
                 self.remove_in_flight(pn_space, &info);
+                if info.ack_eliciting {
+                    self.spaces[pn_space].awaiting_retransmission += 1;
+                }
                 for frame in info.stream_frames {
                     self.streams.retransmit(frame);
                 }
@@ -1438,7 +2025,7 @@ impl Connection {
     }
 
     fn pto_time_and_space(&self, now: Instant) -> Option<(Instant, SpaceId)> {
-        let backoff = 2u32.pow(self.pto_count.min(MAX_BACKOFF_EXPONENT));
+        let backoff = self.pto_backoff();
         let mut duration = self.path.rtt.pto_base() * backoff;
 
         if self.in_flight.ack_eliciting == 0 {
@@ -1461,7 +2048,7 @@ impl Connection {
                     return result;
                 }
                 // Include max_ack_delay and backoff for ApplicationData.
-                duration += self.max_ack_delay() * backoff;
+                duration += self.peer_max_ack_delay() * backoff;
             }
             let last_ack_eliciting = match self.spaces[space].time_of_last_ack_eliciting_packet {
                 Some(time) => time,
@@ -1519,11 +2106,20 @@ impl Connection {
         }
     }
 
+    /// Factor by which the probe timeout is multiplied due to consecutive PTOs
+    fn pto_backoff(&self) -> u32 {
+        let exponent = self
+            .pto_count
+            .min(self.config.max_pto_backoff)
+            .min(MAX_BACKOFF_EXPONENT);
+        2u32.pow(exponent)
+    }
+
     /// Probe Timeout
     fn pto(&self, space: SpaceId) -> Duration {
         let max_ack_delay = match space {
             SpaceId::Initial | SpaceId::Handshake => Duration::new(0, 0),
-            SpaceId::Data => self.max_ack_delay(),
+            SpaceId::Data => self.peer_max_ack_delay(),
         };
         self.path.rtt.pto_base() + max_ack_delay
     }
@@ -1538,6 +2134,7 @@ impl Connection {
         is_1rtt: bool,
     ) {
         self.total_authed_packets += 1;
+        self.last_packet_received = now;
         self.reset_keep_alive(now);
         self.reset_idle_timeout(now, space_id);
         self.permit_idle_reset = true;
@@ -1561,7 +2158,9 @@ impl Connection {
             }
         }
         let space = &mut self.spaces[space_id];
-        space.pending_acks.insert_one(packet, now);
+        space
+            .pending_acks
+            .insert_one(packet, now, self.config.max_ack_ranges);
         if packet >= space.rx_packet {
             space.rx_packet = packet;
             // Update outgoing spin bit, inverting iff we're the client
@@ -1583,7 +2182,7 @@ impl Connection {
     }
 
     fn reset_keep_alive(&mut self, now: Instant) {
-        let interval = match self.config.keep_alive_interval {
+        let interval = match self.keep_alive_interval {
             Some(x) if self.state.is_established() => x,
             _ => return,
         };
@@ -1637,9 +2236,24 @@ impl Connection {
         if let Some(data) = remaining {
             self.handle_coalesced(now, remote, ecn, data);
         }
+        self.update_encryption_level(now);
         Ok(())
     }
 
+    /// Record when the connection reaches a new encryption level
+    fn update_encryption_level(&mut self, now: Instant) {
+        let level = match self.highest_space {
+            SpaceId::Data => EncryptionLevel::OneRtt,
+            SpaceId::Handshake => EncryptionLevel::Handshake,
+            SpaceId::Initial if self.zero_rtt_crypto.is_some() => EncryptionLevel::ZeroRtt,
+            SpaceId::Initial => EncryptionLevel::Initial,
+        };
+        if level != self.encryption_level.0 {
+            trace!(?level, "encryption level changed");
+            self.encryption_level = (level, now);
+        }
+    }
+
     fn init_0rtt(&mut self) {
         let (header, packet) = match self.crypto.early_crypto() {
             Some(x) => x,
@@ -1800,6 +2414,7 @@ impl Connection {
         space.crypto = None;
         space.time_of_last_ack_eliciting_packet = None;
         space.loss_time = None;
+        space.awaiting_retransmission = 0;
         let sent_packets = mem::take(&mut space.sent_packets);
         for (_, packet) in sent_packets.into_iter() {
             self.remove_in_flight(space_id, &packet);
@@ -1964,6 +2579,17 @@ impl Connection {
                                 // packets can be spoofed, so we discard rather than killing the
                                 // connection.
                                 warn!("discarding Initial with invalid retry token");
+                                // Recorded once, so that spoofed Initials can't flush out the
+                                // errors of interest
+                                if !self
+                                    .recent_errors
+                                    .iter()
+                                    .any(|e| e.code == TransportErrorCode::INVALID_TOKEN)
+                                {
+                                    self.record_error(TransportError::INVALID_TOKEN(
+                                        "Initial with invalid retry token",
+                                    ));
+                                }
                                 return;
                             }
                         }
@@ -1994,16 +2620,22 @@ impl Connection {
             self.state = match conn_err {
                 ConnectionError::ApplicationClosed(reason) => State::closed(reason),
                 ConnectionError::ConnectionClosed(reason) => State::closed(reason),
-                ConnectionError::Reset
-                | ConnectionError::TransportError(TransportError {
-                    code: TransportErrorCode::AEAD_LIMIT_REACHED,
-                    ..
-                }) => State::Drained,
+                ConnectionError::Reset => State::Drained,
+                ConnectionError::TransportError(
+                    ref err @ TransportError {
+                        code: TransportErrorCode::AEAD_LIMIT_REACHED,
+                        ..
+                    },
+                ) => {
+                    self.record_error(err.clone());
+                    State::Drained
+                }
                 ConnectionError::TimedOut => {
                     unreachable!("timeouts aren't generated by packet processing");
                 }
                 ConnectionError::TransportError(err) => {
                     debug!("closing connection due to transport error: {}", err);
+                    self.record_error(err.clone());
                     State::closed(err)
                 }
                 ConnectionError::VersionMismatch => State::Draining,
@@ -2179,16 +2811,30 @@ impl Connection {
                             debug_assert!(self.side.is_client());
                             debug!("0-RTT rejected");
                             self.accepted_0rtt = false;
-                            self.streams.zero_rtt_rejected();
-
-                            // Discard already-queued frames
-                            self.spaces[SpaceId::Data].pending = Retransmits::default();
-
-                            // Discard 0-RTT packets
                             let sent_packets =
                                 mem::take(&mut self.spaces[SpaceId::Data].sent_packets);
-                            for (_, packet) in sent_packets {
-                                self.remove_in_flight(SpaceId::Data, &packet);
+                            if self.config.retransmit_rejected_0rtt
+                                && params.validate_resumption_from(&self.peer_params).is_ok()
+                            {
+                                // The server's limits still admit everything sent, so keep the
+                                // streams and send it all again at 1-RTT
+                                debug!("retransmitting rejected 0-RTT data");
+                                self.retransmitted_0rtt = true;
+                                for (_, packet) in sent_packets {
+                                    self.remove_in_flight(SpaceId::Data, &packet);
+                                    self.spaces[SpaceId::Data].pending |= packet.retransmits;
+                                }
+                                self.streams.retransmit_all_for_0rtt();
+                            } else {
+                                self.streams.zero_rtt_rejected();
+
+                                // Discard already-queued frames
+                                self.spaces[SpaceId::Data].pending = Retransmits::default();
+
+                                // Discard 0-RTT packets
+                                for (_, packet) in sent_packets {
+                                    self.remove_in_flight(SpaceId::Data, &packet);
+                                }
                             }
                         } else {
                             self.accepted_0rtt = true;
@@ -2203,7 +2849,9 @@ impl Connection {
                     self.issue_cids(now);
                 } else {
                     // Server-only
-                    self.spaces[SpaceId::Data].pending.handshake_done = true;
+                    let pending = &mut self.spaces[SpaceId::Data].pending;
+                    pending.handshake_done = true;
+                    pending.new_tokens = self.server_config.as_ref().unwrap().new_tokens;
                     self.discard_space(now, SpaceId::Handshake);
                 }
 
@@ -2344,6 +2992,14 @@ impl Connection {
         number: u64,
         payload: Bytes,
     ) -> Result<(), TransportError> {
+        if remote != self.path.remote && self.server_config.as_ref().map_or(false, |x| !x.migration)
+        {
+            // Packets from unrecognized peers are only processed when `close_on_migration` is set
+            debug!(%remote, "closing due to forbidden migration");
+            self.stats.path.migrations_refused += 1;
+            return Err(TransportError::PROTOCOL_VIOLATION("migration is disabled"));
+        }
+
         let is_0rtt = self.spaces[SpaceId::Data].crypto.is_none();
         let mut is_probing_packet = true;
         let mut close = None;
@@ -2418,15 +3074,22 @@ impl Connection {
                     close = Some(reason);
                 }
                 Frame::PathChallenge(token) => {
-                    if self
-                        .path_response
-                        .as_ref()
-                        .map_or(true, |x| x.packet <= number)
-                    {
-                        self.path_response = Some(PathResponse {
-                            packet: number,
-                            token,
-                        });
+                    self.path_responses.push_back(PathResponse {
+                        packet: number,
+                        token,
+                    });
+                    if self.path_responses.len() > self.config.max_pending_path_responses.max(1) {
+                        // Keep the responses to the most recently sent challenges, which are the
+                        // ones a peer still validating a path is waiting on
+                        let (oldest, _) = self
+                            .path_responses
+                            .iter()
+                            .enumerate()
+                            .min_by_key(|(_, x)| x.packet)
+                            .unwrap();
+                        self.path_responses.remove(oldest);
+                        trace!("dropping PATH_CHALLENGE: too many responses queued");
+                        self.stats.path.path_challenges_dropped += 1;
                     }
                     if remote == self.path.remote {
                         // PATH_CHALLENGE on active path, possible off-path packet forwarding
@@ -2440,10 +3103,9 @@ impl Connection {
                         self.timers.stop(Timer::PathValidation);
                         self.path.challenge = None;
                         self.path.validated = true;
-                        if let Some(ref mut prev_path) = self.prev_path {
-                            prev_path.challenge = None;
-                            prev_path.challenge_pending = false;
-                        }
+                        self.migrations += 1;
+                        // The previous path is only retained to fall back on if validation fails
+                        self.prev_path = None;
                     } else {
                         debug!(token, "ignoring invalid PATH_RESPONSE");
                     }
@@ -2598,6 +3260,16 @@ impl Connection {
             .pending_acks
             .packet_received(ack_eliciting);
 
+        if let Some(ref mut limiter) = self.stream_rate {
+            if !limiter.check(now, self.streams.remote_opened()) {
+                debug!("peer is opening streams too quickly");
+                return Err(TransportError::PROTOCOL_VIOLATION(
+                    "stream opening rate limit exceeded",
+                ));
+            }
+        }
+        self.admit_streams();
+
         // Issue stream ID credit due to ACKs of outgoing finish/resets and incoming finish/resets
         // on stopped streams. Incoming finishes/resets on open streams are not handled here as they
         // are only freed, and hence only issue credit, once the application has been notified
@@ -2646,7 +3318,10 @@ impl Connection {
             PathData::new(
                 remote,
                 self.config.initial_rtt,
-                self.config.congestion_controller_factory.build(now),
+                match self.congestion_controller_factory {
+                    Some(ref factory) => factory.build(now),
+                    None => self.config.congestion_controller_factory.build(now),
+                },
                 self.config.initial_max_udp_payload_size,
                 now,
                 false,
@@ -2658,7 +3333,7 @@ impl Connection {
 
         let mut prev = mem::replace(&mut self.path, new_path);
         // Don't clobber the original path if the previous one hasn't been validated yet
-        if prev.challenge.is_none() {
+        if prev.challenge.is_none() && self.config.max_concurrent_paths > 1 {
             prev.challenge = Some(self.rng.gen());
             prev.challenge_pending = true;
             self.prev_path = Some(prev);
@@ -2755,15 +3430,17 @@ impl Connection {
         }
 
         // PATH_RESPONSE
-        if buf.len() + 9 < max_size && space_id == SpaceId::Data {
-            if let Some(response) = self.path_response.take() {
-                sent.non_retransmits = true;
-                sent.requires_padding = true;
-                trace!("PATH_RESPONSE {:08x}", response.token);
-                buf.write(frame::Type::PATH_RESPONSE);
-                buf.write(response.token);
-                self.stats.frame_tx.path_response += 1;
-            }
+        while buf.len() + 9 < max_size && space_id == SpaceId::Data {
+            let response = match self.path_responses.pop_front() {
+                Some(x) => x,
+                None => break,
+            };
+            sent.non_retransmits = true;
+            sent.requires_padding = true;
+            trace!("PATH_RESPONSE {:08x}", response.token);
+            buf.write(frame::Type::PATH_RESPONSE);
+            buf.write(response.token);
+            self.stats.frame_tx.path_response += 1;
         }
 
         // CRYPTO
@@ -2853,8 +3530,36 @@ impl Connection {
             self.stats.frame_tx.retire_connection_id += 1;
         }
 
+        // NEW_TOKEN
+        while space.pending.new_tokens > 0 && !is_0rtt {
+            let server_config = self.server_config.as_ref().unwrap();
+            let mut random_bytes = [0; RetryToken::RANDOM_BYTES_LEN];
+            self.rng.fill(&mut random_bytes[..]);
+            let token = NewToken {
+                issued: SystemTime::now(),
+                random_bytes: &random_bytes,
+            }
+            .encode(&*server_config.token_key, self.path.remote.ip());
+            if buf.len() + 1 + VarInt::from_u32(token.len() as u32).size() + token.len() >= max_size
+            {
+                break;
+            }
+            trace!("NEW_TOKEN");
+            buf.write(frame::Type::NEW_TOKEN);
+            buf.write_var(token.len() as u64);
+            buf.extend_from_slice(&token);
+            space.pending.new_tokens -= 1;
+            sent.retransmits.get_or_create().new_tokens += 1;
+            self.stats.frame_tx.new_token += 1;
+            self.endpoint_events
+                .push_back(EndpointEventInner::NewTokenIssued);
+        }
+
         // DATAGRAM
-        while buf.len() + Datagram::SIZE_BOUND < max_size && space_id == SpaceId::Data {
+        while buf.len() + Datagram::SIZE_BOUND < max_size
+            && space_id == SpaceId::Data
+            && !self.datagram_limit_reached()
+        {
             match self.datagrams.write(buf, max_size) {
                 true => {
                     sent.non_retransmits = true;
@@ -2866,7 +3571,9 @@ impl Connection {
 
         // STREAM
         if space_id == SpaceId::Data {
-            sent.stream_frames = self.streams.write_stream_frames(buf, max_size);
+            sent.stream_frames =
+                self.streams
+                    .write_stream_frames(buf, max_size, &mut self.stats.stream_data);
             self.stats.frame_tx.stream += sent.stream_frames.len() as u64;
         }
 
@@ -2937,11 +3644,22 @@ impl Connection {
 
     fn set_peer_params(&mut self, params: TransportParameters) {
         self.streams.set_params(&params);
-        self.idle_timeout = match (self.config.max_idle_timeout, params.max_idle_timeout) {
-            (None, VarInt(0)) => None,
-            (None, x) => Some(x),
-            (Some(x), VarInt(0)) => Some(x),
-            (Some(x), y) => Some(cmp::min(x, y)),
+        self.idle_timeout = match self.config.idle_timeout_policy {
+            Some(ref policy) => {
+                let to_duration = |x: VarInt| Duration::from_millis(x.0);
+                let peer = Some(params.max_idle_timeout).filter(|&x| x != VarInt(0));
+                policy(
+                    self.config.max_idle_timeout.map(to_duration),
+                    peer.map(to_duration),
+                )
+                .map(|x| VarInt::try_from(x.as_millis()).unwrap_or(VarInt::MAX))
+            }
+            None => match (self.config.max_idle_timeout, params.max_idle_timeout) {
+                (None, VarInt(0)) => None,
+                (None, x) => Some(x),
+                (Some(x), VarInt(0)) => Some(x),
+                (Some(x), y) => Some(cmp::min(x, y)),
+            },
         };
         if let Some(ref info) = params.preferred_address {
             self.rem_cids.insert(frame::NewConnectionId {
@@ -3056,24 +3774,48 @@ impl Connection {
             update_unacked: remote,
         });
         self.key_phase = !self.key_phase;
+        self.key_updates += 1;
     }
 
     /// The number of bytes of packets containing retransmittable frames that have not been
     /// acknowledged or declared lost.
-    #[cfg(test)]
-    pub(crate) fn bytes_in_flight(&self) -> u64 {
+    pub fn bytes_in_flight(&self) -> u64 {
         self.in_flight.bytes
     }
 
+    /// The number of packets counted towards [`bytes_in_flight()`](Self::bytes_in_flight)
+    ///
+    /// Packets containing only ACK frames aren't counted, as they don't occupy the congestion
+    /// window.
+    pub fn packets_in_flight(&self) -> u64 {
+        self.in_flight.packets
+    }
+
+    /// The number of packets declared lost whose frames have not yet been sent again
+    ///
+    /// Lost data is sent ahead of new data, so each subsequent ack-eliciting packet is assumed to
+    /// carry the contents of one lost packet. A large value following a loss burst indicates that
+    /// a stall is due to the retransmission backlog rather than the congestion window.
+    pub fn packets_awaiting_retransmission(&self) -> u64 {
+        self.spaces
+            .iter()
+            .map(|space| space.awaiting_retransmission)
+            .sum()
+    }
+
     /// Number of bytes worth of non-ack-only packets that may be sent
     #[cfg(test)]
     pub(crate) fn congestion_window(&self) -> u64 {
-        self.path
-            .congestion
-            .window()
+        self.congestion_window_limit()
             .saturating_sub(self.in_flight.bytes)
     }
 
+    /// Present `token` in subsequent Initial packets, as if it had been issued by the server
+    #[cfg(test)]
+    pub(crate) fn set_initial_token(&mut self, token: Bytes) {
+        self.retry_token = token;
+    }
+
     /// Whether no timers but keepalive, idle and pushnewcid are running
     #[cfg(test)]
     pub(crate) fn is_idle(&self) -> bool {
@@ -3117,13 +3859,31 @@ impl Connection {
             .push_back(EndpointEventInner::NeedIdentifiers(now, n));
     }
 
+    /// Pin the congestion window to a fixed number of bytes, or restore the congestion
+    /// controller's window with `None`
+    ///
+    /// Intended for tests that need transmission under a known window to be deterministic. The
+    /// congestion controller keeps running and its own state, as reported by
+    /// [`congestion_state()`](Self::congestion_state), is unaffected; only the limit applied to
+    /// sending and pacing changes. Don't use this in production, since it disables congestion
+    /// control.
+    pub fn set_congestion_window_override(&mut self, window: Option<u64>) {
+        self.congestion_window_override = window;
+    }
+
     /// Check the current active remote CID sequence
     #[cfg(test)]
     pub(crate) fn active_rem_cid_seq(&self) -> u64 {
         self.rem_cids.active_seq()
     }
 
-    fn max_ack_delay(&self) -> Duration {
+    /// The congestion window limiting transmission, accounting for any override
+    fn congestion_window_limit(&self) -> u64 {
+        self.congestion_window_override
+            .unwrap_or_else(|| self.path.congestion.window())
+    }
+
+    fn peer_max_ack_delay(&self) -> Duration {
         Duration::from_micros(self.peer_params.max_ack_delay.0 * 1000)
     }
 
@@ -3137,17 +3897,34 @@ impl Connection {
                 .prev_path
                 .as_ref()
                 .map_or(false, |x| x.challenge_pending)
-            || self.path_response.is_some()
+            || !self.path_responses.is_empty()
             || !self.datagrams.outgoing.is_empty()
     }
 
+    /// Whether datagrams should yield to stream data for the rest of the current transmit
+    fn datagram_limit_reached(&self) -> bool {
+        self.config
+            .datagram_frames_per_transmit
+            .map_or(false, |limit| self.datagrams.sent_in_transmit >= limit)
+            && self.streams.can_send_stream_data()
+    }
+
     /// Update counters to account for a packet becoming acknowledged, lost, or abandoned
     fn remove_in_flight(&mut self, space: SpaceId, packet: &SentPacket) {
         self.in_flight.bytes -= u64::from(packet.size);
         self.in_flight.ack_eliciting -= u64::from(packet.ack_eliciting);
+        self.in_flight.packets -= u64::from(packet.size != 0);
         self.spaces[space].in_flight -= u64::from(packet.size);
     }
 
+    /// Remember a transport error for [`recent_errors()`](Self::recent_errors)
+    fn record_error(&mut self, error: TransportError) {
+        if self.recent_errors.len() == MAX_RECENT_ERRORS {
+            self.recent_errors.pop_front();
+        }
+        self.recent_errors.push_back(error);
+    }
+
     /// Terminate the connection instantly, without sending a close packet
     fn kill(&mut self, reason: ConnectionError) {
         self.close_common();
@@ -3181,6 +3958,10 @@ pub enum ConnectionError {
     #[error("closed by peer: {0}")]
     ApplicationClosed(frame::ApplicationClose),
     /// The peer is unable to continue processing this connection, usually due to having restarted
+    ///
+    /// Produced only by a valid stateless reset from the peer, which proves that it has lost the
+    /// connection's state. Unlike for other errors, the connection is drained immediately without
+    /// sending anything further, and a new connection to the same peer can be attempted right away.
     #[error("reset by peer")]
     Reset,
     /// Communication with the peer has lapsed for longer than the negotiated idle timeout
@@ -3304,6 +4085,8 @@ struct InFlight {
     /// considered "in flight" by congestion control. However, if this is nonzero, bytes will always
     /// also be nonzero.
     ack_eliciting: u64,
+    /// Number of packets counted towards `bytes`
+    packets: u64,
 }
 
 impl InFlight {
@@ -3311,15 +4094,72 @@ impl InFlight {
         Self {
             bytes: 0,
             ack_eliciting: 0,
+            packets: 0,
         }
     }
 
     fn insert(&mut self, packet: &SentPacket) {
         self.bytes += u64::from(packet.size);
         self.ack_eliciting += u64::from(packet.ack_eliciting);
+        self.packets += u64::from(packet.size != 0);
     }
 }
 
+/// The factor limiting how quickly a connection is sending data
+///
+/// See [`Connection::send_limit_reason()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SendLimit {
+    /// Everything the application has written has been sent
+    ///
+    /// Throughput is limited by how fast the application supplies data.
+    Application,
+    /// The congestion window is full or outgoing packets are being paced
+    ///
+    /// Throughput is limited by the network path.
+    Congestion,
+    /// Writes are blocked because the peer's connection-level flow control limit or the local
+    /// [`TransportConfig::send_window()`] has been reached
+    ///
+    /// Throughput is limited by how quickly the peer grants more credit, or by the window sizes.
+    FlowControl,
+}
+
+/// Keys a connection is able to protect packets with
+///
+/// See [`Connection::encryption_level()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EncryptionLevel {
+    /// Only the Initial keys derived from the client's first destination connection ID
+    Initial,
+    /// 0-RTT keys, available to clients resuming a session before the server responds
+    ZeroRtt,
+    /// Handshake keys, derived after the server's first flight
+    Handshake,
+    /// 1-RTT keys, used for application data once the handshake completes
+    ///
+    /// Servers reach this level before the handshake is confirmed, in order to send 0.5-RTT data.
+    OneRtt,
+}
+
+/// Stage of a connection's teardown
+///
+/// See [`Connection::close_state()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CloseState {
+    /// The connection hasn't been closed
+    Open,
+    /// A CONNECTION_CLOSE frame has been sent, due to the application closing the connection or a
+    /// local error, and is repeated in response to further packets until the peer's own
+    /// CONNECTION_CLOSE arrives or the closing period ends
+    Closing,
+    /// The peer's CONNECTION_CLOSE has been received, either initiating the close or answering
+    /// ours, and the connection is waiting out the draining period without sending anything
+    Draining,
+    /// Teardown has finished and the connection's resources can be released
+    Drained,
+}
+
 /// Events of interest to the application
 #[derive(Debug)]
 pub enum Event {
@@ -3365,6 +4205,9 @@ const MIN_PACKET_SPACE: usize = 40;
 /// that numbers around 10 are a good compromise.
 const MAX_TRANSMIT_SEGMENTS: usize = 10;
 
+/// Number of transport errors retained for [`Connection::recent_errors()`]
+const MAX_RECENT_ERRORS: usize = 8;
+
 struct ZeroRttCrypto {
     header: Box<dyn HeaderKey>,
     packet: Box<dyn PacketKey>,
@@ -113,6 +113,43 @@ impl Pacer {
         // this is the time at which the pacing window becomes empty
         Some(self.prev + (unscaled_delay / 5) * 4)
     }
+
+    /// Return how many bytes could be sent right away without being delayed
+    ///
+    /// Accounts for the tokens accrued since the pacer was last updated, without updating it.
+    /// Returns `u64::MAX` if pacing is disabled due to an extremely large window or an unknown
+    /// RTT.
+    pub fn available(&self, smoothed_rtt: Duration, mtu: u16, window: u64, now: Instant) -> u64 {
+        if window > u32::MAX.into() || smoothed_rtt.as_nanos() == 0 {
+            return u64::MAX;
+        }
+        let capacity = match window == self.last_window {
+            true => self.capacity,
+            false => optimal_capacity(smoothed_rtt, window, mtu),
+        };
+        let elapsed_rtts =
+            now.saturating_duration_since(self.prev).as_secs_f64() / smoothed_rtt.as_secs_f64();
+        let new_tokens = window as f64 * 1.25 * elapsed_rtts;
+        self.tokens
+            .min(capacity)
+            .saturating_add(new_tokens as _)
+            .min(capacity)
+    }
+}
+
+/// Calculates the average time between packets of `mtu` bytes at the pacing rate
+///
+/// Returns zero when pacing is disabled due to an extremely large window or an unknown RTT.
+pub(super) fn interval(smoothed_rtt: Duration, window: u64, mtu: u16) -> Duration {
+    if window == 0 || window > u32::max_value().into() {
+        return Duration::ZERO;
+    }
+    // Tokens are replenished at 5/4 of a window per RTT
+    smoothed_rtt
+        .checked_mul(u32::from(mtu) * 4)
+        .unwrap_or(Duration::MAX)
+        / (window as u32)
+        / 5
 }
 
 /// Calculates a pacer capacity for a certain window and RTT
@@ -175,6 +212,14 @@ mod tests {
             .is_none());
     }
 
+    #[test]
+    fn pacing_interval() {
+        let rtt = Duration::from_millis(50);
+        // A window of 100 packets per RTT is paced at 125 packets per RTT
+        assert_eq!(interval(rtt, 150_000, 1500), rtt / 125);
+        assert_eq!(interval(rtt, u64::MAX, 1500), Duration::ZERO);
+    }
+
     #[test]
     fn derives_initial_capacity() {
         let window = 2_000_000;
@@ -228,6 +273,27 @@ mod tests {
         assert_eq!(pacer.tokens, initial_tokens / 2);
     }
 
+    #[test]
+    fn available_tokens() {
+        let window = 2_000_000;
+        let mtu = 1500;
+        let rtt = Duration::from_millis(50);
+        let now = Instant::now();
+
+        let mut pacer = Pacer::new(rtt, window, mtu, now);
+        assert_eq!(pacer.available(rtt, mtu, window, now), pacer.capacity);
+        pacer.tokens = 0;
+        assert_eq!(pacer.available(rtt, mtu, window, now), 0);
+        // Tokens accrue at 1.25 windows per RTT, up to the capacity
+        assert_eq!(
+            pacer.available(rtt, mtu, window, now + rtt / 1000),
+            window * 5 / 4 / 1000
+        );
+        assert_eq!(pacer.available(rtt, mtu, window, now + rtt), pacer.capacity);
+        assert_eq!(pacer.tokens, 0);
+        assert_eq!(pacer.available(rtt, mtu, u64::MAX, now), u64::MAX);
+    }
+
     #[test]
     fn computes_pause_correctly() {
         let window = 2_000_000u64;
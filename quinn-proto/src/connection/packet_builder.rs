@@ -51,7 +51,7 @@ impl PacketBuilder {
         let sent_with_keys = conn.spaces[space_id].sent_with_keys;
         if space_id == SpaceId::Data {
             if sent_with_keys.saturating_add(KEY_UPDATE_MARGIN) >= confidentiality_limit {
-                conn.initiate_key_update();
+                conn.update_keys(None, false);
             }
         } else if sent_with_keys.saturating_add(1) == confidentiality_limit {
             // We still have time to attempt a graceful close
@@ -193,6 +193,10 @@ impl PacketBuilder {
         };
 
         conn.in_flight.insert(&packet);
+        if ack_eliciting {
+            let space = &mut conn.spaces[space_id];
+            space.awaiting_retransmission = space.awaiting_retransmission.saturating_sub(1);
+        }
         conn.spaces[space_id].sent(exact_number, packet);
         conn.stats.path.sent_packets += 1;
         conn.reset_keep_alive(now);
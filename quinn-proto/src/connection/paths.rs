@@ -9,6 +9,17 @@ pub struct PathData {
     pub rtt: RttEstimator,
     /// Whether we're enabling ECN on outgoing packets
     pub sending_ecn: bool,
+    /// Whether the peer has reported ECN counts consistent with the packets we marked
+    pub ecn_validated: bool,
+    /// Whether the peer acknowledged a packet without reporting ECN counts
+    pub ecn_unreported: bool,
+    /// When to resume marking outgoing packets after ECN validation failed, if ever
+    pub ecn_retry_at: Option<Instant>,
+    /// Number of the first 1-RTT packet marked since ECN was last resumed
+    ///
+    /// Earlier packets may not have been marked, so aren't expected to be reflected in the peer's
+    /// ECN counts.
+    pub first_ecn_packet: u64,
     /// Congestion controller state
     pub congestion: Box<dyn congestion::Controller>,
     /// Pacing state
@@ -25,6 +36,8 @@ pub struct PathData {
     /// Total size of all UDP datagrams received on this path
     pub total_recvd: u64,
     pub max_udp_payload_size: u16,
+    /// Size of the largest packet sent on this path that the peer acknowledged
+    pub max_acked_packet_size: u16,
     /// Packet number of the first packet sent after an RTT sample was collected on this path
     ///
     /// Used in persistent congestion determination.
@@ -44,6 +57,10 @@ impl PathData {
             remote,
             rtt: RttEstimator::new(initial_rtt),
             sending_ecn: true,
+            ecn_validated: false,
+            ecn_unreported: false,
+            ecn_retry_at: None,
+            first_ecn_packet: 0,
             pacing: Pacer::new(
                 initial_rtt,
                 congestion.initial_window(),
@@ -57,6 +74,7 @@ impl PathData {
             total_sent: 0,
             total_recvd: 0,
             max_udp_payload_size: initial_max_udp_payload_size,
+            max_acked_packet_size: 0,
             first_packet_after_rtt_sample: None,
         }
     }
@@ -74,6 +92,10 @@ impl PathData {
                 now,
             ),
             sending_ecn: true,
+            ecn_validated: false,
+            ecn_unreported: false,
+            ecn_retry_at: None,
+            first_ecn_packet: 0,
             congestion,
             challenge: None,
             challenge_pending: false,
@@ -81,15 +103,72 @@ impl PathData {
             total_sent: 0,
             total_recvd: 0,
             max_udp_payload_size: prev.max_udp_payload_size,
+            max_acked_packet_size: 0,
             first_packet_after_rtt_sample: prev.first_packet_after_rtt_sample,
         }
     }
 
+    pub fn ecn_state(&self) -> EcnState {
+        match (self.sending_ecn, self.ecn_validated) {
+            (false, _) if self.ecn_unreported => EcnState::Unreported,
+            (false, _) => EcnState::Failed,
+            (true, false) => EcnState::Testing,
+            (true, true) => EcnState::Capable,
+        }
+    }
+
     /// Indicates whether we're a server that hasn't validated the peer's address and hasn't
     /// received enough data from the peer to permit sending `bytes_to_send` additional bytes
     pub fn anti_amplification_blocked(&self, bytes_to_send: u64) -> bool {
         !self.validated && self.total_recvd * 3 < self.total_sent + bytes_to_send
     }
+
+    /// Bytes which may still be sent before hitting the anti-amplification limit, or `None` if the
+    /// path is validated
+    pub fn amplification_budget(&self) -> Option<u64> {
+        match self.validated {
+            true => None,
+            false => Some((self.total_recvd * 3).saturating_sub(self.total_sent)),
+        }
+    }
+}
+
+/// Whether explicit congestion notification (ECN) is in use on a path
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EcnState {
+    /// Outgoing packets are marked, but the peer has yet to confirm that the marks arrive intact
+    Testing,
+    /// The peer reports receiving our marks, so congestion may be signaled through ECN
+    Capable,
+    /// ECN has been disabled because the peer's acknowledgements don't report ECN counts
+    ///
+    /// Either the peer doesn't support ECN, or the marks were stripped along the path before
+    /// reaching it.
+    Unreported,
+    /// ECN has been disabled because the reported ECN counts were inconsistent with the packets
+    /// we marked, e.g. because the path mangled the marks
+    Failed,
+}
+
+/// How to react when the peer's ECN counts fail validation
+///
+/// See [`TransportConfig::ecn_validation_policy()`](crate::TransportConfig::ecn_validation_policy).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EcnValidationPolicy {
+    /// Stop marking outgoing packets for the rest of the connection's time on the path
+    Disable,
+    /// Stop marking outgoing packets, then resume and validate ECN again after a delay
+    ///
+    /// Suits paths which mangle ECN marks only intermittently, at the cost of another
+    /// validation failure each time the path is still found to be faulty.
+    Retry(Duration),
+}
+
+impl Default for EcnValidationPolicy {
+    fn default() -> Self {
+        Self::Disable
+    }
 }
 
 /// RTT estimation for a particular network path
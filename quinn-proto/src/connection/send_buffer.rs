@@ -172,6 +172,11 @@ impl SendBuffer {
         self.offset
     }
 
+    /// First stream offset that has never been transmitted
+    pub fn unsent(&self) -> u64 {
+        self.unsent
+    }
+
     /// Whether all sent data has been acknowledged
     pub fn is_fully_acked(&self) -> bool {
         self.unacked_len == 0
@@ -56,9 +56,16 @@ pub(crate) struct PacketSpace {
     pub(crate) loss_time: Option<Instant>,
     /// Number of tail loss probes to send
     pub(crate) loss_probes: u32,
+    /// The packet number of the first packet sent after the probe timeout fired, while no ACK has
+    /// been received since
+    ///
+    /// An ACK for an earlier ack-eliciting packet shows that the timeout was spurious.
+    pub(crate) pto_fired_at: Option<u64>,
     pub(crate) ping_pending: bool,
     /// Number of congestion control "in flight" bytes
     pub(crate) in_flight: u64,
+    /// Number of ack-eliciting packets declared lost whose contents haven't been resent yet
+    pub(crate) awaiting_retransmission: u64,
     /// Number of packets sent in the current key phase
     pub(crate) sent_with_keys: u64,
 }
@@ -86,8 +93,10 @@ impl PacketSpace {
             time_of_last_ack_eliciting_packet: None,
             loss_time: None,
             loss_probes: 0,
+            pto_fired_at: None,
             ping_pending: false,
             in_flight: 0,
+            awaiting_retransmission: 0,
             sent_with_keys: 0,
         }
     }
@@ -233,6 +242,8 @@ pub struct Retransmits {
     pub(crate) new_cids: Vec<IssuedCid>,
     pub(crate) retire_cids: Vec<u64>,
     pub(crate) handshake_done: bool,
+    /// Number of NEW_TOKEN frames to send, each with a freshly issued token
+    pub(crate) new_tokens: u32,
 }
 
 impl Retransmits {
@@ -249,6 +260,7 @@ impl Retransmits {
             && self.new_cids.is_empty()
             && self.retire_cids.is_empty()
             && !self.handshake_done
+            && self.new_tokens == 0
     }
 }
 
@@ -269,6 +281,7 @@ impl ::std::ops::BitOrAssign for Retransmits {
         self.new_cids.extend(&rhs.new_cids);
         self.retire_cids.extend(rhs.retire_cids);
         self.handshake_done |= rhs.handshake_done;
+        self.new_tokens += rhs.new_tokens;
     }
 }
 
@@ -454,12 +467,14 @@ impl PendingAcks {
         self.permit_ack_only = false;
     }
 
-    /// Insert one packet that needs to be acknowledged
-    pub fn insert_one(&mut self, packet: u64, now: Instant) {
+    /// Insert one packet that needs to be acknowledged, forgetting the oldest ranges in excess of
+    /// `max_ranges`
+    pub fn insert_one(&mut self, packet: u64, now: Instant, max_ranges: usize) {
         self.ranges.insert_one(packet);
         self.latest_incoming = Some(now);
 
-        if self.ranges.len() > MAX_ACK_BLOCKS {
+        let max_ranges = max_ranges.clamp(1, MAX_ACK_BLOCKS);
+        while self.ranges.len() > max_ranges {
             self.ranges.pop_min();
         }
     }
@@ -538,6 +553,23 @@ mod test {
         assert_eq!(dedup.window, 1 << (WINDOW_SIZE - 2));
     }
 
+    #[test]
+    fn ack_range_limit() {
+        let now = Instant::now();
+        let mut acks = PendingAcks::default();
+        for packet in (0..10).step_by(2) {
+            acks.insert_one(packet, now, 3);
+        }
+        assert_eq!(acks.ranges().len(), 3);
+        assert_eq!(acks.ranges().min(), Some(4));
+
+        // Oversized limits are capped
+        for packet in (10..200).step_by(2) {
+            acks.insert_one(packet, now, usize::MAX);
+        }
+        assert_eq!(acks.ranges().len(), MAX_ACK_BLOCKS);
+    }
+
     #[test]
     fn sent_packet_size() {
         // The tracking state of sent packets should be minimal, and not grow
@@ -5,6 +5,7 @@ use std::time::Duration;
 
 /// Statistics about UDP datagrams transmitted or received on a connection
 #[derive(Default, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[non_exhaustive]
 pub struct UdpStats {
     /// The amount of UDP datagrams observed
@@ -20,6 +21,7 @@ pub struct UdpStats {
 
 /// Statistics about frames transmitted or received on a connection
 #[derive(Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[non_exhaustive]
 pub struct FrameStats {
     pub acks: u64,
@@ -118,6 +120,7 @@ impl std::fmt::Debug for FrameStats {
 
 /// Statistics related to a transmission path
 #[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[non_exhaustive]
 pub struct PathStats {
     /// Current best estimate of this connection's latency (round-trip-time)
@@ -132,10 +135,99 @@ pub struct PathStats {
     pub lost_bytes: u64,
     /// The amount of packets sent on this path
     pub sent_packets: u64,
+    /// The amount of PATH_CHALLENGE frames left unanswered because too many responses were queued
+    pub path_challenges_dropped: u64,
+    /// The amount of packets from an unrecognized peer address refused because migration is
+    /// disabled
+    ///
+    /// See [`ServerConfig::migration()`](crate::ServerConfig::migration).
+    pub migrations_refused: u64,
+    /// The amount of times the connection had data to send but was stopped by the congestion
+    /// window or pacing
+    ///
+    /// A value which keeps growing indicates that throughput is limited by congestion control
+    /// rather than by the application or the socket.
+    pub congestion_blocked: u64,
+    /// The amount of times the probe timeout fired
+    pub ptos: u64,
+    /// The amount of probe timeouts found to be spurious because a packet sent before the timeout
+    /// was acknowledged afterwards
+    ///
+    /// A large fraction of `ptos` indicates that the timeout is too aggressive for the path's
+    /// jitter, e.g. because the peer's `max_ack_delay` is underestimated.
+    pub spurious_ptos: u64,
+    /// The amount of times the peer's ECN counts were inconsistent with the packets marked,
+    /// disabling ECN
+    ///
+    /// See [`TransportConfig::ecn_validation_policy()`](crate::TransportConfig::ecn_validation_policy).
+    pub ecn_validation_failures: u64,
+}
+
+/// Statistics about unreliable application datagrams
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct DatagramStats {
+    /// The amount of datagrams transmitted to the peer
+    pub sent: u64,
+    /// The amount of datagrams received from the peer, including those later dropped
+    pub received: u64,
+    /// The amount of received datagrams dropped because the receive buffer was full
+    pub recv_dropped: u64,
+    /// The amount of queued outgoing datagrams dropped to make room for newer ones because the
+    /// send buffer was full
+    ///
+    /// Indicates that datagrams are being sent faster than the connection can transmit them.
+    pub send_dropped: u64,
+    /// The amount of datagrams which exceeded the maximum size
+    ///
+    /// Depending on the [`OversizedDatagramPolicy`](crate::OversizedDatagramPolicy), these were
+    /// rejected, dropped, or truncated.
+    ///
+    /// See [`Datagrams::max_size()`](crate::Datagrams::max_size).
+    pub too_large: u64,
+}
+
+/// Statistics about stream data transmitted on a connection
+///
+/// The share of retransmitted bytes indicates how efficiently the current path carries
+/// application data: data lost on the way must be sent again, consuming capacity without making
+/// progress.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct StreamDataStats {
+    /// The amount of stream data bytes transmitted for the first time
+    pub original_bytes: u64,
+    /// The amount of stream data bytes transmitted again after being declared lost
+    pub retransmitted_bytes: u64,
+}
+
+/// Connection-level flow control state
+///
+/// An exhausted send window which stops growing indicates that throughput is limited by the
+/// peer's flow control rather than by congestion control.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct FlowControlStats {
+    /// Total amount of stream data the peer allows us to send (its `MAX_DATA`)
+    pub peer_max_data: u64,
+    /// Total amount of stream data sent, including data not yet acknowledged
+    pub data_sent: u64,
+    /// Total amount of stream data we allow the peer to send (our `MAX_DATA`)
+    pub local_max_data: u64,
+    /// Total amount of stream data received, including any gaps yet to be filled
+    pub data_received: u64,
+    /// Current connection-level receive window
+    ///
+    /// See [`Connection::set_receive_window()`](crate::Connection::set_receive_window).
+    pub receive_window: u64,
 }
 
 /// Connection statistics
 #[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[non_exhaustive]
 pub struct ConnectionStats {
     /// Statistics about UDP datagrams transmitted on a connection
@@ -148,4 +240,17 @@ pub struct ConnectionStats {
     pub frame_rx: FrameStats,
     /// Statistics related to the current transmission path
     pub path: PathStats,
+    /// Statistics about application datagrams
+    pub datagrams: DatagramStats,
+    /// Statistics about transmitted stream data
+    pub stream_data: StreamDataStats,
+    /// The number of ranges of received packets currently tracked for acknowledgement
+    ///
+    /// See [`TransportConfig::max_ack_ranges()`](crate::TransportConfig::max_ack_ranges).
+    pub ack_ranges: u64,
+    /// The number of streams opened by the peer, in either direction
+    ///
+    /// Sample periodically to observe the rate at which the peer opens streams. See
+    /// [`TransportConfig::max_remote_stream_rate()`](crate::TransportConfig::max_remote_stream_rate).
+    pub remote_streams_opened: u64,
 }
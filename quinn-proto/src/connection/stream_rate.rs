@@ -0,0 +1,78 @@
+//! Limiting the rate at which the peer opens streams
+
+use std::time::{Duration, Instant};
+
+/// A token bucket refilled with `count` streams per `interval`, holding at most `count`
+#[derive(Debug)]
+pub(super) struct StreamRateLimiter {
+    count: u32,
+    interval: Duration,
+    tokens: f64,
+    /// When `tokens` was last refilled
+    updated: Option<Instant>,
+    /// Number of streams opened by the peer which have been accounted for
+    counted: u64,
+}
+
+impl StreamRateLimiter {
+    pub(super) fn new(count: u32, interval: Duration) -> Self {
+        Self {
+            count,
+            interval,
+            tokens: count.into(),
+            updated: None,
+            counted: 0,
+        }
+    }
+
+    /// Account for the peer having opened `opened` streams in total
+    ///
+    /// Returns `false` if the streams opened since the last call exceed the limit.
+    pub(super) fn check(&mut self, now: Instant, opened: u64) -> bool {
+        let new = opened.saturating_sub(self.counted);
+        if new == 0 {
+            return true;
+        }
+        self.counted = opened;
+        if let Some(updated) = self.updated {
+            let elapsed = now.saturating_duration_since(updated);
+            let refill = if elapsed >= self.interval {
+                self.count.into()
+            } else {
+                elapsed.as_secs_f64() / self.interval.as_secs_f64() * f64::from(self.count)
+            };
+            self.tokens = (self.tokens + refill).min(self.count.into());
+        }
+        self.updated = Some(now);
+        self.tokens -= new as f64;
+        self.tokens >= 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refill() {
+        let start = Instant::now();
+        let mut limiter = StreamRateLimiter::new(4, Duration::from_secs(1));
+        // A full bucket allows a burst
+        assert!(limiter.check(start, 4));
+        assert!(limiter.check(start, 4));
+        // Half the interval refills half the bucket
+        let half = start + Duration::from_millis(500);
+        assert!(limiter.check(half, 6));
+        assert!(!limiter.check(half, 7));
+    }
+
+    #[test]
+    fn capped() {
+        let start = Instant::now();
+        let mut limiter = StreamRateLimiter::new(2, Duration::from_secs(1));
+        assert!(limiter.check(start, 1));
+        // Idle time doesn't allow bursts larger than the bucket
+        let later = start + Duration::from_secs(60);
+        assert!(!limiter.check(later, 4));
+    }
+}
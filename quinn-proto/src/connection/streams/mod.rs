@@ -21,6 +21,21 @@ use send::{Send, SendState};
 mod state;
 pub use state::StreamsState;
 
+/// Decides whether to admit streams opened by the peer
+///
+/// Installed with [`TransportConfig::stream_admission()`](crate::TransportConfig::stream_admission).
+/// Implemented for any `FnMut(StreamId) -> bool` closure.
+pub trait StreamAdmission: std::marker::Send {
+    /// Called as soon as the peer opens the stream `id`; returning `false` rejects it
+    fn admit(&mut self, id: StreamId) -> bool;
+}
+
+impl<F: FnMut(StreamId) -> bool + std::marker::Send> StreamAdmission for F {
+    fn admit(&mut self, id: StreamId) -> bool {
+        self(id)
+    }
+}
+
 /// Access to streams
 pub struct Streams<'a> {
     pub(super) state: &'a mut StreamsState,
@@ -57,17 +72,27 @@ impl<'a> Streams<'a> {
     ///
     /// Returns `None` if there are no new incoming streams for this connection.
     pub fn accept(&mut self, dir: Dir) -> Option<StreamId> {
-        if self.state.next_remote[dir as usize] == self.state.next_reported_remote[dir as usize] {
-            return None;
-        }
+        loop {
+            if self.state.next_remote[dir as usize] == self.state.next_reported_remote[dir as usize]
+            {
+                return None;
+            }
 
-        let x = self.state.next_reported_remote[dir as usize];
-        self.state.next_reported_remote[dir as usize] = x + 1;
-        if dir == Dir::Bi {
-            self.state.send_streams += 1;
-        }
+            let x = self.state.next_reported_remote[dir as usize];
+            let rejected = &mut self.state.rejected_remote[dir as usize];
+            if rejected.peek_min().map_or(false, |range| range.start == x) {
+                // Refused by the `StreamAdmission` policy
+                self.state.next_reported_remote[dir as usize] = rejected.pop_min().unwrap().end;
+                continue;
+            }
+            self.state.next_reported_remote[dir as usize] = x + 1;
+            let id = StreamId::new(!self.state.side, dir, x);
+            if dir == Dir::Bi {
+                self.state.send_streams += 1;
+            }
 
-        Some(StreamId::new(!self.state.side, dir, x))
+            return Some(id);
+        }
     }
 
     #[cfg(fuzzing)]
@@ -109,6 +134,25 @@ impl<'a> RecvStream<'a> {
         Chunks::new(self.id, ordered, self.state, self.pending)
     }
 
+    /// Number of received bytes which can be read without waiting for more data
+    ///
+    /// For ordered reads, this counts only data contiguous with what's already been read. Returns
+    /// 0 once the stream has been stopped or fully read.
+    pub fn available(&self) -> u64 {
+        self.state
+            .recv
+            .get(&self.id)
+            .map_or(0, |stream| stream.assembler.readable())
+    }
+
+    /// The error code the peer reset the stream with, if it has
+    ///
+    /// Only available until the reset is reported by [`read()`](Self::read), after which the
+    /// stream is forgotten.
+    pub fn reset_code(&self) -> Option<VarInt> {
+        self.state.recv.get(&self.id)?.reset_code()
+    }
+
     /// Stop accepting data on the given receive stream
     ///
     /// Discards unread data and notifies the peer to stop transmitting. Once stopped, further
@@ -304,6 +348,80 @@ impl<'a> SendStream<'a> {
 
         Ok(stream.priority)
     }
+
+    /// Get the amount of data written to the stream, sent, and acknowledged by the peer
+    ///
+    /// Fails with `UnknownStream` once the stream has been reset, or finished and fully
+    /// acknowledged.
+    ///
+    /// # Panics
+    /// - when applied to a receive stream
+    pub fn progress(&self) -> Result<StreamProgress, UnknownStream> {
+        let stream = self
+            .state
+            .send
+            .get(&self.id)
+            .ok_or(UnknownStream { _private: () })?;
+
+        let pending = &stream.pending;
+        Ok(StreamProgress {
+            written: pending.offset(),
+            sent: pending.unsent(),
+            acked: pending.offset() - pending.unacked(),
+        })
+    }
+
+    /// Get the amount of data written to the stream that has never been transmitted
+    ///
+    /// Unlike data in flight, this grows when the application writes faster than the connection
+    /// can send. Returns 0 once the stream has been reset, or finished and fully acknowledged.
+    pub fn buffered(&self) -> u64 {
+        self.state.send.get(&self.id).map_or(0, |stream| {
+            stream.pending.offset() - stream.pending.unsent()
+        })
+    }
+
+    /// Whether the peer's flow control limit for this stream has been reached
+    ///
+    /// While this holds, writes fail with `WriteError::Blocked` until the peer raises the limit
+    /// with a MAX_STREAM_DATA frame, independently of connection-level flow control. Returns
+    /// `false` if the stream no longer exists.
+    pub fn is_flow_control_blocked(&self) -> bool {
+        self.state
+            .send
+            .get(&self.id)
+            .map_or(false, |stream| stream.pending.offset() >= stream.max_data)
+    }
+}
+
+/// Progress of the data written to a send stream, in bytes
+///
+/// Data is sent after it's written and acknowledged after it's sent, so
+/// `acked <= sent <= written` always holds.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct StreamProgress {
+    /// Data written to the stream by the application
+    pub written: u64,
+    /// Data transmitted at least once, including data awaiting retransmission
+    pub sent: u64,
+    /// Data acknowledged by the peer, possibly out of order
+    pub acked: u64,
+}
+
+/// Cumulative limits on the number of streams each endpoint may open, per direction
+///
+/// These are the stream counts carried by the `initial_max_streams_*` transport parameters and
+/// subsequent `MAX_STREAMS` frames, so they include streams which have already been closed.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct StreamLimits {
+    /// Bidirectional streams the peer permits us to open
+    pub local_bi: u64,
+    /// Unidirectional streams the peer permits us to open
+    pub local_uni: u64,
+    /// Bidirectional streams we permit the peer to open
+    pub remote_bi: u64,
+    /// Unidirectional streams we permit the peer to open
+    pub remote_uni: u64,
 }
 
 fn push_pending(pending: &mut BinaryHeap<PendingLevel>, id: StreamId, priority: i32) {
@@ -130,6 +130,14 @@ impl Recv {
         matches!(self.state, RecvState::Recv { .. })
     }
 
+    /// The error code the peer reset the stream with, if it has
+    pub(super) fn reset_code(&self) -> Option<VarInt> {
+        match self.state {
+            RecvState::ResetRecvd { error_code, .. } => Some(error_code),
+            RecvState::Recv { .. } => None,
+        }
+    }
+
     fn final_offset(&self) -> Option<u64> {
         match self.state {
             RecvState::Recv { size } => size,
@@ -307,7 +315,8 @@ impl<'a> Chunks<'a> {
 
         // If the stream hasn't finished, we may need to issue stream-level flow control credit
         if let ChunksState::Readable(mut rs) = state {
-            let (_, max_stream_data) = rs.max_stream_data(self.streams.stream_receive_window);
+            let (_, max_stream_data) =
+                rs.max_stream_data(self.streams.stream_receive_window(self.id));
             should_transmit |= max_stream_data.0;
             if max_stream_data.0 {
                 self.pending.max_stream_data.insert(self.id);
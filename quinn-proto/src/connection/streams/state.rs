@@ -10,12 +10,13 @@ use tracing::{debug, trace};
 
 use super::{
     push_pending, PendingLevel, Recv, Retransmits, Send, SendState, ShouldTransmit, StreamEvent,
-    StreamHalf, ThinRetransmits,
+    StreamHalf, StreamLimits, ThinRetransmits,
 };
 use crate::{
     coding::BufMutExt,
-    connection::stats::FrameStats,
+    connection::stats::{FlowControlStats, FrameStats, StreamDataStats},
     frame::{self, FrameStruct, StreamMetaVec},
+    range_set::RangeSet,
     transport_parameters::TransportParameters,
     Dir, Side, StreamId, TransportError, VarInt, MAX_STREAM_COUNT,
 };
@@ -46,6 +47,13 @@ pub struct StreamsState {
     opened: [bool; 2],
     // Next to report to the application, once opened
     pub(super) next_reported_remote: [u64; 2],
+    /// Lowest remotely-initiated stream index not yet checked against the admission policy
+    next_admitted_remote: [u64; 2],
+    /// Indices of remotely-initiated streams rejected by the admission policy which haven't been
+    /// skipped by `accept` yet, per directionality
+    ///
+    /// Kept as ranges, as rejected streams are freed and a peer may keep opening more of them.
+    pub(super) rejected_remote: [RangeSet; 2],
     /// Number of outbound streams
     ///
     /// This differs from `self.send.len()` in that it does not include streams that the peer is
@@ -76,8 +84,10 @@ pub struct StreamsState {
     pub(super) unacked_data: u64,
     /// Configured upper bound for `unacked_data`
     pub(super) send_window: u64,
-    /// Configured upper bound for how much unacked data the peer can send us per stream
-    pub(super) stream_receive_window: u64,
+    /// Configured upper bound for how much unacked data the peer can send us per stream, for
+    /// bidirectional streams we opened, bidirectional streams the peer opened, and unidirectional
+    /// streams
+    stream_receive_windows: (u64, u64, u64),
     /// Whether the corresponding `max_remote` has increased
     max_streams_dirty: [bool; 2],
 
@@ -97,7 +107,7 @@ impl StreamsState {
         max_remote_bi: VarInt,
         send_window: u64,
         receive_window: VarInt,
-        stream_receive_window: VarInt,
+        stream_receive_windows: (VarInt, VarInt, VarInt),
     ) -> Self {
         let mut this = Self {
             side,
@@ -112,6 +122,8 @@ impl StreamsState {
             next_remote: [0, 0],
             opened: [false, false],
             next_reported_remote: [0, 0],
+            next_admitted_remote: [0, 0],
+            rejected_remote: [RangeSet::new(), RangeSet::new()],
             send_streams: 0,
             pending: BinaryHeap::new(),
             events: VecDeque::new(),
@@ -124,7 +136,11 @@ impl StreamsState {
             data_recvd: 0,
             unacked_data: 0,
             send_window,
-            stream_receive_window: stream_receive_window.into(),
+            stream_receive_windows: (
+                stream_receive_windows.0.into(),
+                stream_receive_windows.1.into(),
+                stream_receive_windows.2.into(),
+            ),
             max_streams_dirty: [false, false],
             initial_max_stream_data_uni: 0u32.into(),
             initial_max_stream_data_bidi_local: 0u32.into(),
@@ -324,6 +340,12 @@ impl StreamsState {
         }
     }
 
+    /// Whether a stream write failed due to connection-level flow control or the send window, and
+    /// no more credit has been received since
+    pub fn is_write_blocked(&self) -> bool {
+        !self.connection_blocked.is_empty()
+    }
+
     /// Whether any stream data is queued, regardless of control frames
     pub fn can_send_stream_data(&self) -> bool {
         // Reset streams may linger in the pending stream list, but will never produce stream frames
@@ -424,6 +446,7 @@ impl StreamsState {
                 None => break,
             };
             pending.max_stream_data.remove(&id);
+            let window = self.stream_receive_window(id);
             let rs = match self.recv.get_mut(&id) {
                 Some(x) => x,
                 None => continue,
@@ -433,7 +456,7 @@ impl StreamsState {
             }
             retransmits.get_or_create().max_stream_data.insert(id);
 
-            let (max, _) = rs.max_stream_data(self.stream_receive_window);
+            let (max, _) = rs.max_stream_data(window);
             rs.record_sent_max_stream_data(max);
 
             trace!(stream = %id, max = max, "MAX_STREAM_DATA");
@@ -469,7 +492,12 @@ impl StreamsState {
         }
     }
 
-    pub fn write_stream_frames(&mut self, buf: &mut Vec<u8>, max_buf_size: usize) -> StreamMetaVec {
+    pub fn write_stream_frames(
+        &mut self,
+        buf: &mut Vec<u8>,
+        max_buf_size: usize,
+        stats: &mut StreamDataStats,
+    ) -> StreamMetaVec {
         let mut stream_frames = StreamMetaVec::new();
         while buf.len() + frame::Stream::SIZE_BOUND < max_buf_size {
             if max_buf_size
@@ -515,7 +543,14 @@ impl StreamsState {
             // Now that we know the `StreamId`, we can better account for how many bytes
             // are required to encode it.
             let max_buf_size = max_buf_size - buf.len() - 1 - VarInt::size(id.into());
+            let unsent = stream.pending.unsent();
             let (offsets, encode_length) = stream.pending.poll_transmit(max_buf_size);
+            let len = offsets.end - offsets.start;
+            if offsets.start < unsent {
+                stats.retransmitted_bytes += len;
+            } else {
+                stats.original_bytes += len;
+            }
             let fin = offsets.end == stream.pending.offset()
                 && matches!(stream.state, SendState::DataSent { .. });
             if fin {
@@ -580,26 +615,33 @@ impl StreamsState {
         }
     }
 
-    pub fn received_ack_of(&mut self, frame: frame::StreamMeta) {
+    /// Process the acknowledgement of a stream frame
+    ///
+    /// Returns the number of bytes of stream data acknowledged for the first time.
+    pub fn received_ack_of(&mut self, frame: frame::StreamMeta) -> u64 {
         let mut entry = match self.send.entry(frame.id) {
-            hash_map::Entry::Vacant(_) => return,
+            hash_map::Entry::Vacant(_) => return 0,
             hash_map::Entry::Occupied(e) => e,
         };
         let stream = entry.get_mut();
         if stream.is_reset() {
             // We account for outstanding data on reset streams at time of reset
-            return;
+            return 0;
         }
         let id = frame.id;
         self.unacked_data -= frame.offsets.end - frame.offsets.start;
-        if !stream.ack(frame) {
+        let unacked = stream.pending.unacked();
+        let finished = stream.ack(frame);
+        let newly_acked = unacked - stream.pending.unacked();
+        if !finished {
             // The stream is unfinished or may still need retransmits
-            return;
+            return newly_acked;
         }
 
         entry.remove_entry();
         self.stream_freed(id, StreamHalf::Send);
         self.events.push_back(StreamEvent::Finished { id });
+        newly_acked
     }
 
     pub fn retransmit(&mut self, frame: frame::StreamMeta) {
@@ -621,9 +663,12 @@ impl StreamsState {
                 let id = StreamId::new(Side::Client, dir, index);
                 let stream = self.send.get_mut(&id).unwrap();
                 if stream.pending.is_fully_acked() && !stream.fin_pending {
-                    // Stream data can't be acked in 0-RTT, so we must not have sent anything on
-                    // this stream
-                    continue;
+                    // Stream data can't be acked in 0-RTT, so we must not have sent any data on
+                    // this stream, but a FIN may have been sent on an empty stream
+                    match stream.state {
+                        SendState::DataSent { .. } => stream.fin_pending = true,
+                        _ => continue,
+                    }
                 }
                 if !stream.is_pending() {
                     push_pending(&mut self.pending, id, stream.priority);
@@ -649,6 +694,49 @@ impl StreamsState {
         Ok(())
     }
 
+    /// Number of streams opened by the peer so far, in either direction
+    pub(crate) fn remote_opened(&self) -> u64 {
+        self.next_remote.iter().sum()
+    }
+
+    /// Yield the next stream opened by the peer which hasn't been checked for admission yet
+    pub(crate) fn next_unadmitted(&mut self, dir: Dir) -> Option<StreamId> {
+        let next = &mut self.next_admitted_remote[dir as usize];
+        if *next == self.next_remote[dir as usize] {
+            return None;
+        }
+        *next += 1;
+        Some(StreamId::new(!self.side, dir, *next - 1))
+    }
+
+    /// Hide a stream rejected by the admission policy from `accept`
+    pub(crate) fn reject_remote(&mut self, id: StreamId) {
+        if id.dir() == Dir::Bi {
+            // Balances the decrement when the reset send half is freed, as `accept` would
+            self.send_streams += 1;
+        }
+        self.rejected_remote[id.dir() as usize].insert_one(id.index());
+    }
+
+    pub(crate) fn limits(&self) -> StreamLimits {
+        StreamLimits {
+            local_bi: self.max[Dir::Bi as usize],
+            local_uni: self.max[Dir::Uni as usize],
+            remote_bi: self.max_remote[Dir::Bi as usize],
+            remote_uni: self.max_remote[Dir::Uni as usize],
+        }
+    }
+
+    pub(crate) fn flow_control_stats(&self) -> FlowControlStats {
+        FlowControlStats {
+            peer_max_data: self.max_data,
+            data_sent: self.data_sent,
+            local_max_data: self.local_max_data,
+            data_received: self.data_recvd,
+            receive_window: self.receive_window,
+        }
+    }
+
     /// Handle increase to connection-level flow control limit
     pub fn received_max_data(&mut self, n: VarInt) {
         self.max_data = self.max_data.max(n.into());
@@ -752,6 +840,15 @@ impl StreamsState {
         Ok(())
     }
 
+    /// Upper bound for how much unacked data the peer can send us on stream `id`
+    pub(super) fn stream_receive_window(&self, id: StreamId) -> u64 {
+        match id.dir() {
+            Dir::Uni => self.stream_receive_windows.2,
+            Dir::Bi if id.initiator() == self.side => self.stream_receive_windows.0,
+            Dir::Bi => self.stream_receive_windows.1,
+        }
+    }
+
     /// Whether a locally initiated stream has never been open
     pub fn is_local_unopened(&self, id: StreamId) -> bool {
         id.index() >= self.next[id.dir() as usize]
@@ -797,7 +894,7 @@ impl StreamsState {
         if bi || remote {
             assert!(self
                 .recv
-                .insert(id, Recv::new(self.stream_receive_window))
+                .insert(id, Recv::new(self.stream_receive_window(id)))
                 .is_none());
         }
     }
@@ -867,7 +964,11 @@ mod tests {
             128u32.into(),
             1024 * 1024,
             (1024 * 1024u32).into(),
-            (1024 * 1024u32).into(),
+            (
+                (1024 * 1024u32).into(),
+                (1024 * 1024u32).into(),
+                (1024 * 1024u32).into(),
+            ),
         )
     }
 
@@ -1251,7 +1352,7 @@ mod tests {
         high.write(b"high").unwrap();
 
         let mut buf = Vec::with_capacity(40);
-        let meta = server.write_stream_frames(&mut buf, 40);
+        let meta = server.write_stream_frames(&mut buf, 40, &mut StreamDataStats::default());
         assert_eq!(meta[0].id, id_high);
         assert_eq!(meta[1].id, id_mid);
         assert_eq!(meta[2].id, id_low);
@@ -1310,7 +1411,7 @@ mod tests {
         high.set_priority(-1).unwrap();
 
         let mut buf = Vec::with_capacity(1000);
-        let meta = server.write_stream_frames(&mut buf, 40);
+        let meta = server.write_stream_frames(&mut buf, 40, &mut StreamDataStats::default());
         assert_eq!(meta.len(), 1);
         assert_eq!(meta[0].id, id_high);
 
@@ -1318,7 +1419,7 @@ mod tests {
         assert_eq!(server.pending.len(), 2);
 
         // Send the remaining data. The initial mid priority one should go first now
-        let meta = server.write_stream_frames(&mut buf, 1000);
+        let meta = server.write_stream_frames(&mut buf, 1000, &mut StreamDataStats::default());
         assert_eq!(meta.len(), 2);
         assert_eq!(meta[0].id, id_mid);
         assert_eq!(meta[1].id, id_high);
@@ -1653,4 +1754,40 @@ mod tests {
         assert_eq!(server.local_max_data, expected_local_max_data);
         assert!(should_transmit.should_transmit());
     }
+
+    #[test]
+    fn rejected_remote_streams() {
+        let mut server = make(Side::Server);
+        let _ = server
+            .received(
+                frame::Stream {
+                    id: StreamId::new(Side::Client, Dir::Uni, 4),
+                    offset: 0,
+                    fin: false,
+                    data: Bytes::from_static(b"hello"),
+                },
+                5,
+            )
+            .unwrap();
+        // Only stream 3 is admitted
+        while let Some(id) = server.next_unadmitted(Dir::Uni) {
+            if id.index() != 3 {
+                server.reject_remote(id);
+            }
+        }
+        // Consecutive rejected streams are tracked together
+        assert_eq!(server.rejected_remote[Dir::Uni as usize].len(), 2);
+
+        let state = ConnState::Established;
+        let mut streams = Streams {
+            state: &mut server,
+            conn_state: &state,
+        };
+        assert_eq!(
+            streams.accept(Dir::Uni),
+            Some(StreamId::new(Side::Client, Dir::Uni, 3))
+        );
+        assert_eq!(streams.accept(Dir::Uni), None);
+        assert!(server.rejected_remote[Dir::Uni as usize].is_empty());
+    }
 }
@@ -49,6 +49,14 @@ pub trait Session: Send + 'static {
     /// If the 0-RTT-encrypted data has been accepted by the peer
     fn early_data_accepted(&self) -> Option<bool>;
 
+    /// Whether the handshake resumed a previous session rather than performing a full handshake
+    ///
+    /// Only meaningful once the server's handshake response has been processed. The default
+    /// implementation returns `false`, for sessions which don't support resumption.
+    fn is_resumed(&self) -> bool {
+        false
+    }
+
     /// Returns `true` until the connection is fully established.
     fn is_handshaking(&self) -> bool;
 
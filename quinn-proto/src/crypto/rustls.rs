@@ -13,6 +13,7 @@ use rustls::{
 };
 
 use crate::{
+    client_hello::{offered_alpn_protocols, take_prefixed},
     crypto::{
         self, CryptoError, ExportKeyingMaterialError, HeaderKey, KeyPair, Keys, UnsupportedVersion,
     },
@@ -26,6 +27,16 @@ pub struct TlsSession {
     got_handshake_data: bool,
     next_secrets: Option<Secrets>,
     inner: Connection,
+    /// Prefix of the ClientHello received so far, if it's yet to be inspected
+    client_hello: Option<Vec<u8>>,
+    /// Application protocols offered in the ClientHello
+    offered_protocols: Option<Vec<Vec<u8>>>,
+    /// Prefix of the server's first flight seen so far, if it's yet to be inspected
+    server_hello: Option<Vec<u8>>,
+    /// Key exchange group selected in the ServerHello
+    key_exchange_group: Option<rustls::NamedGroup>,
+    /// Whether the ServerHello accepted a pre-shared key, resuming a previous session
+    resumed: bool,
 }
 
 impl TlsSession {
@@ -35,6 +46,59 @@ impl TlsSession {
             Connection::Server(_) => Side::Server,
         }
     }
+
+    /// Buffer incoming handshake data until the whole ClientHello can be inspected
+    ///
+    /// rustls doesn't expose the client's offer after ALPN negotiation, so we parse it ourselves.
+    fn record_client_hello(&mut self, buf: &[u8]) {
+        let hello = match self.client_hello {
+            Some(ref mut x) => x,
+            None => return,
+        };
+        hello.extend_from_slice(buf);
+        let len = match hello.get(1..4) {
+            Some(x) => 4 + x.iter().fold(0, |acc, &b| (acc << 8) | usize::from(b)),
+            None => return,
+        };
+        if hello.len() >= len {
+            self.offered_protocols = offered_alpn_protocols(&hello[4..len]);
+        } else if len <= MAX_CLIENT_HELLO_SIZE {
+            return;
+        }
+        self.client_hello = None;
+    }
+
+    /// Buffer the handshake data sent by the server until the ServerHello can be inspected
+    ///
+    /// rustls doesn't expose the negotiated key exchange group or whether the session was resumed,
+    /// so we parse them ourselves. A HelloRetryRequest, which takes the form of a ServerHello, may
+    /// precede the real one.
+    fn record_server_hello(&mut self, buf: &[u8]) {
+        let flight = match self.server_hello {
+            Some(ref mut x) => x,
+            None => return,
+        };
+        flight.extend_from_slice(buf);
+        loop {
+            let len = match flight.get(1..4) {
+                Some(x) => 4 + x.iter().fold(0, |acc, &b| (acc << 8) | usize::from(b)),
+                None => return,
+            };
+            if flight[0] != SERVER_HELLO_TYPE || len > MAX_SERVER_HELLO_SIZE {
+                break;
+            }
+            if flight.len() < len {
+                return;
+            }
+            let hello = &flight[4..len];
+            if let Some(group) = key_share_group(hello) {
+                self.key_exchange_group = Some(group.into());
+            }
+            self.resumed = server_hello_extension(hello, PRE_SHARED_KEY_EXTENSION_TYPE).is_some();
+            flight.drain(..len);
+        }
+        self.server_hello = None;
+    }
 }
 
 impl crypto::Session for TlsSession {
@@ -48,10 +112,13 @@ impl crypto::Session for TlsSession {
         }
         Some(Box::new(HandshakeData {
             protocol: self.inner.alpn_protocol().map(|x| x.into()),
+            offered_protocols: self.offered_protocols.clone(),
             server_name: match self.inner {
                 Connection::Client(_) => None,
                 Connection::Server(ref session) => session.sni_hostname().map(|x| x.into()),
             },
+            key_exchange_group: self.key_exchange_group,
+            cipher_suite: self.inner.negotiated_cipher_suite(),
         }))
     }
 
@@ -73,11 +140,19 @@ impl crypto::Session for TlsSession {
         }
     }
 
+    fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
     fn is_handshaking(&self) -> bool {
         self.inner.is_handshaking()
     }
 
     fn read_handshake(&mut self, buf: &[u8]) -> Result<bool, TransportError> {
+        match self.side() {
+            Side::Client => self.record_server_hello(buf),
+            Side::Server => self.record_client_hello(buf),
+        }
         self.inner.read_hs(buf).map_err(|e| {
             if let Some(alert) = self.inner.alert() {
                 TransportError {
@@ -116,7 +191,12 @@ impl crypto::Session for TlsSession {
     }
 
     fn write_handshake(&mut self, buf: &mut Vec<u8>) -> Option<Keys> {
-        let keys = match self.inner.write_hs(buf)? {
+        let start = buf.len();
+        let change = self.inner.write_hs(buf);
+        if self.side().is_server() {
+            self.record_server_hello(&buf[start..]);
+        }
+        let keys = match change? {
             KeyChange::Handshake { keys } => keys,
             KeyChange::OneRtt { keys, next } => {
                 self.next_secrets = Some(next);
@@ -234,10 +314,36 @@ pub struct HandshakeData {
     ///
     /// Guaranteed to be set if a nonempty list of protocols was specified for this connection.
     pub protocol: Option<Vec<u8>>,
+    /// The application protocols offered by the client, in order of its preference, if it used
+    /// ALPN
+    ///
+    /// Always `None` for outgoing connections
+    pub offered_protocols: Option<Vec<Vec<u8>>>,
     /// The server name specified by the client, if any
     ///
     /// Always `None` for outgoing connections
     pub server_name: Option<String>,
+    /// The group used for key exchange, e.g. X25519
+    ///
+    /// `None` on incoming connections until the ServerHello has been sent. Groups unknown to
+    /// rustls, such as post-quantum hybrids, are reported as [`rustls::NamedGroup::Unknown`].
+    pub key_exchange_group: Option<rustls::NamedGroup>,
+    /// The cipher suite protecting the connection
+    ///
+    /// `None` until negotiation has completed.
+    pub cipher_suite: Option<rustls::SupportedCipherSuite>,
+}
+
+impl HandshakeData {
+    /// Whether the key exchange is resistant to quantum computers
+    ///
+    /// True for the ML-KEM groups and the hybrids combining them, or the draft Kyber, with a
+    /// classical group. Lets deployments rolling out post-quantum key exchange track its adoption
+    /// and spot connections which fell back to classical key exchange.
+    pub fn is_post_quantum(&self) -> bool {
+        self.key_exchange_group
+            .map_or(false, |x| POST_QUANTUM_GROUPS.contains(&x.get_u16()))
+    }
 }
 
 impl crypto::ClientConfig for rustls::ClientConfig {
@@ -252,6 +358,11 @@ impl crypto::ClientConfig for rustls::ClientConfig {
             version,
             got_handshake_data: false,
             next_secrets: None,
+            client_hello: None,
+            offered_protocols: None,
+            server_hello: Some(Vec::new()),
+            key_exchange_group: None,
+            resumed: false,
             inner: Connection::Client(
                 rustls::ClientConnection::new_quic(
                     self,
@@ -278,6 +389,11 @@ impl crypto::ServerConfig for rustls::ServerConfig {
             version,
             got_handshake_data: false,
             next_secrets: None,
+            client_hello: Some(Vec::new()),
+            offered_protocols: None,
+            server_hello: Some(Vec::new()),
+            key_exchange_group: None,
+            resumed: false,
             inner: Connection::Server(
                 rustls::ServerConnection::new_quic(self, version, to_vec(params)).unwrap(),
             ),
@@ -410,10 +526,55 @@ pub(crate) fn server_config(
     Ok(cfg)
 }
 
-fn interpret_version(version: u32) -> Result<Version, UnsupportedVersion> {
+pub(crate) fn interpret_version(version: u32) -> Result<Version, UnsupportedVersion> {
     match version {
         0xff00_001d..=0xff00_0020 => Ok(Version::V1Draft),
         0x0000_0001 | 0xff00_0021..=0xff00_0022 => Ok(Version::V1),
         _ => Err(UnsupportedVersion),
     }
 }
+
+/// Extract the key exchange group from the body of a TLS ServerHello message
+fn key_share_group(body: &[u8]) -> Option<u16> {
+    let data = server_hello_extension(body, KEY_SHARE_EXTENSION_TYPE)?;
+    // Both the ServerHello's KeyShareEntry and the HelloRetryRequest's selected_group start with
+    // the group
+    Some(u16::from_be_bytes(data.get(..2)?.try_into().ok()?))
+}
+
+/// Find the data of the extension of type `ty` in the body of a ServerHello
+fn server_hello_extension(mut body: &[u8], ty: [u8; 2]) -> Option<&[u8]> {
+    // Skip legacy_version and random
+    body = body.get(34..)?;
+    let _session_id = take_prefixed(&mut body, 1)?;
+    // Skip cipher_suite and legacy_compression_method
+    body = body.get(3..)?;
+    let mut extensions = take_prefixed(&mut body, 2)?;
+    while !extensions.is_empty() {
+        let matches = extensions.get(..2)? == ty;
+        extensions = &extensions[2..];
+        let data = take_prefixed(&mut extensions, 2)?;
+        if matches {
+            return Some(data);
+        }
+    }
+    None
+}
+
+/// Largest ClientHello that will be buffered to determine the offered application protocols
+const MAX_CLIENT_HELLO_SIZE: usize = 16 * 1024;
+
+/// Largest ServerHello that will be buffered to determine the key exchange group
+const MAX_SERVER_HELLO_SIZE: usize = 4 * 1024;
+
+const SERVER_HELLO_TYPE: u8 = 2;
+
+const KEY_SHARE_EXTENSION_TYPE: [u8; 2] = [0, 51];
+
+const PRE_SHARED_KEY_EXTENSION_TYPE: [u8; 2] = [0, 41];
+
+/// Codepoints of post-quantum key exchange groups: the ML-KEM hybrids, the draft Kyber hybrids,
+/// and standalone ML-KEM
+const POST_QUANTUM_GROUPS: [u16; 8] = [
+    0x11eb, 0x11ec, 0x11ed, 0x6399, 0x639a, 0x0200, 0x0201, 0x0202,
+];
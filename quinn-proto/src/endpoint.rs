@@ -1,7 +1,7 @@
 use std::{
     collections::{HashMap, VecDeque},
     convert::TryFrom,
-    fmt, iter,
+    fmt, iter, mem,
     net::{IpAddr, SocketAddr},
     ops::{Index, IndexMut},
     sync::Arc,
@@ -16,9 +16,11 @@ use thiserror::Error;
 use tracing::{debug, trace, warn};
 
 use crate::{
-    cid_generator::{ConnectionIdGenerator, RandomConnectionIdGenerator},
+    cid_generator::ConnectionIdGenerator,
+    client_hello,
     coding::BufMutExt,
     config::{ClientConfig, EndpointConfig, ServerConfig},
+    congestion,
     connection::{Connection, ConnectionError},
     crypto::{self, Keys, UnsupportedVersion},
     frame,
@@ -27,9 +29,11 @@ use crate::{
         ConnectionEvent, ConnectionEventInner, ConnectionId, EcnCodepoint, EndpointEvent,
         EndpointEventInner, IssuedCid,
     },
+    source_rate::SourceRateLimiter,
     transport_parameters::TransportParameters,
-    ResetToken, RetryToken, Side, Transmit, TransportConfig, TransportError,
-    INITIAL_MAX_UDP_PAYLOAD_SIZE, MAX_CID_SIZE, MIN_INITIAL_SIZE, RESET_TOKEN_SIZE,
+    NewToken, ResetToken, RetryToken, Side, Transmit, TransportConfig, TransportError,
+    TransportErrorCode, INITIAL_MAX_UDP_PAYLOAD_SIZE, MAX_CID_SIZE, MIN_INITIAL_DCID_LEN,
+    MIN_INITIAL_SIZE, RESET_TOKEN_SIZE,
 };
 
 /// The main entry point to the library
@@ -61,6 +65,24 @@ pub struct Endpoint {
     local_cid_generator: Box<dyn ConnectionIdGenerator>,
     config: Arc<EndpointConfig>,
     server_config: Option<Arc<ServerConfig>>,
+    /// Number of stateless resets sent in response to packets for unknown connections
+    stateless_resets_sent: u64,
+    /// Number of address validation tokens sent in Retry packets
+    retry_tokens_issued: u64,
+    /// Number of address validation tokens accepted from clients
+    retry_tokens_validated: u64,
+    /// Number of address validation tokens sent in NEW_TOKEN frames
+    new_tokens_issued: u64,
+    /// Number of tokens from NEW_TOKEN frames accepted from clients
+    new_tokens_validated: u64,
+    /// Number of connection attempts refused for not offering a required ALPN protocol
+    alpn_rejections: u64,
+    /// Enforces `EndpointConfig::max_handshake_rate_per_ip`, if set
+    source_rate: Option<SourceRateLimiter>,
+    /// Number of connection attempts dropped by `source_rate`
+    handshake_rate_rejections: u64,
+    /// Changes to `connection_ids` not yet retrieved with `poll_cid_change`, if tracked
+    cid_changes: Option<VecDeque<CidChange>>,
 }
 
 impl Endpoint {
@@ -68,17 +90,30 @@ impl Endpoint {
     ///
     /// Returns `Err` if the configuration is invalid.
     pub fn new(config: Arc<EndpointConfig>, server_config: Option<Arc<ServerConfig>>) -> Self {
+        let capacity = config.expected_connections;
+        let source_rate = config.max_handshake_rate_per_ip.map(|(count, interval)| {
+            SourceRateLimiter::new(count, interval, config.handshake_rate_table_size)
+        });
         Self {
             rng: StdRng::from_entropy(),
             transmits: VecDeque::new(),
-            connection_ids_initial: HashMap::default(),
-            connection_ids: FxHashMap::default(),
+            connection_ids_initial: HashMap::with_capacity(capacity),
+            connection_ids: FxHashMap::with_capacity_and_hasher(capacity, Default::default()),
             connection_remotes: HashMap::default(),
             connection_reset_tokens: ResetTokenTable::default(),
-            connections: Slab::new(),
+            connections: Slab::with_capacity(capacity),
             local_cid_generator: (config.connection_id_generator_factory.as_ref())(),
             config,
             server_config,
+            stateless_resets_sent: 0,
+            retry_tokens_issued: 0,
+            retry_tokens_validated: 0,
+            new_tokens_issued: 0,
+            new_tokens_validated: 0,
+            alpn_rejections: 0,
+            source_rate,
+            handshake_rate_rejections: 0,
+            cid_changes: None,
         }
     }
 
@@ -93,6 +128,17 @@ impl Endpoint {
         self.server_config = server_config;
     }
 
+    /// Replace the server configuration, returning the previous one
+    ///
+    /// Like [`set_server_config()`](Self::set_server_config), this affects new incoming
+    /// connections only.
+    pub fn replace_server_config(
+        &mut self,
+        server_config: Option<Arc<ServerConfig>>,
+    ) -> Option<Arc<ServerConfig>> {
+        mem::replace(&mut self.server_config, server_config)
+    }
+
     /// Process `EndpointEvent`s emitted from related `Connection`s
     ///
     /// In turn, processing this event may return a `ConnectionEvent` for the same `Connection`.
@@ -118,18 +164,23 @@ impl Endpoint {
                 if let Some(cid) = self.connections[ch].loc_cids.remove(&seq) {
                     trace!("peer retired CID {}: {}", seq, cid);
                     self.connection_ids.remove(&cid);
+                    self.record_cid_change(CidChange::Retired(cid));
                     if allow_more_cids {
                         return Some(self.send_new_identifiers(now, ch, 1));
                     }
                 }
             }
+            NewTokenIssued => {
+                self.new_tokens_issued += 1;
+            }
             Drained => {
                 let conn = self.connections.remove(ch.0);
                 if conn.init_cid.len() > 0 {
                     self.connection_ids_initial.remove(&conn.init_cid);
                 }
-                for cid in conn.loc_cids.values() {
-                    self.connection_ids.remove(cid);
+                for &cid in conn.loc_cids.values() {
+                    self.connection_ids.remove(&cid);
+                    self.record_cid_change(CidChange::Retired(cid));
                 }
                 self.connection_remotes.remove(&conn.addresses);
                 if let Some((remote, token)) = conn.reset_token {
@@ -166,6 +217,12 @@ impl Endpoint {
                     debug!("dropping packet with unsupported version");
                     return None;
                 }
+                if !self.config.version_negotiation {
+                    debug!(
+                        "dropping packet with unsupported version: version negotiation disabled"
+                    );
+                    return None;
+                }
                 trace!("sending version negotiation");
                 // Negotiate versions
                 let mut buf = Vec::<u8>::new();
@@ -190,6 +247,8 @@ impl Endpoint {
                     contents: buf,
                     segment_size: None,
                     src_ip: local_ip,
+                    metadata: None,
+                    priority: None,
                 });
                 return None;
             }
@@ -317,6 +376,11 @@ impl Endpoint {
         /// Minimum amount of padding for the stateless reset to look like a short-header packet
         const MIN_PADDING_LEN: usize = 5;
 
+        if !self.config.stateless_reset {
+            debug!("not sending stateless reset for {}: disabled", dst_cid);
+            return;
+        }
+
         // Prevent amplification attacks and reset loops by ensuring we pad to at most 1 byte
         // smaller than the inciting packet.
         let max_padding_len = match inciting_dgram_len.checked_sub(RESET_TOKEN_SIZE) {
@@ -347,12 +411,15 @@ impl Endpoint {
 
         debug_assert!(buf.len() < inciting_dgram_len);
 
+        self.stateless_resets_sent += 1;
         self.transmits.push_back(Transmit {
             destination: addresses.remote,
             ecn: None,
             contents: buf,
             segment_size: None,
             src_ip: addresses.local_ip,
+            metadata: None,
+            priority: None,
         });
     }
 
@@ -362,6 +429,24 @@ impl Endpoint {
         config: ClientConfig,
         remote: SocketAddr,
         server_name: &str,
+    ) -> Result<(ConnectionHandle, Connection), ConnectError> {
+        let initial_dcid = self.local_cid_generator.generate_initial_dcid();
+        self.connect_with_initial_dcid(config, remote, server_name, initial_dcid)
+    }
+
+    /// Initiate a connection whose first Initial packet is addressed to `initial_dcid`
+    ///
+    /// Useful when the destination connection ID must be predictable, e.g. so that a load
+    /// balancer in front of the server routes the connection deterministically. The initial keys
+    /// are derived from this ID, so it should still be unpredictable to third parties. Fails with
+    /// [`ConnectError::InvalidInitialDcid`] if `initial_dcid` is shorter than 8 bytes, as required
+    /// by RFC 9000 §7.2.
+    pub fn connect_with_initial_dcid(
+        &mut self,
+        config: ClientConfig,
+        remote: SocketAddr,
+        server_name: &str,
+        initial_dcid: ConnectionId,
     ) -> Result<(ConnectionHandle, Connection), ConnectError> {
         if self.is_full() {
             return Err(ConnectError::TooManyConnections);
@@ -369,8 +454,11 @@ impl Endpoint {
         if remote.port() == 0 {
             return Err(ConnectError::InvalidRemoteAddress(remote));
         }
+        if initial_dcid.len() < MIN_INITIAL_DCID_LEN {
+            return Err(ConnectError::InvalidInitialDcid);
+        }
 
-        let remote_id = RandomConnectionIdGenerator::new(MAX_CID_SIZE).generate_cid();
+        let remote_id = initial_dcid;
         trace!(initial_dcid = %remote_id);
 
         let loc_cid = self.new_cid();
@@ -398,6 +486,7 @@ impl Endpoint {
             tls,
             None,
             config.transport,
+            Some(server_name),
         );
         Ok((ch, conn))
     }
@@ -412,6 +501,7 @@ impl Endpoint {
         for _ in 0..num {
             let id = self.new_cid();
             self.connection_ids.insert(id, ch);
+            self.record_cid_change(CidChange::Issued(id));
             let meta = &mut self.connections[ch];
             meta.cids_issued += 1;
             let sequence = meta.cids_issued;
@@ -473,7 +563,7 @@ impl Endpoint {
         }
 
         let loc_cid = self.new_cid();
-        let server_config = self.server_config.as_ref().unwrap();
+        let server_config = self.server_config.clone().unwrap();
 
         if self.connections.len() >= server_config.concurrent_connections as usize || self.is_full()
         {
@@ -489,8 +579,83 @@ impl Endpoint {
             return None;
         }
 
-        if dst_cid.len() < 8
-            && (!server_config.use_retry || dst_cid.len() != self.local_cid_generator.cid_len())
+        if !server_config.required_alpn_protocols.is_empty() {
+            let hello = client_hello::initial_client_hello(packet.payload.clone().freeze());
+            if let Some(hello) = hello {
+                let offered = client_hello::offered_alpn_protocols(&hello).unwrap_or_default();
+                let required = &server_config.required_alpn_protocols;
+                if !offered.iter().any(|x| required.iter().any(|y| x == y)) {
+                    debug!("refusing connection without a required ALPN protocol");
+                    self.alpn_rejections += 1;
+                    self.initial_close(
+                        version,
+                        addresses,
+                        crypto,
+                        &src_cid,
+                        &loc_cid,
+                        TransportError {
+                            code: TransportErrorCode::crypto(client_hello::NO_APPLICATION_PROTOCOL),
+                            frame: None,
+                            reason: "no application protocol".into(),
+                        },
+                    );
+                    return None;
+                }
+            }
+        }
+
+        let use_retry = match server_config.accept_filter {
+            Some(ref filter) => match filter.filter(addresses.remote, addresses.local_ip) {
+                AcceptDecision::Accept => server_config.use_retry,
+                AcceptDecision::Retry => true,
+                AcceptDecision::Reject => {
+                    debug!("connection rejected by filter");
+                    self.initial_close(
+                        version,
+                        addresses,
+                        crypto,
+                        &src_cid,
+                        &loc_cid,
+                        TransportError::CONNECTION_REFUSED(""),
+                    );
+                    return None;
+                }
+            },
+            None => server_config.use_retry,
+        };
+
+        // Tokens may also have been issued in NEW_TOKEN frames, or by other servers
+        let retry_token = if token.is_empty() {
+            None
+        } else {
+            let decoded = match server_config.retry_token_handler {
+                Some(ref handler) => handler.decode(&addresses.remote, &dst_cid, &token),
+                None => RetryToken::from_bytes(
+                    &*server_config.token_key,
+                    &addresses.remote,
+                    &dst_cid,
+                    &token,
+                )
+                .ok()
+                .map(|token| (token.orig_dst_cid, token.issued)),
+            };
+            decoded
+                .filter(|&(_, issued)| {
+                    issued + server_config.retry_token_lifetime > SystemTime::now()
+                })
+                .map(|(orig_dst_cid, _)| orig_dst_cid)
+        };
+        // A valid token from a NEW_TOKEN frame proves the client's address like a Retry would
+        let new_token_validated = retry_token.is_none()
+            && NewToken::from_bytes(&*server_config.token_key, addresses.remote.ip(), &token)
+                .map_or(false, |token| {
+                    token.issued + server_config.new_token_lifetime > SystemTime::now()
+                });
+        let use_retry = use_retry && !new_token_validated;
+
+        if dst_cid.len() < MIN_INITIAL_DCID_LEN
+            && ((!use_retry && retry_token.is_none())
+                || dst_cid.len() != self.local_cid_generator.cid_len())
         {
             debug!(
                 "rejecting connection due to invalid DCID length {}",
@@ -507,18 +672,53 @@ impl Endpoint {
             return None;
         }
 
-        let (retry_src_cid, orig_dst_cid) = if server_config.use_retry {
-            if token.is_empty() {
-                // First Initial
-                let mut random_bytes = vec![0u8; RetryToken::RANDOM_BYTES_LEN];
-                self.rng.fill_bytes(&mut random_bytes);
-
-                let token = RetryToken {
-                    orig_dst_cid: dst_cid,
-                    issued: SystemTime::now(),
-                    random_bytes: &random_bytes,
+        let (retry_src_cid, orig_dst_cid) = match retry_token {
+            // Honored even if a Retry is no longer required, e.g. because the filter's decision
+            // changed, as the client can't complete the handshake otherwise
+            Some(orig_dst_cid) => {
+                self.retry_tokens_validated += 1;
+                if !self.allow_source(now, addresses.remote.ip()) {
+                    return None;
                 }
-                .encode(&*server_config.token_key, &addresses.remote, &loc_cid);
+                (Some(dst_cid), orig_dst_cid)
+            }
+            // Tokens from NEW_TOKEN frames may have been issued by another server or for another
+            // address, so an invalid one is treated as if none was presented
+            None if use_retry && !token.is_empty() && !NewToken::is_new_token(&token) => {
+                debug!("rejecting invalid stateless retry token");
+                self.initial_close(
+                    version,
+                    addresses,
+                    crypto,
+                    &src_cid,
+                    &loc_cid,
+                    TransportError::INVALID_TOKEN(""),
+                );
+                return None;
+            }
+            None if use_retry => {
+                // First Initial. Its source address is unproven, so it isn't counted against
+                // `max_handshake_rate_per_ip` until the client answers the Retry.
+                let token = match server_config.retry_token_handler {
+                    Some(ref handler) => {
+                        handler.encode(&addresses.remote, &loc_cid, &dst_cid, SystemTime::now())
+                    }
+                    None => {
+                        let mut random_bytes = vec![0u8; RetryToken::RANDOM_BYTES_LEN];
+                        self.rng.fill_bytes(&mut random_bytes);
+
+                        RetryToken {
+                            orig_dst_cid: dst_cid,
+                            issued: SystemTime::now(),
+                            random_bytes: &random_bytes,
+                        }
+                        .encode(
+                            &*server_config.token_key,
+                            &addresses.remote,
+                            &loc_cid,
+                        )
+                    }
+                };
 
                 let header = Header::Retry {
                     src_cid: loc_cid,
@@ -538,39 +738,23 @@ impl Endpoint {
                     contents: buf,
                     segment_size: None,
                     src_ip: addresses.local_ip,
+                    metadata: None,
+                    priority: None,
                 });
+                self.retry_tokens_issued += 1;
                 return None;
             }
-
-            match RetryToken::from_bytes(
-                &*server_config.token_key,
-                &addresses.remote,
-                &dst_cid,
-                &token,
-            ) {
-                Ok(token)
-                    if token.issued + server_config.retry_token_lifetime > SystemTime::now() =>
-                {
-                    (Some(dst_cid), token.orig_dst_cid)
+            None => {
+                if new_token_validated {
+                    self.new_tokens_validated += 1;
                 }
-                _ => {
-                    debug!("rejecting invalid stateless retry token");
-                    self.initial_close(
-                        version,
-                        addresses,
-                        crypto,
-                        &src_cid,
-                        &loc_cid,
-                        TransportError::INVALID_TOKEN(""),
-                    );
+                if !self.allow_source(now, addresses.remote.ip()) {
                     return None;
                 }
+                (None, dst_cid)
             }
-        } else {
-            (None, dst_cid)
         };
 
-        let server_config = server_config.clone();
         let mut params = TransportParameters::new(
             &server_config.transport,
             &self.config,
@@ -594,6 +778,7 @@ impl Endpoint {
             tls,
             Some(server_config),
             transport_config,
+            None,
         );
         if dst_cid.len() != 0 {
             self.connection_ids_initial.insert(dst_cid, ch);
@@ -632,7 +817,22 @@ impl Endpoint {
         tls: Box<dyn crypto::Session>,
         server_config: Option<Arc<ServerConfig>>,
         transport_config: Arc<TransportConfig>,
+        server_name: Option<&str>,
     ) -> (ConnectionHandle, Connection) {
+        let congestion_controller_factory = self
+            .config
+            .congestion_controller_selector
+            .as_ref()
+            .and_then(|select| {
+                select(&congestion::ConnectionProperties {
+                    side: match server_config {
+                        Some(_) => Side::Server,
+                        None => Side::Client,
+                    },
+                    remote: addresses.remote,
+                    server_name,
+                })
+            });
         let conn = Connection::new(
             self.config.clone(),
             server_config,
@@ -646,6 +846,7 @@ impl Endpoint {
             self.local_cid_generator.as_ref(),
             now,
             version,
+            congestion_controller_factory,
         );
 
         let id = self.connections.insert(ConnectionMeta {
@@ -658,13 +859,32 @@ impl Endpoint {
 
         let ch = ConnectionHandle(id);
         match self.local_cid_generator.cid_len() {
-            0 => self.connection_remotes.insert(addresses, ch),
-            _ => self.connection_ids.insert(loc_cid, ch),
-        };
+            0 => {
+                self.connection_remotes.insert(addresses, ch);
+            }
+            _ => {
+                self.connection_ids.insert(loc_cid, ch);
+                self.record_cid_change(CidChange::Issued(loc_cid));
+            }
+        }
 
         (ch, conn)
     }
 
+    /// Account for a connection attempt from `ip` against `max_handshake_rate_per_ip`
+    fn allow_source(&mut self, now: Instant, ip: IpAddr) -> bool {
+        let limiter = match self.source_rate {
+            Some(ref mut limiter) => limiter,
+            None => return true,
+        };
+        if limiter.check(now, ip) {
+            return true;
+        }
+        debug!("dropping connection attempt from rate limited source");
+        self.handshake_rate_rejections += 1;
+        false
+    }
+
     fn initial_close(
         &mut self,
         version: u32,
@@ -701,6 +921,8 @@ impl Endpoint {
             contents: buf,
             segment_size: None,
             src_ip: addresses.local_ip,
+            metadata: None,
+            priority: None,
         })
     }
 
@@ -716,6 +938,105 @@ impl Endpoint {
         &self.config
     }
 
+    /// Number of stateless resets sent in response to packets for unknown connections
+    pub fn stateless_resets_sent(&self) -> u64 {
+        self.stateless_resets_sent
+    }
+
+    /// Number of address validation tokens issued to clients in Retry packets
+    ///
+    /// See [`ServerConfig::use_retry`].
+    pub fn retry_tokens_issued(&self) -> u64 {
+        self.retry_tokens_issued
+    }
+
+    /// Number of address validation tokens from clients which were found valid
+    ///
+    /// Compared to [`retry_tokens_issued()`](Self::retry_tokens_issued), indicates how many clients
+    /// complete address validation.
+    pub fn retry_tokens_validated(&self) -> u64 {
+        self.retry_tokens_validated
+    }
+
+    /// Number of address validation tokens issued to clients in NEW_TOKEN frames
+    ///
+    /// See [`ServerConfig::new_tokens()`].
+    pub fn new_tokens_issued(&self) -> u64 {
+        self.new_tokens_issued
+    }
+
+    /// Number of tokens from NEW_TOKEN frames which were found valid, sparing their clients a
+    /// Retry
+    pub fn new_tokens_validated(&self) -> u64 {
+        self.new_tokens_validated
+    }
+
+    /// Number of connection attempts refused for not offering any ALPN protocol required by
+    /// [`ServerConfig::required_alpn_protocols()`]
+    pub fn alpn_rejections(&self) -> u64 {
+        self.alpn_rejections
+    }
+
+    /// Number of connection attempts dropped for exceeding
+    /// [`EndpointConfig::max_handshake_rate_per_ip()`]
+    pub fn handshake_rate_rejections(&self) -> u64 {
+        self.handshake_rate_rejections
+    }
+
+    /// Zero the counters reported by [`stateless_resets_sent()`](Self::stateless_resets_sent),
+    /// [`retry_tokens_issued()`](Self::retry_tokens_issued),
+    /// [`retry_tokens_validated()`](Self::retry_tokens_validated),
+    /// [`new_tokens_issued()`](Self::new_tokens_issued),
+    /// [`new_tokens_validated()`](Self::new_tokens_validated),
+    /// [`alpn_rejections()`](Self::alpn_rejections), and
+    /// [`handshake_rate_rejections()`](Self::handshake_rate_rejections)
+    pub fn reset_stats(&mut self) {
+        self.stateless_resets_sent = 0;
+        self.retry_tokens_issued = 0;
+        self.retry_tokens_validated = 0;
+        self.new_tokens_issued = 0;
+        self.new_tokens_validated = 0;
+        self.alpn_rejections = 0;
+        self.handshake_rate_rejections = 0;
+    }
+
+    /// Length of the connection IDs issued by this endpoint
+    pub fn local_cid_len(&self) -> usize {
+        self.local_cid_generator.cid_len()
+    }
+
+    /// Enable or disable recording of changes to the set of locally issued connection IDs
+    ///
+    /// While enabled, each connection ID issued or retired by this endpoint is queued for
+    /// retrieval with [`poll_cid_change()`](Self::poll_cid_change), allowing higher layers to
+    /// maintain routing tables shared between endpoints. Enabling queues a
+    /// [`CidChange::Issued`] for every connection ID currently in use. Disabled by default.
+    pub fn track_cid_changes(&mut self, enabled: bool) {
+        self.cid_changes = if enabled {
+            Some(
+                self.connection_ids
+                    .keys()
+                    .map(|&cid| CidChange::Issued(cid))
+                    .collect(),
+            )
+        } else {
+            None
+        };
+    }
+
+    /// Get the next change to the set of locally issued connection IDs
+    ///
+    /// Always `None` unless enabled with [`track_cid_changes()`](Self::track_cid_changes).
+    pub fn poll_cid_change(&mut self) -> Option<CidChange> {
+        self.cid_changes.as_mut()?.pop_front()
+    }
+
+    fn record_cid_change(&mut self, change: CidChange) {
+        if let Some(changes) = self.cid_changes.as_mut() {
+            changes.push_back(change);
+        }
+    }
+
     #[cfg(test)]
     pub(crate) fn known_connections(&self) -> usize {
         let x = self.connections.len();
@@ -779,6 +1100,7 @@ pub(crate) struct ConnectionMeta {
 
 /// Internal identifier for a `Connection` currently associated with an endpoint
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ConnectionHandle(pub usize);
 
 impl From<ConnectionHandle> for usize {
@@ -800,6 +1122,18 @@ impl IndexMut<ConnectionHandle> for Slab<ConnectionMeta> {
     }
 }
 
+/// Change to the set of connection IDs issued by an [`Endpoint`]
+///
+/// See [`Endpoint::track_cid_changes()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CidChange {
+    /// Datagrams addressed to this connection ID are now routed to one of the endpoint's
+    /// connections
+    Issued(ConnectionId),
+    /// The connection ID is no longer in use
+    Retired(ConnectionId),
+}
+
 /// Event resulting from processing a single datagram
 #[allow(clippy::large_enum_variant)] // Not passed around extensively
 pub enum DatagramEvent {
@@ -809,10 +1143,40 @@ pub enum DatagramEvent {
     NewConnection(Connection),
 }
 
+/// How to respond to an incoming connection attempt
+///
+/// Returned by an [`AcceptFilter`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AcceptDecision {
+    /// Proceed with the handshake, subject to [`ServerConfig::use_retry()`]
+    Accept,
+    /// Require the client to prove ownership of its address by responding to a Retry packet
+    Retry,
+    /// Refuse the connection with a `CONNECTION_REFUSED` error
+    Reject,
+}
+
+/// Decides how to respond to incoming connection attempts
+///
+/// Installed with [`ServerConfig::accept_filter()`]. Implemented for any
+/// `Fn(SocketAddr, Option<IpAddr>) -> AcceptDecision` closure, which is passed the client's
+/// address and the local IP address the attempt was sent to, if known.
+pub trait AcceptFilter: Send + Sync {
+    /// Called for each connection attempt from `remote` before any state is allocated for it
+    fn filter(&self, remote: SocketAddr, local_ip: Option<IpAddr>) -> AcceptDecision;
+}
+
+impl<F: Fn(SocketAddr, Option<IpAddr>) -> AcceptDecision + Send + Sync> AcceptFilter for F {
+    fn filter(&self, remote: SocketAddr, local_ip: Option<IpAddr>) -> AcceptDecision {
+        self(remote, local_ip)
+    }
+}
+
 /// Errors in the parameters being used to create a new connection
 ///
 /// These arise before any I/O has been performed.
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ConnectError {
     /// The endpoint can no longer create new connections
     ///
@@ -840,6 +1204,17 @@ pub enum ConnectError {
     /// The cryptographic layer does not support the specified QUIC version
     #[error("unsupported QUIC version")]
     UnsupportedVersion,
+    /// The client configuration's cryptographic settings can't be adjusted
+    ///
+    /// Only configurations created from a rustls configuration, e.g. with
+    /// `ClientConfig::with_rustls`, support this.
+    #[error("opaque crypto config")]
+    OpaqueCryptoConfig,
+    /// The initial destination connection ID supplied was too short
+    ///
+    /// Clients must use an initial destination connection ID of at least 8 bytes.
+    #[error("invalid initial destination connection ID")]
+    InvalidInitialDcid,
 }
 
 /// Reset Tokens which are associated with peer socket addresses
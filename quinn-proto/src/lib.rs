@@ -25,12 +25,16 @@ use std::{
     time::Duration,
 };
 
+use bytes::Bytes;
+
 mod cid_queue;
+mod client_hello;
 #[doc(hidden)]
 pub mod coding;
 mod constant_time;
 mod packet;
 mod range_set;
+mod source_rate;
 #[cfg(all(test, feature = "rustls"))]
 mod tests;
 pub mod transport_parameters;
@@ -38,16 +42,22 @@ mod varint;
 
 pub use varint::{VarInt, VarIntBoundsExceeded};
 
+mod app_error_code;
+pub use app_error_code::{AppErrorCode, InvalidAppErrorCode};
+
 mod connection;
 pub use crate::connection::{
-    BytesSource, Chunk, Chunks, Connection, ConnectionError, ConnectionStats, Datagrams, Event,
-    FinishError, ReadError, ReadableError, RecvStream, RttEstimator, SendDatagramError, SendStream,
-    StreamEvent, Streams, UnknownStream, WriteError, Written,
+    BytesSource, Chunk, Chunks, CloseState, Connection, ConnectionError, ConnectionStats,
+    DatagramDropPolicy, Datagrams, EcnState, EcnValidationPolicy, EncryptionLevel, Event,
+    FinishError, FlowControlStats, OversizedDatagramPolicy, ReadError, ReadableError, RecvStream,
+    RttEstimator, SendDatagramError, SendLimit, SendStream, StreamAdmission, StreamDataStats,
+    StreamEvent, StreamLimits, StreamProgress, Streams, UnknownStream, WriteError, Written,
 };
 
 mod config;
 pub use config::{
-    ClientConfig, ConfigError, EndpointConfig, IdleTimeout, ServerConfig, TransportConfig,
+    ClientConfig, ConfigError, EndpointConfig, IdleTimeout, ServerConfig, TransmitScheduling,
+    TransportConfig,
 };
 
 pub mod crypto;
@@ -57,7 +67,10 @@ use crate::frame::Frame;
 pub use crate::frame::{ApplicationClose, ConnectionClose, Datagram};
 
 mod endpoint;
-pub use crate::endpoint::{ConnectError, ConnectionHandle, DatagramEvent, Endpoint};
+pub use crate::endpoint::{
+    AcceptDecision, AcceptFilter, CidChange, ConnectError, ConnectionHandle, DatagramEvent,
+    Endpoint,
+};
 
 mod shared;
 pub use crate::shared::{ConnectionEvent, ConnectionId, EcnCodepoint, EndpointEvent};
@@ -67,15 +80,25 @@ pub use crate::transport_error::{Code as TransportErrorCode, Error as TransportE
 
 pub mod congestion;
 
+mod buffer_pool;
+pub use crate::buffer_pool::BufferPool;
+
 mod cid_generator;
-pub use crate::cid_generator::{ConnectionIdGenerator, RandomConnectionIdGenerator};
+pub use crate::cid_generator::{
+    ConnectionIdGenerator, RandomConnectionIdGenerator, SequentialConnectionIdGenerator,
+};
 
 mod token;
-use token::{ResetToken, RetryToken};
+pub use token::RetryTokenHandler;
+use token::{NewToken, ResetToken, RetryToken};
 
 #[cfg(feature = "arbitrary")]
 use arbitrary::Arbitrary;
 
+#[doc(hidden)]
+#[cfg(feature = "serde")]
+pub use serde;
+
 #[doc(hidden)]
 #[cfg(fuzzing)]
 pub mod fuzzing {
@@ -244,6 +267,26 @@ impl StreamId {
     pub fn index(self) -> u64 {
         self.0 >> 2
     }
+    /// Shorthand for `self.initiator() == Side::Client`
+    pub fn is_client_initiated(self) -> bool {
+        self.initiator().is_client()
+    }
+    /// Shorthand for `self.initiator() == Side::Server`
+    pub fn is_server_initiated(self) -> bool {
+        self.initiator().is_server()
+    }
+    /// Shorthand for `self.dir() == Dir::Bi`
+    pub fn is_bidi(self) -> bool {
+        self.dir() == Dir::Bi
+    }
+    /// Shorthand for `self.dir() == Dir::Uni`
+    pub fn is_uni(self) -> bool {
+        self.dir() == Dir::Uni
+    }
+    /// Whether the stream was opened by `side`, e.g. the local side of a connection
+    pub fn is_initiated_by(self, side: Side) -> bool {
+        self.initiator() == side
+    }
 }
 
 impl From<StreamId> for VarInt {
@@ -281,6 +324,16 @@ pub struct Transmit {
     pub segment_size: Option<usize>,
     /// Optional source IP address for the datagram
     pub src_ip: Option<IpAddr>,
+    /// Opaque data for custom socket implementations, set via
+    /// [`Connection::set_transmit_metadata()`]
+    ///
+    /// Always `None` for datagrams not sent by a connection, such as stateless resets.
+    pub metadata: Option<Bytes>,
+    /// Priority for the OS to queue the datagram with, set via
+    /// [`Connection::set_socket_priority()`]
+    ///
+    /// Always `None` for datagrams not sent by a connection.
+    pub priority: Option<u32>,
 }
 
 //
@@ -291,6 +344,8 @@ pub struct Transmit {
 const LOC_CID_COUNT: u64 = 8;
 const RESET_TOKEN_SIZE: usize = 16;
 const MAX_CID_SIZE: usize = 20;
+/// <https://www.rfc-editor.org/rfc/rfc9000.html#section-7.2-3>
+const MIN_INITIAL_DCID_LEN: usize = 8;
 const MIN_INITIAL_SIZE: u16 = 1200;
 /// <https://www.rfc-editor.org/rfc/rfc9000.html#name-datagram-size>
 const INITIAL_MAX_UDP_PAYLOAD_SIZE: u16 = 1200;
@@ -54,6 +54,8 @@ pub(crate) enum EndpointEventInner {
     /// Stop routing connection ID for this sequence number to the connection
     /// When `bool == true`, a new connection ID will be issued to peer
     RetireConnectionId(Instant, u64, bool),
+    /// An address validation token has been sent in a NEW_TOKEN frame
+    NewTokenIssued,
 }
 
 /// Protocol-level identifier for a connection.
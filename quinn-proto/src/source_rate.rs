@@ -0,0 +1,127 @@
+use std::{
+    collections::BTreeMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHashMap;
+
+/// Limits the rate of connection attempts from each source IP address
+///
+/// Each address has a token bucket refilled with `count` attempts per `interval`, holding at most
+/// `count`. Only the `capacity` most recently seen addresses are tracked; an address which was
+/// evicted starts over with a full bucket.
+#[derive(Debug)]
+pub(crate) struct SourceRateLimiter {
+    count: u32,
+    interval: Duration,
+    capacity: usize,
+    sources: FxHashMap<IpAddr, Source>,
+    /// Tracked addresses by the stamp of their most recent attempt, least recent first
+    order: BTreeMap<u64, IpAddr>,
+    next_stamp: u64,
+}
+
+impl SourceRateLimiter {
+    pub(crate) fn new(count: u32, interval: Duration, capacity: usize) -> Self {
+        Self {
+            count,
+            interval,
+            capacity: capacity.max(1),
+            sources: FxHashMap::default(),
+            order: BTreeMap::new(),
+            next_stamp: 0,
+        }
+    }
+
+    /// Account for a connection attempt from `ip`, returning whether it's within the limit
+    pub(crate) fn check(&mut self, now: Instant, ip: IpAddr) -> bool {
+        let stamp = self.next_stamp;
+        self.next_stamp += 1;
+        let count = f64::from(self.count);
+        if !self.sources.contains_key(&ip) && self.sources.len() >= self.capacity {
+            let oldest = *self.order.keys().next().unwrap();
+            let evicted = self.order.remove(&oldest).unwrap();
+            self.sources.remove(&evicted);
+        }
+        let source = self.sources.entry(ip).or_insert(Source {
+            tokens: count,
+            updated: now,
+            stamp,
+        });
+        self.order.remove(&source.stamp);
+        let elapsed = now.saturating_duration_since(source.updated);
+        let refill = if elapsed >= self.interval {
+            count
+        } else {
+            elapsed.as_secs_f64() / self.interval.as_secs_f64() * count
+        };
+        source.tokens = (source.tokens + refill).min(count);
+        source.updated = now;
+        source.stamp = stamp;
+        self.order.insert(stamp, ip);
+        // Rejected attempts don't consume tokens, so a flood doesn't lock a source out for longer
+        // than it lasts
+        if source.tokens < 1.0 {
+            return false;
+        }
+        source.tokens -= 1.0;
+        true
+    }
+
+    /// Number of source addresses currently tracked
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.sources.len()
+    }
+}
+
+#[derive(Debug)]
+struct Source {
+    tokens: f64,
+    /// When `tokens` was last refilled
+    updated: Instant,
+    /// Key of this source in `order`
+    stamp: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn ip(x: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, x))
+    }
+
+    #[test]
+    fn per_source() {
+        let start = Instant::now();
+        let mut limiter = SourceRateLimiter::new(2, Duration::from_secs(1), 16);
+        assert!(limiter.check(start, ip(1)));
+        assert!(limiter.check(start, ip(1)));
+        assert!(!limiter.check(start, ip(1)));
+        // Other sources are unaffected
+        assert!(limiter.check(start, ip(2)));
+        // Half the interval refills half the bucket
+        let half = start + Duration::from_millis(500);
+        assert!(limiter.check(half, ip(1)));
+        assert!(!limiter.check(half, ip(1)));
+    }
+
+    #[test]
+    fn evicts_least_recent() {
+        let start = Instant::now();
+        let mut limiter = SourceRateLimiter::new(1, Duration::from_secs(60), 2);
+        assert!(limiter.check(start, ip(1)));
+        assert!(limiter.check(start, ip(2)));
+        // Refresh 1, making 2 the least recently seen
+        assert!(!limiter.check(start, ip(1)));
+        assert!(limiter.check(start, ip(3)));
+        assert_eq!(limiter.len(), 2);
+        assert!(!limiter.check(start, ip(1)));
+        // 2 was forgotten, so starts over
+        assert!(limiter.check(start, ip(2)));
+    }
+}
@@ -1,8 +1,8 @@
 use std::{
     convert::TryInto,
-    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
-    sync::Arc,
-    time::{Duration, Instant},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use assert_matches::assert_matches;
@@ -16,6 +16,7 @@ use tracing::info;
 use super::*;
 use crate::{
     cid_generator::{ConnectionIdGenerator, RandomConnectionIdGenerator},
+    congestion::CongestionPhase,
     frame::FrameStruct,
 };
 mod util;
@@ -49,6 +50,25 @@ fn version_negotiate_server() {
     assert_matches!(server.poll_transmit(), None);
 }
 
+#[test]
+fn version_negotiate_disabled() {
+    let _guard = subscribe();
+    let client_addr = "[::2]:7890".parse().unwrap();
+    let mut config = EndpointConfig::default();
+    config.version_negotiation(false);
+    let mut server = Endpoint::new(Arc::new(config), Some(Arc::new(server_config())));
+    let event = server.handle(
+        Instant::now(),
+        client_addr,
+        None,
+        None,
+        // Long-header packet with reserved version number
+        hex!("80 0a1a2a3a 04 00000000 04 00000000 00")[..].into(),
+    );
+    assert!(event.is_none());
+    assert_matches!(server.poll_transmit(), None);
+}
+
 #[test]
 fn version_negotiate_client() {
     let _guard = subscribe();
@@ -97,6 +117,14 @@ fn lifecycle() {
     assert_matches!(pair.client_conn_mut(client_ch).poll(), None);
     assert!(pair.client_conn_mut(client_ch).using_ecn());
     assert!(pair.server_conn_mut(server_ch).using_ecn());
+    assert_eq!(
+        pair.client_conn_mut(client_ch).ecn_state(),
+        EcnState::Capable
+    );
+    assert_eq!(
+        pair.server_conn_mut(server_ch).ecn_state(),
+        EcnState::Capable
+    );
 
     const REASON: &[u8] = b"whee";
     info!("closing");
@@ -117,6 +145,143 @@ fn lifecycle() {
     assert_eq!(pair.server.known_cids(), 0);
 }
 
+#[test]
+fn ecn_unreported() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+    // Strip the marks so that the server's acknowledgements omit ECN counts
+    for _ in 0..5 {
+        pair.drive_client();
+        for (_, ecn, _) in &mut pair.server.inbound {
+            *ecn = None;
+        }
+        pair.drive_server();
+    }
+    assert_eq!(
+        pair.client_conn_mut(client_ch).ecn_state(),
+        EcnState::Unreported
+    );
+}
+
+#[test]
+fn ecn_validation_retry() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let mut transport = TransportConfig::default();
+    transport.ecn_validation_policy(EcnValidationPolicy::Retry(Duration::from_secs(1)));
+    let mut client_config = client_config();
+    client_config.transport_config(Arc::new(transport));
+    let (client_ch, _) = pair.connect_with(client_config);
+    assert_eq!(
+        pair.client_conn_mut(client_ch).ecn_state(),
+        EcnState::Capable
+    );
+
+    // Strip the marks so that the server's ECN counts stop increasing
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&[0; 1000]).unwrap();
+    for _ in 0..5 {
+        pair.drive_client();
+        for (_, ecn, _) in &mut pair.server.inbound {
+            *ecn = None;
+        }
+        pair.drive_server();
+    }
+    assert_eq!(
+        pair.client_conn_mut(client_ch).ecn_state(),
+        EcnState::Failed
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .stats()
+            .path
+            .ecn_validation_failures,
+        1
+    );
+
+    // Marks are validated again once the delay has passed
+    pair.time += Duration::from_secs(2);
+    pair.client_send(client_ch, s).write(&[0; 1000]).unwrap();
+    pair.drive();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).ecn_state(),
+        EcnState::Capable
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .stats()
+            .path
+            .ecn_validation_failures,
+        1
+    );
+}
+
+#[test]
+fn validate_client_config() {
+    let config = ClientConfig::with_rustls(Arc::new(client_crypto()));
+    assert_eq!(config.validate(), Ok(()));
+
+    let mut unsupported = config.clone();
+    unsupported.version(0x0a1a_2a3a);
+    assert_eq!(unsupported.validate(), Err(ConfigError::UnsupportedVersion));
+
+    let bad_alpn = config
+        .map_rustls(|crypto| crypto.alpn_protocols = vec![b"h3".to_vec(), Vec::new()])
+        .unwrap();
+    assert_eq!(bad_alpn.validate(), Err(ConfigError::InvalidAlpn));
+
+    let mut transport = TransportConfig::default();
+    transport
+        .max_idle_timeout(Some(VarInt(1000).into()))
+        .keep_alive_interval(Some(Duration::from_secs(1)));
+    let mut config = client_config();
+    config.transport_config(Arc::new(transport));
+    assert_matches!(
+        config.validate(),
+        Err(ConfigError::InconsistentTransport(_))
+    );
+}
+
+#[test]
+fn connect_with_initial_dcid() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+
+    assert_matches!(
+        pair.client.connect_with_initial_dcid(
+            client_config(),
+            pair.server.addr,
+            "localhost",
+            ConnectionId::new(&[0xab; 7]),
+        ),
+        Err(ConnectError::InvalidInitialDcid)
+    );
+
+    let initial_dcid = ConnectionId::new(&[0xab; 8]);
+    let (client_ch, client_conn) = pair
+        .client
+        .connect_with_initial_dcid(client_config(), pair.server.addr, "localhost", initial_dcid)
+        .unwrap();
+    pair.client.connections.insert(client_ch, client_conn);
+    pair.drive_client();
+    let (_, _, packet) = pair.server.inbound.front().unwrap();
+    // Long header: flags, version, DCID length, DCID
+    assert_eq!(packet[5] as usize, initial_dcid.len());
+    assert_eq!(&packet[6..6 + initial_dcid.len()], &initial_dcid[..]);
+
+    pair.drive();
+    pair.server.assert_accept();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::HandshakeDataReady)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Connected)
+    );
+}
+
 #[test]
 fn draft_version_compat() {
     let _guard = subscribe();
@@ -128,6 +293,8 @@ fn draft_version_compat() {
     let (client_ch, server_ch) = pair.connect_with(client_config);
 
     assert_matches!(pair.client_conn_mut(client_ch).poll(), None);
+    assert_eq!(pair.client_conn_mut(client_ch).version(), 0xff00_0020);
+    assert_eq!(pair.server_conn_mut(server_ch).version(), 0xff00_0020);
     assert!(pair.client_conn_mut(client_ch).using_ecn());
     assert!(pair.server_conn_mut(server_ch).using_ecn());
 
@@ -150,6 +317,88 @@ fn draft_version_compat() {
     assert_eq!(pair.server.known_cids(), 0);
 }
 
+#[test]
+fn encryption_level() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+    assert_eq!(
+        pair.client_conn_mut(client_ch).encryption_level(),
+        EncryptionLevel::Initial
+    );
+    let start = pair.client_conn_mut(client_ch).encryption_level_since();
+
+    pair.drive();
+    let server_ch = pair.server.assert_accept();
+    let client = pair.client_conn_mut(client_ch);
+    assert_eq!(client.encryption_level(), EncryptionLevel::OneRtt);
+    assert_ne!(client.encryption_level_since(), start);
+    assert_eq!(
+        pair.server_conn_mut(server_ch).encryption_level(),
+        EncryptionLevel::OneRtt
+    );
+}
+
+#[test]
+fn last_packet_received() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    let before = pair.client_conn_mut(client_ch).last_packet_received();
+
+    // Sending alone doesn't count
+    pair.time += Duration::from_millis(10);
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive_client();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).last_packet_received(),
+        before
+    );
+
+    pair.server_conn_mut(server_ch).ping();
+    pair.drive();
+    assert!(pair.client_conn_mut(client_ch).last_packet_received() > before);
+}
+
+#[test]
+fn close_state() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).close_state(),
+        CloseState::Open
+    );
+
+    let now = pair.time;
+    pair.client_conn_mut(client_ch)
+        .close(now, VarInt(0), Bytes::new());
+    assert_eq!(
+        pair.client_conn_mut(client_ch).close_state(),
+        CloseState::Closing
+    );
+    pair.drive_client();
+    pair.drive_server();
+    assert_eq!(
+        pair.server_conn_mut(server_ch).close_state(),
+        CloseState::Draining
+    );
+    assert!(pair.server_conn_mut(server_ch).peer_closing());
+    assert!(!pair.client_conn_mut(client_ch).peer_closing());
+
+    pair.drive();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).close_state(),
+        CloseState::Drained
+    );
+    assert_eq!(
+        pair.server_conn_mut(server_ch).close_state(),
+        CloseState::Drained
+    );
+    assert!(pair.server_conn_mut(server_ch).peer_closing());
+    assert!(!pair.client_conn_mut(client_ch).peer_closing());
+}
+
 #[test]
 fn stateless_retry() {
     let _guard = subscribe();
@@ -161,6 +410,198 @@ fn stateless_retry() {
         },
     );
     pair.connect();
+    assert_eq!(pair.server.endpoint.retry_tokens_issued(), 1);
+    assert_eq!(pair.server.endpoint.retry_tokens_validated(), 1);
+
+    pair.server.endpoint.reset_stats();
+    assert_eq!(pair.server.endpoint.retry_tokens_issued(), 0);
+    assert_eq!(pair.server.endpoint.retry_tokens_validated(), 0);
+}
+
+#[test]
+fn new_tokens_limit() {
+    let _guard = subscribe();
+    let mut pair = Pair::new(
+        Default::default(),
+        ServerConfig {
+            new_tokens: 2,
+            ..server_config()
+        },
+    );
+    let (client_ch, server_ch) = pair.connect();
+    pair.drive();
+    assert_eq!(
+        pair.server_conn_mut(server_ch).stats().frame_tx.new_token,
+        2
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch).stats().frame_rx.new_token,
+        2
+    );
+    assert_eq!(pair.server.endpoint.new_tokens_issued(), 2);
+
+    // Tokens are never sent by default
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    pair.drive();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).stats().frame_rx.new_token,
+        0
+    );
+}
+
+#[test]
+fn new_token_validation() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        use_retry: true,
+        ..server_config()
+    };
+    let connect_with_token = |ip: Option<IpAddr>, issued| {
+        let mut pair = Pair::new(Default::default(), server.clone());
+        let token = NewToken {
+            issued,
+            random_bytes: &[0; RetryToken::RANDOM_BYTES_LEN],
+        }
+        .encode(&*server.token_key, ip.unwrap_or(pair.client.addr.ip()));
+        let client_ch = pair.begin_connect(client_config());
+        pair.client_conn_mut(client_ch)
+            .set_initial_token(token.into());
+        pair.drive();
+        pair.server.assert_accept();
+        assert_matches!(
+            pair.client_conn_mut(client_ch).poll(),
+            Some(Event::HandshakeDataReady)
+        );
+        assert_matches!(
+            pair.client_conn_mut(client_ch).poll(),
+            Some(Event::Connected)
+        );
+        pair
+    };
+
+    // A valid token validates the client's address in place of a Retry
+    let pair = connect_with_token(None, SystemTime::now());
+    assert_eq!(pair.server.endpoint.retry_tokens_issued(), 0);
+    assert_eq!(pair.server.endpoint.new_tokens_validated(), 1);
+
+    // Tokens issued to another address or expired are treated as absent, rather than refused
+    let other_ip = Some(Ipv6Addr::UNSPECIFIED.into());
+    for pair in [
+        connect_with_token(other_ip, SystemTime::now()),
+        connect_with_token(None, UNIX_EPOCH),
+    ] {
+        assert_eq!(pair.server.endpoint.retry_tokens_validated(), 1);
+        assert_eq!(pair.server.endpoint.new_tokens_validated(), 0);
+    }
+}
+
+#[test]
+fn custom_retry_token_handler() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Stores tokens in the clear, as a load balancer validating them itself might
+    #[derive(Default)]
+    struct PlainTokens {
+        decoded: AtomicUsize,
+    }
+
+    impl RetryTokenHandler for PlainTokens {
+        fn encode(
+            &self,
+            _: &SocketAddr,
+            _: &ConnectionId,
+            orig_dst_cid: &ConnectionId,
+            issued: SystemTime,
+        ) -> Vec<u8> {
+            let secs = issued.duration_since(UNIX_EPOCH).unwrap().as_secs();
+            let mut token = secs.to_be_bytes().to_vec();
+            token.extend_from_slice(orig_dst_cid);
+            token
+        }
+
+        fn decode(
+            &self,
+            _: &SocketAddr,
+            _: &ConnectionId,
+            token: &[u8],
+        ) -> Option<(ConnectionId, SystemTime)> {
+            self.decoded.fetch_add(1, Ordering::Relaxed);
+            let secs = u64::from_be_bytes(token.get(..8)?.try_into().unwrap());
+            let issued = UNIX_EPOCH + Duration::from_secs(secs);
+            Some((ConnectionId::new(&token[8..]), issued))
+        }
+    }
+
+    let _guard = subscribe();
+    let handler = Arc::new(PlainTokens::default());
+    let mut server = ServerConfig {
+        use_retry: true,
+        ..server_config()
+    };
+    server.retry_token_handler(Some(handler.clone()));
+    let mut pair = Pair::new(Default::default(), server);
+    pair.connect();
+    assert_eq!(pair.server.endpoint.retry_tokens_issued(), 1);
+    assert_eq!(pair.server.endpoint.retry_tokens_validated(), 1);
+    assert_eq!(handler.decoded.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn accept_filter() {
+    let _guard = subscribe();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let filtered_pair = |decision| {
+        let seen = seen.clone();
+        let mut server = server_config();
+        server.accept_filter(Some(Arc::new(move |remote: SocketAddr, _| {
+            seen.lock().unwrap().push(remote);
+            decision
+        })));
+        Pair::new(Default::default(), server)
+    };
+
+    let mut pair = filtered_pair(AcceptDecision::Accept);
+    pair.connect();
+    assert_eq!(pair.server.endpoint.retry_tokens_issued(), 0);
+    assert_eq!(*seen.lock().unwrap(), [pair.client.addr]);
+
+    let mut pair = filtered_pair(AcceptDecision::Retry);
+    pair.connect();
+    assert_eq!(pair.server.endpoint.retry_tokens_issued(), 1);
+    assert_eq!(pair.server.endpoint.retry_tokens_validated(), 1);
+
+    // Tokens which weren't issued in a Retry, e.g. from NEW_TOKEN frames, don't prevent a
+    // filter's acceptance
+    let mut pair = filtered_pair(AcceptDecision::Accept);
+    let client_ch = pair.begin_connect(client_config());
+    pair.client_conn_mut(client_ch)
+        .set_initial_token(Bytes::from_static(b"not a retry token"));
+    pair.drive();
+    pair.server.assert_accept();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::HandshakeDataReady)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Connected)
+    );
+    assert_eq!(pair.server.endpoint.retry_tokens_validated(), 0);
+
+    let mut pair = filtered_pair(AcceptDecision::Reject);
+    let client_ch = pair.begin_connect(client_config());
+    pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::ConnectionLost {
+            reason: ConnectionError::ConnectionClosed(frame::ConnectionClose {
+                error_code: TransportErrorCode::CONNECTION_REFUSED,
+                ..
+            }),
+        })
+    );
+    assert_eq!(pair.server.connections.len(), 0);
 }
 
 #[test]
@@ -181,6 +622,7 @@ fn server_stateless_reset() {
     pair.client.connections.get_mut(&client_ch).unwrap().ping();
     info!("resetting");
     pair.drive();
+    assert_eq!(pair.server.endpoint.stateless_resets_sent(), 1);
     assert_matches!(
         pair.client_conn_mut(client_ch).poll(),
         Some(Event::ConnectionLost {
@@ -189,6 +631,24 @@ fn server_stateless_reset() {
     );
 }
 
+#[test]
+fn server_stateless_reset_disabled() {
+    let _guard = subscribe();
+    let mut endpoint_config = EndpointConfig::default();
+    endpoint_config.stateless_reset(false);
+    let endpoint_config = Arc::new(endpoint_config);
+
+    let mut pair = Pair::new(endpoint_config.clone(), server_config());
+    let (client_ch, _) = pair.connect();
+    pair.drive();
+    pair.server.endpoint = Endpoint::new(endpoint_config, Some(Arc::new(server_config())));
+    pair.client.connections.get_mut(&client_ch).unwrap().ping();
+    pair.drive();
+    assert_eq!(pair.server.endpoint.stateless_resets_sent(), 0);
+    // The client isn't told that the server lost its state
+    assert_matches!(pair.client_conn_mut(client_ch).poll(), None);
+}
+
 #[test]
 fn client_stateless_reset() {
     let _guard = subscribe();
@@ -284,6 +744,178 @@ fn finish_stream_simple() {
     let _ = chunks.finalize();
 }
 
+#[test]
+fn goodput() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    let time = pair.time;
+    assert_eq!(pair.client_conn_mut(client_ch).goodput(time), 0);
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&[0; 4096]).unwrap();
+    pair.drive();
+    let time = pair.time;
+    assert_eq!(pair.client_conn_mut(client_ch).goodput(time), 4096);
+    // Acknowledged data ages out of the window
+    let later = time + Duration::from_secs(2);
+    assert_eq!(pair.client_conn_mut(client_ch).goodput(later), 0);
+}
+
+#[test]
+fn stream_progress() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = b"hello";
+    pair.client_send(client_ch, s).write(MSG).unwrap();
+    assert_eq!(
+        pair.client_send(client_ch, s).progress().unwrap(),
+        StreamProgress {
+            written: 5,
+            sent: 0,
+            acked: 0
+        }
+    );
+    assert_eq!(pair.client_send(client_ch, s).buffered(), 5);
+
+    pair.drive_client();
+    assert_eq!(pair.client_send(client_ch, s).buffered(), 0);
+    assert_eq!(
+        pair.client_send(client_ch, s).progress().unwrap(),
+        StreamProgress {
+            written: 5,
+            sent: 5,
+            acked: 0
+        }
+    );
+
+    pair.drive();
+    assert_eq!(
+        pair.client_send(client_ch, s).progress().unwrap(),
+        StreamProgress {
+            written: 5,
+            sent: 5,
+            acked: 5
+        }
+    );
+}
+
+#[test]
+fn send_limit_reason() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).send_limit_reason(),
+        SendLimit::Application
+    );
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    let msg = vec![0xAB; 100_000];
+    assert_eq!(pair.client_send(client_ch, s).write(&msg), Ok(msg.len()));
+    pair.drive_client();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).send_limit_reason(),
+        SendLimit::Congestion
+    );
+
+    pair.drive();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).send_limit_reason(),
+        SendLimit::Application
+    );
+}
+
+#[test]
+fn set_receive_window() {
+    let _guard = subscribe();
+    const WINDOW: u64 = 5000;
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            receive_window: VarInt(WINDOW),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    // Growing the window is advertised immediately
+    pair.server_conn_mut(server_ch)
+        .set_receive_window(VarInt(2 * WINDOW));
+    pair.drive();
+    let stats = pair.server_conn_mut(server_ch).flow_control_stats();
+    assert_eq!(stats.receive_window, 2 * WINDOW);
+    assert_eq!(stats.local_max_data, 2 * WINDOW);
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .flow_control_stats()
+            .peer_max_data,
+        2 * WINDOW
+    );
+
+    // Credit already granted isn't revoked by shrinking the window
+    pair.server_conn_mut(server_ch)
+        .set_receive_window(VarInt(WINDOW));
+    pair.drive();
+    let stats = pair.server_conn_mut(server_ch).flow_control_stats();
+    assert_eq!(stats.receive_window, WINDOW);
+    assert_eq!(stats.local_max_data, 2 * WINDOW);
+}
+
+#[test]
+fn send_limit_flow_control() {
+    let _guard = subscribe();
+    const WINDOW: u64 = 5000;
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            receive_window: VarInt(WINDOW),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, _) = pair.connect();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    let msg = vec![0xAB; 2 * WINDOW as usize];
+    assert_eq!(
+        pair.client_send(client_ch, s).write(&msg),
+        Ok(WINDOW as usize)
+    );
+    assert_eq!(
+        pair.client_send(client_ch, s).write(&msg),
+        Err(WriteError::Blocked)
+    );
+    pair.drive_client();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).send_limit_reason(),
+        SendLimit::FlowControl
+    );
+}
+
+#[test]
+fn flow_control_stats() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = b"hello";
+    pair.client_send(client_ch, s).write(MSG).unwrap();
+    pair.drive();
+
+    let client = pair.client_conn_mut(client_ch).flow_control_stats();
+    let server = pair.server_conn_mut(server_ch).flow_control_stats();
+    assert_eq!(client.data_sent, MSG.len() as u64);
+    assert_eq!(server.data_received, MSG.len() as u64);
+    assert_eq!(client.peer_max_data, server.local_max_data);
+    assert_eq!(server.peer_max_data, client.local_max_data);
+}
+
 #[test]
 fn reset_stream() {
     let _guard = subscribe();
@@ -307,6 +939,7 @@ fn reset_stream() {
     );
     assert_matches!(pair.server_streams(server_ch).accept(Dir::Uni), Some(stream) if stream == s);
     let mut recv = pair.server_recv(server_ch, s);
+    assert_eq!(recv.reset_code(), Some(ERROR));
     let mut chunks = recv.read(false).unwrap();
     assert_matches!(chunks.next(usize::MAX), Err(ReadError::Reset(ERROR)));
     let _ = chunks.finalize();
@@ -429,6 +1062,101 @@ fn congestion() {
     pair.client_send(client_ch, s).write(&[42; 1024]).unwrap();
 }
 
+#[test]
+fn congestion_phase() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).congestion_phase(),
+        CongestionPhase::SlowStart
+    );
+
+    // Lose a packet, then send enough for the loss to be detected once they're acknowledged
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&[42; 100]).unwrap();
+    pair.drive_client();
+    pair.server.inbound.clear();
+    for _ in 0..4 {
+        pair.client_send(client_ch, s).write(&[42; 100]).unwrap();
+        pair.drive_client();
+    }
+    pair.drive_server();
+    pair.drive_client();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).congestion_phase(),
+        CongestionPhase::Recovery
+    );
+    assert_eq!(
+        pair.server_conn_mut(server_ch).congestion_phase(),
+        CongestionPhase::SlowStart
+    );
+
+    // Recovery ends once data sent afterwards is acknowledged
+    pair.drive();
+    pair.time += Duration::from_millis(10);
+    pair.client_send(client_ch, s).write(&[42; 100]).unwrap();
+    pair.drive();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).congestion_phase(),
+        CongestionPhase::CongestionAvoidance
+    );
+}
+
+#[test]
+fn congestion_blocked_stat() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .stats()
+            .path
+            .congestion_blocked,
+        0
+    );
+
+    // Write more than the initial congestion window without letting any ACKs through
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    let window = pair.client_conn_mut(client_ch).congestion_window();
+    let mut written = 0;
+    while written <= window {
+        written += pair.client_send(client_ch, s).write(&[42; 1024]).unwrap() as u64;
+    }
+    pair.drive_client();
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .stats()
+            .path
+            .congestion_blocked,
+        1
+    );
+}
+
+#[test]
+fn spurious_pto_stat() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+
+    // A lost packet makes the probe timeout genuine
+    pair.client_send(client_ch, s).write(&[42; 100]).unwrap();
+    pair.drive_client();
+    pair.server.inbound.clear();
+    pair.drive();
+    let stats = pair.client_conn_mut(client_ch).stats().path;
+    assert!(stats.ptos > 0);
+    assert_eq!(stats.spurious_ptos, 0);
+
+    // A packet which is merely delayed makes it spurious
+    pair.latency = Duration::from_secs(1);
+    pair.client_send(client_ch, s).write(&[42; 100]).unwrap();
+    pair.drive();
+    let stats = pair.client_conn_mut(client_ch).stats().path;
+    assert_eq!(stats.spurious_ptos, 1);
+}
+
 #[allow(clippy::field_reassign_with_default)] // https://github.com/rust-lang/rust-clippy/issues/6527
 #[test]
 fn high_latency_handshake() {
@@ -472,6 +1200,10 @@ fn zero_rtt_happypath() {
     info!("resuming session");
     let client_ch = pair.begin_connect(config);
     assert!(pair.client_conn_mut(client_ch).has_0rtt());
+    assert_eq!(
+        pair.client_conn_mut(client_ch).encryption_level(),
+        EncryptionLevel::ZeroRtt
+    );
     let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
     const MSG: &[u8] = b"Hello, 0-RTT!";
     pair.client_send(client_ch, s).write(MSG).unwrap();
@@ -575,11 +1307,163 @@ fn zero_rtt_rejection() {
     let s2 = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
     assert_eq!(s, s2);
 
-    let mut recv = pair.server_recv(server_ch, s2);
-    let mut chunks = recv.read(false).unwrap();
-    assert_eq!(chunks.next(usize::MAX), Err(ReadError::Blocked));
-    let _ = chunks.finalize();
-    assert_eq!(pair.client_conn_mut(client_ch).lost_packets(), 0);
+    let mut recv = pair.server_recv(server_ch, s2);
+    let mut chunks = recv.read(false).unwrap();
+    assert_eq!(chunks.next(usize::MAX), Err(ReadError::Blocked));
+    let _ = chunks.finalize();
+    assert_eq!(pair.client_conn_mut(client_ch).lost_packets(), 0);
+}
+
+#[test]
+fn zero_rtt_rejection_retransmit() {
+    let _guard = subscribe();
+    let mut server_crypto = server_crypto();
+    server_crypto.alpn_protocols = vec!["foo".into(), "bar".into()];
+    let server_config = ServerConfig::with_crypto(Arc::new(server_crypto));
+    let mut pair = Pair::new(Arc::new(EndpointConfig::default()), server_config);
+    let mut transport = TransportConfig::default();
+    transport.retransmit_rejected_0rtt(true);
+    let transport = Arc::new(transport);
+    let mut client_crypto = client_crypto();
+    client_crypto.alpn_protocols = vec!["foo".into()];
+    let mut client_config = ClientConfig::new(Arc::new(client_crypto.clone()));
+    client_config.transport_config(transport.clone());
+
+    // Establish normal connection
+    let (client_ch, _) = pair.connect_with(client_config);
+    let now = pair.time;
+    pair.client_conn_mut(client_ch)
+        .close(now, VarInt(0), [][..].into());
+    pair.drive();
+    pair.client.connections.clear();
+    pair.server.connections.clear();
+
+    // Changing protocols invalidates 0-RTT
+    client_crypto.alpn_protocols = vec!["bar".into()];
+    let mut client_config = ClientConfig::new(Arc::new(client_crypto));
+    client_config.transport_config(transport);
+    info!("resuming session");
+    let client_ch = pair.begin_connect(client_config);
+    assert!(pair.client_conn_mut(client_ch).has_0rtt());
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = b"Hello, 0-RTT!";
+    pair.client_send(client_ch, s).write(MSG).unwrap();
+    pair.client_send(client_ch, s).finish().unwrap();
+    pair.drive();
+    assert!(!pair.client_conn_mut(client_ch).accepted_0rtt());
+    assert!(pair.client_conn_mut(client_ch).retransmitted_0rtt());
+
+    // The stream survived the rejection, and its data was delivered at 1-RTT
+    let server_ch = pair.server.assert_accept();
+    let mut recv = pair.server_recv(server_ch, s);
+    let mut chunks = recv.read(false).unwrap();
+    assert_matches!(chunks.next(usize::MAX), Ok(Some(chunk)) if chunk.bytes == MSG);
+    assert_matches!(chunks.next(usize::MAX), Ok(None));
+    let _ = chunks.finalize();
+}
+
+#[test]
+fn max_handshake_rate_per_ip() {
+    let _guard = subscribe();
+    let mut endpoint_config = EndpointConfig::default();
+    endpoint_config.max_handshake_rate_per_ip(Some((1, Duration::from_secs(3600))));
+    let mut pair = Pair::new(Arc::new(endpoint_config), server_config());
+    pair.connect();
+    assert_eq!(pair.server.endpoint.handshake_rate_rejections(), 0);
+
+    // A second attempt from the same address is dropped without a response
+    pair.begin_connect(client_config());
+    pair.drive_client();
+    pair.drive_server();
+    assert_eq!(pair.server.endpoint.handshake_rate_rejections(), 1);
+    assert_eq!(pair.server.connections.len(), 1);
+    assert!(pair.server.outbound.is_empty());
+}
+
+#[test]
+fn max_handshake_rate_per_ip_retry() {
+    let _guard = subscribe();
+    let limited_pair = |attempts| {
+        let mut endpoint_config = EndpointConfig::default();
+        endpoint_config.max_handshake_rate_per_ip(Some((attempts, Duration::from_secs(3600))));
+        let server = ServerConfig {
+            use_retry: true,
+            ..server_config()
+        };
+        Pair::new(Arc::new(endpoint_config), server)
+    };
+    // Initials without a retry token could come from anyone, so they don't consume the source's
+    // allowance
+    let replay_initial = |pair: &mut Pair| {
+        let initial = pair.server.inbound.back().unwrap().clone();
+        for _ in 0..3 {
+            pair.server.inbound.push_front(initial.clone());
+        }
+    };
+
+    let mut pair = limited_pair(1);
+    let client_ch = pair.begin_connect(client_config());
+    pair.drive_client();
+    replay_initial(&mut pair);
+    pair.drive();
+    assert_eq!(pair.server.endpoint.retry_tokens_issued(), 4);
+    assert_eq!(pair.server.endpoint.handshake_rate_rejections(), 0);
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::HandshakeDataReady)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Connected)
+    );
+
+    // An attempt which completes the Retry is counted
+    let mut pair = limited_pair(0);
+    pair.begin_connect(client_config());
+    pair.drive_client();
+    replay_initial(&mut pair);
+    pair.drive_server();
+    assert_eq!(pair.server.endpoint.retry_tokens_issued(), 4);
+    assert_eq!(pair.server.endpoint.handshake_rate_rejections(), 0);
+    pair.drive_client();
+    pair.drive_server();
+    assert_eq!(pair.server.endpoint.retry_tokens_validated(), 1);
+    assert_eq!(pair.server.endpoint.handshake_rate_rejections(), 1);
+    assert!(pair.server.connections.is_empty());
+}
+
+#[test]
+fn required_alpn_protocols() {
+    let _guard = subscribe();
+    let mut server_crypto = server_crypto();
+    server_crypto.alpn_protocols = vec!["bar".into()];
+    let mut server = ServerConfig::with_crypto(Arc::new(server_crypto));
+    server.required_alpn_protocols(vec![b"bar".to_vec()]);
+    let mut pair = Pair::new(Default::default(), server);
+    let client_config = |alpn: &[&str]| {
+        let mut crypto = client_crypto();
+        crypto.alpn_protocols = alpn.iter().map(|x| x.as_bytes().to_vec()).collect();
+        ClientConfig::new(Arc::new(crypto))
+    };
+
+    for alpn in [&[][..], &["foo"][..]] {
+        let client_ch = pair.begin_connect(client_config(alpn));
+        pair.drive();
+        assert_matches!(
+            pair.client_conn_mut(client_ch).poll(),
+            Some(Event::ConnectionLost {
+                reason: ConnectionError::ConnectionClosed(frame::ConnectionClose {
+                    error_code,
+                    ..
+                }),
+            }) if error_code == TransportErrorCode::crypto(120)
+        );
+    }
+    assert_eq!(pair.server.endpoint.alpn_rejections(), 2);
+    assert_eq!(pair.server.connections.len(), 0);
+
+    pair.connect_with(client_config(&["foo", "bar"]));
+    assert_eq!(pair.server.endpoint.alpn_rejections(), 2);
 }
 
 #[test]
@@ -614,6 +1498,91 @@ fn alpn_success() {
         .downcast::<crate::crypto::rustls::HandshakeData>()
         .unwrap();
     assert_eq!(hd.protocol.unwrap(), &b"bar"[..]);
+    assert_eq!(hd.offered_protocols, None);
+
+    let hd = pair
+        .server_conn_mut(server_ch)
+        .crypto_session()
+        .handshake_data()
+        .unwrap()
+        .downcast::<crate::crypto::rustls::HandshakeData>()
+        .unwrap();
+    assert_eq!(hd.protocol.unwrap(), &b"bar"[..]);
+    assert_eq!(
+        hd.offered_protocols.unwrap(),
+        [&b"bar"[..], &b"quux"[..], &b"corge"[..]]
+    );
+}
+
+#[test]
+fn offered_protocols_without_alpn() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (_, server_ch) = pair.connect();
+    let hd = pair
+        .server_conn_mut(server_ch)
+        .crypto_session()
+        .handshake_data()
+        .unwrap()
+        .downcast::<crate::crypto::rustls::HandshakeData>()
+        .unwrap();
+    assert_eq!(hd.offered_protocols, None);
+}
+
+#[test]
+fn key_exchange_group() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    let handshake_data = |conn: &mut Connection| {
+        conn.crypto_session()
+            .handshake_data()
+            .unwrap()
+            .downcast::<crate::crypto::rustls::HandshakeData>()
+            .unwrap()
+    };
+    for hd in [
+        handshake_data(pair.client_conn_mut(client_ch)),
+        handshake_data(pair.server_conn_mut(server_ch)),
+    ] {
+        assert_eq!(hd.key_exchange_group, Some(rustls::NamedGroup::X25519));
+        assert!(!hd.is_post_quantum());
+    }
+}
+
+#[test]
+fn key_exchange_group_after_retry_request() {
+    let _guard = subscribe();
+    // The client's initial key share is for X25519, so the server must request another
+    let server_crypto = rustls::ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_kx_groups(&[&rustls::kx_group::SECP256R1])
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(CERTIFICATE.serialize_der().unwrap())],
+            rustls::PrivateKey(CERTIFICATE.serialize_private_key_der()),
+        )
+        .unwrap();
+    let mut pair = Pair::new(
+        Default::default(),
+        ServerConfig::with_crypto(Arc::new(server_crypto)),
+    );
+    let (client_ch, server_ch) = pair.connect();
+    let handshake_data = |conn: &mut Connection| {
+        conn.crypto_session()
+            .handshake_data()
+            .unwrap()
+            .downcast::<crate::crypto::rustls::HandshakeData>()
+            .unwrap()
+    };
+    for hd in [
+        handshake_data(pair.client_conn_mut(client_ch)),
+        handshake_data(pair.server_conn_mut(server_ch)),
+    ] {
+        assert_eq!(hd.key_exchange_group, Some(rustls::NamedGroup::secp256r1));
+    }
 }
 
 #[test]
@@ -669,6 +1638,115 @@ fn alpn_mismatch() {
     );
 }
 
+#[test]
+fn stream_admission() {
+    let _guard = subscribe();
+    const ERROR: VarInt = VarInt(42);
+    let mut transport = TransportConfig::default();
+    transport.stream_admission(|| Box::new(|id: StreamId| id.dir() == Dir::Uni), ERROR);
+    let server = ServerConfig {
+        transport: Arc::new(transport),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    let bi = pair.client_streams(client_ch).open(Dir::Bi).unwrap();
+    pair.client_send(client_ch, bi).write(b"hello").unwrap();
+    let uni = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, uni).write(b"hello").unwrap();
+    pair.drive();
+
+    // Only the unidirectional stream is admitted
+    assert_matches!(pair.server_streams(server_ch).accept(Dir::Bi), None);
+    assert_matches!(pair.server_streams(server_ch).accept(Dir::Uni), Some(stream) if stream == uni);
+
+    assert_matches!(
+        pair.client_send(client_ch, bi).write(b"foo"),
+        Err(WriteError::Stopped(ERROR))
+    );
+    let mut recv = pair.client_recv(client_ch, bi);
+    let mut chunks = recv.read(false).unwrap();
+    assert_matches!(chunks.next(usize::MAX), Err(ReadError::Reset(ERROR)));
+    let _ = chunks.finalize();
+    pair.client_send(client_ch, uni).write(b"foo").unwrap();
+}
+
+#[test]
+fn remote_stream_rate_limit() {
+    let _guard = subscribe();
+    let mut transport = TransportConfig::default();
+    transport.max_remote_stream_rate(Some((2, Duration::from_secs(10))));
+    let server = ServerConfig {
+        transport: Arc::new(transport),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    for _ in 0..2 {
+        let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+        pair.client_send(client_ch, s).finish().unwrap();
+        pair.drive();
+    }
+    assert_eq!(
+        pair.server_conn_mut(server_ch)
+            .stats()
+            .remote_streams_opened,
+        2
+    );
+    assert_eq!(pair.server_conn_mut(server_ch).recent_errors().count(), 0);
+
+    // The limit is exceeded before it's refilled
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).finish().unwrap();
+    pair.drive();
+    let errors = pair
+        .server_conn_mut(server_ch)
+        .recent_errors()
+        .map(|e| e.code)
+        .collect::<Vec<_>>();
+    assert_eq!(errors, [TransportErrorCode::PROTOCOL_VIOLATION]);
+    // Errors reported by the peer aren't recorded
+    assert_eq!(pair.client_conn_mut(client_ch).recent_errors().count(), 0);
+    let lost =
+        std::iter::from_fn(|| pair.client_conn_mut(client_ch).poll()).find_map(|e| match e {
+            Event::ConnectionLost { reason } => Some(reason),
+            _ => None,
+        });
+    assert_matches!(
+        lost,
+        Some(ConnectionError::ConnectionClosed(frame::ConnectionClose {
+            error_code: TransportErrorCode::PROTOCOL_VIOLATION,
+            ..
+        }))
+    );
+}
+
+#[test]
+fn invalid_initial_tokens_recorded_once() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+    pair.drive_client();
+    // Every further Initial from the client carries a token the server didn't expect, as could be
+    // arranged by an off-path attacker spoofing the client's address
+    pair.client_conn_mut(client_ch)
+        .set_initial_token(Bytes::from_static(b"not the expected token"));
+    for _ in 0..4 {
+        pair.client_conn_mut(client_ch).ping();
+        pair.drive_client();
+        pair.drive_server();
+    }
+    let server_ch = pair.server.assert_accept();
+    let errors = pair
+        .server_conn_mut(server_ch)
+        .recent_errors()
+        .map(|e| e.code)
+        .collect::<Vec<_>>();
+    assert_eq!(errors, [TransportErrorCode::INVALID_TOKEN]);
+}
+
 #[test]
 fn stream_id_limit() {
     let _guard = subscribe();
@@ -790,11 +1868,16 @@ fn key_update_simple() {
     let _ = chunks.finalize();
 
     info!("initiating key update");
-    pair.client_conn_mut(client_ch).initiate_key_update();
+    assert_eq!(pair.client_conn_mut(client_ch).key_update_count(), 0);
+    assert!(pair.client_conn_mut(client_ch).initiate_key_update());
+    // The previous keys must be discarded before the next update
+    assert!(!pair.client_conn_mut(client_ch).initiate_key_update());
 
     const MSG2: &[u8] = b"hello2";
     pair.client_send(client_ch, s).write(MSG2).unwrap();
     pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).key_update_count(), 1);
+    assert_eq!(pair.server_conn_mut(server_ch).key_update_count(), 1);
 
     assert_matches!(pair.server_conn_mut(server_ch).poll(), Some(Event::Stream(StreamEvent::Readable { id })) if id == s);
     assert_matches!(pair.server_conn_mut(server_ch).poll(), None);
@@ -945,6 +2028,13 @@ fn idle_timeout() {
     };
     let mut pair = Pair::new(Default::default(), server);
     let (client_ch, server_ch) = pair.connect();
+    // The client's default timeout is longer, so the server's prevails
+    for timeout in [
+        pair.client_conn_mut(client_ch).max_idle_timeout(),
+        pair.server_conn_mut(server_ch).max_idle_timeout(),
+    ] {
+        assert_eq!(timeout, Some(Duration::from_millis(IDLE_TIMEOUT)));
+    }
     pair.client_conn_mut(client_ch).ping();
     let start = pair.time;
 
@@ -974,6 +2064,30 @@ fn idle_timeout() {
     );
 }
 
+#[test]
+fn max_pto_backoff() {
+    let _guard = subscribe();
+    let client_config = ClientConfig {
+        transport: Arc::new(TransportConfig {
+            max_pto_backoff: 1,
+            ..TransportConfig::default()
+        }),
+        ..client_config()
+    };
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect_with(client_config);
+    let base = pair.client_conn_mut(client_ch).current_pto();
+
+    // Lose everything the client sends, so that PTOs fire repeatedly
+    pair.client_conn_mut(client_ch).ping();
+    for _ in 0..4 {
+        pair.drive_client();
+        pair.server.inbound.clear();
+        pair.time = pair.client.next_wakeup().unwrap();
+    }
+    assert_eq!(pair.client_conn_mut(client_ch).current_pto(), base * 2);
+}
+
 #[test]
 fn connection_close_sends_acks() {
     let _guard = subscribe();
@@ -1048,6 +2162,7 @@ fn migration() {
     let _guard = subscribe();
     let mut pair = Pair::default();
     let (client_ch, server_ch) = pair.connect();
+    assert!(pair.server_conn_mut(server_ch).path_validated());
     pair.client.addr = SocketAddr::new(
         Ipv4Addr::new(127, 0, 0, 1).into(),
         CLIENT_PORTS.lock().unwrap().next().unwrap(),
@@ -1059,15 +2174,219 @@ fn migration() {
     pair.drive_client();
     pair.drive_server();
     assert_ne!(pair.server_conn_mut(server_ch).total_recvd(), 0);
+    assert!(!pair.server_conn_mut(server_ch).path_validated());
+    assert_eq!(pair.server_conn_mut(server_ch).active_paths(), 2);
+    assert_eq!(pair.server_conn_mut(server_ch).migration_count(), 0);
 
     pair.drive();
     assert_matches!(pair.client_conn_mut(client_ch).poll(), None);
+    assert!(pair.server_conn_mut(server_ch).path_validated());
+    assert_eq!(pair.server_conn_mut(server_ch).migration_count(), 1);
+    assert_eq!(pair.client_conn_mut(client_ch).migration_count(), 0);
+    assert_eq!(pair.server_conn_mut(server_ch).active_paths(), 1);
     assert_eq!(
         pair.server_conn_mut(server_ch).remote_address(),
         pair.client.addr
     );
 }
 
+#[test]
+fn migration_single_path() {
+    let _guard = subscribe();
+    let mut transport = TransportConfig::default();
+    transport.max_concurrent_paths(1);
+    let mut pair = Pair::new(
+        Default::default(),
+        ServerConfig {
+            transport: Arc::new(transport),
+            ..server_config()
+        },
+    );
+    let (client_ch, server_ch) = pair.connect();
+    pair.client.addr = SocketAddr::new(
+        Ipv4Addr::new(127, 0, 0, 1).into(),
+        CLIENT_PORTS.lock().unwrap().next().unwrap(),
+    );
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive_client();
+    pair.drive_server();
+    assert!(!pair.server_conn_mut(server_ch).path_validated());
+    assert_eq!(pair.server_conn_mut(server_ch).active_paths(), 1);
+
+    pair.drive();
+    let server = pair.server_conn_mut(server_ch);
+    assert!(server.path_validated());
+    assert_eq!(server.active_paths(), 1);
+    assert_eq!(server.remote_address(), pair.client.addr);
+}
+
+#[test]
+fn migration_single_path_spoofed() {
+    let _guard = subscribe();
+    // Whether the server falls back to the client's real address after a spoofed migration
+    for &(max_paths, recovers) in &[(2, true), (1, false)] {
+        let mut transport = TransportConfig::default();
+        transport.max_concurrent_paths(max_paths);
+        let mut pair = Pair::new(
+            Default::default(),
+            ServerConfig {
+                transport: Arc::new(transport),
+                ..server_config()
+            },
+        );
+        let (client_ch, server_ch) = pair.connect();
+        let real = pair.client.addr;
+        let spoofed = SocketAddr::new(real.ip(), CLIENT_PORTS.lock().unwrap().next().unwrap());
+
+        // Deliver a client packet as if it came from an address the client can't receive on
+        pair.client_conn_mut(client_ch).ping();
+        pair.drive_client();
+        pair.client.addr = spoofed;
+        pair.drive_server();
+        pair.client.addr = real;
+        assert_eq!(pair.server_conn_mut(server_ch).remote_address(), spoofed);
+
+        // The client stays silent, so path validation fails
+        for _ in 0..2 {
+            pair.time = pair.time.max(pair.server.next_wakeup().unwrap());
+            pair.drive_server();
+            pair.client.inbound.clear();
+        }
+        let server = pair.server_conn_mut(server_ch);
+        assert_eq!(server.remote_address() == real, recovers);
+        assert_eq!(server.path_validated(), recovers);
+    }
+}
+
+#[test]
+fn transmit_metadata() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    let now = pair.time;
+    let conn = pair.client_conn_mut(client_ch);
+    conn.ping();
+    assert_eq!(conn.poll_transmit(now, 1).unwrap().metadata, None);
+
+    let metadata = Bytes::from_static(b"route");
+    conn.set_transmit_metadata(Some(metadata.clone()));
+    conn.ping();
+    assert_eq!(conn.poll_transmit(now, 1).unwrap().metadata, Some(metadata));
+}
+
+#[test]
+fn socket_priority() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    let now = pair.time;
+    let conn = pair.client_conn_mut(client_ch);
+    conn.ping();
+    assert_eq!(conn.poll_transmit(now, 1).unwrap().priority, None);
+
+    conn.set_socket_priority(Some(3));
+    conn.ping();
+    assert_eq!(conn.poll_transmit(now, 1).unwrap().priority, Some(3));
+}
+
+#[test]
+fn path_responses_keep_newest() {
+    let _guard = subscribe();
+    // Deliver the server's challenges for two migrations in and out of order
+    for &reordered in &[false, true] {
+        let mut pair = Pair::default();
+        let mut transport = TransportConfig::default();
+        transport.max_pending_path_responses(1);
+        let mut client_config = client_config();
+        client_config.transport_config(Arc::new(transport));
+        let (client_ch, server_ch) = pair.connect_with(client_config);
+        let ip = pair.client.addr.ip();
+
+        let mut challenges = Vec::new();
+        for _ in 0..2 {
+            pair.client.addr = SocketAddr::new(ip, CLIENT_PORTS.lock().unwrap().next().unwrap());
+            pair.client_conn_mut(client_ch).ping();
+            pair.drive_client();
+            pair.drive_server();
+            challenges.extend(pair.client.inbound.drain(..));
+        }
+        if reordered {
+            challenges.reverse();
+        }
+        pair.client.inbound.extend(challenges);
+
+        // Only the response to the latest challenge validates the path the server is now using
+        pair.drive_client();
+        pair.drive_server();
+        assert!(pair.server_conn_mut(server_ch).path_validated());
+        assert_eq!(
+            pair.client_conn_mut(client_ch)
+                .stats()
+                .path
+                .path_challenges_dropped,
+            1
+        );
+    }
+}
+
+#[test]
+fn migration_disabled() {
+    let _guard = subscribe();
+    let mut pair = Pair::new(
+        Default::default(),
+        ServerConfig {
+            migration: false,
+            ..server_config()
+        },
+    );
+    let (client_ch, server_ch) = pair.connect();
+    let original_addr = pair.client.addr;
+    pair.client.addr = SocketAddr::new(
+        Ipv4Addr::new(127, 0, 0, 1).into(),
+        CLIENT_PORTS.lock().unwrap().next().unwrap(),
+    );
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive_client();
+    pair.drive_server();
+
+    let server = pair.server_conn_mut(server_ch);
+    assert!(!server.is_closed());
+    assert_eq!(server.remote_address(), original_addr);
+    assert_eq!(server.stats().path.migrations_refused, 1);
+}
+
+#[test]
+fn close_on_migration() {
+    let _guard = subscribe();
+    let mut pair = Pair::new(
+        Default::default(),
+        ServerConfig {
+            migration: false,
+            close_on_migration: true,
+            ..server_config()
+        },
+    );
+    let (client_ch, server_ch) = pair.connect();
+    pair.client.addr = SocketAddr::new(
+        Ipv4Addr::new(127, 0, 0, 1).into(),
+        CLIENT_PORTS.lock().unwrap().next().unwrap(),
+    );
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive_client();
+    pair.drive_server();
+
+    assert_matches!(pair.server_conn_mut(server_ch).poll(),
+                    Some(Event::ConnectionLost { reason: ConnectionError::TransportError(ref error)})
+                    if error.code == TransportErrorCode::PROTOCOL_VIOLATION);
+    assert_eq!(
+        pair.server_conn_mut(server_ch)
+            .stats()
+            .path
+            .migrations_refused,
+        1
+    );
+}
+
 fn test_flow_control(config: TransportConfig, window_size: usize) {
     let _guard = subscribe();
     let mut pair = Pair::new(
@@ -1189,6 +2508,44 @@ fn conn_flow_control() {
     );
 }
 
+#[test]
+fn uni_stream_flow_control() {
+    test_flow_control(
+        TransportConfig {
+            stream_receive_window_uni: Some(2000u32.into()),
+            ..TransportConfig::default()
+        },
+        2000,
+    );
+}
+
+#[test]
+fn stream_receive_window_per_type() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            stream_receive_window: 2000u32.into(),
+            stream_receive_window_uni: Some(4000u32.into()),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, _) = pair.connect();
+
+    let uni = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    assert_eq!(
+        pair.client_send(client_ch, uni).write(&[0; 5000]).unwrap(),
+        4000
+    );
+    // Bidirectional streams opened by the client use the server's default window
+    let bi = pair.client_streams(client_ch).open(Dir::Bi).unwrap();
+    assert_eq!(
+        pair.client_send(client_ch, bi).write(&[0; 5000]).unwrap(),
+        2000
+    );
+}
+
 #[test]
 fn stop_opens_bidi() {
     let _guard = subscribe();
@@ -1371,6 +2728,8 @@ fn cid_retirement() {
     let _guard = subscribe();
     let mut pair = Pair::default();
     let (client_ch, server_ch) = pair.connect();
+    let initial_cids = pair.server_conn_mut(server_ch).active_connection_ids();
+    assert!(initial_cids.len() > 1);
 
     // Server retires current active remote CIDs
     pair.server_conn_mut(server_ch)
@@ -1380,6 +2739,9 @@ fn cid_retirement() {
     assert!(!pair.client_conn_mut(client_ch).is_closed());
     assert!(!pair.server_conn_mut(server_ch).is_closed());
     assert_matches!(pair.client_conn_mut(client_ch).active_rem_cid_seq(), 1);
+    let cids = pair.server_conn_mut(server_ch).active_connection_ids();
+    assert!(!cids.contains(&initial_cids[0]));
+    assert_eq!(cids[..initial_cids.len() - 1], initial_cids[1..]);
 
     use crate::cid_queue::CidQueue;
     use crate::LOC_CID_COUNT;
@@ -1398,6 +2760,8 @@ fn cid_retirement() {
         pair.client_conn_mut(client_ch).active_rem_cid_seq(),
         _next_retire_prior_to
     );
+    let cids = pair.server_conn_mut(server_ch).active_connection_ids();
+    assert!(initial_cids.iter().all(|cid| !cids.contains(cid)));
 }
 
 #[test]
@@ -1550,8 +2914,22 @@ fn congested_tail_loss() {
     // Ensure that the congestion state recovers after retransmits occur and are ACKed
     info!("recovering");
     pair.drive();
-    assert!(pair.client_conn_mut(client_ch).congestion_window() > TARGET);
-    pair.client_send(client_ch, s).write(&[42; 1024]).unwrap();
+    assert!(pair.client_conn_mut(client_ch).congestion_window() > TARGET);
+    pair.client_send(client_ch, s).write(&[42; 1024]).unwrap();
+}
+
+#[test]
+fn handshake_confirmation() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+    assert!(pair.client_conn_mut(client_ch).is_handshaking());
+    assert!(!pair.client_conn_mut(client_ch).is_handshake_confirmed());
+    pair.drive();
+    let server_ch = pair.server.assert_accept();
+    assert!(!pair.client_conn_mut(client_ch).is_handshaking());
+    assert!(pair.client_conn_mut(client_ch).is_handshake_confirmed());
+    assert!(pair.server_conn_mut(server_ch).is_handshake_confirmed());
 }
 
 #[test]
@@ -1573,6 +2951,73 @@ fn datagram_send_recv() {
     assert_matches!(pair.server_datagrams(server_ch).recv(), None);
 }
 
+#[test]
+fn datagram_stats() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_config = ClientConfig {
+        transport: Arc::new(TransportConfig {
+            datagram_send_buffer_size: 10,
+            ..TransportConfig::default()
+        }),
+        ..client_config()
+    };
+    let (client_ch, server_ch) = pair.connect_with(client_config);
+
+    let max_size = pair.client_datagrams(client_ch).max_size().unwrap();
+    assert_matches!(
+        pair.client_datagrams(client_ch)
+            .send(vec![0; max_size + 1].into()),
+        Err(SendDatagramError::TooLarge)
+    );
+    const DATA: &[u8] = &[0xAB; 8];
+    for _ in 0..3 {
+        pair.client_datagrams(client_ch).send(DATA.into()).unwrap();
+    }
+    pair.drive();
+
+    let stats = pair.client_conn_mut(client_ch).stats().datagrams;
+    assert_eq!(stats.sent, 2);
+    assert_eq!(stats.send_dropped, 1);
+    assert_eq!(stats.too_large, 1);
+    let stats = pair.server_conn_mut(server_ch).stats().datagrams;
+    assert_eq!(stats.received, 2);
+    assert_eq!(stats.recv_dropped, 0);
+}
+
+#[test]
+fn datagram_oversized_policy() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_config = ClientConfig {
+        transport: Arc::new(TransportConfig {
+            oversized_datagram_policy: OversizedDatagramPolicy::Drop,
+            ..TransportConfig::default()
+        }),
+        ..client_config()
+    };
+    let (client_ch, server_ch) = pair.connect_with(client_config);
+    let max_size = pair.client_datagrams(client_ch).max_size().unwrap();
+    let data = Bytes::from(vec![0xAB; max_size + 1]);
+
+    pair.client_datagrams(client_ch).send(data.clone()).unwrap();
+    pair.drive();
+    assert_matches!(pair.server_datagrams(server_ch).recv(), None);
+
+    pair.client_datagrams(client_ch)
+        .set_oversized_policy(OversizedDatagramPolicy::Truncate);
+    pair.client_datagrams(client_ch).send(data.clone()).unwrap();
+    pair.drive();
+    assert_eq!(
+        pair.server_datagrams(server_ch).recv().unwrap(),
+        data.slice(..max_size)
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch).stats().datagrams.too_large,
+        2
+    );
+}
+
 #[test]
 fn datagram_recv_buffer_overflow() {
     let _guard = subscribe();
@@ -1603,6 +3048,14 @@ fn datagram_recv_buffer_overflow() {
         pair.server_conn_mut(server_ch).poll(),
         Some(Event::DatagramReceived)
     );
+    assert_eq!(pair.server_datagrams(server_ch).recv_queue_len(), 2);
+    assert_eq!(
+        pair.server_conn_mut(server_ch)
+            .stats()
+            .datagrams
+            .recv_dropped,
+        1
+    );
     assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), DATA2);
     assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), DATA3);
     assert_matches!(pair.server_datagrams(server_ch).recv(), None);
@@ -1613,6 +3066,184 @@ fn datagram_recv_buffer_overflow() {
     assert_matches!(pair.server_datagrams(server_ch).recv(), None);
 }
 
+#[test]
+fn datagram_recv_buffer_capacity() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            datagram_receive_buffer_capacity: Some(1),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    // Only the newest datagram is kept by default
+    pair.client_datagrams(client_ch)
+        .send(b"a"[..].into())
+        .unwrap();
+    pair.client_datagrams(client_ch)
+        .send(b"b"[..].into())
+        .unwrap();
+    pair.drive();
+    assert_eq!(pair.server_datagrams(server_ch).recv_queue_len(), 1);
+    assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), &b"b"[..]);
+
+    // Switching policy keeps the oldest instead
+    pair.server_datagrams(server_ch)
+        .set_recv_buffer(Some(2), DatagramDropPolicy::DropNewest);
+    for data in [&b"c"[..], b"d", b"e"] {
+        pair.client_datagrams(client_ch).send(data.into()).unwrap();
+    }
+    pair.drive();
+    assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), &b"c"[..]);
+    assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), &b"d"[..]);
+    assert_matches!(pair.server_datagrams(server_ch).recv(), None);
+    assert_eq!(
+        pair.server_conn_mut(server_ch)
+            .stats()
+            .datagrams
+            .recv_dropped,
+        2
+    );
+}
+
+#[test]
+fn datagram_frames_per_transmit() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            datagram_frames_per_transmit: Some(1),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    // Flood the connection with datagrams, then queue some stream data behind them
+    let size = pair.server_datagrams(server_ch).max_size().unwrap();
+    for _ in 0..20 {
+        pair.server_datagrams(server_ch)
+            .send(vec![0xAB; size].into())
+            .unwrap();
+    }
+    const MSG: &[u8] = &[0xCD; 2000];
+    let s = pair.server_streams(server_ch).open(Dir::Uni).unwrap();
+    pair.server_send(server_ch, s).write(MSG).unwrap();
+    pair.server_send(server_ch, s).finish().unwrap();
+
+    let before = pair.server_conn_mut(server_ch).stats().frame_tx;
+    let now = pair.time;
+    pair.server_conn_mut(server_ch)
+        .poll_transmit(now, 10)
+        .unwrap();
+    let after = pair.server_conn_mut(server_ch).stats().frame_tx;
+    assert!(after.stream > before.stream);
+    assert!(after.datagram > before.datagram + 1);
+
+    pair.drive();
+    assert_matches!(
+        pair.client_streams(client_ch).accept(Dir::Uni),
+        Some(stream) if stream == s
+    );
+    let mut recv = pair.client_recv(client_ch, s);
+    let mut chunks = recv.read(true).unwrap();
+    let mut received = Vec::new();
+    while let Ok(Some(chunk)) = chunks.next(usize::MAX) {
+        received.extend_from_slice(&chunk.bytes);
+    }
+    let _ = chunks.finalize();
+    assert_eq!(received, MSG);
+}
+
+#[test]
+fn datagram_zero_limit() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            datagram_receive_buffer_size: Some(0),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+    assert_matches!(pair.client_datagrams(client_ch).max_size(), None);
+    // Support is negotiated independently in each direction
+    assert!(!pair.client_datagrams(client_ch).can_send());
+    assert!(pair.client_datagrams(client_ch).can_receive());
+    assert!(pair.server_datagrams(server_ch).can_send());
+    assert!(!pair.server_datagrams(server_ch).can_receive());
+    assert_matches!(
+        pair.client_datagrams(client_ch).send(Bytes::new()),
+        Err(SendDatagramError::UnsupportedByPeer)
+    );
+}
+
+#[test]
+fn congestion_minimum_window() {
+    let _guard = subscribe();
+    let mut congestion = congestion::NewRenoConfig::default();
+    congestion.max_datagram_size(1000).minimum_window_packets(4);
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            congestion_controller_factory: Box::new(Arc::new(congestion)),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+    assert_eq!(
+        pair.server_conn_mut(server_ch)
+            .congestion_state()
+            .minimum_window(),
+        4000
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .congestion_state()
+            .minimum_window(),
+        2 * 1232
+    );
+}
+
+#[test]
+fn congestion_controller_selector() {
+    let _guard = subscribe();
+    let mut endpoint_config = EndpointConfig::default();
+    endpoint_config.congestion_controller_selector(|props| {
+        let mut congestion = congestion::NewRenoConfig::default();
+        congestion
+            .max_datagram_size(1000)
+            .minimum_window_packets(match props.side {
+                Side::Client => {
+                    assert_eq!(props.server_name, Some("localhost"));
+                    return None;
+                }
+                Side::Server => 4,
+            });
+        assert_eq!(props.server_name, None);
+        Some(Arc::new(Arc::new(congestion)))
+    });
+    let mut pair = Pair::new(Arc::new(endpoint_config), server_config());
+    let (client_ch, server_ch) = pair.connect();
+    assert_eq!(
+        pair.server_conn_mut(server_ch)
+            .congestion_state()
+            .minimum_window(),
+        4000
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .congestion_state()
+            .minimum_window(),
+        2 * 1232
+    );
+}
+
 #[test]
 fn datagram_unsupported() {
     let _guard = subscribe();
@@ -1627,6 +3258,11 @@ fn datagram_unsupported() {
     let (client_ch, server_ch) = pair.connect();
     assert_matches!(pair.server_conn_mut(server_ch).poll(), None);
     assert_matches!(pair.client_datagrams(client_ch).max_size(), None);
+    assert!(!pair.client_datagrams(client_ch).can_send());
+    assert!(pair.client_datagrams(client_ch).can_receive());
+    // Sending requires local support too
+    assert!(!pair.server_datagrams(server_ch).can_send());
+    assert!(!pair.server_datagrams(server_ch).can_receive());
 
     match pair.client_datagrams(client_ch).send(Bytes::new()) {
         Err(SendDatagramError::UnsupportedByPeer) => {}
@@ -1854,6 +3490,19 @@ fn handshake_anti_deadlock_probe() {
     );
 }
 
+#[test]
+fn max_sent_packet_size() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+    assert_eq!(pair.client_conn_mut(client_ch).max_sent_packet_size(), 0);
+    pair.drive();
+    // The client's Initial packets are padded to the minimum size
+    assert!(pair.client_conn_mut(client_ch).max_sent_packet_size() >= 1200);
+    let server_ch = pair.server.assert_accept();
+    assert!(pair.server_conn_mut(server_ch).max_sent_packet_size() > 0);
+}
+
 /// Ensures that the server can respond with 3 initial packets during the handshake
 /// before the anti-amplification limit kicks in when MTUs are similar.
 #[test]
@@ -1872,8 +3521,21 @@ fn server_can_send_3_inital_packets() {
     pair.drive_server();
     // Server should have queued 3 packets at this time
     assert_eq!(pair.client.inbound.len(), 3);
+    let server_ch = pair.server.assert_accept();
+    let budget = pair
+        .server_conn_mut(server_ch)
+        .amplification_budget()
+        .unwrap();
+    assert!(budget < 1200);
+    assert_eq!(pair.client_conn_mut(client_ch).amplification_budget(), None);
 
     pair.drive();
+    assert_eq!(pair.server_conn_mut(server_ch).amplification_budget(), None);
+    let withheld = pair.server_conn_mut(server_ch).amplification_withheld();
+    // Only the part of the first flight which didn't fit in the initial budget is held back
+    assert!(withheld > 0);
+    assert!(withheld < pair.server_conn_mut(server_ch).stats().udp_tx.bytes - 3 * 1200);
+    assert_eq!(pair.client_conn_mut(client_ch).amplification_withheld(), 0);
     assert_matches!(
         pair.client_conn_mut(client_ch).poll(),
         Some(Event::HandshakeDataReady)
@@ -1911,3 +3573,220 @@ fn malformed_token_len() {
         hex!("8900 0000 0101 0000 1b1b 841b 0000 0000 3f00")[..].into(),
     );
 }
+
+#[test]
+fn packets_awaiting_retransmission() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    assert_eq!(pair.client_conn_mut(client_ch).packets_in_flight(), 0);
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    for _ in 0..6 {
+        pair.client_send(client_ch, s).write(&[42; 1000]).unwrap();
+        pair.drive_client();
+    }
+    assert_eq!(pair.client_conn_mut(client_ch).packets_in_flight(), 6);
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .packets_awaiting_retransmission(),
+        0
+    );
+
+    // Only the last packet arrives, so its ACK makes the client declare the first three lost by
+    // packet threshold
+    let last = pair.server.inbound.pop_back().unwrap();
+    pair.server.inbound.clear();
+    pair.server.inbound.push_back(last);
+    pair.drive_server();
+
+    // Deliver the ACK without giving the client a chance to retransmit
+    while let Some((_, ecn, packet)) = pair.client.inbound.pop_front() {
+        let (time, addr) = (pair.time, pair.server.addr);
+        if let Some((ch, DatagramEvent::ConnectionEvent(event))) =
+            pair.client
+                .handle(time, addr, None, ecn, packet.as_slice().into())
+        {
+            pair.client
+                .connections
+                .get_mut(&ch)
+                .unwrap()
+                .handle_event(event);
+        }
+    }
+    let conn = pair.client_conn_mut(client_ch);
+    assert_eq!(conn.packets_awaiting_retransmission(), 3);
+    assert_eq!(conn.packets_in_flight(), 2);
+
+    pair.drive();
+    let conn = pair.client_conn_mut(client_ch);
+    assert_eq!(conn.packets_awaiting_retransmission(), 0);
+    assert_eq!(conn.packets_in_flight(), 0);
+}
+
+#[test]
+fn stream_limits() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            max_concurrent_bidi_streams: 5u32.into(),
+            max_concurrent_uni_streams: 1u32.into(),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    let client_limits = StreamLimits {
+        local_bi: 5,
+        local_uni: 1,
+        remote_bi: 100,
+        remote_uni: 100,
+    };
+    assert_eq!(
+        pair.client_conn_mut(client_ch).stream_limits(),
+        client_limits
+    );
+    assert_eq!(
+        pair.server_conn_mut(server_ch).stream_limits(),
+        StreamLimits {
+            local_bi: 100,
+            local_uni: 100,
+            remote_bi: 5,
+            remote_uni: 1,
+        }
+    );
+
+    pair.server_conn_mut(server_ch)
+        .set_max_concurrent_streams(Dir::Uni, 3u32.into());
+    pair.drive();
+    assert_eq!(
+        pair.server_conn_mut(server_ch).stream_limits().remote_uni,
+        3
+    );
+    assert_eq!(pair.client_conn_mut(client_ch).stream_limits().local_uni, 3);
+}
+
+#[test]
+fn stream_flow_control_blocked() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            stream_receive_window: 2000u32.into(),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    assert!(!pair.client_send(client_ch, s).is_flow_control_blocked());
+    assert_eq!(
+        pair.client_send(client_ch, s).write(&[0; 4000]).unwrap(),
+        2000
+    );
+    assert!(pair.client_send(client_ch, s).is_flow_control_blocked());
+    assert_matches!(
+        pair.client_send(client_ch, s).write(&[0; 10]),
+        Err(WriteError::Blocked)
+    );
+    pair.drive();
+
+    assert_eq!(pair.server_streams(server_ch).accept(Dir::Uni), Some(s));
+    let mut recv = pair.server_recv(server_ch, s);
+    let mut chunks = recv.read(false).unwrap();
+    let mut read = 0;
+    while let Ok(Some(chunk)) = chunks.next(usize::MAX) {
+        read += chunk.bytes.len();
+    }
+    assert_eq!(read, 2000);
+    let _ = chunks.finalize();
+    pair.drive();
+
+    assert!(!pair.client_send(client_ch, s).is_flow_control_blocked());
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Stream(StreamEvent::Writable { id })) if id == s
+    );
+}
+
+#[test]
+fn congestion_window_override() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    const WINDOW: u64 = 5000;
+    pair.client_conn_mut(client_ch)
+        .set_congestion_window_override(Some(WINDOW));
+    assert_eq!(pair.client_conn_mut(client_ch).stats().path.cwnd, WINDOW);
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&[42; 20_000]).unwrap();
+    pair.drive_client();
+    let in_flight = pair.client_conn_mut(client_ch).bytes_in_flight();
+    assert!(in_flight > 0 && in_flight <= WINDOW);
+    assert_eq!(
+        pair.client_conn_mut(client_ch).send_limit_reason(),
+        SendLimit::Congestion
+    );
+
+    pair.client_conn_mut(client_ch)
+        .set_congestion_window_override(None);
+    assert!(pair.client_conn_mut(client_ch).stats().path.cwnd > WINDOW);
+    pair.drive();
+    assert_eq!(pair.client_send(client_ch, s).buffered(), 0);
+}
+
+#[test]
+fn retransmitted_stream_data_stats() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    for _ in 0..6 {
+        pair.client_send(client_ch, s).write(&[42; 1000]).unwrap();
+        pair.drive_client();
+    }
+    let stats = pair.client_conn_mut(client_ch).stats().stream_data;
+    assert_eq!(stats.original_bytes, 6000);
+    assert_eq!(stats.retransmitted_bytes, 0);
+
+    // Only the last packet arrives, so all others are eventually declared lost and retransmitted
+    let last = pair.server.inbound.pop_back().unwrap();
+    pair.server.inbound.clear();
+    pair.server.inbound.push_back(last);
+    pair.drive();
+    let stats = pair.client_conn_mut(client_ch).stats().stream_data;
+    assert_eq!(stats.original_bytes, 6000);
+    assert_eq!(stats.retransmitted_bytes, 5000);
+    assert_eq!(pair.client_conn_mut(client_ch).original_bytes(), 6000);
+    assert_eq!(pair.client_conn_mut(client_ch).retransmitted_bytes(), 5000);
+}
+
+#[test]
+fn idle_timeout_policy() {
+    let _guard = subscribe();
+    let mut server_transport = TransportConfig::default();
+    server_transport
+        .max_idle_timeout(Some(VarInt(100).into()))
+        .idle_timeout_policy(|local, peer| {
+            assert_eq!(local, Some(Duration::from_millis(100)));
+            assert_eq!(peer, Some(Duration::from_millis(10_000)));
+            local.max(peer)
+        });
+    let server = ServerConfig {
+        transport: Arc::new(server_transport),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).max_idle_timeout(),
+        Some(Duration::from_millis(100))
+    );
+    assert_eq!(
+        pair.server_conn_mut(server_ch).max_idle_timeout(),
+        Some(Duration::from_secs(10))
+    );
+}
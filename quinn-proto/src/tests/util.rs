@@ -454,6 +454,8 @@ fn split_transmit(transmit: Transmit) -> Vec<Transmit> {
             contents,
             segment_size: None,
             src_ip: transmit.src_ip,
+            metadata: transmit.metadata.clone(),
+            priority: transmit.priority,
         });
 
         offset = end;
@@ -13,6 +13,43 @@ use crate::{
     RESET_TOKEN_SIZE,
 };
 
+/// Generates and validates the address validation tokens sent in Retry packets
+///
+/// By default, tokens are sealed with the [`ServerConfig`](crate::ServerConfig)'s token key, so
+/// they can only be validated by servers sharing that key. A custom implementation allows
+/// validation to happen elsewhere, e.g. in a load balancer distributing connections across a
+/// fleet of servers. See
+/// [`ServerConfig::retry_token_handler()`](crate::ServerConfig::retry_token_handler).
+pub trait RetryTokenHandler: Send + Sync {
+    /// Generate a token for the client at `remote`
+    ///
+    /// `retry_src_cid` is the connection ID chosen by the server for the Retry packet, which the
+    /// client will address its next Initial packet to, and `orig_dst_cid` is the destination
+    /// connection ID of the client's first Initial packet. Both `orig_dst_cid` and `issued` must be
+    /// recoverable by [`decode()`](Self::decode).
+    fn encode(
+        &self,
+        remote: &SocketAddr,
+        retry_src_cid: &ConnectionId,
+        orig_dst_cid: &ConnectionId,
+        issued: SystemTime,
+    ) -> Vec<u8>;
+
+    /// Validate a token presented by the client at `remote`
+    ///
+    /// `retry_src_cid` is the destination connection ID of the Initial packet carrying the token.
+    /// Returns the original destination connection ID and issue time passed to
+    /// [`encode()`](Self::encode), or `None` if the token is invalid. Tokens older than
+    /// [`ServerConfig::retry_token_lifetime()`](crate::ServerConfig::retry_token_lifetime) are
+    /// rejected regardless.
+    fn decode(
+        &self,
+        remote: &SocketAddr,
+        retry_src_cid: &ConnectionId,
+        token: &[u8],
+    ) -> Option<(ConnectionId, SystemTime)>;
+}
+
 pub struct RetryToken<'a> {
     /// The destination connection ID set in the very first packet from the client
     pub orig_dst_cid: ConnectionId,
@@ -46,6 +83,7 @@ impl<'a> RetryToken<'a> {
         aead_key.seal(&mut buf, additional_data).unwrap();
 
         let mut token = Vec::new();
+        token.put_u8(RETRY_TOKEN_TYPE);
         token.put_slice(self.random_bytes);
         token.put_slice(&buf);
         token
@@ -57,14 +95,16 @@ impl<'a> RetryToken<'a> {
         retry_src_cid: &ConnectionId,
         raw_token_bytes: &'a [u8],
     ) -> Result<Self, CryptoError> {
-        if raw_token_bytes.len() < Self::RANDOM_BYTES_LEN {
-            // Invalid length
+        if raw_token_bytes.len() < 1 + Self::RANDOM_BYTES_LEN
+            || raw_token_bytes[0] != RETRY_TOKEN_TYPE
+        {
+            // Invalid length or not a retry token
             return Err(CryptoError);
         }
 
-        let random_bytes = &raw_token_bytes[..Self::RANDOM_BYTES_LEN];
+        let random_bytes = &raw_token_bytes[1..1 + Self::RANDOM_BYTES_LEN];
         let aead_key = key.aead_from_hkdf(random_bytes);
-        let mut sealed_token = raw_token_bytes[Self::RANDOM_BYTES_LEN..].to_vec();
+        let mut sealed_token = raw_token_bytes[1 + Self::RANDOM_BYTES_LEN..].to_vec();
 
         let mut additional_data = [0u8; Self::MAX_ADDITIONAL_DATA_SIZE];
         let additional_data =
@@ -103,6 +143,84 @@ impl<'a> RetryToken<'a> {
     pub const RANDOM_BYTES_LEN: usize = 32;
 }
 
+/// An address validation token sent in a NEW_TOKEN frame, for use in a future connection
+pub struct NewToken<'a> {
+    /// The time at which this token was issued
+    pub issued: SystemTime,
+    /// Random bytes for deriving AEAD key
+    pub random_bytes: &'a [u8],
+}
+
+impl<'a> NewToken<'a> {
+    /// Seal the token for the client at `ip`
+    ///
+    /// Only the IP address is bound, as the client's port is likely to change before the token is
+    /// used.
+    pub fn encode(&self, key: &dyn HandshakeTokenKey, ip: IpAddr) -> Vec<u8> {
+        let aead_key = key.aead_from_hkdf(self.random_bytes);
+
+        let mut buf = Vec::new();
+        buf.write::<u64>(
+            self.issued
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or(0),
+        );
+        aead_key.seal(&mut buf, &Self::additional_data(ip)).unwrap();
+
+        let mut token = Vec::new();
+        token.put_u8(NEW_TOKEN_TYPE);
+        token.put_slice(self.random_bytes);
+        token.put_slice(&buf);
+        token
+    }
+
+    pub fn from_bytes(
+        key: &dyn HandshakeTokenKey,
+        ip: IpAddr,
+        raw_token_bytes: &'a [u8],
+    ) -> Result<Self, CryptoError> {
+        if !Self::is_new_token(raw_token_bytes)
+            || raw_token_bytes.len() < 1 + RetryToken::RANDOM_BYTES_LEN
+        {
+            return Err(CryptoError);
+        }
+
+        let random_bytes = &raw_token_bytes[1..1 + RetryToken::RANDOM_BYTES_LEN];
+        let aead_key = key.aead_from_hkdf(random_bytes);
+        let mut sealed_token = raw_token_bytes[1 + RetryToken::RANDOM_BYTES_LEN..].to_vec();
+        let data = aead_key.open(&mut sealed_token, &Self::additional_data(ip))?;
+
+        let mut reader = io::Cursor::new(data);
+        let issued = UNIX_EPOCH + Duration::new(reader.get::<u64>().map_err(|_| CryptoError)?, 0);
+
+        Ok(Self {
+            issued,
+            random_bytes,
+        })
+    }
+
+    /// Whether `raw_token_bytes` claims to have been issued in a NEW_TOKEN frame
+    ///
+    /// Such tokens may legitimately fail validation, e.g. if they were issued by another server or
+    /// the client's address changed, so they're never grounds for closing a connection.
+    pub fn is_new_token(raw_token_bytes: &[u8]) -> bool {
+        raw_token_bytes.first() == Some(&NEW_TOKEN_TYPE)
+    }
+
+    fn additional_data(ip: IpAddr) -> Vec<u8> {
+        match ip {
+            IpAddr::V4(x) => x.octets().to_vec(),
+            IpAddr::V6(x) => x.octets().to_vec(),
+        }
+    }
+}
+
+/// Leading byte of the tokens sealed with the token key for Retry packets
+const RETRY_TOKEN_TYPE: u8 = 0;
+/// Leading byte of the tokens sent in NEW_TOKEN frames
+const NEW_TOKEN_TYPE: u8 = 1;
+
 /// Stateless reset token
 ///
 /// Used for an endpoint to securely communicate that it has lost state for a connection.
@@ -232,4 +350,39 @@ mod test {
         // Assert: completely invalid retry token returns error
         assert!(RetryToken::from_bytes(&prk, &addr, &retry_src_cid, &invalid_token).is_err());
     }
+
+    #[cfg(feature = "ring")]
+    #[test]
+    fn new_token_sanity() {
+        use super::*;
+        use rand::RngCore;
+        use std::net::Ipv6Addr;
+
+        let rng = &mut rand::thread_rng();
+
+        let mut master_key = vec![0u8; 64];
+        rng.fill_bytes(&mut master_key);
+
+        let mut random_bytes = [0; 32];
+        rng.fill_bytes(&mut random_bytes);
+
+        let prk = ring::hkdf::Salt::new(ring::hkdf::HKDF_SHA256, &[]).extract(&master_key);
+
+        let ip = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        let token = NewToken {
+            issued: UNIX_EPOCH + Duration::new(42, 0),
+            random_bytes: &random_bytes,
+        };
+        let encoded = token.encode(&prk, ip);
+        assert!(NewToken::is_new_token(&encoded));
+
+        let decoded = NewToken::from_bytes(&prk, ip, &encoded).expect("token didn't validate");
+        assert_eq!(token.issued, decoded.issued);
+
+        // Bound to the client's IP address, and never mistaken for a retry token
+        assert!(NewToken::from_bytes(&prk, Ipv6Addr::UNSPECIFIED.into(), &encoded).is_err());
+        let addr = SocketAddr::new(ip, 0);
+        let cid = ConnectionId::new(&[]);
+        assert!(RetryToken::from_bytes(&prk, &addr, &cid, &encoded).is_err());
+    }
 }
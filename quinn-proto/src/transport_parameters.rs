@@ -125,14 +125,15 @@ impl TransportParameters {
         initial_src_cid: ConnectionId,
         server_config: Option<&ServerConfig>,
     ) -> Self {
+        let (bidi_local, bidi_remote, uni) = config.stream_receive_windows();
         TransportParameters {
             initial_src_cid: Some(initial_src_cid),
             initial_max_streams_bidi: config.max_concurrent_bidi_streams,
             initial_max_streams_uni: config.max_concurrent_uni_streams,
             initial_max_data: config.receive_window,
-            initial_max_stream_data_bidi_local: config.stream_receive_window,
-            initial_max_stream_data_bidi_remote: config.stream_receive_window,
-            initial_max_stream_data_uni: config.stream_receive_window,
+            initial_max_stream_data_bidi_local: bidi_local,
+            initial_max_stream_data_bidi_remote: bidi_remote,
+            initial_max_stream_data_uni: uni,
             max_udp_payload_size: endpoint_config.max_udp_payload_size,
             max_idle_timeout: config.max_idle_timeout.unwrap_or(VarInt(0)),
             disable_active_migration: server_config.map_or(false, |c| !c.migration),
@@ -146,6 +147,7 @@ impl TransportParameters {
                 .datagram_receive_buffer_size
                 .map(|x| (x.min(u16::max_value().into()) as u16).into()),
             grease_quic_bit: endpoint_config.grease_quic_bit,
+            max_ack_delay: VarInt(config.max_ack_delay.as_millis().min((1 << 14) - 1) as u64),
             ..Self::default()
         }
     }
@@ -444,6 +446,7 @@ fn decode_cid(len: usize, value: &mut Option<ConnectionId>, r: &mut impl Buf) ->
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::time::Duration;
 
     #[test]
     fn coding() {
@@ -484,4 +487,24 @@ mod test {
         high_limit.validate_resumption_from(&low_limit).unwrap();
         low_limit.validate_resumption_from(&high_limit).unwrap_err();
     }
+
+    #[test]
+    fn max_ack_delay_from_config() {
+        let params = |max_ack_delay| {
+            let mut config = TransportConfig::default();
+            config.max_ack_delay(max_ack_delay);
+            TransportParameters::new(
+                &config,
+                &EndpointConfig::default(),
+                &crate::RandomConnectionIdGenerator::new(8),
+                ConnectionId::new(&[]),
+                None,
+            )
+        };
+        assert_eq!(params(Duration::from_micros(5500)).max_ack_delay, VarInt(5));
+        assert_eq!(
+            params(Duration::from_secs(60)).max_ack_delay,
+            VarInt((1 << 14) - 1)
+        );
+    }
 }
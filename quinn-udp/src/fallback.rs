@@ -14,6 +14,8 @@ use super::{log_sendmsg_error, RecvMeta, UdpSockRef, UdpState, IO_ERROR_LOG_INTE
 #[derive(Debug)]
 pub struct UdpSocketState {
     last_send_error: Instant,
+    /// Whether the socket is connected to a single peer
+    connected: bool,
 }
 
 impl UdpSocketState {
@@ -21,9 +23,18 @@ impl UdpSocketState {
         let now = Instant::now();
         Self {
             last_send_error: now.checked_sub(2 * IO_ERROR_LOG_INTERVAL).unwrap_or(now),
+            connected: false,
         }
     }
 
+    /// Set whether the socket has been `connect`ed to a single peer
+    ///
+    /// Connected sockets must be sent to without an explicit destination on some platforms, so
+    /// the destination of each [`Transmit`] is ignored and must be the connected peer.
+    pub fn set_connected(&mut self, connected: bool) {
+        self.connected = connected;
+    }
+
     pub fn configure(socket: UdpSockRef<'_>) -> io::Result<()> {
         socket.0.set_nonblocking(true)
     }
@@ -36,10 +47,15 @@ impl UdpSocketState {
     ) -> Result<usize, io::Error> {
         let mut sent = 0;
         for transmit in transmits {
-            match socket.0.send_to(
-                &transmit.contents,
-                &socket2::SockAddr::from(transmit.destination),
-            ) {
+            let result = if self.connected {
+                socket.0.send(&transmit.contents)
+            } else {
+                socket.0.send_to(
+                    &transmit.contents,
+                    &socket2::SockAddr::from(transmit.destination),
+                )
+            };
+            match result {
                 Ok(_) => {
                     sent += 1;
                 }
@@ -101,8 +117,18 @@ impl Default for UdpSocketState {
 pub fn udp_state() -> super::UdpState {
     super::UdpState {
         max_gso_segments: std::sync::atomic::AtomicUsize::new(1),
-        gro_segments: 1,
+        gro_segments: std::sync::atomic::AtomicUsize::new(1),
     }
 }
 
+/// Enumerate the IP addresses assigned to the host's network interfaces
+///
+/// Not supported on this platform.
+pub fn local_interfaces() -> io::Result<Vec<std::net::IpAddr>> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "enumerating local interfaces is not supported on this platform",
+    ))
+}
+
 pub const BATCH_SIZE: usize = 1;
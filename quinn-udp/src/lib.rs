@@ -4,6 +4,7 @@ use std::os::unix::io::AsRawFd;
 #[cfg(windows)]
 use std::os::windows::io::AsRawSocket;
 use std::{
+    io,
     net::{IpAddr, Ipv6Addr, SocketAddr},
     sync::atomic::{AtomicUsize, Ordering},
     time::{Duration, Instant},
@@ -36,7 +37,7 @@ pub const BATCH_SIZE: usize = imp::BATCH_SIZE;
 #[derive(Debug)]
 pub struct UdpState {
     max_gso_segments: AtomicUsize,
-    gro_segments: usize,
+    gro_segments: AtomicUsize,
 }
 
 impl UdpState {
@@ -60,7 +61,19 @@ impl UdpState {
     /// Returns 1 if the platform doesn't support GRO.
     #[inline]
     pub fn gro_segments(&self) -> usize {
+        self.gro_segments.load(Ordering::Relaxed)
+    }
+
+    /// Probe the platform's capabilities again, replacing the current values
+    ///
+    /// Allows recovering if they change at runtime, e.g. due to a virtual machine migrating to
+    /// different network hardware. This also re-enables GSO if it was disabled after send errors.
+    pub fn refresh(&self) {
+        let fresh = imp::udp_state();
+        self.max_gso_segments
+            .store(fresh.max_gso_segments(), Ordering::Relaxed);
         self.gro_segments
+            .store(fresh.gro_segments(), Ordering::Relaxed);
     }
 }
 
@@ -72,9 +85,13 @@ impl Default for UdpState {
 
 #[derive(Debug, Copy, Clone)]
 pub struct RecvMeta {
+    /// The source address of the datagram
     pub addr: SocketAddr,
+    /// The number of bytes received into the buffer
     pub len: usize,
+    /// The size of each datagram in the buffer, less than `len` if several were coalesced by GRO
     pub stride: usize,
+    /// The explicit congestion notification bits of the datagram, if known
     pub ecn: Option<EcnCodepoint>,
     /// The destination IP address which was encoded in this datagram
     pub dst_ip: Option<IpAddr>,
@@ -93,6 +110,13 @@ impl Default for RecvMeta {
     }
 }
 
+/// Enumerate the IP addresses assigned to the host's network interfaces
+///
+/// Includes loopback and link-local addresses. Currently only supported on Unix.
+pub fn local_interfaces() -> io::Result<Vec<IpAddr>> {
+    imp::local_interfaces()
+}
+
 /// Log at most 1 IO error per minute
 const IO_ERROR_LOG_INTERVAL: Duration = std::time::Duration::from_secs(60);
 
@@ -121,6 +145,18 @@ fn log_sendmsg_error(
 // Wrapper around socket2 to avoid making it a public dependency and incurring stability risk
 pub struct UdpSockRef<'a>(socket2::SockRef<'a>);
 
+impl UdpSockRef<'_> {
+    /// Set the size of the socket's kernel send buffer (`SO_SNDBUF`)
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        self.0.set_send_buffer_size(size)
+    }
+
+    /// Set the size of the socket's kernel receive buffer (`SO_RCVBUF`)
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        self.0.set_recv_buffer_size(size)
+    }
+}
+
 #[cfg(unix)]
 impl<'s, S> From<&'s S> for UdpSockRef<'s>
 where
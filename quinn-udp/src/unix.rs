@@ -1,11 +1,10 @@
-#[cfg(not(any(target_os = "macos", target_os = "ios")))]
-use std::ptr;
 use std::{
     io,
     io::IoSliceMut,
     mem::{self, MaybeUninit},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     os::unix::io::AsRawFd,
+    ptr,
     sync::atomic::AtomicUsize,
     time::Instant,
 };
@@ -27,6 +26,8 @@ type IpTosTy = libc::c_int;
 #[derive(Debug)]
 pub struct UdpSocketState {
     last_send_error: Instant,
+    /// Whether the socket is connected to a single peer
+    connected: bool,
 }
 
 impl UdpSocketState {
@@ -34,9 +35,18 @@ impl UdpSocketState {
         let now = Instant::now();
         Self {
             last_send_error: now.checked_sub(2 * IO_ERROR_LOG_INTERVAL).unwrap_or(now),
+            connected: false,
         }
     }
 
+    /// Set whether the socket has been `connect`ed to a single peer
+    ///
+    /// Connected sockets must be sent to without an explicit destination on some platforms, so
+    /// the destination of each [`Transmit`] is ignored and must be the connected peer.
+    pub fn set_connected(&mut self, connected: bool) {
+        self.connected = connected;
+    }
+
     pub fn configure(sock: UdpSockRef<'_>) -> io::Result<()> {
         init(sock.0)
     }
@@ -47,7 +57,13 @@ impl UdpSocketState {
         state: &UdpState,
         transmits: &[Transmit],
     ) -> Result<usize, io::Error> {
-        send(state, socket.0, &mut self.last_send_error, transmits)
+        send(
+            state,
+            socket.0,
+            &mut self.last_send_error,
+            self.connected,
+            transmits,
+        )
     }
 
     pub fn recv(
@@ -71,6 +87,9 @@ fn init(io: SockRef<'_>) -> io::Result<()> {
     if cfg!(target_os = "linux") {
         cmsg_platform_space +=
             unsafe { libc::CMSG_SPACE(mem::size_of::<libc::in6_pktinfo>() as _) as usize };
+        // SO_PRIORITY
+        cmsg_platform_space +=
+            unsafe { libc::CMSG_SPACE(mem::size_of::<libc::c_int>() as _) as usize };
     }
 
     assert!(
@@ -198,6 +217,7 @@ fn send(
     state: &UdpState,
     io: SockRef<'_>,
     last_send_error: &mut Instant,
+    connected: bool,
     transmits: &[Transmit],
 ) -> io::Result<usize> {
     let mut msgs: [libc::mmsghdr; BATCH_SIZE] = unsafe { mem::zeroed() };
@@ -222,6 +242,7 @@ fn send(
         prepare_msg(
             transmit,
             dst_addr,
+            connected,
             &mut msgs[i].msg_hdr,
             &mut iovecs[i],
             &mut cmsgs[i],
@@ -281,6 +302,7 @@ fn send(
     _state: &UdpState,
     io: SockRef<'_>,
     last_send_error: &mut Instant,
+    connected: bool,
     transmits: &[Transmit],
 ) -> io::Result<usize> {
     let mut hdr: libc::msghdr = unsafe { mem::zeroed() };
@@ -289,7 +311,14 @@ fn send(
     let mut sent = 0;
     while sent < transmits.len() {
         let addr = socket2::SockAddr::from(transmits[sent].destination);
-        prepare_msg(&transmits[sent], &addr, &mut hdr, &mut iov, &mut ctrl);
+        prepare_msg(
+            &transmits[sent],
+            &addr,
+            connected,
+            &mut hdr,
+            &mut iov,
+            &mut ctrl,
+        );
         let n = unsafe { libc::sendmsg(io.as_raw_fd(), &hdr, 0) };
         if n == -1 {
             let e = io::Error::last_os_error();
@@ -384,15 +413,48 @@ fn recv(io: SockRef<'_>, bufs: &mut [IoSliceMut<'_>], meta: &mut [RecvMeta]) ->
 pub fn udp_state() -> UdpState {
     UdpState {
         max_gso_segments: AtomicUsize::new(gso::max_gso_segments()),
-        gro_segments: gro::gro_segments(),
+        gro_segments: AtomicUsize::new(gro::gro_segments()),
     }
 }
 
+/// Enumerate the IP addresses assigned to the host's network interfaces
+pub fn local_interfaces() -> io::Result<Vec<IpAddr>> {
+    let mut ifaddrs = ptr::null_mut::<libc::ifaddrs>();
+    if unsafe { libc::getifaddrs(&mut ifaddrs) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut addrs = Vec::new();
+    let mut cursor = ifaddrs;
+    while let Some(ifaddr) = unsafe { cursor.as_ref() } {
+        cursor = ifaddr.ifa_next;
+        let addr = match unsafe { ifaddr.ifa_addr.as_ref() } {
+            Some(addr) => addr,
+            None => continue,
+        };
+        match addr.sa_family as libc::c_int {
+            libc::AF_INET => {
+                let addr = unsafe { &*(ifaddr.ifa_addr as *const libc::sockaddr_in) };
+                addrs.push(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)).into());
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe { &*(ifaddr.ifa_addr as *const libc::sockaddr_in6) };
+                addrs.push(Ipv6Addr::from(addr.sin6_addr.s6_addr).into());
+            }
+            _ => {}
+        }
+    }
+
+    unsafe { libc::freeifaddrs(ifaddrs) };
+    Ok(addrs)
+}
+
 const CMSG_LEN: usize = 88;
 
 fn prepare_msg(
     transmit: &Transmit,
     dst_addr: &socket2::SockAddr,
+    connected: bool,
     hdr: &mut libc::msghdr,
     iov: &mut libc::iovec,
     ctrl: &mut cmsg::Aligned<[u8; CMSG_LEN]>,
@@ -405,10 +467,13 @@ fn prepare_msg(
     // as per the POSIX spec. See the section on the sys/socket.h
     // header for details. The type is only mutable in the first
     // place because it is reused by recvmsg as well.
-    let name = dst_addr.as_ptr() as *mut libc::c_void;
-    let namelen = dst_addr.len();
-    hdr.msg_name = name as *mut _;
-    hdr.msg_namelen = namelen;
+    // Connected sockets reject explicit destinations on some platforms
+    if !connected {
+        let name = dst_addr.as_ptr() as *mut libc::c_void;
+        let namelen = dst_addr.len();
+        hdr.msg_name = name as *mut _;
+        hdr.msg_namelen = namelen;
+    }
     hdr.msg_iov = iov;
     hdr.msg_iovlen = 1;
 
@@ -452,6 +517,12 @@ fn prepare_msg(
         }
     }
 
+    // Older kernels reject the datagram with EINVAL, so only request a priority if asked to
+    #[cfg(target_os = "linux")]
+    if let Some(priority) = transmit.priority {
+        encoder.push(libc::SOL_SOCKET, libc::SO_PRIORITY, priority as libc::c_int);
+    }
+
     encoder.finish();
 }
 
@@ -5,6 +5,7 @@ use std::{
 };
 
 use bencher::{benchmark_group, benchmark_main, Bencher};
+use bytes::Bytes;
 use tokio::runtime::{Builder, Runtime};
 use tracing::error_span;
 use tracing_futures::Instrument as _;
@@ -16,7 +17,8 @@ benchmark_group!(
     large_data_1_stream,
     large_data_10_streams,
     small_data_1_stream,
-    small_data_100_streams
+    small_data_100_streams,
+    small_datagrams
 );
 benchmark_main!(benches);
 
@@ -36,6 +38,35 @@ fn small_data_100_streams(bench: &mut Bencher) {
     send_data(bench, SMALL_DATA, 100);
 }
 
+/// Many tiny packets, exercising the overhead of each endpoint driver poll
+fn small_datagrams(bench: &mut Bencher) {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let ctx = Context::new();
+    let (addr, thread) = ctx.spawn_server();
+    let (endpoint, client, runtime) = ctx.make_client(addr);
+
+    bench.bytes = (SMALL_DATA.len() * DATAGRAMS) as u64;
+    bench.iter(|| {
+        runtime.block_on(async {
+            for _ in 0..DATAGRAMS {
+                client
+                    .send_datagram(Bytes::from_static(SMALL_DATA))
+                    .unwrap();
+                // Let the driver transmit each datagram in a packet of its own
+                tokio::task::yield_now().await;
+            }
+            // Wait for the burst to be delivered, so the connection can later be closed promptly
+            let mut stream = client.open_uni().await.unwrap();
+            stream.write_all(SMALL_DATA).await.unwrap();
+            stream.finish().await.unwrap();
+        });
+    });
+    drop(client);
+    runtime.block_on(endpoint.wait_idle());
+    thread.join().unwrap()
+}
+
 fn send_data(bench: &mut Bencher, data: &'static [u8], concurrent_streams: usize) {
     let _ = tracing_subscriber::fmt::try_init();
 
@@ -113,6 +144,10 @@ impl Context {
                         .await
                         .expect("connect");
 
+                    tokio::spawn({
+                        let connection = connection.clone();
+                        async move { while connection.read_datagram().await.is_ok() {} }
+                    });
                     while let Ok(mut stream) = connection.accept_uni().await {
                         tokio::spawn(async move {
                             while stream
@@ -160,3 +195,5 @@ fn rt() -> Runtime {
 const LARGE_DATA: &[u8] = &[0xAB; 1024 * 1024];
 
 const SMALL_DATA: &[u8] = &[0xAB; 1];
+
+const DATAGRAMS: usize = 1000;
@@ -1,10 +1,15 @@
 use std::{
     any::Any,
+    collections::VecDeque,
     fmt,
     future::Future,
+    mem,
     net::{IpAddr, SocketAddr},
     pin::Pin,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
     task::{Context, Poll, Waker},
     time::{Duration, Instant},
 };
@@ -12,20 +17,27 @@ use std::{
 use crate::runtime::{AsyncTimer, Runtime};
 use bytes::Bytes;
 use pin_project_lite::pin_project;
-use proto::{ConnectionError, ConnectionHandle, ConnectionStats, Dir, StreamEvent, StreamId};
+use proto::{
+    CloseState, ConnectionError, ConnectionHandle, ConnectionId, ConnectionStats,
+    DatagramDropPolicy, Dir, EcnState, EncryptionLevel, FlowControlStats, OversizedDatagramPolicy,
+    SendLimit, Side, StreamEvent, StreamId, StreamLimits, TransportError,
+};
 use rustc_hash::FxHashMap;
 use thiserror::Error;
 use tokio::sync::{futures::Notified, mpsc, oneshot, Notify};
-use tracing::debug_span;
+use tracing::{debug, debug_span, Dispatch, Level};
 use udp::UdpState;
 
 use crate::{
+    datagram_budget::DatagramBudget,
+    endpoint::{HandshakeLimiter, HandshakePermit, TimerCounters},
     mutex::Mutex,
     recv_stream::RecvStream,
     send_stream::{SendStream, WriteError},
+    trace_level::LevelOverride,
     ConnectionEvent, EndpointEvent, VarInt,
 };
-use proto::congestion::Controller;
+use proto::congestion::{CongestionPhase, Controller};
 
 /// In-progress connection attempt future
 #[derive(Debug)]
@@ -37,6 +49,7 @@ pub struct Connecting {
 }
 
 impl Connecting {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         handle: ConnectionHandle,
         conn: proto::Connection,
@@ -44,6 +57,9 @@ impl Connecting {
         conn_events: mpsc::UnboundedReceiver<ConnectionEvent>,
         udp_state: Arc<UdpState>,
         runtime: Arc<dyn Runtime>,
+        handshake_limiter: Option<Arc<HandshakeLimiter>>,
+        timer_counters: Arc<TimerCounters>,
+        datagram_budget: Arc<DatagramBudget>,
     ) -> Connecting {
         let (on_handshake_data_send, on_handshake_data_recv) = oneshot::channel();
         let (on_connected_send, on_connected_recv) = oneshot::channel();
@@ -56,9 +72,26 @@ impl Connecting {
             on_connected_send,
             udp_state,
             runtime.clone(),
+            timer_counters,
+            datagram_budget,
         );
 
-        runtime.spawn(Box::pin(ConnectionDriver(conn.clone())));
+        let driver = ConnectionDriver(conn.clone());
+        match handshake_limiter {
+            Some(limiter) => runtime.spawn(Box::pin(async move {
+                // Don't begin the handshake until we're allowed to
+                {
+                    let permit = limiter.acquire().await;
+                    let mut state = driver.0.state.lock("handshake_permit");
+                    // A connection which was closed or abandoned while waiting gives its turn up
+                    if state.error.is_none() {
+                        state.handshake_permit = Some(permit);
+                    }
+                }
+                driver.await
+            })),
+            None => runtime.spawn(Box::pin(driver)),
+        }
 
         Connecting {
             conn: Some(conn),
@@ -83,7 +116,8 @@ impl Connecting {
     ///
     /// On incoming connections, this enables transmission of 0.5-RTT data, which might be
     /// intercepted by a man-in-the-middle. If this occurs, the handshake will not complete
-    /// successfully.
+    /// successfully. The client has not been authenticated yet, so 0.5-RTT data must not depend on
+    /// its identity. See [`ServerConfig::half_rtt()`](crate::ServerConfig::half_rtt).
     ///
     /// # Errors
     ///
@@ -91,13 +125,17 @@ impl Connecting {
     /// a previous connection to the same server is available, and includes a 0-RTT key. If no such
     /// ticket is found, `self` is returned unmodified.
     ///
-    /// For incoming connections, a 0.5-RTT connection will always be successfully constructed.
+    /// For incoming connections, a 0.5-RTT connection will always be successfully constructed
+    /// unless 0.5-RTT data has been disabled in the `ServerConfig`.
     pub fn into_0rtt(mut self) -> Result<(Connection, ZeroRttAccepted), Self> {
         // This lock borrows `self` and would normally be dropped at the end of this scope, so we'll
         // have to release it explicitly before returning `self` by value.
         let conn = (self.conn.as_mut().unwrap()).state.lock("into_0rtt");
 
-        let is_ok = conn.inner.has_0rtt() || conn.inner.side().is_server();
+        let is_ok = match conn.inner.side() {
+            Side::Client => conn.inner.has_0rtt(),
+            Side::Server => conn.inner.allows_half_rtt(),
+        };
         drop(conn);
 
         if is_ok {
@@ -184,6 +222,108 @@ impl Connecting {
         let conn_ref: &ConnectionRef = self.conn.as_ref().expect("used after yielding Ready");
         conn_ref.state.lock("remote_address").inner.remote_address()
     }
+
+    /// A reference to the connection that doesn't keep it alive
+    pub(crate) fn downgrade(&self) -> WeakConnection {
+        let conn_ref: &ConnectionRef = self.conn.as_ref().expect("used after yielding Ready");
+        WeakConnection(Arc::downgrade(&conn_ref.0))
+    }
+
+    /// Fail the handshake with `ConnectionError::TimedOut` if it hasn't completed by `deadline`
+    pub(crate) fn set_deadline(&self, deadline: Instant) {
+        let conn_ref: &ConnectionRef = self.conn.as_ref().expect("used after yielding Ready");
+        let mut state = conn_ref.state.lock("set_deadline");
+        state.handshake_deadline = Some(state.runtime.new_timer(deadline));
+        state.wake();
+    }
+
+    /// Wait for the handshake to complete, returning the connection along with the parameters
+    /// negotiated for it
+    ///
+    /// Equivalent to awaiting `self` and then querying the [`Connection`], but without the risk of
+    /// the details being unavailable by the time they're queried.
+    pub async fn handshake(self) -> Result<(Connection, HandshakeInfo), ConnectionError> {
+        let conn = self.await?;
+        let info = {
+            let state = conn.0.state.lock("handshake");
+            let session = state.inner.crypto_session();
+            HandshakeInfo {
+                version: state.inner.version(),
+                rtt: state.inner.rtt(),
+                handshake_data: session.handshake_data(),
+                peer_identity: session.peer_identity(),
+            }
+        };
+        Ok((conn, info))
+    }
+
+    /// Wait for the handshake to be confirmed, returning the connection
+    ///
+    /// Awaiting `self` resolves once the handshake is complete, at which point a client may already
+    /// send 1-RTT data even though the server hasn't yet acknowledged that the handshake succeeded.
+    /// A handshake is confirmed once both peers are guaranteed to have 1-RTT keys: immediately
+    /// upon completion for servers, and upon receipt of the server's HANDSHAKE_DONE frame for
+    /// clients, one round trip later.
+    ///
+    /// # Security
+    ///
+    /// Unlike 0-RTT data (see [`into_0rtt()`](Self::into_0rtt)), data sent after the handshake
+    /// completes can't be replayed. However, a client can't yet be sure that the server accepted
+    /// its half of the handshake, e.g. its certificate, so the server may still reject the
+    /// connection. Waiting for confirmation ensures that sensitive data is only sent once both
+    /// peers have committed to the connection. See [`Connection::handshake_confirmed()`] to check
+    /// the state of an established connection.
+    pub async fn confirmed(self) -> Result<Connection, ConnectionError> {
+        let conn = self.await?;
+        loop {
+            {
+                let state = conn.0.state.lock("confirmed");
+                if let Some(error) = state.error.as_ref() {
+                    return Err(error.clone());
+                }
+                if state.inner.is_handshake_confirmed() {
+                    break;
+                }
+                // Construct the future while the lock is held to ensure we can't miss a wakeup
+                conn.0.shared.handshake_confirmed.notified()
+            }
+            .await;
+        }
+        Ok(conn)
+    }
+
+    /// The handle identifying this connection within its endpoint
+    ///
+    /// Matches [`Connection::handle()`] once the handshake completes, so endpoint-level events and
+    /// logs can be correlated with connections that are still being established.
+    ///
+    /// Will panic if called after `poll` has returned `Ready`.
+    pub fn handle(&self) -> ConnectionHandle {
+        let conn_ref: &ConnectionRef = self.conn.as_ref().expect("used after yielding Ready");
+        conn_ref.state.lock("handle").handle
+    }
+}
+
+/// Parameters negotiated during a connection's handshake
+///
+/// See [`Connecting::handshake()`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct HandshakeInfo {
+    /// The QUIC version in use, as reported by [`Connection::version()`]
+    pub version: u32,
+    /// The round-trip time measured during the handshake
+    pub rtt: Duration,
+    /// The negotiated cryptographic parameters, as reported by [`Connection::handshake_data()`]
+    ///
+    /// For the default `rustls` session, this is always `Some` and can be
+    /// [`downcast`](Box::downcast) to a
+    /// [`crypto::rustls::HandshakeData`](crate::crypto::rustls::HandshakeData), which describes
+    /// the application protocol, key exchange group and cipher suite. Custom
+    /// [`crypto::Session`](crate::crypto::Session)s may not provide any.
+    pub handshake_data: Option<Box<dyn Any>>,
+    /// The cryptographic identity of the peer, as reported by [`Connection::peer_identity()`]
+    pub peer_identity: Option<Box<dyn Any>>,
 }
 
 /// Future that completes when a connection is fully established
@@ -200,6 +340,117 @@ impl Future for ZeroRttAccepted {
     }
 }
 
+/// Counts of the reasons a connection's driver ran
+///
+/// A single run may have several reasons, in which case each is counted. Runs which had none of
+/// these reasons, e.g. due to events from the endpoint, aren't counted.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(proto::serde::Serialize),
+    serde(crate = "proto::serde")
+)]
+#[non_exhaustive]
+pub struct WakeStats {
+    /// The amount of runs which handled received packets
+    pub packets: u64,
+    /// The amount of runs in which a timer expired
+    pub timers: u64,
+    /// The amount of runs prompted by the application, e.g. by writing to a stream or closing
+    /// the connection
+    pub application: u64,
+    /// The reason of the most recent counted run
+    ///
+    /// When a run had several reasons, received packets take precedence over timers, which take
+    /// precedence over the application.
+    pub last: Option<WakeReason>,
+}
+
+impl WakeStats {
+    fn record(&mut self, packets: bool, timer: bool, application: bool) {
+        self.packets += packets as u64;
+        self.timers += timer as u64;
+        self.application += application as u64;
+        if application {
+            self.last = Some(WakeReason::Application);
+        }
+        if timer {
+            self.last = Some(WakeReason::Timer);
+        }
+        if packets {
+            self.last = Some(WakeReason::Packet);
+        }
+    }
+}
+
+/// Why a connection's driver ran
+///
+/// See [`WakeStats`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(proto::serde::Serialize),
+    serde(crate = "proto::serde")
+)]
+pub enum WakeReason {
+    /// Packets were received from the peer
+    Packet,
+    /// A timer, e.g. for loss detection or the idle timeout, expired
+    Timer,
+    /// The application interacted with the connection or its streams
+    Application,
+}
+
+/// Limits on the amount of data a connection may transfer
+///
+/// Amounts are the total size of UDP datagrams, as reported by [`Connection::bytes_sent()`] and
+/// [`Connection::bytes_received()`]. `None` means unlimited. See [`Connection::set_byte_quota()`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct ByteQuota {
+    /// Limit on the amount of data sent and received combined
+    pub total: Option<u64>,
+    /// Limit on the amount of data sent
+    pub send: Option<u64>,
+    /// Limit on the amount of data received
+    pub recv: Option<u64>,
+    /// The error code the connection is closed with once a limit is reached
+    pub error_code: VarInt,
+}
+
+impl ByteQuota {
+    /// The amounts left of each limit, given the amounts transferred so far
+    fn remaining(&self, sent: u64, received: u64) -> Self {
+        Self {
+            total: self
+                .total
+                .map(|x| x.saturating_sub(sent.saturating_add(received))),
+            send: self.send.map(|x| x.saturating_sub(sent)),
+            recv: self.recv.map(|x| x.saturating_sub(received)),
+            error_code: self.error_code,
+        }
+    }
+
+    /// Whether any limit has been used up
+    fn is_exhausted(&self) -> bool {
+        [self.total, self.send, self.recv].contains(&Some(0))
+    }
+}
+
+/// A snapshot of a connection's congestion control state
+///
+/// See [`Connection::set_cwnd_sampling()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CwndSample {
+    /// When the sample was taken
+    pub time: Instant,
+    /// The congestion window, in bytes
+    pub cwnd: u64,
+    /// The smoothed round-trip time estimate
+    pub rtt: Duration,
+    /// The number of bytes of unacknowledged ack-eliciting packets
+    pub bytes_in_flight: u64,
+}
+
 /// A future that drives protocol logic for a connection
 ///
 /// This future handles the protocol logic for a single connection, routing events from the
@@ -221,19 +472,45 @@ impl Future for ConnectionDriver {
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         let conn = &mut *self.0.state.lock("poll");
 
+        let _dispatch = conn
+            .trace_dispatch
+            .as_ref()
+            .map(tracing::dispatcher::set_default);
         let span = debug_span!("drive", id = conn.handle.0);
         let _guard = span.enter();
 
-        if let Err(e) = conn.process_conn_events(&self.0.shared, cx) {
-            conn.terminate(e, &self.0.shared);
-            return Poll::Ready(());
-        }
+        // Any data awaiting coalescing is transmitted below
+        conn.flush_timer = None;
+
+        let packets = match conn.process_conn_events(&self.0.shared, cx) {
+            Ok(packets) => packets,
+            Err(e) => {
+                conn.terminate(e, &self.0.shared);
+                conn.driver_finished(&self.0.shared);
+                return Poll::Ready(());
+            }
+        };
+        let application = mem::take(&mut conn.woken_by_app);
+        conn.drive_handshake_deadline(&self.0.shared, cx);
         let mut keep_going = conn.drive_transmit();
+        if !keep_going {
+            // Everything the connection could send has been handed to the endpoint
+            self.0.shared.transmitted.notify_waiters();
+        }
         // If a timer expires, there might be more to transmit. When we transmit something, we
         // might need to reset a timer. Hence, we must loop until neither happens.
-        keep_going |= conn.drive_timer(cx);
-        conn.forward_endpoint_events();
+        let timer = conn.drive_timer(cx);
+        keep_going |= timer;
+        conn.wakes.record(packets, timer, application);
+        // Report how the connection or its handshake ended to the endpoint before it learns that
+        // the connection drained, after which such reports are discarded
         conn.forward_app_events(&self.0.shared);
+        conn.forward_endpoint_events();
+        conn.sync_datagram_budget(Some(cx.waker()));
+        conn.update_byte_counts(&self.0.shared);
+        conn.update_datagram_size(&self.0.shared);
+        keep_going |= conn.enforce_byte_quota(&self.0.shared);
+        conn.sample_cwnd();
 
         if !conn.inner.is_drained() {
             if keep_going {
@@ -247,6 +524,7 @@ impl Future for ConnectionDriver {
         if conn.error.is_none() {
             unreachable!("drained connections always have an error");
         }
+        conn.driver_finished(&self.0.shared);
         Poll::Ready(())
     }
 }
@@ -308,13 +586,63 @@ impl Connection {
     /// Receive an application datagram
     pub fn read_datagram(&self) -> ReadDatagram<'_> {
         ReadDatagram {
-            conn: self.0.clone(),
+            conn: &self.0,
             notify: self.0.shared.datagrams.notified(),
         }
     }
 
+    /// Hold back newly written data for up to `timeout` so that it can share packets
+    ///
+    /// Like disabling [`SendStream::set_nodelay`] on every stream at once, but also applying to
+    /// application datagrams and coalescing writes across streams: after a write, the connection
+    /// waits up to `timeout` for further writes before transmitting, so that many tiny writes
+    /// produce fewer, fuller packets at the cost of up to `timeout` of added latency. Retransmits,
+    /// acknowledgements, and other timer-driven transmissions are never delayed, nor are finishing
+    /// or resetting streams. `None`, the default, restores per-stream behavior and transmits any
+    /// data that is currently being held back.
+    ///
+    /// Use [`flush()`](Self::flush) to transmit held back data early.
+    pub fn set_coalesce_timeout(&self, timeout: Option<Duration>) {
+        let mut conn = self.0.state.lock("set_coalesce_timeout");
+        conn.coalesce_timeout = timeout;
+        if timeout.is_none() && conn.flush_timer.take().is_some() {
+            conn.wake();
+        }
+    }
+
+    /// Transmit pending data immediately
+    ///
+    /// Data written to streams with [`SendStream::set_nodelay`] disabled, and frames queued by
+    /// other operations, are normally sent whenever the connection's driver next runs. This wakes
+    /// the driver right away and resolves once it has handed everything that congestion and flow
+    /// control permit to the endpoint for transmission, which can reduce latency for small
+    /// request/response exchanges.
+    ///
+    /// Fails if the connection is closed before or while flushing.
+    pub async fn flush(&self) -> Result<(), ConnectionError> {
+        {
+            let mut conn = self.0.state.lock("flush");
+            if let Some(error) = conn.error.as_ref() {
+                return Err(error.clone());
+            }
+            // Transmission is imminent, so there's no need to wait out the coalescing delay
+            conn.flush_timer = None;
+            conn.wake();
+            // Construct the future while the lock is held to ensure we can't miss a wakeup
+            self.0.shared.transmitted.notified()
+        }
+        .await;
+        match self.0.state.lock("flush").error.as_ref() {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+
     /// Wait for the connection to be closed for any reason
     ///
+    /// Resolves as soon as the connection begins closing or draining, so new work should no longer
+    /// be routed to it. See [`drained()`](Self::drained) to wait until it's completely gone.
+    ///
     /// Despite the return type's name, closed connections are often not an error condition at the
     /// application layer. Cases that might be routine include [`ConnectionError::LocallyClosed`]
     /// and [`ConnectionError::ApplicationClosed`].
@@ -339,6 +667,24 @@ impl Connection {
             .clone()
     }
 
+    /// Wait for the connection to finish draining
+    ///
+    /// After [`closed()`](Self::closed) resolves, a connection lingers in a closing or draining
+    /// state for a few round trips so that the peer can learn of the close and any packets still
+    /// in flight can be discarded. Resolves once that period has elapsed and the endpoint has
+    /// forgotten the connection, or immediately if the endpoint has been dropped.
+    pub async fn drained(&self) {
+        {
+            let conn = self.0.state.lock("drained");
+            if conn.drained {
+                return;
+            }
+            // Construct the future while the lock is held to ensure we can't miss a wakeup
+            self.0.shared.drained.notified()
+        }
+        .await;
+    }
+
     /// If the connection is closed, the reason why.
     ///
     /// Returns `None` if the connection is still open.
@@ -346,6 +692,36 @@ impl Connection {
         self.0.state.lock("close_reason").error.clone()
     }
 
+    /// The most advanced keys available to the connection, and when it obtained them
+    ///
+    /// A connection which stays at [`EncryptionLevel::Initial`] or
+    /// [`EncryptionLevel::Handshake`] for long points at a specific handshake problem, e.g. a lost
+    /// or rejected server flight, rather than general latency.
+    pub fn encryption_level(&self) -> (EncryptionLevel, Instant) {
+        let state = self.0.state.lock("encryption_level");
+        (
+            state.inner.encryption_level(),
+            state.inner.encryption_level_since(),
+        )
+    }
+
+    /// How far the connection's teardown has progressed
+    ///
+    /// Unlike [`close_reason()`](Self::close_reason), distinguishes waiting for the peer to
+    /// acknowledge a close from draining after the peer's close, and from having fully finished.
+    pub fn close_state(&self) -> CloseState {
+        self.0.state.lock("close_state").inner.close_state()
+    }
+
+    /// Whether the peer initiated the connection's teardown by sending CONNECTION_CLOSE
+    ///
+    /// If so, closing the connection locally has no effect, since no CONNECTION_CLOSE frames are
+    /// sent while draining. `false` if the connection was closed locally first, or lost for
+    /// another reason, such as a timeout.
+    pub fn peer_closing(&self) -> bool {
+        self.0.state.lock("peer_closing").inner.peer_closing()
+    }
+
     /// Close the connection immediately.
     ///
     /// Pending operations will fail immediately with [`ConnectionError::LocallyClosed`]. Delivery
@@ -353,7 +729,9 @@ impl Connection {
     /// when all important communications have been completed, e.g. by calling [`finish`] on
     /// outstanding [`SendStream`]s and waiting for the resulting futures to complete.
     ///
-    /// `error_code` and `reason` are not interpreted, and are provided directly to the peer.
+    /// `error_code` and `reason` are not interpreted, and are provided directly to the peer. An
+    /// [`AppErrorCode`](crate::AppErrorCode) may be converted into `error_code` to guard against
+    /// using codes reserved by the application protocol.
     ///
     /// `reason` will be truncated to fit in a single packet with overhead; to improve odds that it
     /// is preserved in full, it should be kept under 1KiB.
@@ -371,25 +749,54 @@ impl Connection {
     /// Application datagrams are a low-level primitive. They may be lost or delivered out of order,
     /// and `data` must both fit inside a single QUIC packet and be smaller than the maximum
     /// dictated by the peer.
+    ///
+    /// Datagrams larger than [`max_datagram_size()`](Self::max_datagram_size) are handled
+    /// according to the connection's [`OversizedDatagramPolicy`].
     pub fn send_datagram(&self, data: Bytes) -> Result<(), SendDatagramError> {
-        let conn = &mut *self.0.state.lock("send_datagram");
-        if let Some(ref x) = conn.error {
-            return Err(SendDatagramError::ConnectionLost(x.clone()));
-        }
-        use proto::SendDatagramError::*;
-        match conn.inner.datagrams().send(data) {
-            Ok(()) => {
-                conn.wake();
-                Ok(())
+        self.0.state.lock("send_datagram").send_datagram(data)
+    }
+
+    /// Transmit `data` as an unreliable, unordered application datagram once it fits
+    ///
+    /// Unlike [`send_datagram()`](Self::send_datagram), this waits until
+    /// [`max_datagram_size()`](Self::max_datagram_size) is at least `data.len()`, e.g. until the
+    /// handshake completes, rather than applying the [`OversizedDatagramPolicy`]. The maximum size
+    /// is bounded by the limit advertised by the peer as well as by the path MTU, so applications
+    /// which might exceed the former should wrap this in a timeout.
+    pub async fn send_datagram_wait(&self, data: Bytes) -> Result<(), SendDatagramError> {
+        loop {
+            {
+                let mut conn = self.0.state.lock("send_datagram_wait");
+                let fits = match conn.inner.datagrams().max_size() {
+                    Some(max) => data.len() <= max,
+                    // Datagram support is unknown until the peer's transport parameters arrive
+                    None => conn.connected,
+                };
+                if fits || conn.error.is_some() {
+                    return conn.send_datagram(data);
+                }
+                // Construct the future while the lock is held to ensure we can't miss a wakeup
+                self.0.shared.datagram_size.notified()
             }
-            Err(e) => Err(match e {
-                UnsupportedByPeer => SendDatagramError::UnsupportedByPeer,
-                Disabled => SendDatagramError::Disabled,
-                TooLarge => SendDatagramError::TooLarge,
-            }),
+            .await;
         }
     }
 
+    /// Choose how datagrams larger than [`max_datagram_size()`](Self::max_datagram_size) are
+    /// handled by [`send_datagram()`](Self::send_datagram)
+    ///
+    /// Overrides [`TransportConfig::oversized_datagram_policy()`] for this connection.
+    ///
+    /// [`TransportConfig::oversized_datagram_policy()`]: crate::TransportConfig::oversized_datagram_policy
+    pub fn set_oversized_datagram_policy(&self, policy: OversizedDatagramPolicy) {
+        self.0
+            .state
+            .lock("set_oversized_datagram_policy")
+            .inner
+            .datagrams()
+            .set_oversized_policy(policy);
+    }
+
     /// Compute the maximum size of datagrams that may be passed to [`send_datagram()`].
     ///
     /// Returns `None` if datagrams are unsupported by the peer or disabled locally.
@@ -410,6 +817,43 @@ impl Connection {
             .max_size()
     }
 
+    /// Whether application datagrams can be sent on this connection
+    ///
+    /// False if datagrams are disabled locally, or if the peer did not advertise support for them
+    /// during the handshake, in which case applications may fall back to streams. Equivalent to
+    /// [`max_datagram_size()`](Self::max_datagram_size) returning `Some`.
+    pub fn datagrams_enabled(&self) -> bool {
+        self.max_datagram_size().is_some()
+    }
+
+    /// Whether datagrams may be sent to the peer
+    ///
+    /// Datagram support is negotiated separately for each direction, so this may differ from
+    /// [`can_receive_datagrams()`](Self::can_receive_datagrams). Requires the peer to have
+    /// advertised support, and datagrams not to be disabled locally.
+    pub fn can_send_datagrams(&self) -> bool {
+        self.0
+            .state
+            .lock("can_send_datagrams")
+            .inner
+            .datagrams()
+            .can_send()
+    }
+
+    /// Whether the peer may send datagrams to us
+    ///
+    /// True if datagrams are enabled locally through
+    /// [`TransportConfig::datagram_receive_buffer_size()`](crate::TransportConfig::datagram_receive_buffer_size),
+    /// regardless of whether the peer supports them.
+    pub fn can_receive_datagrams(&self) -> bool {
+        self.0
+            .state
+            .lock("can_receive_datagrams")
+            .inner
+            .datagrams()
+            .can_receive()
+    }
+
     /// Bytes available in the outgoing datagram buffer
     ///
     /// When greater than zero, calling [`send_datagram()`](Self::send_datagram) with a datagram of
@@ -423,6 +867,42 @@ impl Connection {
             .send_buffer_space()
     }
 
+    /// Number of received datagrams waiting to be read with [`read_datagram()`]
+    ///
+    /// A consistently growing value indicates that the application is not keeping up with the
+    /// peer. Datagrams dropped because the receive buffer overflowed are counted in
+    /// [`ConnectionStats`]' `datagrams.recv_dropped`.
+    ///
+    /// [`read_datagram()`]: Connection::read_datagram
+    pub fn datagram_queue_len(&self) -> usize {
+        self.0
+            .state
+            .lock("datagram_queue_len")
+            .inner
+            .datagrams()
+            .recv_queue_len()
+    }
+
+    /// Limit the number of received datagrams buffered until read with
+    /// [`read_datagram()`](Self::read_datagram), and choose which to drop when the limit is hit
+    ///
+    /// For example, a real-time application which only cares about the most recent datagram can
+    /// use a capacity of 1 with [`DatagramDropPolicy::DropOldest`]. Overrides
+    /// [`TransportConfig::datagram_receive_buffer_capacity()`] and
+    /// [`TransportConfig::datagram_drop_policy()`] for this connection. Dropped datagrams are
+    /// counted in [`ConnectionStats`]' `datagrams.recv_dropped`.
+    ///
+    /// [`TransportConfig::datagram_receive_buffer_capacity()`]: crate::TransportConfig::datagram_receive_buffer_capacity
+    /// [`TransportConfig::datagram_drop_policy()`]: crate::TransportConfig::datagram_drop_policy
+    pub fn set_datagram_buffer(&self, capacity: Option<usize>, policy: DatagramDropPolicy) {
+        self.0
+            .state
+            .lock("set_datagram_buffer")
+            .inner
+            .datagrams()
+            .set_recv_buffer(capacity, policy);
+    }
+
     /// The peer's UDP address
     ///
     /// If `ServerConfig::migration` is `true`, clients may change addresses at will, e.g. when
@@ -431,6 +911,29 @@ impl Connection {
         self.0.state.lock("remote_address").inner.remote_address()
     }
 
+    /// Number of network paths currently maintained for this connection
+    ///
+    /// This is 1, plus 1 while the peer's previous address is retained during validation of a new
+    /// one. Bounded by [`TransportConfig::max_concurrent_paths()`].
+    ///
+    /// [`TransportConfig::max_concurrent_paths()`]: crate::TransportConfig::max_concurrent_paths
+    pub fn active_paths(&self) -> usize {
+        self.0.state.lock("active_paths").inner.active_paths()
+    }
+
+    /// Connection IDs issued by the local endpoint which the peer hasn't retired yet, oldest first
+    ///
+    /// These are the IDs the peer may currently address packets for this connection to. Useful to
+    /// verify that IDs are rotated, e.g. after a network change, and that old ones get retired.
+    /// Empty if the endpoint uses zero-length connection IDs.
+    pub fn active_connection_ids(&self) -> Vec<ConnectionId> {
+        self.0
+            .state
+            .lock("active_connection_ids")
+            .inner
+            .active_connection_ids()
+    }
+
     /// The local IP address which was used when the peer established
     /// the connection
     ///
@@ -449,16 +952,378 @@ impl Connection {
         self.0.state.lock("local_ip").inner.local_ip()
     }
 
+    /// The QUIC version negotiated for this connection
+    ///
+    /// Useful for tracking the adoption of newer versions and for diagnosing version-specific
+    /// interoperability issues. See [`proto::EndpointConfig::supported_versions()`].
+    pub fn version(&self) -> u32 {
+        self.0.state.lock("version").inner.version()
+    }
+
+    /// Whether the handshake is still in progress
+    ///
+    /// Only connections obtained through [`Connecting::into_0rtt()`] can be observed in this state.
+    pub fn is_handshaking(&self) -> bool {
+        self.0.state.lock("is_handshaking").inner.is_handshaking()
+    }
+
+    /// Whether the handshake resumed a previous session rather than being a full handshake
+    ///
+    /// Only resumed connections can use 0-RTT. Combined with the outcome reported by
+    /// [`ZeroRttAccepted`], this distinguishes fresh handshakes, resumptions whose 0-RTT data was
+    /// accepted, and resumptions which didn't use 0-RTT or had it rejected. Meaningless while the
+    /// handshake is in progress.
+    pub fn was_resumed(&self) -> bool {
+        self.0.state.lock("was_resumed").inner.was_resumed()
+    }
+
+    /// Whether the handshake has been confirmed by both peers
+    ///
+    /// Data sent after confirmation cannot be replayed, unlike 0-RTT data. For clients, this may
+    /// become `true` slightly after the connection is established, once the server's
+    /// HANDSHAKE_DONE frame arrives.
+    pub fn handshake_confirmed(&self) -> bool {
+        self.0
+            .state
+            .lock("handshake_confirmed")
+            .inner
+            .is_handshake_confirmed()
+    }
+
+    /// When a packet was most recently sent or received on this connection
+    ///
+    /// Useful for evicting the least recently used connections from a pool. Packets sent for
+    /// protocol maintenance, such as acknowledgements and keep-alives, count as activity too.
+    pub fn last_activity(&self) -> Instant {
+        self.0.state.lock("last_activity").last_activity
+    }
+
+    /// How long ago the handshake completed
+    ///
+    /// Zero while the handshake is still in progress, as may be the case for connections using
+    /// 0-RTT or 0.5-RTT. Keeps counting after the connection is closed.
+    pub fn duration(&self) -> Duration {
+        self.0
+            .state
+            .lock("duration")
+            .established
+            .map_or(Duration::ZERO, |t| t.elapsed())
+    }
+
+    /// When a packet was most recently received from the peer
+    ///
+    /// Unlike [`last_activity()`](Self::last_activity), packets sent by this endpoint don't count,
+    /// so the time since is a direct measure of how long the peer has been silent. Every
+    /// authenticated packet counts, including those carrying only acknowledgements or keep-alives.
+    /// Before any packet has been received, this is when the connection was created.
+    pub fn last_packet_received(&self) -> Instant {
+        self.0
+            .state
+            .lock("last_packet_received")
+            .inner
+            .last_packet_received()
+    }
+
+    /// The number of bytes of packets containing retransmittable frames that have not been
+    /// acknowledged or declared lost
+    pub fn bytes_in_flight(&self) -> u64 {
+        self.0.state.lock("bytes_in_flight").inner.bytes_in_flight()
+    }
+
+    /// The number of packets counted towards [`bytes_in_flight()`](Self::bytes_in_flight)
+    pub fn packets_in_flight(&self) -> u64 {
+        self.0
+            .state
+            .lock("packets_in_flight")
+            .inner
+            .packets_in_flight()
+    }
+
+    /// The number of packets declared lost whose contents have not yet been sent again
+    ///
+    /// A large value following a loss burst indicates that a stall is due to the retransmission
+    /// backlog rather than a small congestion window.
+    pub fn packets_awaiting_retransmission(&self) -> u64 {
+        self.0
+            .state
+            .lock("packets_awaiting_retransmission")
+            .inner
+            .packets_awaiting_retransmission()
+    }
+
     /// Current best estimate of this connection's latency (round-trip-time)
     pub fn rtt(&self) -> Duration {
         self.0.state.lock("rtt").inner.rtt()
     }
 
+    /// Whether explicit congestion notification is in use on the current path
+    ///
+    /// ECN starts out being tested on every path, and is disabled if the peer or the network
+    /// turns out not to support it. [`EcnState::Unreported`] indicates that the peer isn't
+    /// reporting ECN counts back, as opposed to full end-to-end support in
+    /// [`EcnState::Capable`].
+    pub fn ecn_state(&self) -> EcnState {
+        self.0.state.lock("ecn_state").inner.ecn_state()
+    }
+
+    /// Size of the largest UDP payload sent on the current path that the peer acknowledged
+    ///
+    /// A lower bound on the path MTU which is known to work end to end, whereas
+    /// [`max_datagram_size()`](Self::max_datagram_size) is derived from the configured and
+    /// negotiated limits. Zero until a packet has been acknowledged. Packets coalesced into a
+    /// single UDP datagram are counted individually.
+    pub fn max_sent_packet_size(&self) -> u16 {
+        self.0
+            .state
+            .lock("max_sent_packet_size")
+            .inner
+            .max_sent_packet_size()
+    }
+
+    /// Whether the peer's address on the current path has been validated
+    ///
+    /// A freshly migrated path is unvalidated until the peer answers a path challenge, and until
+    /// then the amount of data that may be sent on it is limited to prevent amplification attacks.
+    /// Applications may wish to defer large transfers until this returns `true`.
+    pub fn path_validated(&self) -> bool {
+        self.0.state.lock("path_validated").inner.path_validated()
+    }
+
+    /// Number of times the peer has successfully migrated to a new address
+    ///
+    /// Incremented when the peer's new address is validated. Frequent migrations suggest an
+    /// unstable network path. Only the server side of a connection observes migrations, so this is
+    /// always 0 for clients, which change addresses via [`Endpoint::rebind()`](crate::Endpoint::rebind).
+    pub fn migration_count(&self) -> u64 {
+        self.0.state.lock("migration_count").inner.migration_count()
+    }
+
+    /// Number of handshake bytes the connection was prevented from sending by the
+    /// anti-amplification limit
+    ///
+    /// Servers may send at most three times as much data as they've received on a path until it's
+    /// validated, so a server with a large first flight, e.g. due to a long certificate chain,
+    /// stalls waiting for more data from an unvalidated client. A large value here suggests
+    /// [`ServerConfig::use_retry()`](crate::ServerConfig::use_retry), which validates clients
+    /// earlier, may speed up handshakes.
+    pub fn amplification_withheld(&self) -> u64 {
+        self.0
+            .state
+            .lock("amplification_withheld")
+            .inner
+            .amplification_withheld()
+    }
+
+    /// Number of bytes which may still be sent before hitting the anti-amplification limit
+    ///
+    /// `None` once the peer's address has been validated, which is always the case for clients.
+    /// Allows servers to defer large responses until the limit no longer applies, rather than
+    /// having them stall as counted by [`amplification_withheld()`](Self::amplification_withheld).
+    pub fn amplification_budget(&self) -> Option<u64> {
+        self.0
+            .state
+            .lock("amplification_budget")
+            .inner
+            .amplification_budget()
+    }
+
+    /// Current probe timeout, including any backoff due to unacknowledged probes
+    ///
+    /// Approximately how long the connection waits for an acknowledgement before probing the peer,
+    /// which grows while the path is unresponsive. See
+    /// [`proto::TransportConfig::max_pto_backoff()`].
+    pub fn current_pto(&self) -> Duration {
+        self.0.state.lock("current_pto").inner.current_pto()
+    }
+
+    /// Average interval between packets imposed by pacing
+    ///
+    /// Pacing spreads each congestion window's worth of packets over a round trip rather than
+    /// sending them in a single burst, so when sending at full rate, packets leave this far apart
+    /// on average. Comparing it against observed latency shows how much of that latency is
+    /// self-imposed rather than caused by the network. Derived from the current congestion window
+    /// and RTT estimate.
+    pub fn pacing_delay(&self) -> Duration {
+        self.0.state.lock("pacing_delay").inner.pacing_delay()
+    }
+
+    /// Number of bytes the pacer would currently release immediately
+    ///
+    /// Handing the connection chunks of at most this size lets them be sent right away rather than
+    /// waiting in the pacer. Congestion and flow control may still hold data back. `u64::MAX` if
+    /// pacing is effectively disabled.
+    pub fn pacing_tokens(&self) -> u64 {
+        self.0
+            .state
+            .lock("pacing_tokens")
+            .inner
+            .pacing_tokens(Instant::now())
+    }
+
+    /// What's preventing the connection from sending data faster
+    ///
+    /// Distinguishes a full congestion window from exhausted flow control credit and from the
+    /// application simply not writing data quickly enough, which helps to diagnose poor
+    /// throughput.
+    pub fn send_limit_reason(&self) -> SendLimit {
+        self.0
+            .state
+            .lock("send_limit_reason")
+            .inner
+            .send_limit_reason()
+    }
+
     /// Returns connection statistics
     pub fn stats(&self) -> ConnectionStats {
         self.0.state.lock("stats").inner.stats()
     }
 
+    /// The amount of stream data bytes transmitted for the first time
+    ///
+    /// Together with [`retransmitted_bytes()`](Self::retransmitted_bytes), this gives the share of
+    /// sent stream data which actually made progress.
+    pub fn original_bytes(&self) -> u64 {
+        self.0.state.lock("original_bytes").inner.original_bytes()
+    }
+
+    /// The amount of stream data bytes transmitted again after being declared lost
+    pub fn retransmitted_bytes(&self) -> u64 {
+        self.0
+            .state
+            .lock("retransmitted_bytes")
+            .inner
+            .retransmitted_bytes()
+    }
+
+    /// Why the connection's driver has been running
+    ///
+    /// Helps to diagnose excessive wakeups, e.g. by telling apart a connection which is woken
+    /// for every small application write from one busy with network traffic.
+    pub fn wake_stats(&self) -> WakeStats {
+        self.0.state.lock("wake_stats").wakes
+    }
+
+    /// Total size of all UDP datagrams sent on this connection
+    ///
+    /// Unlike [`stats()`](Self::stats), this doesn't take the connection's lock, so it's cheap
+    /// enough to sample at a high rate. Updated each time the connection is driven.
+    pub fn bytes_sent(&self) -> u64 {
+        self.0.shared.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Total size of all UDP datagrams received on this connection
+    ///
+    /// Unlike [`stats()`](Self::stats), this doesn't take the connection's lock, so it's cheap
+    /// enough to sample at a high rate. Updated each time the connection is driven.
+    pub fn bytes_received(&self) -> u64 {
+        self.0.shared.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Limit the amount of data this connection may transfer
+    ///
+    /// Once any limit in `quota` is reached, the connection is closed with the quota's error code,
+    /// as if by [`close()`](Self::close). Data transferred before the quota was set counts towards
+    /// it, including the handshake, and since the quota is checked each time the connection is
+    /// driven, it may be exceeded by up to a few datagrams. `None`, the default, removes any
+    /// limits.
+    pub fn set_byte_quota(&self, quota: Option<ByteQuota>) {
+        let mut conn = self.0.state.lock("set_byte_quota");
+        conn.byte_quota = quota;
+        // Enforce a quota that's already exhausted
+        conn.wake();
+    }
+
+    /// The amounts left of the limits set by [`set_byte_quota()`](Self::set_byte_quota)
+    ///
+    /// Returns `None` if no quota is set.
+    pub fn remaining_byte_quota(&self) -> Option<ByteQuota> {
+        let quota = self.0.state.lock("remaining_byte_quota").byte_quota?;
+        Some(quota.remaining(self.bytes_sent(), self.bytes_received()))
+    }
+
+    /// Keep up to `capacity` recent samples of the congestion control state
+    ///
+    /// When enabled, the connection's driver records the congestion window, RTT estimate and bytes
+    /// in flight whenever any of them has changed since the last sample, which happens mostly as
+    /// ACKs are processed. Once `capacity` samples are held, the oldest is discarded for each new
+    /// one. Intended for plotting the behavior of congestion controllers; retrieve the samples
+    /// with [`cwnd_samples()`](Self::cwnd_samples). 0, the default, disables sampling and discards
+    /// any samples held.
+    pub fn set_cwnd_sampling(&self, capacity: usize) {
+        let mut conn = self.0.state.lock("set_cwnd_sampling");
+        conn.cwnd_sample_capacity = capacity;
+        let excess = conn.cwnd_samples.len().saturating_sub(capacity);
+        conn.cwnd_samples.drain(..excess);
+    }
+
+    /// The samples recorded since sampling was enabled with
+    /// [`set_cwnd_sampling()`](Self::set_cwnd_sampling), oldest first
+    ///
+    /// Samples are retained until displaced by newer ones, so repeated calls may return
+    /// overlapping ranges; use [`CwndSample::time`] to tell them apart.
+    pub fn cwnd_samples(&self) -> Vec<CwndSample> {
+        let conn = self.0.state.lock("cwnd_samples");
+        conn.cwnd_samples.iter().copied().collect()
+    }
+
+    /// Record this connection's events up to `level`, regardless of the subscriber's own filter
+    ///
+    /// Helps to investigate a single misbehaving connection without flooding logs with the
+    /// verbose output of all others. While the connection's driver runs, it wraps the subscriber
+    /// that is the default on the thread calling this method, enabling all events and spans up to
+    /// `level` in addition to those the subscriber enables itself, so this can only increase
+    /// verbosity. Events emitted outside the driver, e.g. while reading or writing streams, aren't
+    /// affected. `None`, the default, restores the subscriber's own filtering.
+    ///
+    /// Since callsites can no longer be filtered out statically, tracing gets somewhat more
+    /// expensive for all connections as long as any connection has a level set.
+    pub fn set_trace_level(&self, level: Option<Level>) {
+        let dispatch = level.map(|level| {
+            let inner = tracing::dispatcher::get_default(Dispatch::clone);
+            Dispatch::new(LevelOverride::new(inner, level))
+        });
+        self.0.state.lock("set_trace_level").trace_dispatch = dispatch;
+    }
+
+    /// Fraction of recently sent packets which were lost
+    ///
+    /// Unlike the cumulative counts in [`stats()`](Self::stats), this reflects current network
+    /// conditions, allowing applications to adapt quickly when they deteriorate.
+    pub fn recent_loss_rate(&self) -> f32 {
+        self.0
+            .state
+            .lock("recent_loss_rate")
+            .inner
+            .recent_loss_rate()
+    }
+
+    /// Rate at which application data is being delivered, in bytes per second
+    ///
+    /// Counts stream data newly acknowledged by the peer over the last second, excluding
+    /// retransmissions and protocol overhead.
+    pub fn goodput(&self) -> u64 {
+        self.0.state.lock("goodput").inner.goodput(Instant::now())
+    }
+
+    /// Returns the current connection-level flow control state
+    ///
+    /// Useful for telling whether a throughput stall is due to flow control or congestion control.
+    pub fn flow_control_stats(&self) -> FlowControlStats {
+        self.0
+            .state
+            .lock("flow_control_stats")
+            .inner
+            .flow_control_stats()
+    }
+
+    /// Limits on the number of streams each endpoint may open
+    ///
+    /// Once the connection is established, this reflects the limits negotiated during the
+    /// handshake, as since raised by `MAX_STREAMS` frames.
+    pub fn stream_limits(&self) -> StreamLimits {
+        self.0.state.lock("stream_limits").inner.stream_limits()
+    }
+
     /// Current state of the congestion control algorithm, for debugging purposes
     pub fn congestion_state(&self) -> Box<dyn Controller> {
         self.0
@@ -469,6 +1334,18 @@ impl Connection {
             .clone_box()
     }
 
+    /// Whether the congestion controller is in slow start, congestion avoidance, or recovery
+    ///
+    /// Useful to understand how throughput ramps up, e.g. to tell a connection repeatedly
+    /// recovering from loss apart from one still probing for capacity in slow start.
+    pub fn congestion_phase(&self) -> CongestionPhase {
+        self.0
+            .state
+            .lock("congestion_phase")
+            .inner
+            .congestion_phase()
+    }
+
     /// Parameters negotiated during the handshake
     ///
     /// Guaranteed to return `Some` on fully established connections or after
@@ -485,6 +1362,20 @@ impl Connection {
             .handshake_data()
     }
 
+    /// Application protocols offered by the client, in order of its preference
+    ///
+    /// Complements the negotiated protocol reported by [`handshake_data()`](Self::handshake_data),
+    /// e.g. to learn how many clients support a newer protocol version during a rollout. Returns
+    /// `None` for outgoing connections, if the client didn't use ALPN, or if the handshake data
+    /// isn't available yet.
+    #[cfg(feature = "tls-rustls")]
+    pub fn offered_alpn_protocols(&self) -> Option<Vec<Vec<u8>>> {
+        self.handshake_data()?
+            .downcast::<crate::crypto::rustls::HandshakeData>()
+            .ok()?
+            .offered_protocols
+    }
+
     /// Cryptographic identity of the peer
     ///
     /// The dynamic type returned is determined by the configured
@@ -507,16 +1398,64 @@ impl Connection {
         self.0.stable_id()
     }
 
-    // Update traffic keys spontaneously for testing purposes.
+    /// The handle identifying this connection within its endpoint
+    ///
+    /// Unlike [`stable_id()`](Self::stable_id), handles may be reused by later connections once
+    /// this one has been fully cleaned up. Used with [`Endpoint::close_connections()`].
+    ///
+    /// [`Endpoint::close_connections()`]: crate::Endpoint::close_connections
+    pub fn handle(&self) -> ConnectionHandle {
+        self.0.state.lock("handle").handle
+    }
+
+    // Update traffic keys spontaneously for testing purposes. Returns `false`, doing nothing, under
+    // the same conditions as `initiate_key_update`.
     #[doc(hidden)]
-    pub fn force_key_update(&self) {
+    pub fn force_key_update(&self) -> bool {
+        self.initiate_key_update()
+    }
+
+    /// Switch to new 1-RTT packet protection keys
+    ///
+    /// Key updates happen automatically before the keys have been used for too many packets, so
+    /// this is only needed to rotate keys more often, e.g. to satisfy a security policy on
+    /// long-lived connections. Returns `false`, doing nothing, if an update isn't possible yet,
+    /// because the handshake hasn't been confirmed or the previous update is too recent.
+    pub fn initiate_key_update(&self) -> bool {
         self.0
             .state
-            .lock("force_key_update")
+            .lock("initiate_key_update")
             .inner
             .initiate_key_update()
     }
 
+    /// Number of times the 1-RTT packet protection keys have been updated
+    ///
+    /// Counts updates initiated by either side, including automatic ones, allowing to verify that
+    /// keys are being rotated.
+    pub fn key_update_count(&self) -> u64 {
+        self.0
+            .state
+            .lock("key_update_count")
+            .inner
+            .key_update_count()
+    }
+
+    /// The most recent transport errors detected on this connection, oldest first
+    ///
+    /// Includes both the error that closed the connection, if it was detected locally, and
+    /// protocol violations by the peer which were tolerated. Useful to understand the sequence of
+    /// problems leading up to a connection's failure, beyond the final reason it was lost.
+    pub fn recent_errors(&self) -> Vec<TransportError> {
+        self.0
+            .state
+            .lock("recent_errors")
+            .inner
+            .recent_errors()
+            .cloned()
+            .collect()
+    }
+
     /// Derive keying material from this connection's TLS session secrets.
     ///
     /// When both peers call this method with the same `label` and `context`
@@ -550,7 +1489,155 @@ impl Connection {
         conn.wake();
     }
 
-    /// See [`proto::TransportConfig::receive_window()`]
+    /// The idle timeout negotiated with the peer
+    ///
+    /// This is the minimum of both peers' [`proto::TransportConfig::max_idle_timeout()`], or
+    /// `None` if both disabled it, unless a different
+    /// [`idle_timeout_policy()`](proto::TransportConfig::idle_timeout_policy) is configured.
+    /// Keep-alives scheduled by the application must be sent more
+    /// frequently than this to prevent the connection from timing out.
+    pub fn max_idle_timeout(&self) -> Option<Duration> {
+        self.0
+            .state
+            .lock("max_idle_timeout")
+            .inner
+            .max_idle_timeout()
+    }
+
+    /// Override [`proto::TransportConfig::keep_alive_interval()`] for this connection
+    ///
+    /// Keep-alives are minimal ack-eliciting packets which keep an otherwise idle connection from
+    /// timing out. This allows a connection pool to keep connections warm without sending
+    /// application data, e.g. by enabling keep-alives only on pooled connections while they're
+    /// unused. The interval must be shorter than the negotiated idle timeout, which is the minimum
+    /// of both peers' [`proto::TransportConfig::max_idle_timeout()`], for this to be effective.
+    /// `None` stops sending keep-alives.
+    pub fn set_keep_alive_interval(&self, interval: Option<Duration>) {
+        let mut conn = self.0.state.lock("set_keep_alive_interval");
+        conn.inner.set_keep_alive_interval(Instant::now(), interval);
+        conn.wake();
+    }
+
+    /// Change the bound on how long this endpoint may delay acknowledgements
+    ///
+    /// Initially [`TransportConfig::max_ack_delay()`], which is advertised to the peer during the
+    /// handshake. Transport parameters can't be renegotiated and the ACK frequency extension isn't
+    /// supported, so the peer's loss detection keeps using the advertised value.
+    ///
+    /// [`TransportConfig::max_ack_delay()`]: crate::TransportConfig::max_ack_delay
+    pub fn set_max_ack_delay(&self, value: Duration) {
+        self.0
+            .state
+            .lock("set_max_ack_delay")
+            .inner
+            .set_max_ack_delay(value);
+    }
+
+    /// Pin the congestion window to a fixed number of bytes, or restore the congestion
+    /// controller's window with `None`
+    ///
+    /// Intended for tests that need transmission under a known window to be deterministic, without
+    /// inducing real congestion. The congestion controller's own state, as reported by
+    /// [`congestion_state()`](Self::congestion_state), is unaffected. Don't use this in
+    /// production, since it disables congestion control.
+    pub fn set_congestion_window_override(&self, window: Option<u64>) {
+        let mut conn = self.0.state.lock("set_congestion_window_override");
+        conn.inner.set_congestion_window_override(window);
+        // A larger window may allow sending immediately
+        conn.wake();
+    }
+
+    /// Set this connection's share of the endpoint's socket under
+    /// [`TransmitScheduling::WeightedFair`](crate::TransmitScheduling::WeightedFair)
+    ///
+    /// When the socket is saturated, a connection with weight `n` may send `n` datagrams for every
+    /// one sent by a connection with weight 1. Defaults to 1; 0 is treated as 1.
+    pub fn set_transmit_weight(&self, weight: u32) {
+        let mut conn = self.0.state.lock("set_transmit_weight");
+        conn.transmit_weight = weight;
+        conn.update_transmit_schedule();
+    }
+
+    /// Set this connection's priority under
+    /// [`TransmitScheduling::StrictPriority`](crate::TransmitScheduling::StrictPriority)
+    ///
+    /// Pending datagrams of connections with higher priority are always written to the socket
+    /// before those of connections with lower priority. Also determines the order in which
+    /// [`Endpoint::close_by_priority()`](crate::Endpoint::close_by_priority) closes connections.
+    /// Defaults to 0.
+    pub fn set_transmit_priority(&self, priority: i32) {
+        let mut conn = self.0.state.lock("set_transmit_priority");
+        conn.transmit_priority = priority;
+        conn.update_transmit_schedule();
+    }
+
+    /// Send this connection's datagrams from `ip` rather than an address picked by the system
+    ///
+    /// Useful for endpoints bound to a wildcard address on a multi-homed host, where replies should
+    /// leave through the address the connection was established on (see
+    /// [`local_ip()`](Self::local_ip)) to keep the path symmetric for middleboxes and firewalls
+    /// which require it. The address is applied by the endpoint when writing datagrams to the
+    /// socket, and must be one the socket is able to send from. `None`, the default, leaves the
+    /// choice to the connection.
+    pub fn set_preferred_local_ip(&self, ip: Option<IpAddr>) {
+        let conn = self.0.state.lock("set_preferred_local_ip");
+        // If the endpoint driver is gone, noop.
+        let _ = conn
+            .endpoint_events
+            .send((conn.handle, EndpointEvent::PreferLocalIp(ip)));
+    }
+
+    /// Attach opaque data to every datagram this connection sends from now on
+    ///
+    /// The data is passed to [`AsyncUdpSocket::poll_send()`](crate::AsyncUdpSocket::poll_send) in
+    /// [`proto::Transmit::metadata`], allowing custom socket implementations to receive
+    /// per-connection hints such as routing information. `None`, the default, attaches nothing.
+    pub fn set_transmit_metadata(&self, metadata: Option<Bytes>) {
+        self.0
+            .state
+            .lock("set_transmit_metadata")
+            .inner
+            .set_transmit_metadata(metadata);
+    }
+
+    /// Set the priority for the OS to queue datagrams this connection sends with
+    ///
+    /// On Linux, this is passed as an `SO_PRIORITY` control message with each datagram, which
+    /// influences how queueing disciplines schedule it relative to other traffic. Unlike
+    /// [`set_transmit_priority()`](Self::set_transmit_priority), which orders connections within
+    /// the endpoint, this takes effect below the socket, and can therefore prioritize the
+    /// connection relative to other applications' traffic. Priorities above 6 require
+    /// `CAP_NET_ADMIN`.
+    ///
+    /// Per-datagram priorities are only supported by recent kernels; older ones reject such
+    /// datagrams. Ignored on other platforms. `None`, the default, leaves the socket's priority in
+    /// effect.
+    pub fn set_socket_priority(&self, priority: Option<u32>) {
+        self.0
+            .state
+            .lock("set_socket_priority")
+            .inner
+            .set_socket_priority(priority);
+    }
+
+    /// Send an ack-eliciting packet to the peer
+    ///
+    /// Useful for checking that an idle connection is still usable, as a lost connection will
+    /// eventually be detected if the ping isn't acknowledged.
+    pub fn ping(&self) {
+        let mut conn = self.0.state.lock("ping");
+        conn.inner.ping();
+        conn.wake();
+    }
+
+    /// Change the connection-level receive window, overriding
+    /// [`proto::TransportConfig::receive_window()`]
+    ///
+    /// Useful to temporarily allow the peer to send more data for a burst transfer. Growing the
+    /// window advertises the extra credit to the peer immediately. Credit which has already been
+    /// granted can't be revoked, so shrinking the window only takes effect gradually, as the peer
+    /// uses up what it was previously allowed to send. The current window is reported in
+    /// [`FlowControlStats::receive_window`].
     pub fn set_receive_window(&self, receive_window: VarInt) {
         let mut conn = self.0.state.lock("set_receive_window");
         conn.inner.set_receive_window(receive_window);
@@ -706,7 +1793,7 @@ fn poll_accept<'a>(
 pin_project! {
     /// Future produced by [`Connection::read_datagram`]
     pub struct ReadDatagram<'a> {
-        conn: ConnectionRef,
+        conn: &'a ConnectionRef,
         #[pin]
         notify: Notified<'a>,
     }
@@ -715,19 +1802,26 @@ pin_project! {
 impl Future for ReadDatagram<'_> {
     type Output = Result<Bytes, ConnectionError>;
     fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = self.project();
+        let mut this = self.project();
         let mut conn = this.conn.state.lock("ReadDatagram::poll");
+        // Apply any drops requested by the endpoint's datagram budget first
+        conn.sync_datagram_budget(None);
         // Check for buffered datagrams before checking `state.error` so that already-received
         // datagrams, which are necessarily finite, can be drained from a closed connection.
         if let Some(x) = conn.inner.datagrams().recv() {
-            Poll::Ready(Ok(x))
+            conn.sync_datagram_budget(None);
+            return Poll::Ready(Ok(x));
         } else if let Some(ref e) = conn.error {
-            Poll::Ready(Err(e.clone()))
-        } else if this.notify.poll(ctx).is_pending() {
-            // `conn` lock ensures we don't race with readiness
-            Poll::Pending
-        } else {
-            unreachable!("ReadDatagram notified with no datagrams pending");
+            return Poll::Ready(Err(e.clone()));
+        }
+        loop {
+            match this.notify.as_mut().poll(ctx) {
+                // `conn` lock ensures we don't race with readiness
+                Poll::Pending => return Poll::Pending,
+                // The datagram we were notified of was dropped to stay within the endpoint's
+                // budget; get a new future
+                Poll::Ready(()) => this.notify.set(this.conn.shared.datagrams.notified()),
+            }
         }
     }
 }
@@ -746,6 +1840,8 @@ impl ConnectionRef {
         on_connected: oneshot::Sender<bool>,
         udp_state: Arc<UdpState>,
         runtime: Arc<dyn Runtime>,
+        timer_counters: Arc<TimerCounters>,
+        datagram_budget: Arc<DatagramBudget>,
     ) -> Self {
         Self(Arc::new(ConnectionInner {
             state: Mutex::new(State {
@@ -755,8 +1851,27 @@ impl ConnectionRef {
                 on_handshake_data: Some(on_handshake_data),
                 on_connected: Some(on_connected),
                 connected: false,
+                handshake_confirmed: false,
+                established: None,
+                handshake_permit: None,
+                handshake_deadline: None,
+                last_activity: Instant::now(),
+                transmit_weight: 1,
+                transmit_priority: 0,
+                byte_quota: None,
+                cwnd_samples: VecDeque::new(),
+                cwnd_sample_capacity: 0,
+                datagram_size: None,
                 timer: None,
                 timer_deadline: None,
+                timer_counters,
+                datagram_budget,
+                datagrams_accounted: 0,
+                flush_timer: None,
+                coalesce_timeout: None,
+                woken_by_app: false,
+                trace_dispatch: None,
+                wakes: WakeStats::default(),
                 conn_events,
                 endpoint_events,
                 blocked_writers: FxHashMap::default(),
@@ -767,6 +1882,7 @@ impl ConnectionRef {
                 ref_count: 0,
                 udp_state,
                 runtime,
+                drained: false,
             }),
             shared: Shared::default(),
         }))
@@ -777,6 +1893,67 @@ impl ConnectionRef {
     }
 }
 
+/// A reference to a connection that doesn't keep it alive or prevent it from being implicitly
+/// closed
+#[derive(Debug)]
+pub(crate) struct WeakConnection(Weak<ConnectionInner>);
+
+impl WeakConnection {
+    /// Statistics of the connection, if it still exists
+    pub(crate) fn stats(&self) -> Option<ConnectionStats> {
+        Some(self.0.upgrade()?.state.lock("stats").inner.stats())
+    }
+
+    /// Priority set by `Connection::set_transmit_priority`, if the connection still exists
+    pub(crate) fn transmit_priority(&self) -> Option<i32> {
+        Some(
+            self.0
+                .upgrade()?
+                .state
+                .lock("transmit_priority")
+                .transmit_priority,
+        )
+    }
+
+    /// Deadline of the timer armed by the connection's driver, if any and the connection still
+    /// exists
+    pub(crate) fn timer_deadline(&self) -> Option<Instant> {
+        self.0
+            .upgrade()?
+            .state
+            .lock("timer_deadline")
+            .timer_deadline
+    }
+
+    /// Wake the connection's driver, if the connection still exists
+    pub(crate) fn wake(&self) -> bool {
+        match self.0.upgrade() {
+            Some(inner) => {
+                inner.state.lock("wake").wake();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Call `f` with the connection if it's established and hasn't been closed
+    pub(crate) fn with_connection(&self, f: impl FnOnce(&Connection)) -> bool {
+        let inner = match self.0.upgrade() {
+            Some(x) => x,
+            None => return false,
+        };
+        {
+            let mut state = inner.state.lock("with_connection");
+            if !state.connected || state.error.is_some() {
+                return false;
+            }
+            state.ref_count += 1;
+        }
+        f(&Connection(ConnectionRef(inner)));
+        true
+    }
+}
+
 impl Clone for ConnectionRef {
     fn clone(&self) -> Self {
         self.state.lock("clone").ref_count += 1;
@@ -822,6 +1999,18 @@ pub(crate) struct Shared {
     stream_incoming: [Notify; 2],
     datagrams: Notify,
     closed: Notify,
+    /// Notified when the maximum size of outgoing datagrams may have changed, or the connection
+    /// was lost
+    datagram_size: Notify,
+    /// Notified when the connection driver has finished
+    drained: Notify,
+    /// Notified when the connection driver has transmitted everything it can
+    transmitted: Notify,
+    /// Notified when the handshake is confirmed, or the connection was lost
+    handshake_confirmed: Notify,
+    /// Copies of the connection's UDP byte counts, readable without locking the state
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
 }
 
 pub(crate) struct State {
@@ -831,8 +2020,45 @@ pub(crate) struct State {
     on_handshake_data: Option<oneshot::Sender<()>>,
     on_connected: Option<oneshot::Sender<bool>>,
     connected: bool,
+    /// Whether `Shared::handshake_confirmed` has been notified of confirmation
+    handshake_confirmed: bool,
+    /// When the handshake completed, if it has
+    established: Option<Instant>,
+    /// Held by outgoing connections while the handshake is in progress
+    handshake_permit: Option<HandshakePermit>,
+    /// Fires if the handshake is still in progress when the deadline passed to
+    /// `Endpoint::connect_with_deadline` is reached
+    handshake_deadline: Option<Pin<Box<dyn AsyncTimer>>>,
+    /// When a packet was last sent or received
+    last_activity: Instant,
+    /// Scheduling parameters for `TransmitScheduling`, mirrored by the endpoint
+    transmit_weight: u32,
+    transmit_priority: i32,
+    /// Set by `Connection::set_byte_quota`
+    byte_quota: Option<ByteQuota>,
+    /// Recent congestion control states, oldest first, for `Connection::cwnd_samples`
+    cwnd_samples: VecDeque<CwndSample>,
+    /// Maximum length of `cwnd_samples`, with 0 disabling sampling
+    cwnd_sample_capacity: usize,
+    /// Maximum size of outgoing datagrams when last checked by the driver
+    datagram_size: Option<usize>,
     timer: Option<Pin<Box<dyn AsyncTimer>>>,
     timer_deadline: Option<Instant>,
+    /// Endpoint-wide timer activity, for `EndpointStats`
+    timer_counters: Arc<TimerCounters>,
+    /// Endpoint-wide bound on buffered received datagrams
+    datagram_budget: Arc<DatagramBudget>,
+    /// Number of buffered datagrams last reported to `datagram_budget`
+    datagrams_accounted: usize,
+    /// Wakes the driver once data written by streams with `nodelay` disabled should be sent
+    flush_timer: Option<Pin<Box<dyn AsyncTimer>>>,
+    /// How long to hold back written data for coalescing, overriding per-stream `nodelay`
+    coalesce_timeout: Option<Duration>,
+    /// Whether the application has woken the driver since it last ran
+    woken_by_app: bool,
+    /// Subscriber the driver runs under, set by `Connection::set_trace_level`
+    trace_dispatch: Option<Dispatch>,
+    wakes: WakeStats,
     conn_events: mpsc::UnboundedReceiver<ConnectionEvent>,
     endpoint_events: mpsc::UnboundedSender<(ConnectionHandle, EndpointEvent)>,
     pub(crate) blocked_writers: FxHashMap<StreamId, Waker>,
@@ -844,7 +2070,9 @@ pub(crate) struct State {
     /// Number of live handles that can be used to initiate or handle I/O; excludes the driver
     ref_count: usize,
     udp_state: Arc<UdpState>,
-    runtime: Arc<dyn Runtime>,
+    pub(crate) runtime: Arc<dyn Runtime>,
+    /// Whether the connection driver has finished, typically due to the connection draining
+    drained: bool,
 }
 
 impl State {
@@ -853,16 +2081,21 @@ impl State {
         let mut transmits = 0;
 
         let max_datagrams = self.udp_state.max_gso_segments();
+        let mut sent_packets = self.inner.sent_packets();
 
         while let Some(t) = self.inner.poll_transmit(now, max_datagrams) {
             transmits += match t.segment_size {
                 None => 1,
                 Some(s) => (t.contents.len() + s - 1) / s, // round up
             };
+            self.last_activity = now;
+            let packets = self.inner.sent_packets();
             // If the endpoint driver is gone, noop.
-            let _ = self
-                .endpoint_events
-                .send((self.handle, EndpointEvent::Transmit(t)));
+            let _ = self.endpoint_events.send((
+                self.handle,
+                EndpointEvent::Transmit(t, packets - sent_packets),
+            ));
+            sent_packets = packets;
 
             if transmits >= MAX_TRANSMIT_DATAGRAMS {
                 // TODO: What isn't ideal here yet is that if we don't poll all
@@ -876,6 +2109,57 @@ impl State {
         false
     }
 
+    fn update_byte_counts(&self, shared: &Shared) {
+        shared
+            .bytes_sent
+            .store(self.inner.udp_tx_bytes(), Ordering::Relaxed);
+        shared
+            .bytes_received
+            .store(self.inner.udp_rx_bytes(), Ordering::Relaxed);
+    }
+
+    /// Record the congestion control state if sampling is enabled and it has changed
+    fn sample_cwnd(&mut self) {
+        if self.cwnd_sample_capacity == 0 {
+            return;
+        }
+        let sample = CwndSample {
+            time: Instant::now(),
+            cwnd: self.inner.congestion_state().window(),
+            rtt: self.inner.rtt(),
+            bytes_in_flight: self.inner.bytes_in_flight(),
+        };
+        if let Some(last) = self.cwnd_samples.back() {
+            if (last.cwnd, last.rtt, last.bytes_in_flight)
+                == (sample.cwnd, sample.rtt, sample.bytes_in_flight)
+            {
+                return;
+            }
+        }
+        if self.cwnd_samples.len() == self.cwnd_sample_capacity {
+            self.cwnd_samples.pop_front();
+        }
+        self.cwnd_samples.push_back(sample);
+    }
+
+    /// Close the connection if it has used up its byte quota, returning whether it did so
+    fn enforce_byte_quota(&mut self, shared: &Shared) -> bool {
+        let quota = match self.byte_quota {
+            Some(quota) if self.error.is_none() => quota,
+            _ => return false,
+        };
+        let remaining = quota.remaining(
+            shared.bytes_sent.load(Ordering::Relaxed),
+            shared.bytes_received.load(Ordering::Relaxed),
+        );
+        if !remaining.is_exhausted() {
+            return false;
+        }
+        debug!("byte quota exhausted");
+        self.close(quota.error_code, Bytes::new(), shared);
+        true
+    }
+
     fn forward_endpoint_events(&mut self) {
         while let Some(event) = self.inner.poll_endpoint_events() {
             // If the endpoint driver is gone, noop.
@@ -885,18 +2169,23 @@ impl State {
         }
     }
 
+    /// Returns whether any packets were received
+    ///
     /// If this returns `Err`, the endpoint is dead, so the driver should exit immediately.
     fn process_conn_events(
         &mut self,
         shared: &Shared,
         cx: &mut Context,
-    ) -> Result<(), ConnectionError> {
+    ) -> Result<bool, ConnectionError> {
+        let mut packets = false;
         loop {
             match self.conn_events.poll_recv(cx) {
                 Poll::Ready(Some(ConnectionEvent::Ping)) => {
                     self.inner.ping();
                 }
                 Poll::Ready(Some(ConnectionEvent::Proto(event))) => {
+                    packets = true;
+                    self.last_activity = Instant::now();
                     self.inner.handle_event(event);
                 }
                 Poll::Ready(Some(ConnectionEvent::Close { reason, error_code })) => {
@@ -910,12 +2199,31 @@ impl State {
                     }));
                 }
                 Poll::Pending => {
-                    return Ok(());
+                    return Ok(packets);
                 }
             }
         }
     }
 
+    /// Abandon the handshake if it hasn't completed by the caller's deadline
+    fn drive_handshake_deadline(&mut self, shared: &Shared, cx: &mut Context) {
+        let timer = match self.handshake_deadline {
+            Some(ref mut timer) => timer,
+            None => return,
+        };
+        if self.connected || self.error.is_some() {
+            self.handshake_deadline = None;
+            return;
+        }
+        if timer.as_mut().poll(cx).is_pending() {
+            return;
+        }
+        self.handshake_deadline = None;
+        debug!("handshake deadline reached");
+        self.inner.close(Instant::now(), 0u32.into(), Bytes::new());
+        self.terminate(ConnectionError::TimedOut, shared);
+    }
+
     fn forward_app_events(&mut self, shared: &Shared) {
         while let Some(event) = self.inner.poll() {
             use proto::Event::*;
@@ -927,9 +2235,14 @@ impl State {
                 }
                 Connected => {
                     self.connected = true;
+                    // Whether datagrams are supported is now known
+                    shared.datagram_size.notify_waiters();
+                    self.handshake_permit = None;
+                    self.established = Some(Instant::now());
                     if let Some(x) = self.on_connected.take() {
                         // We don't care if the on-connected future was dropped
                         let _ = x.send(self.inner.accepted_0rtt());
+                        self.report_handshake(Ok(()));
                     }
                 }
                 ConnectionLost { reason } => {
@@ -977,6 +2290,10 @@ impl State {
                 }
             }
         }
+        if !self.handshake_confirmed && self.inner.is_handshake_confirmed() {
+            self.handshake_confirmed = true;
+            shared.handshake_confirmed.notify_waiters();
+        }
     }
 
     fn drive_timer(&mut self, cx: &mut Context) -> bool {
@@ -994,15 +2311,17 @@ impl State {
                         .unwrap_or(true)
                     {
                         delay.as_mut().reset(deadline);
+                        self.timer_counters.resets.fetch_add(1, Ordering::Relaxed);
                     }
                 } else {
                     self.timer = Some(self.runtime.new_timer(deadline));
+                    self.timer_counters.resets.fetch_add(1, Ordering::Relaxed);
                 }
                 // Store the actual expiration time of the timer
-                self.timer_deadline = Some(deadline);
+                self.set_timer_deadline(Some(deadline));
             }
             None => {
-                self.timer_deadline = None;
+                self.set_timer_deadline(None);
                 return false;
             }
         }
@@ -1025,20 +2344,85 @@ impl State {
         // A timer expired, so the caller needs to check for
         // new transmits, which might cause new timers to be set.
         self.inner.handle_timeout(Instant::now());
-        self.timer_deadline = None;
+        self.set_timer_deadline(None);
         true
     }
 
+    fn update_transmit_schedule(&mut self) {
+        // If the endpoint driver is gone, noop.
+        let _ = self.endpoint_events.send((
+            self.handle,
+            EndpointEvent::TransmitSchedule {
+                weight: self.transmit_weight,
+                priority: self.transmit_priority,
+            },
+        ));
+    }
+
+    /// Update `timer_deadline`, keeping the endpoint's count of armed timers accurate
+    fn set_timer_deadline(&mut self, deadline: Option<Instant>) {
+        match (self.timer_deadline.is_some(), deadline.is_some()) {
+            (false, true) => self.timer_counters.armed.fetch_add(1, Ordering::Relaxed),
+            (true, false) => self.timer_counters.armed.fetch_sub(1, Ordering::Relaxed),
+            _ => 0,
+        };
+        self.timer_deadline = deadline;
+    }
+
     /// Wake up a blocked `Driver` task to process I/O
     pub(crate) fn wake(&mut self) {
+        self.woken_by_app = true;
         if let Some(x) = self.driver.take() {
             x.wake();
         }
     }
 
+    /// Wake up a blocked `Driver` task to transmit newly written application data
+    ///
+    /// Delays the wakeup to coalesce further writes unless both `nodelay` is set and no
+    /// connection-wide coalescing timeout is configured.
+    pub(crate) fn wake_for_data(&mut self, nodelay: bool) {
+        if nodelay && self.coalesce_timeout.is_none() {
+            self.wake();
+        } else {
+            self.wake_delayed();
+        }
+    }
+
+    /// Wake up a blocked `Driver` task after a short delay, so that further writes in the
+    /// meantime can be coalesced into the same packets
+    fn wake_delayed(&mut self) {
+        self.woken_by_app = true;
+        let waker = match self.driver {
+            Some(ref x) => x.clone(),
+            // The driver is already scheduled to run, and will transmit everything that's pending
+            None => return,
+        };
+        if self.flush_timer.is_some() {
+            return;
+        }
+        let delay = self.coalesce_timeout.unwrap_or(COALESCE_DELAY);
+        let mut timer = self.runtime.new_timer(Instant::now() + delay);
+        // Register the driver to be woken when the timer expires
+        if timer
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_ready()
+        {
+            self.wake();
+            return;
+        }
+        self.flush_timer = Some(timer);
+    }
+
     /// Used to wake up all blocked futures when the connection becomes closed for any reason
     fn terminate(&mut self, reason: ConnectionError, shared: &Shared) {
+        let first = self.error.is_none();
         self.error = Some(reason.clone());
+        if first {
+            self.report_close(&reason);
+        }
+        self.handshake_permit = None;
         if let Some(x) = self.on_handshake_data.take() {
             let _ = x.send(());
         }
@@ -1052,19 +2436,66 @@ impl State {
         shared.stream_budget_available[Dir::Bi as usize].notify_waiters();
         shared.stream_incoming[Dir::Uni as usize].notify_waiters();
         shared.stream_incoming[Dir::Bi as usize].notify_waiters();
+        shared.datagram_size.notify_waiters();
         shared.datagrams.notify_waiters();
         for (_, x) in self.finishing.drain() {
             let _ = x.send(Some(WriteError::ConnectionLost(reason.clone())));
         }
         if let Some(x) = self.on_connected.take() {
             let _ = x.send(false);
+            self.report_handshake(Err(reason.clone()));
         }
         for (_, waker) in self.stopped.drain() {
             waker.wake();
         }
+        shared.handshake_confirmed.notify_waiters();
         shared.closed.notify_waiters();
     }
 
+    fn send_datagram(&mut self, data: Bytes) -> Result<(), SendDatagramError> {
+        if let Some(ref x) = self.error {
+            return Err(SendDatagramError::ConnectionLost(x.clone()));
+        }
+        use proto::SendDatagramError::*;
+        match self.inner.datagrams().send(data) {
+            Ok(()) => {
+                self.wake_for_data(true);
+                Ok(())
+            }
+            Err(e) => Err(match e {
+                UnsupportedByPeer => SendDatagramError::UnsupportedByPeer,
+                Disabled => SendDatagramError::Disabled,
+                TooLarge => SendDatagramError::TooLarge,
+            }),
+        }
+    }
+
+    /// Report the number of buffered received datagrams to the endpoint, dropping the oldest ones
+    /// if the endpoint's budget is exceeded
+    ///
+    /// `waker` is woken when datagrams must be dropped on behalf of another connection.
+    fn sync_datagram_budget(&mut self, waker: Option<&Waker>) {
+        let buffered = self.inner.datagrams().recv_queue_len();
+        let discard = self.datagram_budget.update(
+            self.handle,
+            &mut self.datagrams_accounted,
+            buffered,
+            waker,
+        );
+        for _ in 0..discard {
+            self.inner.datagrams().recv();
+        }
+    }
+
+    /// Wake tasks waiting for larger datagrams to fit if the maximum size has changed
+    fn update_datagram_size(&mut self, shared: &Shared) {
+        let size = self.inner.datagrams().max_size();
+        if size != self.datagram_size {
+            self.datagram_size = size;
+            shared.datagram_size.notify_waiters();
+        }
+    }
+
     fn close(&mut self, error_code: VarInt, reason: Bytes, shared: &Shared) {
         self.inner.close(Instant::now(), error_code, reason);
         self.terminate(ConnectionError::LocallyClosed, shared);
@@ -1076,9 +2507,32 @@ impl State {
         self.close(0u32.into(), Bytes::new(), shared);
     }
 
+    fn driver_finished(&mut self, shared: &Shared) {
+        self.drained = true;
+        shared.drained.notify_waiters();
+        shared.transmitted.notify_waiters();
+    }
+
+    /// Inform the endpoint of the outcome of the handshake, for its statistics
+    fn report_handshake(&mut self, result: Result<(), ConnectionError>) {
+        // If the endpoint driver is gone, noop.
+        let _ = self
+            .endpoint_events
+            .send((self.handle, EndpointEvent::Handshake(result)));
+    }
+
+    /// Inform the endpoint of why the connection closed, for its statistics
+    fn report_close(&mut self, reason: &ConnectionError) {
+        // If the endpoint driver is gone, noop.
+        let _ = self
+            .endpoint_events
+            .send((self.handle, EndpointEvent::Closed(reason.clone())));
+    }
+
     pub(crate) fn check_0rtt(&self) -> Result<(), ()> {
         if self.inner.is_handshaking()
             || self.inner.accepted_0rtt()
+            || self.inner.retransmitted_0rtt()
             || self.inner.side().is_server()
         {
             Ok(())
@@ -1090,6 +2544,9 @@ impl State {
 
 impl Drop for State {
     fn drop(&mut self) {
+        self.set_timer_deadline(None);
+        self.datagram_budget
+            .remove(self.handle, self.datagrams_accounted);
         if !self.inner.is_drained() {
             // Ensure the endpoint can tidy up
             let _ = self.endpoint_events.send((
@@ -1132,6 +2589,9 @@ pub enum SendDatagramError {
 /// and allows other tasks (like receiving ACKs) to run in between.
 const MAX_TRANSMIT_DATAGRAMS: usize = 20;
 
+/// How long data written to streams with `nodelay` disabled may be held back for coalescing
+const COALESCE_DELAY: Duration = Duration::from_millis(2);
+
 /// Error indicating that a stream has already been finished or reset
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
 #[error("unknown stream")]
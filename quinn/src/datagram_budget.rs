@@ -0,0 +1,190 @@
+use std::{
+    collections::VecDeque,
+    iter,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    task::Waker,
+};
+
+use proto::ConnectionHandle;
+use rustc_hash::FxHashMap;
+
+/// Received datagrams buffered by an endpoint's connections, shared with each of them
+///
+/// Connections report changes to the number of datagrams they buffer. When the total exceeds the
+/// endpoint's [`max_buffered_datagrams`](proto::EndpointConfig::max_buffered_datagrams), the
+/// connection which received the oldest datagram is told to drop it, the next time it reports in.
+#[derive(Debug)]
+pub(crate) struct DatagramBudget {
+    capacity: Option<usize>,
+    /// Whether any connection has datagrams to drop, allowing others to skip locking `state`
+    evicting: AtomicBool,
+    state: Mutex<BudgetState>,
+}
+
+impl DatagramBudget {
+    pub(crate) fn new(capacity: Option<usize>) -> Self {
+        Self {
+            capacity,
+            evicting: AtomicBool::new(false),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Account for connection `handle` now buffering `buffered` datagrams
+    ///
+    /// `accounted` is the number of datagrams the connection reported most recently, and is
+    /// updated. `waker` wakes the connection if it must drop datagrams on behalf of another.
+    /// Returns the number of its oldest datagrams the connection must drop.
+    pub(crate) fn update(
+        &self,
+        handle: ConnectionHandle,
+        accounted: &mut usize,
+        buffered: usize,
+        waker: Option<&Waker>,
+    ) -> usize {
+        if buffered == *accounted && !self.evicting.load(Ordering::Relaxed) {
+            return 0;
+        }
+        let mut state = self.state.lock().unwrap();
+        // Datagrams assigned for eviction have already been removed from the total
+        let evicted = state.evict.remove(&handle).unwrap_or(0).min(*accounted);
+        let mut discard = evicted.min(buffered);
+        state.record(
+            handle,
+            *accounted - evicted,
+            buffered - discard,
+            self.capacity.is_some(),
+        );
+
+        let mut wake = Vec::new();
+        if let Some(capacity) = self.capacity {
+            while state.total > capacity {
+                let owner = match state.order.pop_front() {
+                    Some(x) => x,
+                    None => break,
+                };
+                if state.take_stale(owner) {
+                    continue;
+                }
+                state.total -= 1;
+                state.dropped += 1;
+                if owner == handle {
+                    discard += 1;
+                } else {
+                    *state.evict.entry(owner).or_default() += 1;
+                    wake.extend(state.wakers.get(&owner).cloned());
+                }
+            }
+            state.compact(capacity);
+            match waker {
+                Some(waker) if buffered > discard => {
+                    state.wakers.insert(handle, waker.clone());
+                }
+                Some(_) => {
+                    state.wakers.remove(&handle);
+                }
+                None => {}
+            }
+        }
+        self.evicting
+            .store(!state.evict.is_empty(), Ordering::Relaxed);
+        drop(state);
+        for waker in wake {
+            waker.wake();
+        }
+        *accounted = buffered - discard;
+        discard
+    }
+
+    /// Forget connection `handle`, which had reported buffering `accounted` datagrams
+    pub(crate) fn remove(&self, handle: ConnectionHandle, accounted: usize) {
+        let mut state = self.state.lock().unwrap();
+        let evicted = state.evict.remove(&handle).unwrap_or(0).min(accounted);
+        state.record(handle, accounted - evicted, 0, self.capacity.is_some());
+        state.wakers.remove(&handle);
+        self.evicting
+            .store(!state.evict.is_empty(), Ordering::Relaxed);
+    }
+
+    /// Number of datagrams currently buffered by all connections
+    pub(crate) fn buffered(&self) -> usize {
+        self.state.lock().unwrap().total
+    }
+
+    /// Number of datagrams dropped to stay within the capacity
+    pub(crate) fn dropped(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+
+    pub(crate) fn reset_dropped(&self) {
+        self.state.lock().unwrap().dropped = 0;
+    }
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    /// Datagrams buffered by all connections, excluding those assigned for eviction
+    total: usize,
+    dropped: u64,
+    /// The connection which received each buffered datagram, oldest first
+    ///
+    /// Only maintained if the capacity is limited. Entries of datagrams which are no longer
+    /// buffered are removed lazily: the oldest `stale[handle]` entries for each connection don't
+    /// correspond to a buffered datagram.
+    order: VecDeque<ConnectionHandle>,
+    stale: FxHashMap<ConnectionHandle, usize>,
+    /// Number of their oldest datagrams which connections must drop
+    evict: FxHashMap<ConnectionHandle, usize>,
+    /// Wakers of connections with buffered datagrams
+    wakers: FxHashMap<ConnectionHandle, Waker>,
+}
+
+impl BudgetState {
+    /// Account for a change in the number of datagrams buffered by `handle`
+    fn record(&mut self, handle: ConnectionHandle, old: usize, new: usize, ordered: bool) {
+        if new > old {
+            self.total += new - old;
+            if ordered {
+                self.order.extend(iter::repeat(handle).take(new - old));
+            }
+        } else if new < old {
+            self.total -= old - new;
+            if ordered {
+                *self.stale.entry(handle).or_default() += old - new;
+            }
+        }
+    }
+
+    /// If the oldest entry of `handle` in `order` is stale, mark it as removed
+    fn take_stale(&mut self, handle: ConnectionHandle) -> bool {
+        match self.stale.get_mut(&handle) {
+            Some(count) => {
+                *count -= 1;
+                if *count == 0 {
+                    self.stale.remove(&handle);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove stale entries from `order`, keeping its length proportional to `capacity`
+    fn compact(&mut self, capacity: usize) {
+        while let Some(&front) = self.order.front() {
+            if !self.take_stale(front) {
+                break;
+            }
+            self.order.pop_front();
+        }
+        if self.order.len() <= 2 * capacity.max(self.total) {
+            return;
+        }
+        let mut order = std::mem::take(&mut self.order);
+        order.retain(|&handle| !self.take_stale(handle));
+        self.order = order;
+    }
+}
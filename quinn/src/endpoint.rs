@@ -1,30 +1,42 @@
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
+    fmt,
     future::Future,
     io,
     io::IoSliceMut,
-    mem::MaybeUninit,
-    net::{SocketAddr, SocketAddrV6},
+    iter,
+    mem::{self, MaybeUninit},
+    net::{IpAddr, SocketAddr, SocketAddrV6},
     pin::Pin,
     str,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     task::{Context, Poll, Waker},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use crate::runtime::{default_runtime, AsyncUdpSocket, Runtime};
+use crate::runtime::{default_runtime, AsyncTimer, AsyncUdpSocket, Runtime};
 use bytes::{Bytes, BytesMut};
 use pin_project_lite::pin_project;
 use proto::{
-    self as proto, ClientConfig, ConnectError, ConnectionHandle, DatagramEvent, ServerConfig,
+    self as proto, ClientConfig, ConnectError, ConnectionError, ConnectionHandle, ConnectionId,
+    ConnectionStats, DatagramEvent, ServerConfig,
 };
-use rustc_hash::FxHashMap;
-use tokio::sync::{futures::Notified, mpsc, Notify};
+use rustc_hash::{FxHashMap, FxHashSet};
+use tokio::sync::{futures::Notified, mpsc, Notify, OwnedSemaphorePermit, Semaphore};
 use udp::{RecvMeta, UdpState, BATCH_SIZE};
 
 use crate::{
-    connection::Connecting, work_limiter::WorkLimiter, ConnectionEvent, EndpointConfig,
-    EndpointEvent, VarInt, IO_LOOP_BOUND, RECV_TIME_BOUND, SEND_TIME_BOUND,
+    connection::{Connecting, Connection, WeakConnection},
+    datagram_budget::DatagramBudget,
+    offload::{DatagramSource, OffloadSender, TransmitOffload},
+    router::{ConnectionRouter, ForwardedDatagram, RouterMembership},
+    transmit_queue::TransmitQueue,
+    work_limiter::WorkLimiter,
+    ConnectionEvent, EndpointConfig, EndpointEvent, VarInt, IO_LOOP_BOUND, REBIND_PING_INTERVAL,
+    RECV_TIME_BOUND, SEND_TIME_BOUND,
 };
 
 /// A QUIC endpoint.
@@ -53,15 +65,12 @@ impl Endpoint {
     /// communicate within.
     #[cfg(feature = "ring")]
     pub fn client(addr: SocketAddr) -> io::Result<Self> {
+        let config = EndpointConfig::default();
         let socket = std::net::UdpSocket::bind(addr)?;
         let runtime = default_runtime()
             .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no async runtime found"))?;
-        Self::new_with_runtime(
-            EndpointConfig::default(),
-            None,
-            runtime.wrap_udp_socket(socket)?,
-            runtime,
-        )
+        let socket = wrap_udp_socket(&*runtime, &config, socket)?;
+        Self::new_with_runtime(config, None, socket, runtime)
     }
 
     /// Helper to construct an endpoint for use with both incoming and outgoing connections
@@ -72,26 +81,35 @@ impl Endpoint {
     /// communicate within.
     #[cfg(feature = "ring")]
     pub fn server(config: ServerConfig, addr: SocketAddr) -> io::Result<Self> {
+        let endpoint_config = EndpointConfig::default();
         let socket = std::net::UdpSocket::bind(addr)?;
         let runtime = default_runtime()
             .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no async runtime found"))?;
-        Self::new_with_runtime(
-            EndpointConfig::default(),
-            Some(config),
-            runtime.wrap_udp_socket(socket)?,
-            runtime,
-        )
+        let socket = wrap_udp_socket(&*runtime, &endpoint_config, socket)?;
+        Self::new_with_runtime(endpoint_config, Some(config), socket, runtime)
     }
 
     /// Construct an endpoint with arbitrary configuration and socket
+    ///
+    /// `socket` is switched to non-blocking mode, so it need not be configured in advance. Socket
+    /// buffer sizes set in `config` are also applied to it.
+    ///
+    /// `socket` may be [`connect`](std::net::UdpSocket::connect)ed to a single remote address,
+    /// letting the operating system route outgoing packets more cheaply and discard incoming
+    /// packets from any other source. Such an endpoint can only communicate with that one
+    /// address: connecting elsewhere fails with [`ConnectError::InvalidRemoteAddress`], and peers
+    /// which migrate to a new address will be unreachable.
     pub fn new(
         config: EndpointConfig,
         server_config: Option<ServerConfig>,
         socket: std::net::UdpSocket,
         runtime: impl Runtime,
     ) -> io::Result<Self> {
-        let socket = runtime.wrap_udp_socket(socket)?;
-        Self::new_with_runtime(config, server_config, socket, Arc::new(runtime))
+        let connected_peer = socket.peer_addr().ok();
+        let socket = wrap_udp_socket(&runtime, &config, socket)?;
+        let endpoint = Self::new_with_runtime(config, server_config, socket, Arc::new(runtime))?;
+        endpoint.inner.state.lock().unwrap().connected_peer = connected_peer;
+        Ok(endpoint)
     }
 
     /// Construct an endpoint with arbitrary configuration and pre-constructed abstract socket
@@ -117,7 +135,7 @@ impl Endpoint {
         let rc = EndpointRef::new(
             socket,
             proto::Endpoint::new(Arc::new(config), server_config.map(Arc::new)),
-            addr.is_ipv6(),
+            addr,
             runtime.clone(),
         );
         let driver = EndpointDriver(rc.clone());
@@ -144,11 +162,49 @@ impl Endpoint {
         }
     }
 
+    /// Remove and return all incoming connection attempts which haven't been accepted yet
+    ///
+    /// Useful during shutdown to refuse buffered connections with an informative reason, by
+    /// awaiting each and then calling [`Connection::close()`](crate::Connection::close), rather
+    /// than dropping them. Attempts which arrive afterwards are buffered for
+    /// [`accept()`](Self::accept) as usual.
+    pub fn take_incoming(&self) -> Vec<Connecting> {
+        self.inner
+            .state
+            .lock()
+            .unwrap()
+            .incoming
+            .drain(..)
+            .collect()
+    }
+
     /// Set the client configuration used by `connect`
     pub fn set_default_client_config(&mut self, config: ClientConfig) {
         self.default_client_config = Some(config);
     }
 
+    /// Replace the client configuration used by `connect`, returning the previous one
+    ///
+    /// Passing the returned value back restores the previous state, including the absence of a
+    /// default configuration, which makes temporary overrides straightforward.
+    pub fn replace_default_client_config(
+        &mut self,
+        config: Option<ClientConfig>,
+    ) -> Option<ClientConfig> {
+        mem::replace(&mut self.default_client_config, config)
+    }
+
+    /// Get another handle to this endpoint whose `connect` uses `config`
+    ///
+    /// The default client configuration is a property of each handle, so the returned handle
+    /// shares the same socket and connections as `self` without affecting its configuration.
+    pub fn with_default_client_config(&self, config: ClientConfig) -> Self {
+        Self {
+            default_client_config: Some(config),
+            ..self.clone()
+        }
+    }
+
     /// Connect to a remote endpoint
     ///
     /// `server_name` must be covered by the certificate presented by the server. This prevents a
@@ -156,7 +212,8 @@ impl Endpoint {
     /// server.
     ///
     /// May fail immediately due to configuration errors, or in the future if the connection could
-    /// not be established.
+    /// not be established. Fails with [`ConnectError::EndpointStopping`] if the endpoint has been
+    /// [closed](Endpoint::close).
     pub fn connect(&self, addr: SocketAddr, server_name: &str) -> Result<Connecting, ConnectError> {
         let config = match &self.default_client_config {
             Some(config) => config.clone(),
@@ -176,24 +233,118 @@ impl Endpoint {
         config: ClientConfig,
         addr: SocketAddr,
         server_name: &str,
+    ) -> Result<Connecting, ConnectError> {
+        self.connect_inner(config, addr, server_name, None)
+    }
+
+    /// Connect to a remote endpoint, addressing the first Initial packet to `initial_dcid`
+    ///
+    /// Normally the initial destination connection ID is chosen by the endpoint's
+    /// [`ConnectionIdGenerator`](proto::ConnectionIdGenerator). Supplying it explicitly lets
+    /// deployments with connection ID-aware load balancers route the connection deterministically.
+    /// Fails with [`ConnectError::InvalidInitialDcid`] if `initial_dcid` is shorter than 8 bytes.
+    ///
+    /// See [`connect_with()`](Self::connect_with) for details.
+    pub fn connect_with_initial_dcid(
+        &self,
+        config: ClientConfig,
+        addr: SocketAddr,
+        server_name: &str,
+        initial_dcid: ConnectionId,
+    ) -> Result<Connecting, ConnectError> {
+        self.connect_inner(config, addr, server_name, Some(initial_dcid))
+    }
+
+    fn connect_inner(
+        &self,
+        config: ClientConfig,
+        addr: SocketAddr,
+        server_name: &str,
+        initial_dcid: Option<ConnectionId>,
     ) -> Result<Connecting, ConnectError> {
         let mut endpoint = self.inner.state.lock().unwrap();
-        if endpoint.driver_lost {
+        if endpoint.driver_lost || endpoint.connections.close.is_some() {
             return Err(ConnectError::EndpointStopping);
         }
-        if addr.is_ipv6() && !endpoint.ipv6 {
-            return Err(ConnectError::InvalidRemoteAddress(addr));
-        }
-        let addr = if endpoint.ipv6 {
-            SocketAddr::V6(ensure_ipv6(addr))
-        } else {
-            addr
+        let addr = endpoint.remote_for_socket(addr)?;
+        let (ch, conn) = match initial_dcid {
+            Some(initial_dcid) => {
+                endpoint
+                    .inner
+                    .connect_with_initial_dcid(config, addr, server_name, initial_dcid)?
+            }
+            None => endpoint.inner.connect(config, addr, server_name)?,
         };
-        let (ch, conn) = endpoint.inner.connect(config, addr, server_name)?;
+        endpoint.sync_router();
         let udp_state = endpoint.udp_state.clone();
-        Ok(endpoint
-            .connections
-            .insert(ch, conn, udp_state, self.runtime.clone()))
+        Ok(endpoint.connections.insert(
+            ch,
+            conn,
+            udp_state,
+            self.runtime.clone(),
+            Some(self.inner.shared.handshake_limiter.clone()),
+        ))
+    }
+
+    /// Connect to a remote endpoint, giving up if the handshake hasn't completed by `deadline`
+    ///
+    /// See [`connect_with()`](Self::connect_with) for details. If the deadline passes first, the
+    /// connection is closed and the returned [`Connecting`] fails with
+    /// [`ConnectionError::TimedOut`](crate::ConnectionError::TimedOut). This uses the endpoint's
+    /// [`Runtime`] rather than requiring a runtime-specific timeout wrapper, and ensures the
+    /// abandoned connection is cleaned up even if the `Connecting` is dropped.
+    pub fn connect_with_deadline(
+        &self,
+        config: ClientConfig,
+        addr: SocketAddr,
+        server_name: &str,
+        deadline: Instant,
+    ) -> Result<Connecting, ConnectError> {
+        let connecting = self.connect_with(config, addr, server_name)?;
+        connecting.set_deadline(deadline);
+        Ok(connecting)
+    }
+
+    /// Connect to a remote endpoint, verifying its certificate with `verifier`
+    ///
+    /// Uses the default client configuration with only its certificate verifier replaced, e.g. to
+    /// pin a particular certificate for one server while using the usual roots for others. The
+    /// default configuration must have been created from a rustls configuration, e.g. with
+    /// [`ClientConfig::with_rustls()`]; otherwise this fails with
+    /// [`ConnectError::OpaqueCryptoConfig`].
+    ///
+    /// See [`connect()`](Self::connect) for details.
+    #[cfg(feature = "dangerous-configuration")]
+    pub fn connect_with_verifier(
+        &self,
+        addr: SocketAddr,
+        server_name: &str,
+        verifier: Arc<dyn rustls::client::ServerCertVerifier>,
+    ) -> Result<Connecting, ConnectError> {
+        let config = match &self.default_client_config {
+            Some(config) => {
+                config.map_rustls(|crypto| crypto.dangerous().set_certificate_verifier(verifier))?
+            }
+            None => return Err(ConnectError::NoDefaultClientConfig),
+        };
+
+        self.connect_with(config, addr, server_name)
+    }
+
+    /// Whether a connection to `addr` could be attempted through the endpoint's current socket
+    ///
+    /// Returns `false` for addresses [`connect()`](Self::connect) would reject with
+    /// [`ConnectError::InvalidRemoteAddress`], e.g. IPv6 addresses when the socket is bound to an
+    /// IPv4 address, addresses other than the peer of a connected socket, or port 0. Useful for
+    /// filtering the results of a DNS lookup that returned a mix of A and AAAA records. Says
+    /// nothing about whether a server is actually listening at `addr`.
+    pub fn can_reach(&self, addr: SocketAddr) -> bool {
+        self.inner
+            .state
+            .lock()
+            .unwrap()
+            .remote_for_socket(addr)
+            .is_ok()
     }
 
     /// Switch to a new UDP socket
@@ -201,23 +352,219 @@ impl Endpoint {
     /// Allows the endpoint's address to be updated live, affecting all active connections. Incoming
     /// connections and connections to servers unreachable from the new address will be lost.
     ///
+    /// Socket buffer sizes set in the endpoint's [`EndpointConfig`] are applied to `socket`.
+    ///
     /// On error, the old UDP socket is retained.
+    ///
+    /// Every connection sends a PING immediately so that peers notice the new address promptly.
+    /// See [`rebind_quiet()`](Self::rebind_quiet) to avoid this. To avoid a storm of PINGs when
+    /// rebinding repeatedly, e.g. in response to a flapping network interface, PINGs for rebinds
+    /// within [`REBIND_PING_INTERVAL`] of the previous PINGs are deferred until that interval has
+    /// passed, and coalesced into a single PING per connection. The socket is switched
+    /// immediately regardless.
+    ///
+    /// For endpoints with [additional sockets](Self::add_socket), this replaces the primary
+    /// socket, and only affects the connections using it.
     pub fn rebind(&self, socket: std::net::UdpSocket) -> io::Result<()> {
+        self.rebind_inner(socket, true)
+    }
+
+    /// Switch the endpoint's first IPv4 socket to a new one
+    ///
+    /// Like [`rebind()`](Self::rebind), but replaces whichever of the endpoint's sockets,
+    /// including those [added](Self::add_socket) later, is the first bound to an IPv4 address.
+    /// Only the connections using that socket are affected and sent a PING, so e.g. in a
+    /// dual-stack deployment where only the IPv4 address changed, IPv6 connections are left
+    /// alone. Fails if `socket` isn't bound to an IPv4 address or the endpoint has no IPv4 socket.
+    pub fn rebind_v4(&self, socket: std::net::UdpSocket) -> io::Result<()> {
+        self.rebind_family(socket, false)
+    }
+
+    /// Switch the endpoint's first IPv6 socket to a new one
+    ///
+    /// See [`rebind_v4()`](Self::rebind_v4). Connections with IPv4 peers using an IPv6 socket in
+    /// dual-stack mode are affected as well.
+    pub fn rebind_v6(&self, socket: std::net::UdpSocket) -> io::Result<()> {
+        self.rebind_family(socket, true)
+    }
+
+    fn rebind_family(&self, socket: std::net::UdpSocket, ipv6: bool) -> io::Result<()> {
+        if socket.local_addr()?.is_ipv6() != ipv6 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "socket is bound to an address of the wrong family",
+            ));
+        }
+        let mut state = self.inner.state.lock().unwrap();
+        let index = state
+            .sockets
+            .iter()
+            .position(|s| s.addr.is_ipv6() == ipv6)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "endpoint has no socket of the same address family",
+                )
+            })?;
+        self.replace_socket(&mut state, index, socket, true)
+    }
+
+    /// Switch to a new UDP socket without generating any traffic
+    ///
+    /// Like [`rebind()`](Self::rebind), but connections are not prompted to send anything, which
+    /// avoids waking idle peers, e.g. battery-sensitive mobile devices. Peers will only learn of
+    /// the new address when a connection next sends a packet for some other reason. Until then,
+    /// anything they send is addressed to the old socket and lost, and connections with no
+    /// traffic in either direction may hit their idle timeout if keep-alives are disabled.
+    pub fn rebind_quiet(&self, socket: std::net::UdpSocket) -> io::Result<()> {
+        self.rebind_inner(socket, false)
+    }
+
+    fn rebind_inner(&self, socket: std::net::UdpSocket, ping: bool) -> io::Result<()> {
+        let mut state = self.inner.state.lock().unwrap();
+        self.replace_socket(&mut state, 0, socket, ping)
+    }
+
+    /// Replace the socket at `index` in the endpoint's sockets
+    fn replace_socket(
+        &self,
+        state: &mut State,
+        index: usize,
+        socket: std::net::UdpSocket,
+        ping: bool,
+    ) -> io::Result<()> {
         let addr = socket.local_addr()?;
-        let socket = self.runtime.wrap_udp_socket(socket)?;
-        let mut inner = self.inner.state.lock().unwrap();
-        inner.socket = socket;
-        inner.ipv6 = addr.is_ipv6();
+        let connected_peer = socket.peer_addr().ok();
+        let socket = wrap_udp_socket(&*self.runtime, state.inner.config(), socket)?;
+        state.sockets[index] = SocketState {
+            outgoing: mem::take(&mut state.sockets[index].outgoing),
+            ..SocketState::new(socket, addr)
+        };
+        if index == 0 {
+            state.ipv6 = addr.is_ipv6();
+            state.connected_peer = connected_peer;
+        }
 
-        // Generate some activity so peers notice the rebind
-        for sender in inner.connections.senders.values() {
-            // Ignoring errors from dropped connections
-            let _ = sender.send(ConnectionEvent::Ping);
+        if ping {
+            if !state.rebind_pings.contains(&index) {
+                state.rebind_pings.push(index);
+            }
+            let now = Instant::now();
+            match state.last_rebind_ping {
+                Some(last) if now < last + REBIND_PING_INTERVAL => {
+                    if state.rebind_ping_timer.is_none() {
+                        state.rebind_ping_timer =
+                            Some(self.runtime.new_timer(last + REBIND_PING_INTERVAL));
+                        // Ensure the driver polls the timer
+                        if let Some(task) = state.driver.take() {
+                            task.wake();
+                        }
+                    }
+                }
+                _ => state.send_rebind_pings(now),
+            }
         }
 
         Ok(())
     }
 
+    /// Set a function to be called for every incoming connection attempt
+    ///
+    /// `f` is called as soon as the endpoint starts handling a new incoming connection, before it
+    /// is yielded by [`accept()`](Self::accept) and regardless of whether its handshake ever
+    /// completes, making it suitable for auditing scans and abandoned attempts. Attempts rejected
+    /// outright by the endpoint, e.g. while it is answering with a retry, are not reported.
+    ///
+    /// `f` runs on the endpoint's driver with the endpoint locked, so it should be quick and must
+    /// not call back into this `Endpoint`. Passing `None` removes a previously set function.
+    pub fn set_connection_attempt_callback(&self, f: Option<Box<AttemptFn>>) {
+        self.inner.state.lock().unwrap().connection_attempt = f.map(AttemptCallback);
+    }
+
+    /// Set a function to be called for every incoming connection as it's created
+    ///
+    /// Unlike the [connection attempt callback](Self::set_connection_attempt_callback), `f`
+    /// receives the new connection's handle, as later reported by [`Connection::handle()`], along
+    /// with the peer's address. It's called once the connection exists but before it is queued
+    /// for [`accept()`](Self::accept), so it can be registered with application-level tracking
+    /// before anything else can observe it, even if it's never accepted.
+    ///
+    /// `f` runs on the endpoint's driver with the endpoint locked, but not the connection, so it
+    /// should be quick and must not call back into this `Endpoint`. Passing `None` removes a
+    /// previously set function.
+    pub fn set_new_connection_callback(&self, f: Option<Box<NewConnectionFn>>) {
+        self.inner.state.lock().unwrap().new_connection = f.map(NewConnectionCallback);
+    }
+
+    /// Share a connection routing table with other endpoints bound to the same port
+    ///
+    /// Datagrams this endpoint receives for connections belonging to another member of `router`
+    /// are forwarded to that member, and vice versa. Replaces any router previously joined. The
+    /// endpoint leaves the router when its driver stops. See [`ConnectionRouter`] for details.
+    pub fn join_router(&self, router: Arc<ConnectionRouter>) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.router = None;
+        state.inner.track_cid_changes(true);
+        state.router = Some(router.join());
+        state.sync_router();
+        // Ensure the driver polls for forwarded datagrams
+        if let Some(task) = state.driver.take() {
+            task.wake();
+        }
+    }
+
+    /// Hand outgoing datagrams to the application instead of sending them on the sockets
+    ///
+    /// Allows a custom send path, e.g. a dedicated thread sending through io_uring or AF_XDP,
+    /// while the endpoint keeps handling all connection logic. Up to `capacity` transmits, and at
+    /// least one, may be handed over without being reported sent. Replaces any previous offload.
+    /// Dropping the returned [`TransmitOffload`] resumes sending on the sockets. See its
+    /// documentation for details.
+    pub fn offload_transmits(&self, capacity: usize) -> TransmitOffload {
+        let (sender, offload) = OffloadSender::new(capacity.max(1));
+        let mut state = self.inner.state.lock().unwrap();
+        state.offload = Some(sender);
+        // Ensure queued datagrams are handed over
+        if let Some(task) = state.driver.take() {
+            task.wake();
+        }
+        offload
+    }
+
+    /// Receive datagrams from `source` instead of the sockets
+    ///
+    /// Allows a custom receive path, e.g. an AF_XDP ring, while the endpoint keeps handling all
+    /// connection logic. The sockets aren't read while a source is set. Passing `None` removes a
+    /// previously set source, returning to the sockets. See [`DatagramSource`] for details.
+    pub fn set_datagram_source(&self, source: Option<Box<dyn DatagramSource>>) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.datagram_source = source;
+        // Ensure the driver polls the new source
+        if let Some(task) = state.driver.take() {
+            task.wake();
+        }
+    }
+
+    /// Probe the platform's UDP offload capabilities again
+    ///
+    /// The capabilities are detected when the endpoint is created, so they may be wrong if the
+    /// network hardware changes underneath it, e.g. after a virtual machine's live migration,
+    /// which can cause sends to fail. This detects them afresh, affecting existing connections,
+    /// and resizes the endpoint's receive buffer to suit.
+    pub fn refresh_udp_state(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        let state = &mut *state;
+        state.udp_state.refresh();
+        let len = recv_buf_len(state.inner.config(), &state.udp_state);
+        if len != state.recv_buf.len() {
+            let recv_buf = alloc_recv_buf(state.inner.config(), len);
+            let old = mem::replace(&mut state.recv_buf, recv_buf);
+            if let Some(pool) = state.inner.config().get_buffer_pool() {
+                pool.release(old);
+            }
+        }
+    }
+
     /// Replace the server configuration, affecting new incoming connections only
     ///
     /// Useful for e.g. refreshing TLS certificates without disrupting existing connections.
@@ -230,9 +577,90 @@ impl Endpoint {
             .set_server_config(server_config.map(Arc::new))
     }
 
+    /// Replace the server configuration, returning the previous one
+    ///
+    /// The swap happens atomically with respect to incoming connections, so the returned
+    /// configuration can be passed back to [`set_server_config()`](Self::set_server_config) to
+    /// roll back a change without having to keep a copy of it elsewhere.
+    pub fn replace_server_config(
+        &self,
+        server_config: Option<ServerConfig>,
+    ) -> Option<ServerConfig> {
+        let old = self
+            .inner
+            .state
+            .lock()
+            .unwrap()
+            .inner
+            .replace_server_config(server_config.map(Arc::new))?;
+        Some(Arc::try_unwrap(old).unwrap_or_else(|old| (*old).clone()))
+    }
+
     /// Get the local `SocketAddr` the underlying socket is bound to
+    ///
+    /// For endpoints with [additional sockets](Self::add_socket), this is the address of the
+    /// primary socket, which outgoing connections use.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        self.inner.state.lock().unwrap().socket.local_addr()
+        self.inner.state.lock().unwrap().sockets[0]
+            .socket
+            .local_addr()
+    }
+
+    /// Receive incoming connections on an additional UDP socket
+    ///
+    /// Lets a single endpoint and driver serve several local ports. Connections accepted on
+    /// `socket` keep using it for the rest of their lifetime, while outgoing connections always
+    /// use the primary socket, i.e. the one the endpoint was created with or most recently
+    /// [rebound](Self::rebind) to. `socket` may be of a different address family than the primary
+    /// socket, e.g. to serve both IPv4 and IPv6 without relying on dual-stack sockets. Socket
+    /// buffer sizes set in the endpoint's [`EndpointConfig`] are applied to it.
+    pub fn add_socket(&self, socket: std::net::UdpSocket) -> io::Result<()> {
+        let addr = socket.local_addr()?;
+        let mut state = self.inner.state.lock().unwrap();
+        let socket = wrap_udp_socket(&*self.runtime, state.inner.config(), socket)?;
+        state.sockets.push(SocketState::new(socket, addr));
+        // Ensure the driver polls the new socket
+        if let Some(task) = state.driver.take() {
+            task.wake();
+        }
+        Ok(())
+    }
+
+    /// Get every local `SocketAddr` the endpoint may send from
+    ///
+    /// Covers the primary socket and any [additional sockets](Self::add_socket), in that order.
+    /// Sockets bound to a specific address contribute just that address. For sockets bound to a
+    /// wildcard address, the host's interface addresses of the same family are enumerated and
+    /// combined with the bound port. Enumerating interfaces is currently only supported on Unix.
+    pub fn local_addresses(&self) -> io::Result<Vec<SocketAddr>> {
+        let bound = self
+            .inner
+            .state
+            .lock()
+            .unwrap()
+            .sockets
+            .iter()
+            .map(|s| s.addr)
+            .collect::<Vec<_>>();
+        let mut interfaces = None;
+        let mut addrs = Vec::new();
+        for addr in bound {
+            if !addr.ip().is_unspecified() {
+                addrs.push(addr);
+                continue;
+            }
+            if interfaces.is_none() {
+                interfaces = Some(udp::local_interfaces()?);
+            }
+            addrs.extend(
+                interfaces
+                    .iter()
+                    .flatten()
+                    .filter(|ip| ip.is_ipv6() == addr.is_ipv6())
+                    .map(|&ip| SocketAddr::new(ip, addr.port())),
+            );
+        }
+        Ok(addrs)
     }
 
     /// Close all of this endpoint's connections immediately and cease accepting new connections.
@@ -254,6 +682,240 @@ impl Endpoint {
         self.inner.shared.incoming.notify_waiters();
     }
 
+    /// Close all of this endpoint's connections in tiers of equal priority and cease accepting new
+    /// connections
+    ///
+    /// Connections are grouped by the priority set with [`Connection::set_transmit_priority()`],
+    /// and the groups are closed one after another in the given `order`, waiting `tier_delay`
+    /// between them if specified. This allows a controlled shutdown to give more important
+    /// connections extra time to finish their work. Otherwise behaves like
+    /// [`close()`](Self::close).
+    pub async fn close_by_priority(
+        &self,
+        error_code: VarInt,
+        reason: &[u8],
+        order: PriorityOrder,
+        tier_delay: Option<Duration>,
+    ) {
+        let mut tiers = {
+            let mut endpoint = self.inner.state.lock().unwrap();
+            endpoint.connections.close = Some((error_code, Bytes::copy_from_slice(reason)));
+            let mut tiers = BTreeMap::<i32, Vec<ConnectionHandle>>::new();
+            for (&handle, conn) in &endpoint.connections.refs {
+                if let Some(priority) = conn.transmit_priority() {
+                    tiers.entry(priority).or_default().push(handle);
+                }
+            }
+            tiers.into_values().collect::<Vec<_>>()
+        };
+        self.inner.shared.incoming.notify_waiters();
+        if order == PriorityOrder::HighestFirst {
+            tiers.reverse();
+        }
+
+        for (i, handles) in tiers.iter().enumerate() {
+            if let (true, Some(delay)) = (i > 0, tier_delay) {
+                Sleep(self.runtime.new_timer(Instant::now() + delay)).await;
+            }
+            self.close_connections(handles, error_code, reason);
+        }
+    }
+
+    /// Close a set of this endpoint's connections immediately
+    ///
+    /// Behaves like calling [`Connection::close()`] on each connection identified by `handles`,
+    /// but only accesses the endpoint once. Returns the number of connections that were found,
+    /// excluding those which had already been cleaned up.
+    ///
+    /// [`Connection::close()`]: crate::Connection::close
+    pub fn close_connections(
+        &self,
+        handles: &[ConnectionHandle],
+        error_code: VarInt,
+        reason: &[u8],
+    ) -> usize {
+        let reason = Bytes::copy_from_slice(reason);
+        let endpoint = self.inner.state.lock().unwrap();
+        handles
+            .iter()
+            .filter_map(|handle| endpoint.connections.senders.get(handle))
+            .filter(|sender| {
+                sender
+                    .send(ConnectionEvent::Close {
+                        error_code,
+                        reason: reason.clone(),
+                    })
+                    .is_ok()
+            })
+            .count()
+    }
+
+    /// Call `f` on every established connection of this endpoint
+    ///
+    /// Suited to bulk operations such as adjusting settings or collecting statistics across all
+    /// connections, without having to keep track of every [`Connection`] separately. Connections
+    /// which are still handshaking or have been closed are skipped.
+    ///
+    /// The endpoint is locked for the duration, so `f` must not call any method of this
+    /// `Endpoint`, or of a [`Connecting`] or [`Connection`] belonging to it that accesses the
+    /// endpoint, or it will deadlock. Methods of the [`Connection`] passed to `f` are safe to call.
+    /// Returns the number of connections visited.
+    pub fn for_each_connection(&self, mut f: impl FnMut(&Connection)) -> usize {
+        let endpoint = self.inner.state.lock().unwrap();
+        endpoint
+            .connections
+            .refs
+            .values()
+            .filter(|conn| conn.with_connection(&mut f))
+            .count()
+    }
+
+    /// Statistics of the connection identified by `handle`
+    ///
+    /// Allows monitoring code keyed by [`Connection::handle()`] to read statistics without
+    /// retaining a [`Connection`]. Returns `None` if no such connection exists, including once it
+    /// has finished draining.
+    pub fn connection_stats(&self, handle: ConnectionHandle) -> Option<ConnectionStats> {
+        let endpoint = self.inner.state.lock().unwrap();
+        endpoint.connections.refs.get(&handle)?.stats()
+    }
+
+    /// The deadline of each connection's pending timer, soonest first
+    ///
+    /// Intended for debugging scheduling issues, e.g. an idle timeout that never seems to fire.
+    /// Lists the deadline each connection's driver is currently waiting for, which is the earliest
+    /// of its loss detection, idle, keep-alive, and other timers. Connections without a timer
+    /// armed are omitted.
+    pub fn timer_snapshot(&self) -> Vec<(ConnectionHandle, Instant)> {
+        let state = self.inner.state.lock().unwrap();
+        let mut timers = state
+            .connections
+            .refs
+            .iter()
+            .filter_map(|(&handle, conn)| Some((handle, conn.timer_deadline()?)))
+            .collect::<Vec<_>>();
+        drop(state);
+        timers.sort_unstable_by_key(|&(handle, deadline)| (deadline, handle));
+        timers
+    }
+
+    /// Have the connection identified by `handle` processed by its driver as soon as possible
+    ///
+    /// The driver handles pending events and timers and transmits whatever the connection can
+    /// send, including data held back for coalescing, without waiting for a write, timer or
+    /// incoming packet to wake it. Useful to make tests deterministic, or to flush a connection
+    /// early. Returns `false` if no such connection exists, including once it has finished
+    /// draining.
+    pub fn drive_connection_now(&self, handle: ConnectionHandle) -> bool {
+        let endpoint = self.inner.state.lock().unwrap();
+        match endpoint.connections.refs.get(&handle) {
+            Some(conn) => conn.wake(),
+            None => false,
+        }
+    }
+
+    /// Whether the underlying socket could not accept the most recent outgoing datagrams
+    ///
+    /// While `true`, outgoing data is being produced faster than the socket can take it,
+    /// regardless of per-connection congestion control.
+    pub fn is_send_blocked(&self) -> bool {
+        self.inner.state.lock().unwrap().send_blocked
+    }
+
+    /// Wait until the underlying socket is accepting writes
+    ///
+    /// Completes immediately unless [`is_send_blocked()`](Self::is_send_blocked) is `true`.
+    /// Applications producing data for many connections sharing a congested uplink can use this
+    /// to pause production while the socket is backed up. Also completes if the endpoint's driver
+    /// has stopped.
+    pub async fn writable(&self) {
+        loop {
+            {
+                let endpoint = self.inner.state.lock().unwrap();
+                if !endpoint.send_blocked || endpoint.driver_lost {
+                    return;
+                }
+                // Construct the future while the lock is held to ensure we can't miss a wakeup
+                self.inner.shared.writable.notified()
+            }
+            .await;
+        }
+    }
+
+    /// How long ago the endpoint was constructed
+    pub fn uptime(&self) -> Duration {
+        self.inner.created.elapsed()
+    }
+
+    /// Returns endpoint statistics
+    pub fn stats(&self) -> EndpointStats {
+        let endpoint = self.inner.state.lock().unwrap();
+        let mut stats = endpoint.stats;
+        stats.stateless_resets_sent = endpoint.inner.stateless_resets_sent();
+        stats.tokens_issued =
+            endpoint.inner.retry_tokens_issued() + endpoint.inner.new_tokens_issued();
+        stats.tokens_validated =
+            endpoint.inner.retry_tokens_validated() + endpoint.inner.new_tokens_validated();
+        stats.alpn_rejections = endpoint.inner.alpn_rejections();
+        stats.handshake_rate_rejections = endpoint.inner.handshake_rate_rejections();
+        let timers = &endpoint.connections.timer_counters;
+        stats.armed_timers = timers.armed.load(Ordering::Relaxed) as u64;
+        stats.timer_updates = timers.resets.load(Ordering::Relaxed);
+        let datagrams = &endpoint.connections.datagram_budget;
+        stats.buffered_datagrams = datagrams.buffered() as u64;
+        stats.datagrams_dropped = datagrams.dropped();
+        stats.max_connections = endpoint.connections.max_connections as u64;
+        stats.handshakes.in_progress = endpoint.connections.handshaking.len() as u64;
+        stats.handshakes.established = endpoint.connections.established.len() as u64;
+        drop(endpoint);
+        stats.outgoing_handshakes = self.inner.shared.handshake_limiter.in_progress() as u64;
+        stats
+    }
+
+    /// Statistics of the endpoint and each of its connections, collected at once
+    ///
+    /// Intended for one-shot diagnostic dumps. With the `serde` feature enabled, the result can be
+    /// serialized to e.g. JSON as is.
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
+        let endpoint = self.stats();
+        let state = self.inner.state.lock().unwrap();
+        let mut connections = state
+            .connections
+            .refs
+            .iter()
+            .filter_map(|(&handle, conn)| {
+                Some(ConnectionStatsSnapshot {
+                    handle,
+                    stats: conn.stats()?,
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(state);
+        connections.sort_unstable_by_key(|x| x.handle);
+        StatsSnapshot {
+            endpoint,
+            connections,
+        }
+    }
+
+    /// Zero the cumulative counters in [`EndpointStats`]
+    ///
+    /// Allows metrics to be scraped with interval semantics by calling this after each
+    /// [`stats()`](Self::stats). Gauges reflecting the current state of the endpoint, such as
+    /// `armed_timers` and `buffered_datagrams`, and the `max_connections` high-water mark are
+    /// unaffected.
+    pub fn reset_stats(&self) {
+        let mut endpoint = self.inner.state.lock().unwrap();
+        endpoint.stats = EndpointStats::default();
+        endpoint.inner.reset_stats();
+        endpoint
+            .connections
+            .timer_counters
+            .resets
+            .store(0, Ordering::Relaxed);
+        endpoint.connections.datagram_budget.reset_dropped();
+    }
+
     /// Wait for all connections on the endpoint to be cleanly shut down
     ///
     /// Waiting for this condition before exiting ensures that a good-faith effort is made to notify
@@ -275,6 +937,67 @@ impl Endpoint {
             self.inner.shared.idle.notified().await;
         }
     }
+
+    /// Wait until fewer than `n` connections on the endpoint remain
+    ///
+    /// Like [`wait_idle()`](Self::wait_idle), but resolves once the number of connections which
+    /// haven't been fully shut down drops below `n`, rather than to zero. Useful for multi-phase
+    /// shutdown, e.g. to proceed once most connections are closed without waiting out a few slow
+    /// peers. `wait_until_connections_below(1)` is equivalent to `wait_idle()`, while an `n` of 0
+    /// never resolves.
+    pub async fn wait_until_connections_below(&self, n: usize) {
+        loop {
+            {
+                let endpoint = self.inner.state.lock().unwrap();
+                if endpoint.connections.len() < n {
+                    break;
+                }
+                // Construct the future while the lock is held to ensure we can't miss a wakeup
+                self.inner.shared.idle.notified()
+            }
+            .await;
+        }
+    }
+
+    /// Wait until the endpoint has at least one connection
+    ///
+    /// Resolves as soon as a connection is initiated or an incoming connection attempt is
+    /// received, without waiting for its handshake to complete. The inverse of
+    /// [`wait_idle()`](Self::wait_idle), useful for synchronizing on startup.
+    pub async fn wait_for_connection(&self) {
+        let added = self.inner.state.lock().unwrap().connections.added.clone();
+        loop {
+            {
+                let endpoint = self.inner.state.lock().unwrap();
+                if !endpoint.connections.refs.is_empty() {
+                    break;
+                }
+                // Construct the future while the lock is held to ensure we can't miss a wakeup
+                added.notified()
+            }
+            .await;
+        }
+    }
+}
+
+/// Order in which [`Endpoint::close_by_priority()`] closes connections
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PriorityOrder {
+    /// Close the connections with the lowest priority first
+    LowestFirst,
+    /// Close the connections with the highest priority first
+    HighestFirst,
+}
+
+/// Adapts an `AsyncTimer` into a future
+struct Sleep(Pin<Box<dyn AsyncTimer>>);
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        self.0.as_mut().poll(cx)
+    }
 }
 
 /// A future that drives IO on an endpoint
@@ -302,10 +1025,44 @@ impl Future for EndpointDriver {
         }
 
         let now = Instant::now();
-        let mut keep_going = false;
-        keep_going |= endpoint.drive_recv(cx, now)?;
-        keep_going |= endpoint.handle_events(cx, &self.0.shared);
-        keep_going |= endpoint.drive_send(cx)?;
+        // Intermediate timestamps are only taken when slow polls are being looked for
+        let slow_poll_threshold = endpoint.inner.config().get_slow_poll_threshold();
+        let timestamp = || slow_poll_threshold.map(|_| Instant::now());
+        let recv_more = endpoint.drive_recv(cx, now)?;
+        let recv_done = timestamp();
+        let events_more = endpoint.handle_events(cx, &self.0.shared);
+        endpoint.sync_router();
+        endpoint.drive_rebind_pings(cx);
+        let events_done = timestamp();
+        let send_more = endpoint.drive_send(cx, &self.0.shared)?;
+        if let (Some(threshold), Some(recv_done), Some(events_done)) =
+            (slow_poll_threshold, recv_done, events_done)
+        {
+            let send_done = Instant::now();
+            let elapsed = send_done - now;
+            if elapsed > threshold {
+                tracing::warn!(
+                    ?elapsed,
+                    recv = ?(recv_done - now),
+                    connections = ?(events_done - recv_done),
+                    send = ?(send_done - events_done),
+                    "slow endpoint driver poll"
+                );
+            }
+        }
+        let keep_going = recv_more || events_more || send_more;
+        if keep_going {
+            tracing::trace!(
+                recv_more,
+                events_more,
+                send_more,
+                "endpoint driver yielding with more work"
+            );
+            let stats = &mut endpoint.stats.driver_yields;
+            stats.recv += recv_more as u64;
+            stats.events += events_more as u64;
+            stats.send += send_more as u64;
+        }
 
         if !endpoint.incoming.is_empty() {
             self.0.shared.incoming.notify_waiters();
@@ -330,7 +1087,10 @@ impl Drop for EndpointDriver {
     fn drop(&mut self) {
         let mut endpoint = self.0.state.lock().unwrap();
         endpoint.driver_lost = true;
+        endpoint.router = None;
+        endpoint.offload = None;
         self.0.shared.incoming.notify_waiters();
+        self.0.shared.writable.notify_waiters();
         // Drop all outgoing channels, signaling the termination of the endpoint to the associated
         // connections.
         endpoint.connections.senders.clear();
@@ -341,41 +1101,471 @@ impl Drop for EndpointDriver {
 pub(crate) struct EndpointInner {
     pub(crate) state: Mutex<State>,
     pub(crate) shared: Shared,
+    /// When the endpoint was constructed
+    created: Instant,
 }
 
 #[derive(Debug)]
 pub(crate) struct State {
-    socket: Box<dyn AsyncUdpSocket>,
+    /// The endpoint's sockets, starting with the primary one used by outgoing connections
+    sockets: Vec<SocketState>,
+    /// The index into `sockets` of connections accepted on a socket other than the primary one
+    connection_sockets: FxHashMap<ConnectionHandle, usize>,
+    /// Source addresses set by `Connection::set_preferred_local_ip`
+    preferred_local_ips: FxHashMap<ConnectionHandle, IpAddr>,
+    /// The index into `sockets` of the socket to receive from first
+    next_recv_socket: usize,
     udp_state: Arc<UdpState>,
     inner: proto::Endpoint,
-    outgoing: VecDeque<proto::Transmit>,
+    /// Datagrams produced by connections which are waiting to join a socket's `outgoing`
+    transmit_queue: TransmitQueue,
     incoming: VecDeque<Connecting>,
     driver: Option<Waker>,
     ipv6: bool,
+    /// The only remote address reachable through the primary socket, if it's connected
+    connected_peer: Option<SocketAddr>,
     connections: ConnectionSet,
     events: mpsc::UnboundedReceiver<(ConnectionHandle, EndpointEvent)>,
     /// Number of live handles that can be used to initiate or handle I/O; excludes the driver
     ref_count: usize,
     driver_lost: bool,
+    /// Whether the most recent attempt to send on the sockets would have blocked
+    send_blocked: bool,
     recv_limiter: WorkLimiter,
-    recv_buf: Box<[u8]>,
+    /// Datagrams already read from a socket whose handling was deferred by `recv_limiter`, along
+    /// with the index into `sockets` they were received on
+    recv_backlog: VecDeque<(usize, ForwardedDatagram)>,
+    /// Allocated by `EndpointConfig::buffer_pool`, if set
+    recv_buf: Vec<u8>,
+    /// Metadata of the datagrams received into `recv_buf`, kept to avoid reinitializing it on
+    /// every poll
+    recv_metas: [RecvMeta; BATCH_SIZE],
     send_limiter: WorkLimiter,
     runtime: Arc<dyn Runtime>,
+    stats: EndpointStats,
+    connection_attempt: Option<AttemptCallback>,
+    new_connection: Option<NewConnectionCallback>,
+    router: Option<RouterMembership>,
+    /// Receives outgoing datagrams in place of the sockets, if set by `offload_transmits`
+    offload: Option<OffloadSender>,
+    /// Read for incoming datagrams in place of the sockets, if set by `set_datagram_source`
+    datagram_source: Option<Box<dyn DatagramSource>>,
+    /// Indices into `sockets` of rebound sockets whose connections are yet to be pinged
+    rebind_pings: Vec<usize>,
+    /// When connections were last pinged after a rebind
+    last_rebind_ping: Option<Instant>,
+    /// Fires when deferred pings for `rebind_pings` should be sent
+    rebind_ping_timer: Option<Pin<Box<dyn AsyncTimer>>>,
+}
+
+#[derive(Debug)]
+struct SocketState {
+    socket: Box<dyn AsyncUdpSocket>,
+    /// Local address the socket is bound to
+    addr: SocketAddr,
+    /// Batch of datagrams being written to the socket
+    outgoing: VecDeque<proto::Transmit>,
+    /// Whether the socket would have blocked during the current `drive_send` call
+    blocked: bool,
+}
+
+impl SocketState {
+    fn new(socket: Box<dyn AsyncUdpSocket>, addr: SocketAddr) -> Self {
+        Self {
+            socket,
+            addr,
+            outgoing: VecDeque::new(),
+            blocked: false,
+        }
+    }
+}
+
+/// Details of an incoming connection attempt
+///
+/// See [`Endpoint::set_connection_attempt_callback()`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ConnectionAttempt {
+    /// The address the attempt came from
+    pub remote: SocketAddr,
+    /// The local IP address the attempt was sent to, if known
+    pub local_ip: Option<IpAddr>,
+    /// When the endpoint received the attempt's first packet
+    pub time: Instant,
+}
+
+type AttemptFn = dyn Fn(&ConnectionAttempt) + Send;
+
+struct AttemptCallback(Box<AttemptFn>);
+
+impl fmt::Debug for AttemptCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AttemptCallback")
+    }
+}
+
+type NewConnectionFn = dyn Fn(ConnectionHandle, SocketAddr) + Send;
+
+struct NewConnectionCallback(Box<NewConnectionFn>);
+
+impl fmt::Debug for NewConnectionCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NewConnectionCallback")
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct Shared {
     incoming: Notify,
     idle: Notify,
+    /// Notified when the socket accepts writes again after being blocked
+    writable: Notify,
+    handshake_limiter: Arc<HandshakeLimiter>,
+}
+
+/// Statistics on [`Endpoint`] activity
+///
+/// Most fields are cumulative counters, which [`Endpoint::reset_stats()`] sets back to zero. The
+/// exceptions are the gauges `outgoing_handshakes`, `armed_timers` and `buffered_datagrams`, which
+/// describe the endpoint's current state, and the `max_connections` high-water mark.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(proto::serde::Serialize),
+    serde(crate = "proto::serde")
+)]
+#[non_exhaustive]
+pub struct EndpointStats {
+    /// The amount of outgoing connections currently performing a handshake
+    ///
+    /// Connections waiting for a slot due to
+    /// [`EndpointConfig::max_concurrent_outgoing_handshakes()`] are not included.
+    pub outgoing_handshakes: u64,
+    /// The amount of buffers returned by the socket, each holding one or more UDP datagrams
+    pub recv_buffers: u64,
+    /// The amount of UDP datagrams received
+    ///
+    /// Divided by `recv_buffers`, this gives the average number of datagrams coalesced into each
+    /// buffer by Generic Receive Offload (GRO).
+    pub recv_datagrams: u64,
+    /// The amount of received buffers which held more than one datagram due to GRO
+    ///
+    /// Each such buffer is also logged at trace level with its sender, segment count and stride,
+    /// to tell which datagrams arrived coalesced.
+    pub gro_coalesced_buffers: u64,
+    /// The amount of UDP datagrams produced by connections for sending
+    pub sent_datagrams: u64,
+    /// The amount of QUIC packets contained in `sent_datagrams`
+    ///
+    /// Divided by `sent_datagrams`, this gives the average number of QUIC packets coalesced into
+    /// each datagram. Values close to 1 during handshakes suggest that handshake packets are not
+    /// being coalesced effectively.
+    pub sent_packets: u64,
+    /// Why drained connections were closed
+    pub closes: CloseStats,
+    /// Why the endpoint driver rescheduled itself immediately rather than waiting for I/O
+    pub driver_yields: DriverYieldStats,
+    /// The amount of stateless resets sent in response to packets for unknown connections
+    ///
+    /// See [`EndpointConfig::stateless_reset()`].
+    pub stateless_resets_sent: u64,
+    /// The amount of address validation tokens issued to clients in Retry packets and NEW_TOKEN
+    /// frames
+    ///
+    /// Retry packets are sent when [`ServerConfig::use_retry`](proto::ServerConfig::use_retry) is
+    /// set, and NEW_TOKEN frames as configured by
+    /// [`ServerConfig::new_tokens`](proto::ServerConfig::new_tokens).
+    pub tokens_issued: u64,
+    /// The amount of address validation tokens presented by clients which were accepted
+    pub tokens_validated: u64,
+    /// The amount of connection attempts refused for not offering a required ALPN protocol
+    ///
+    /// See [`ServerConfig::required_alpn_protocols()`](proto::ServerConfig::required_alpn_protocols).
+    pub alpn_rejections: u64,
+    /// The amount of connection attempts dropped for exceeding the per-source rate limit
+    ///
+    /// See [`EndpointConfig::max_handshake_rate_per_ip()`].
+    pub handshake_rate_rejections: u64,
+    /// The amount of connections currently waiting on a timer
+    pub armed_timers: u64,
+    /// The amount of received application datagrams currently buffered by all connections,
+    /// waiting to be read
+    pub buffered_datagrams: u64,
+    /// The amount of received application datagrams dropped to stay within
+    /// [`EndpointConfig::max_buffered_datagrams()`]
+    pub datagrams_dropped: u64,
+    /// The amount of times a connection timer was created or moved to a new deadline
+    ///
+    /// Sample this periodically to derive the rate at which timers are being rescheduled, which
+    /// grows with the number of active connections and how often their deadlines change.
+    pub timer_updates: u64,
+    /// The largest number of connections the endpoint has had at once since it was created
+    ///
+    /// Unlike periodic samples of the current count, this captures brief spikes, making it
+    /// suitable for capacity planning.
+    pub max_connections: u64,
+    /// The amount of times outgoing datagrams were held back because the socket's send buffer was
+    /// full, or a [`TransmitOffload`] was at capacity
+    ///
+    /// A value which keeps growing indicates that throughput is limited by the socket, which a
+    /// larger [`EndpointConfig::socket_send_buffer_size()`] may help with. Compare with the
+    /// per-connection `congestion_blocked` count in [`ConnectionStats`], which grows when
+    /// throughput is limited by congestion control instead.
+    pub socket_send_blocked: u64,
+    /// Outcomes of the handshakes of incoming and outgoing connections
+    pub handshakes: HandshakeStats,
+}
+
+/// Timer activity shared between an endpoint and its connections
+#[derive(Debug, Default)]
+pub(crate) struct TimerCounters {
+    /// Number of connections with a pending timer
+    pub(crate) armed: AtomicUsize,
+    /// Number of timers created or reset
+    pub(crate) resets: AtomicU64,
+}
+
+/// Counts of the reasons the endpoint driver yielded with work remaining
+///
+/// The driver bounds the work done in each poll to preserve fairness with other tasks, waking
+/// itself immediately if that bound is reached. Steady growth in these counters under light load
+/// indicates a busy loop. A single poll may count towards more than one reason. Each yield is also
+/// logged at `trace` level.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(proto::serde::Serialize),
+    serde(crate = "proto::serde")
+)]
+#[non_exhaustive]
+pub struct DriverYieldStats {
+    /// The socket may have more datagrams to receive
+    pub recv: u64,
+    /// Connections may have more events for the endpoint
+    pub events: u64,
+    /// More datagrams may be sent without blocking
+    pub send: u64,
+}
+
+/// Counts of connection handshakes by outcome
+///
+/// A failure rate concentrated in one kind of error points at its cause: timeouts at packet loss
+/// or an unreachable peer, crypto errors at certificate or ALPN misconfiguration, and version
+/// mismatches at incompatible peers.
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(proto::serde::Serialize),
+    serde(crate = "proto::serde")
+)]
+#[non_exhaustive]
+pub struct HandshakeStats {
+    /// Handshakes which completed successfully
+    pub succeeded: u64,
+    /// Handshakes which failed because the peer stopped responding
+    pub timed_out: u64,
+    /// Handshakes which failed in the TLS layer
+    ///
+    /// This includes certificate validation and protocol negotiation failures. Failures detected
+    /// by the peer are usually reported before 1-RTT keys are available, which hides their error
+    /// code, so they count towards `other_failure` instead.
+    pub crypto_error: u64,
+    /// Handshakes which failed due to the peer not supporting any compatible version
+    pub version_mismatch: u64,
+    /// Handshakes which failed for any other reason, e.g. being closed by either application or
+    /// refused by the server
+    pub other_failure: u64,
+    /// Handshakes currently in progress
+    ///
+    /// Unlike the other fields, this is a gauge, unaffected by [`Endpoint::reset_stats()`].
+    pub in_progress: u64,
+    /// Connections whose handshake succeeded and which haven't been fully shut down yet
+    ///
+    /// A gauge like `in_progress`. Many handshakes in progress compared to established
+    /// connections may indicate a flood of connection attempts.
+    pub established: u64,
+}
+
+impl HandshakeStats {
+    fn record(&mut self, error: Option<&ConnectionError>) {
+        let counter = match error {
+            None => &mut self.succeeded,
+            Some(ConnectionError::TimedOut) => &mut self.timed_out,
+            Some(ConnectionError::VersionMismatch) => &mut self.version_mismatch,
+            Some(ConnectionError::TransportError(proto::TransportError { code, .. }))
+            | Some(ConnectionError::ConnectionClosed(proto::ConnectionClose {
+                error_code: code,
+                ..
+            })) if is_crypto_error(*code) => &mut self.crypto_error,
+            Some(_) => &mut self.other_failure,
+        };
+        *counter += 1;
+    }
+}
+
+/// Whether `code` carries a TLS alert
+fn is_crypto_error(code: proto::TransportErrorCode) -> bool {
+    (0x100..0x200).contains(&u64::from(code))
+}
+
+/// Counts of connections closed for each kind of [`ConnectionError`]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(proto::serde::Serialize),
+    serde(crate = "proto::serde")
+)]
+#[non_exhaustive]
+pub struct CloseStats {
+    /// Connections which timed out after being idle
+    pub timed_out: u64,
+    /// Connections closed by the local application, including those implicitly closed by
+    /// dropping every handle
+    pub locally_closed: u64,
+    /// Connections closed by the peer's application
+    pub peer_application_closed: u64,
+    /// Connections aborted by the peer's QUIC stack
+    pub peer_transport_closed: u64,
+    /// Connections aborted locally because the peer violated the protocol
+    pub transport_error: u64,
+    /// Connections reset by the peer, usually because it restarted
+    ///
+    /// This is the number of valid stateless resets received, each of which closes a connection
+    /// with [`ConnectionError::Reset`].
+    pub reset: u64,
+    /// Connections which failed due to the peer not supporting any compatible version
+    pub version_mismatch: u64,
+}
+
+impl CloseStats {
+    fn record(&mut self, reason: &ConnectionError) {
+        let counter = match reason {
+            ConnectionError::TimedOut => &mut self.timed_out,
+            ConnectionError::LocallyClosed => &mut self.locally_closed,
+            ConnectionError::ApplicationClosed(_) => &mut self.peer_application_closed,
+            ConnectionError::ConnectionClosed(_) => &mut self.peer_transport_closed,
+            ConnectionError::TransportError(_) => &mut self.transport_error,
+            ConnectionError::Reset => &mut self.reset,
+            ConnectionError::VersionMismatch => &mut self.version_mismatch,
+        };
+        *counter += 1;
+    }
+}
+
+/// Statistics of an [`Endpoint`] and its connections
+///
+/// See [`Endpoint::stats_snapshot()`].
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(proto::serde::Serialize),
+    serde(crate = "proto::serde")
+)]
+#[non_exhaustive]
+pub struct StatsSnapshot {
+    /// Statistics of the endpoint itself
+    pub endpoint: EndpointStats,
+    /// Statistics of each of the endpoint's connections, ordered by handle
+    pub connections: Vec<ConnectionStatsSnapshot>,
+}
+
+/// Statistics of a single connection in a [`StatsSnapshot`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(proto::serde::Serialize),
+    serde(crate = "proto::serde")
+)]
+#[non_exhaustive]
+pub struct ConnectionStatsSnapshot {
+    /// The connection's handle, as returned by [`Connection::handle()`]
+    pub handle: ConnectionHandle,
+    /// The connection's statistics
+    pub stats: ConnectionStats,
+}
+
+/// Bounds the number of concurrent outgoing handshakes
+#[derive(Debug)]
+pub(crate) struct HandshakeLimiter {
+    /// Unset if the number of handshakes is unlimited
+    semaphore: Option<Arc<Semaphore>>,
+    in_progress: AtomicUsize,
+}
+
+impl HandshakeLimiter {
+    fn new(limit: Option<usize>) -> Self {
+        Self {
+            semaphore: limit.map(|x| Arc::new(Semaphore::new(x))),
+            in_progress: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait until another handshake may be started
+    pub(crate) async fn acquire(self: Arc<Self>) -> HandshakePermit {
+        let permit = match self.semaphore {
+            // The semaphore is never closed
+            Some(ref semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        self.in_progress.fetch_add(1, Ordering::Relaxed);
+        HandshakePermit {
+            limiter: self,
+            _permit: permit,
+        }
+    }
+
+    fn in_progress(&self) -> usize {
+        self.in_progress.load(Ordering::Relaxed)
+    }
+}
+
+/// Held by an outgoing connection until its handshake completes or fails
+#[derive(Debug)]
+pub(crate) struct HandshakePermit {
+    limiter: Arc<HandshakeLimiter>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for HandshakePermit {
+    fn drop(&mut self) {
+        self.limiter.in_progress.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 impl State {
-    fn drive_recv<'a>(&'a mut self, cx: &mut Context, now: Instant) -> Result<bool, io::Error> {
+    fn drive_recv(&mut self, cx: &mut Context, now: Instant) -> Result<bool, io::Error> {
         self.recv_limiter.start_cycle();
-        let mut metas = [RecvMeta::default(); BATCH_SIZE];
+        loop {
+            let datagram = match self.router.as_mut().map(|router| router.poll_forwarded(cx)) {
+                Some(Poll::Ready(Some(datagram))) => datagram,
+                _ => break,
+            };
+            self.handle_datagram(now, 0, datagram);
+        }
+        if !self.handle_recv_backlog(now) {
+            self.recv_limiter.finish_cycle();
+            return Ok(true);
+        }
+
+        // Handling datagrams requires `&mut self` while the `IoSliceMut`s borrow `recv_buf`
+        let mut recv_buf = mem::take(&mut self.recv_buf);
+        let result = self.recv_from_socket(cx, now, &mut recv_buf);
+        self.recv_buf = recv_buf;
+        result
+    }
+
+    fn recv_from_socket<'a>(
+        &mut self,
+        cx: &mut Context,
+        now: Instant,
+        recv_buf: &'a mut [u8],
+    ) -> Result<bool, io::Error> {
+        // The `IoSliceMut`s borrow `recv_buf`, so unlike `recv_metas` they can't outlive this call.
+        // Constructing them is cheap and involves no allocation.
         let mut iovs = MaybeUninit::<[IoSliceMut<'a>; BATCH_SIZE]>::uninit();
-        self.recv_buf
-            .chunks_mut(self.recv_buf.len() / BATCH_SIZE)
+        let chunk_len = recv_buf.len() / BATCH_SIZE;
+        recv_buf
+            .chunks_mut(chunk_len)
             .enumerate()
             .for_each(|(i, buf)| unsafe {
                 iovs.as_mut_ptr()
@@ -384,75 +1574,260 @@ impl State {
                     .write(IoSliceMut::<'a>::new(buf));
             });
         let mut iovs = unsafe { iovs.assume_init() };
-        loop {
-            match self.socket.poll_recv(cx, &mut iovs, &mut metas) {
-                Poll::Ready(Ok(msgs)) => {
-                    self.recv_limiter.record_work(msgs);
-                    for (meta, buf) in metas.iter().zip(iovs.iter()).take(msgs) {
-                        let mut data: BytesMut = buf[0..meta.len].into();
-                        while !data.is_empty() {
-                            let buf = data.split_to(meta.stride.min(data.len()));
-                            match self
-                                .inner
-                                .handle(now, meta.addr, meta.dst_ip, meta.ecn, buf)
-                            {
-                                Some((handle, DatagramEvent::NewConnection(conn))) => {
-                                    let conn = self.connections.insert(
-                                        handle,
-                                        conn,
-                                        self.udp_state.clone(),
-                                        self.runtime.clone(),
-                                    );
-                                    self.incoming.push_back(conn);
-                                }
-                                Some((handle, DatagramEvent::ConnectionEvent(event))) => {
-                                    // Ignoring errors from dropped connections that haven't yet been cleaned up
-                                    let _ = self
-                                        .connections
-                                        .senders
-                                        .get_mut(&handle)
-                                        .unwrap()
-                                        .send(ConnectionEvent::Proto(event));
+        // Rotate the starting socket so a busy one can't starve the others. Datagrams from a
+        // custom source are treated as if received on the primary socket.
+        let count = match self.datagram_source {
+            Some(_) => 1,
+            None => self.sockets.len(),
+        };
+        let per_datagram = self.inner.config().get_per_datagram_recv_limit();
+        for offset in 0..count {
+            let index = (self.next_recv_socket + offset) % count;
+            loop {
+                let result = match self.datagram_source {
+                    Some(ref mut source) => source.poll_recv(cx, &mut iovs, &mut self.recv_metas),
+                    None => {
+                        self.sockets[index]
+                            .socket
+                            .poll_recv(cx, &mut iovs, &mut self.recv_metas)
+                    }
+                };
+                match result {
+                    Poll::Ready(Ok(msgs)) => {
+                        if !per_datagram {
+                            self.recv_limiter.record_work(msgs);
+                        }
+                        for (i, buf) in iovs.iter().enumerate().take(msgs) {
+                            let meta = self.recv_metas[i];
+                            self.stats.recv_buffers += 1;
+                            if meta.stride < meta.len {
+                                self.stats.gro_coalesced_buffers += 1;
+                                tracing::trace!(
+                                    remote = %meta.addr,
+                                    segments = (meta.len + meta.stride - 1) / meta.stride,
+                                    stride = meta.stride,
+                                    "received datagrams coalesced by GRO"
+                                );
+                            }
+                            let mut data: BytesMut = buf[0..meta.len].into();
+                            while !data.is_empty() {
+                                let buf = data.split_to(meta.stride.min(data.len()));
+                                self.stats.recv_datagrams += 1;
+                                let datagram = ForwardedDatagram {
+                                    remote: meta.addr,
+                                    local_ip: meta.dst_ip,
+                                    ecn: meta.ecn,
+                                    data: buf,
+                                };
+                                let datagram = match self.router {
+                                    Some(ref router) => {
+                                        match router.route(self.inner.local_cid_len(), datagram) {
+                                            Some(datagram) => datagram,
+                                            None => continue,
+                                        }
+                                    }
+                                    None => datagram,
+                                };
+                                if per_datagram {
+                                    self.recv_backlog.push_back((index, datagram));
+                                } else {
+                                    self.handle_datagram(now, index, datagram);
                                 }
-                                None => {}
                             }
                         }
+                        if per_datagram && !self.handle_recv_backlog(now) {
+                            self.next_recv_socket = (index + 1) % count;
+                            self.recv_limiter.finish_cycle();
+                            return Ok(true);
+                        }
+                    }
+                    Poll::Pending => {
+                        break;
+                    }
+                    // Ignore ECONNRESET as it's undefined in QUIC and may be injected by an
+                    // attacker
+                    Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::ConnectionReset => {
+                        continue;
+                    }
+                    Poll::Ready(Err(e)) => {
+                        return Err(e);
                     }
                 }
-                Poll::Pending => {
-                    break;
+                if !self.recv_limiter.allow_work() {
+                    self.next_recv_socket = (index + 1) % count;
+                    self.recv_limiter.finish_cycle();
+                    return Ok(true);
                 }
-                // Ignore ECONNRESET as it's undefined in QUIC and may be injected by an
-                // attacker
-                Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::ConnectionReset => {
-                    continue;
+            }
+        }
+
+        self.recv_limiter.finish_cycle();
+        Ok(false)
+    }
+
+    /// Handle datagrams from `recv_backlog` one at a time, for as long as `recv_limiter` allows
+    ///
+    /// Returns whether the backlog was emptied.
+    fn handle_recv_backlog(&mut self, now: Instant) -> bool {
+        while let Some((socket, datagram)) = self.recv_backlog.pop_front() {
+            self.handle_datagram(now, socket, datagram);
+            self.recv_limiter.record_work(1);
+            if !self.recv_backlog.is_empty() && !self.recv_limiter.allow_work() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Handle a datagram received on the socket at `socket` in `sockets`
+    fn handle_datagram(&mut self, now: Instant, socket: usize, datagram: ForwardedDatagram) {
+        let ForwardedDatagram {
+            remote,
+            local_ip,
+            ecn,
+            data,
+        } = datagram;
+        let event = self.inner.handle(now, remote, local_ip, ecn, data);
+        // Responses sent by the endpoint itself, e.g. retries and stateless resets, go out the
+        // way the datagram came in
+        while let Some(transmit) = self.inner.poll_transmit() {
+            self.sockets[socket].outgoing.push_back(transmit);
+        }
+        match event {
+            Some((handle, DatagramEvent::NewConnection(conn))) => {
+                if socket != 0 {
+                    self.connection_sockets.insert(handle, socket);
+                }
+                if let Some(AttemptCallback(ref f)) = self.connection_attempt {
+                    f(&ConnectionAttempt {
+                        remote,
+                        local_ip,
+                        time: now,
+                    });
                 }
-                Poll::Ready(Err(e)) => {
-                    return Err(e);
+                let conn = self.connections.insert(
+                    handle,
+                    conn,
+                    self.udp_state.clone(),
+                    self.runtime.clone(),
+                    None,
+                );
+                if let Some(NewConnectionCallback(ref f)) = self.new_connection {
+                    f(handle, remote);
                 }
+                self.incoming.push_back(conn);
             }
-            if !self.recv_limiter.allow_work() {
-                self.recv_limiter.finish_cycle();
-                return Ok(true);
+            Some((handle, DatagramEvent::ConnectionEvent(event))) => {
+                // Ignoring errors from dropped connections that haven't yet been cleaned up
+                let _ = self
+                    .connections
+                    .senders
+                    .get_mut(&handle)
+                    .unwrap()
+                    .send(ConnectionEvent::Proto(event));
             }
+            None => {}
         }
+    }
 
-        self.recv_limiter.finish_cycle();
-        Ok(false)
+    /// Translate `addr` into the form used by the socket, failing if it can't be reached through it
+    fn remote_for_socket(&self, addr: SocketAddr) -> Result<SocketAddr, ConnectError> {
+        if addr.port() == 0 || (addr.is_ipv6() && !self.ipv6) {
+            return Err(ConnectError::InvalidRemoteAddress(addr));
+        }
+        let addr = if self.ipv6 {
+            SocketAddr::V6(ensure_ipv6(addr))
+        } else {
+            addr
+        };
+        if self.connected_peer.map_or(false, |peer| peer != addr) {
+            return Err(ConnectError::InvalidRemoteAddress(addr));
+        }
+        Ok(addr)
+    }
+
+    /// Send deferred pings for rebound sockets once `rebind_ping_timer` fires
+    fn drive_rebind_pings(&mut self, cx: &mut Context) {
+        let timer = match self.rebind_ping_timer {
+            Some(ref mut timer) => timer,
+            None => return,
+        };
+        if timer.as_mut().poll(cx).is_pending() {
+            return;
+        }
+        self.rebind_ping_timer = None;
+        self.send_rebind_pings(Instant::now());
+    }
+
+    /// Generate some activity on connections using rebound sockets so peers notice the rebind
+    fn send_rebind_pings(&mut self, now: Instant) {
+        for (ch, sender) in &self.connections.senders {
+            let index = self.connection_sockets.get(ch).map_or(0, |&i| i);
+            if self.rebind_pings.contains(&index) {
+                // Ignoring errors from dropped connections
+                let _ = sender.send(ConnectionEvent::Ping);
+            }
+        }
+        self.rebind_pings.clear();
+        self.last_rebind_ping = Some(now);
     }
 
-    fn drive_send(&mut self, cx: &mut Context) -> Result<bool, io::Error> {
+    /// Publish changes to the connection IDs issued by this endpoint to its router, if any
+    fn sync_router(&mut self) {
+        if let Some(ref router) = self.router {
+            let inner = &mut self.inner;
+            router.update(iter::from_fn(|| inner.poll_cid_change()));
+        }
+    }
+
+    fn drive_send(&mut self, cx: &mut Context, shared: &Shared) -> Result<bool, io::Error> {
+        if self.offload.as_ref().map_or(false, |x| x.is_abandoned()) {
+            self.offload = None;
+        }
         self.send_limiter.start_cycle();
+        for socket in &mut self.sockets {
+            socket.blocked = false;
+        }
 
-        let result = loop {
-            while self.outgoing.len() < BATCH_SIZE {
-                match self.inner.poll_transmit() {
-                    Some(x) => self.outgoing.push_back(x),
+        let result = 'send: loop {
+            // Transmits for sockets which would block don't count towards the batch, so that one
+            // blocked socket can't starve the others
+            let mut queued = self
+                .sockets
+                .iter()
+                .filter(|s| !s.blocked)
+                .map(|s| s.outgoing.len())
+                .sum::<usize>();
+            while queued < BATCH_SIZE {
+                // Transmits for a blocked socket which already has a full batch stay in the queue,
+                // where scheduling still applies, until it drains
+                let full = |s: &SocketState| s.blocked && s.outgoing.len() >= BATCH_SIZE;
+                if self.sockets.iter().all(full) {
+                    break;
+                }
+                let (sockets, connection_sockets) = (&self.sockets, &self.connection_sockets);
+                let (ch, mut transmit) = match self.transmit_queue.pop(|ch| {
+                    !full(&sockets[connection_sockets.get(&ch).map_or(0, |&index| index)])
+                }) {
+                    Some(x) => x,
                     None => break,
+                };
+                if let Some(&ip) = self.preferred_local_ips.get(&ch) {
+                    transmit.src_ip = Some(ip);
+                }
+                let index = self.connection_sockets.get(&ch).map_or(0, |&index| index);
+                let socket = &mut self.sockets[index];
+                socket.outgoing.push_back(transmit);
+                if !socket.blocked {
+                    queued += 1;
                 }
             }
 
-            if self.outgoing.is_empty() {
+            if queued == 0 {
+                if self.sockets.iter().any(|s| s.blocked) {
+                    self.send_blocked = true;
+                    self.stats.socket_send_blocked += 1;
+                }
                 break Ok(false);
             }
 
@@ -460,23 +1835,50 @@ impl State {
                 break Ok(true);
             }
 
-            match self
-                .socket
-                .poll_send(&self.udp_state, cx, self.outgoing.as_slices().0)
-            {
-                Poll::Ready(Ok(n)) => {
-                    self.outgoing.drain(..n);
-                    // We count transmits instead of `poll_send` calls since the cost
-                    // of a `sendmmsg` still linearily increases with number of packets.
-                    self.send_limiter.record_work(n);
-                }
-                Poll::Pending => {
-                    break Ok(false);
+            let pool = self.inner.config().get_buffer_pool();
+            let mut sent = 0;
+            for socket in &mut self.sockets {
+                if socket.blocked || socket.outgoing.is_empty() {
+                    continue;
                 }
-                Poll::Ready(Err(e)) => {
-                    break Err(e);
+                let result = match self.offload {
+                    Some(ref offload) => offload
+                        .poll_send(cx, socket.addr, &mut socket.outgoing)
+                        .map(Ok),
+                    None => socket
+                        .socket
+                        .poll_send(&self.udp_state, cx, socket.outgoing.as_slices().0)
+                        .map_ok(|n| {
+                            // Platforms may send fewer than offered; the rest stays queued in
+                            // order for the next pass
+                            for transmit in socket.outgoing.drain(..n) {
+                                if let Some(pool) = pool {
+                                    pool.release(transmit.contents);
+                                }
+                            }
+                            n
+                        }),
+                };
+                match result {
+                    Poll::Ready(Ok(0)) | Poll::Pending => socket.blocked = true,
+                    Poll::Ready(Ok(n)) => sent += n,
+                    Poll::Ready(Err(e)) => {
+                        break 'send Err(e);
+                    }
                 }
             }
+
+            if sent == 0 {
+                // Every socket we tried would block; queue up work for any others
+                continue;
+            }
+            if self.send_blocked {
+                self.send_blocked = false;
+                shared.writable.notify_waiters();
+            }
+            // We count transmits instead of `poll_send` calls since the cost
+            // of a `sendmmsg` still linearily increases with number of packets.
+            self.send_limiter.record_work(sent);
         };
 
         self.send_limiter.finish_cycle();
@@ -492,9 +1894,15 @@ impl State {
                     Proto(e) => {
                         if e.is_drained() {
                             self.connections.senders.remove(&ch);
-                            if self.connections.is_empty() {
-                                shared.idle.notify_waiters();
-                            }
+                            self.connections.refs.remove(&ch);
+                            self.connections.handshaking.remove(&ch);
+                            self.connections.established.remove(&ch);
+                            self.transmit_queue.forget(ch);
+                            self.connection_sockets.remove(&ch);
+                            self.preferred_local_ips.remove(&ch);
+                            // Wakes `wait_idle` and `wait_until_connections_below`, which recheck
+                            // the number of connections themselves
+                            shared.idle.notify_waiters();
                         }
                         if let Some(event) = self.inner.handle_event(ch, e) {
                             // Ignoring errors from dropped connections that haven't yet been cleaned up
@@ -506,7 +1914,34 @@ impl State {
                                 .send(ConnectionEvent::Proto(event));
                         }
                     }
-                    Transmit(t) => self.outgoing.push_back(t),
+                    Transmit(t, packets) => {
+                        self.stats.sent_datagrams += match t.segment_size {
+                            None => 1,
+                            Some(s) => ((t.contents.len() + s - 1) / s) as u64, // round up
+                        };
+                        self.stats.sent_packets += packets;
+                        self.transmit_queue.push(ch, t);
+                    }
+                    TransmitSchedule { weight, priority } => {
+                        self.transmit_queue.configure(ch, weight, priority)
+                    }
+                    Closed(reason) => self.stats.closes.record(&reason),
+                    Handshake(result) => {
+                        if self.connections.handshaking.remove(&ch) {
+                            self.stats.handshakes.record(result.as_ref().err());
+                            if result.is_ok() {
+                                self.connections.established.insert(ch);
+                            }
+                        }
+                    }
+                    PreferLocalIp(Some(ip)) => {
+                        if self.connections.senders.contains_key(&ch) {
+                            self.preferred_local_ips.insert(ch, ip);
+                        }
+                    }
+                    PreferLocalIp(None) => {
+                        self.preferred_local_ips.remove(&ch);
+                    }
                 },
                 Poll::Ready(None) => unreachable!("EndpointInner owns one sender"),
                 Poll::Pending => {
@@ -523,10 +1958,24 @@ impl State {
 struct ConnectionSet {
     /// Senders for communicating with the endpoint's connections
     senders: FxHashMap<ConnectionHandle, mpsc::UnboundedSender<ConnectionEvent>>,
+    /// References to the endpoint's connections, for `Endpoint::for_each_connection`
+    refs: FxHashMap<ConnectionHandle, WeakConnection>,
     /// Stored to give out clones to new ConnectionInners
     sender: mpsc::UnboundedSender<(ConnectionHandle, EndpointEvent)>,
     /// Set if the endpoint has been manually closed
     close: Option<(VarInt, Bytes)>,
+    /// Timer activity of the endpoint's connections
+    timer_counters: Arc<TimerCounters>,
+    /// Received datagrams buffered by the endpoint's connections
+    datagram_budget: Arc<DatagramBudget>,
+    /// Notified when a connection is added, for `Endpoint::wait_for_connection`
+    added: Arc<Notify>,
+    /// The largest number of connections the endpoint has had at once
+    max_connections: usize,
+    /// Connections which haven't reported the outcome of their handshake yet
+    handshaking: FxHashSet<ConnectionHandle>,
+    /// Connections whose handshake succeeded
+    established: FxHashSet<ConnectionHandle>,
 }
 
 impl ConnectionSet {
@@ -536,6 +1985,7 @@ impl ConnectionSet {
         conn: proto::Connection,
         udp_state: Arc<UdpState>,
         runtime: Arc<dyn Runtime>,
+        handshake_limiter: Option<Arc<HandshakeLimiter>>,
     ) -> Connecting {
         let (send, recv) = mpsc::unbounded_channel();
         if let Some((error_code, ref reason)) = self.close {
@@ -546,12 +1996,69 @@ impl ConnectionSet {
             .unwrap();
         }
         self.senders.insert(handle, send);
-        Connecting::new(handle, conn, self.sender.clone(), recv, udp_state, runtime)
+        self.handshaking.insert(handle);
+        self.max_connections = self.max_connections.max(self.senders.len());
+        let connecting = Connecting::new(
+            handle,
+            conn,
+            self.sender.clone(),
+            recv,
+            udp_state,
+            runtime,
+            handshake_limiter,
+            self.timer_counters.clone(),
+            self.datagram_budget.clone(),
+        );
+        self.refs.insert(handle, connecting.downgrade());
+        self.added.notify_waiters();
+        connecting
     }
 
     fn is_empty(&self) -> bool {
         self.senders.is_empty()
     }
+
+    fn len(&self) -> usize {
+        self.senders.len()
+    }
+}
+
+/// Allocate a zeroed receive buffer of `len` bytes from the configured pool, if any
+fn alloc_recv_buf(config: &EndpointConfig, len: usize) -> Vec<u8> {
+    match config.get_buffer_pool() {
+        Some(pool) => {
+            let mut buf = pool.allocate(len);
+            buf.clear();
+            buf.resize(len, 0);
+            buf
+        }
+        None => vec![0; len],
+    }
+}
+
+/// Size of the buffer needed to receive a batch of datagrams
+fn recv_buf_len(config: &EndpointConfig, udp_state: &UdpState) -> usize {
+    config.get_max_udp_payload_size().min(64 * 1024) as usize
+        * udp_state.gro_segments()
+        * BATCH_SIZE
+}
+
+/// Apply `config`'s socket options to `socket` and hand it over to `runtime`
+fn wrap_udp_socket(
+    runtime: &dyn Runtime,
+    config: &EndpointConfig,
+    socket: std::net::UdpSocket,
+) -> io::Result<Box<dyn AsyncUdpSocket>> {
+    // Async runtimes require non-blocking sockets, but custom `Runtime`s might not enforce it
+    socket.set_nonblocking(true)?;
+    let sock_ref = udp::UdpSockRef::from(&socket);
+    if let Some(size) = config.get_socket_send_buffer_size() {
+        sock_ref.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = config.get_socket_recv_buffer_size() {
+        sock_ref.set_recv_buffer_size(size)?;
+    }
+    runtime.wrap_udp_socket(socket)
 }
 
 fn ensure_ipv6(x: SocketAddr) -> SocketAddrV6 {
@@ -604,42 +2111,74 @@ impl EndpointRef {
     pub(crate) fn new(
         socket: Box<dyn AsyncUdpSocket>,
         inner: proto::Endpoint,
-        ipv6: bool,
+        addr: SocketAddr,
         runtime: Arc<dyn Runtime>,
     ) -> Self {
         let udp_state = Arc::new(UdpState::new());
-        let recv_buf = vec![
-            0;
-            inner.config().get_max_udp_payload_size().min(64 * 1024) as usize
-                * udp_state.gro_segments()
-                * BATCH_SIZE
-        ];
+        let recv_buf = alloc_recv_buf(inner.config(), recv_buf_len(inner.config(), &udp_state));
+        let transmit_queue = TransmitQueue::new(inner.config().get_transmit_scheduling());
+        let handshake_limiter = Arc::new(HandshakeLimiter::new(
+            inner.config().get_max_concurrent_outgoing_handshakes(),
+        ));
+        let datagram_budget = Arc::new(DatagramBudget::new(
+            inner.config().get_max_buffered_datagrams(),
+        ));
         let (sender, events) = mpsc::unbounded_channel();
+        let senders = FxHashMap::with_capacity_and_hasher(
+            inner.config().get_expected_connections(),
+            Default::default(),
+        );
         Self(Arc::new(EndpointInner {
+            created: Instant::now(),
             shared: Shared {
                 incoming: Notify::new(),
                 idle: Notify::new(),
+                writable: Notify::new(),
+                handshake_limiter,
             },
             state: Mutex::new(State {
-                socket,
+                sockets: vec![SocketState::new(socket, addr)],
+                connection_sockets: FxHashMap::default(),
+                preferred_local_ips: FxHashMap::default(),
+                next_recv_socket: 0,
                 udp_state,
                 inner,
-                ipv6,
+                ipv6: addr.is_ipv6(),
+                connected_peer: None,
                 events,
-                outgoing: VecDeque::new(),
+                transmit_queue,
                 incoming: VecDeque::new(),
                 driver: None,
                 connections: ConnectionSet {
-                    senders: FxHashMap::default(),
+                    senders,
+                    refs: FxHashMap::default(),
                     sender,
                     close: None,
+                    timer_counters: Arc::default(),
+                    datagram_budget,
+                    max_connections: 0,
+                    added: Arc::default(),
+                    handshaking: FxHashSet::default(),
+                    established: FxHashSet::default(),
                 },
                 ref_count: 0,
                 driver_lost: false,
-                recv_buf: recv_buf.into(),
+                send_blocked: false,
+                recv_buf,
+                recv_metas: [RecvMeta::default(); BATCH_SIZE],
                 recv_limiter: WorkLimiter::new(RECV_TIME_BOUND),
+                recv_backlog: VecDeque::new(),
                 send_limiter: WorkLimiter::new(SEND_TIME_BOUND),
                 runtime,
+                stats: EndpointStats::default(),
+                connection_attempt: None,
+                new_connection: None,
+                router: None,
+                offload: None,
+                datagram_source: None,
+                rebind_pings: Vec::new(),
+                last_rebind_ping: None,
+                rebind_ping_timer: None,
             }),
         }))
     }
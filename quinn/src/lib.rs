@@ -39,7 +39,7 @@
 //! encryption alone.
 #![warn(missing_docs)]
 
-use std::time::Duration;
+use std::{net::IpAddr, time::Duration};
 
 macro_rules! ready {
     ($e:expr $(,)?) => {
@@ -51,25 +51,39 @@ macro_rules! ready {
 }
 
 mod connection;
+mod datagram_budget;
 mod endpoint;
 mod mutex;
+mod offload;
 mod recv_stream;
+mod router;
 mod runtime;
 mod send_stream;
+mod trace_level;
+mod transmit_queue;
 mod work_limiter;
 
 pub use proto::{
-    congestion, crypto, ApplicationClose, Chunk, ClientConfig, ConfigError, ConnectError,
-    ConnectionClose, ConnectionError, EndpointConfig, IdleTimeout, ServerConfig, StreamId,
-    Transmit, TransportConfig, VarInt,
+    congestion, crypto, AcceptDecision, AcceptFilter, AppErrorCode, ApplicationClose, BufferPool,
+    Chunk, ClientConfig, CloseState, ConfigError, ConnectError, ConnectionClose, ConnectionError,
+    ConnectionHandle, ConnectionId, DatagramDropPolicy, Dir, EcnState, EcnValidationPolicy,
+    EncryptionLevel, EndpointConfig, IdleTimeout, InvalidAppErrorCode, OversizedDatagramPolicy,
+    SendLimit, ServerConfig, Side, StreamAdmission, StreamId, StreamLimits, StreamProgress,
+    Transmit, TransmitScheduling, TransportConfig, TransportError, VarInt,
 };
 
 pub use crate::connection::{
-    AcceptBi, AcceptUni, Connecting, Connection, OpenBi, OpenUni, ReadDatagram, SendDatagramError,
-    UnknownStream, ZeroRttAccepted,
+    AcceptBi, AcceptUni, ByteQuota, Connecting, Connection, CwndSample, HandshakeInfo, OpenBi,
+    OpenUni, ReadDatagram, SendDatagramError, UnknownStream, WakeReason, WakeStats,
+    ZeroRttAccepted,
 };
-pub use crate::endpoint::{Accept, Endpoint};
+pub use crate::endpoint::{
+    Accept, CloseStats, ConnectionAttempt, ConnectionStatsSnapshot, DriverYieldStats, Endpoint,
+    EndpointStats, HandshakeStats, PriorityOrder, StatsSnapshot,
+};
+pub use crate::offload::{DatagramSource, RecvTransmits, TransmitOffload};
 pub use crate::recv_stream::{ReadError, ReadExactError, ReadToEndError, RecvStream};
+pub use crate::router::ConnectionRouter;
 #[cfg(feature = "runtime-async-std")]
 pub use crate::runtime::AsyncStdRuntime;
 #[cfg(feature = "runtime-tokio")]
@@ -93,7 +107,19 @@ enum ConnectionEvent {
 #[derive(Debug)]
 enum EndpointEvent {
     Proto(proto::EndpointEvent),
-    Transmit(proto::Transmit),
+    /// Datagrams to send, and the number of QUIC packets they contain
+    Transmit(proto::Transmit, u64),
+    /// Update the connection's share of the socket, for `TransmitScheduling`
+    TransmitSchedule {
+        weight: u32,
+        priority: i32,
+    },
+    /// The connection is about to be drained after being closed for the given reason
+    Closed(ConnectionError),
+    /// The handshake completed, or failed with the given reason
+    Handshake(Result<(), ConnectionError>),
+    /// Set the source address of the connection's datagrams
+    PreferLocalIp(Option<IpAddr>),
 }
 
 /// Maximum number of datagrams processed in send/recv calls to make before moving on to other processing
@@ -112,3 +138,9 @@ const RECV_TIME_BOUND: Duration = Duration::from_micros(50);
 
 /// The maximum amount of time that should be spent in `sendmsg()` calls per endpoint iteration
 const SEND_TIME_BOUND: Duration = Duration::from_micros(50);
+
+/// Minimum time between the PINGs sent by connections after their endpoint is rebound
+///
+/// PINGs for further rebinds within this interval are deferred until it has passed and coalesced.
+/// See [`Endpoint::rebind()`].
+pub const REBIND_PING_INTERVAL: Duration = Duration::from_millis(500);
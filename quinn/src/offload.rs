@@ -0,0 +1,230 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    future::Future,
+    io::{self, IoSliceMut},
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use proto::Transmit;
+use udp::RecvMeta;
+
+/// Receives the datagrams an endpoint would otherwise send on its sockets
+///
+/// Obtained from [`Endpoint::offload_transmits()`](crate::Endpoint::offload_transmits). Lets a
+/// dedicated thread own the send path, e.g. to send through io_uring or AF_XDP, while the endpoint
+/// keeps running all connection logic. Transmits are handed over in the order the sockets would
+/// have sent them, with [`Transmit::src_ip`] already reflecting any
+/// [`Connection::set_preferred_local_ip()`](crate::Connection::set_preferred_local_ip). A transmit
+/// with a `segment_size` holds several datagrams, if the endpoint's sockets support segmentation
+/// offload, which a custom send path must split itself if it can't send them as one.
+///
+/// Each transmit is paired with the local address of the socket it would have been sent on. This
+/// is always the primary socket's, unless sockets were added with
+/// [`Endpoint::add_socket()`](crate::Endpoint::add_socket), in which case connections accepted on
+/// them must keep sending from their address for the peer to recognize them.
+///
+/// At most `capacity` transmits are outstanding at once: once that many have been handed over
+/// without being reported sent through [`complete()`](Self::complete), the endpoint holds further
+/// datagrams back, as it would if a socket's send buffer were full.
+///
+/// Dropping the `TransmitOffload` returns the endpoint to sending on its sockets. Transmits it
+/// received but never sent are lost, like any other datagram.
+#[derive(Debug)]
+pub struct TransmitOffload {
+    state: Arc<Mutex<OffloadState>>,
+}
+
+impl TransmitOffload {
+    /// Move the transmits waiting to be sent into `buf`, or register to be woken when there are
+    /// some
+    ///
+    /// Returns the number of transmits appended to `buf`, or `None` once the endpoint's driver
+    /// has stopped and every transmit has been taken.
+    pub fn poll_recv(
+        &self,
+        cx: &mut Context,
+        buf: &mut Vec<(SocketAddr, Transmit)>,
+    ) -> Poll<Option<usize>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.queue.is_empty() {
+            let n = state.queue.len();
+            buf.extend(state.queue.drain(..));
+            return Poll::Ready(Some(n));
+        }
+        if state.endpoint_stopped {
+            return Poll::Ready(None);
+        }
+        state.reader = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Wait for transmits to be sent, moving them into `buf`
+    ///
+    /// Async equivalent of [`poll_recv()`](Self::poll_recv).
+    pub fn recv<'a>(&'a self, buf: &'a mut Vec<(SocketAddr, Transmit)>) -> RecvTransmits<'a> {
+        RecvTransmits { offload: self, buf }
+    }
+
+    /// Report that the `n` oldest outstanding transmits have been sent, or given up on
+    ///
+    /// Frees capacity for the endpoint to hand over further transmits.
+    pub fn complete(&self, n: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.outstanding = state.outstanding.saturating_sub(n);
+        if let Some(waker) = state.driver.take() {
+            drop(state);
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for TransmitOffload {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.abandoned = true;
+        // Let the driver return to the sockets
+        if let Some(waker) = state.driver.take() {
+            drop(state);
+            waker.wake();
+        }
+    }
+}
+
+/// Future produced by [`TransmitOffload::recv()`]
+#[derive(Debug)]
+pub struct RecvTransmits<'a> {
+    offload: &'a TransmitOffload,
+    buf: &'a mut Vec<(SocketAddr, Transmit)>,
+}
+
+impl Future for RecvTransmits<'_> {
+    type Output = Option<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.offload.poll_recv(cx, this.buf)
+    }
+}
+
+/// The endpoint's end of a [`TransmitOffload`]
+#[derive(Debug)]
+pub(crate) struct OffloadSender {
+    state: Arc<Mutex<OffloadState>>,
+}
+
+impl OffloadSender {
+    pub(crate) fn new(capacity: usize) -> (Self, TransmitOffload) {
+        let state = Arc::new(Mutex::new(OffloadState {
+            queue: VecDeque::new(),
+            outstanding: 0,
+            capacity,
+            reader: None,
+            driver: None,
+            endpoint_stopped: false,
+            abandoned: false,
+        }));
+        (
+            Self {
+                state: state.clone(),
+            },
+            TransmitOffload { state },
+        )
+    }
+
+    /// Whether the application has dropped its [`TransmitOffload`]
+    pub(crate) fn is_abandoned(&self) -> bool {
+        self.state.lock().unwrap().abandoned
+    }
+
+    /// Hand over as many of `transmits`, queued for the socket bound to `local_addr`, as capacity
+    /// allows, or register to be woken when capacity is freed
+    pub(crate) fn poll_send(
+        &self,
+        cx: &mut Context,
+        local_addr: SocketAddr,
+        transmits: &mut VecDeque<Transmit>,
+    ) -> Poll<usize> {
+        let mut state = self.state.lock().unwrap();
+        let n = state
+            .capacity
+            .saturating_sub(state.outstanding)
+            .min(transmits.len());
+        if n == 0 {
+            state.driver = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        state.outstanding += n;
+        state
+            .queue
+            .extend(transmits.drain(..n).map(|t| (local_addr, t)));
+        if let Some(waker) = state.reader.take() {
+            drop(state);
+            waker.wake();
+        }
+        Poll::Ready(n)
+    }
+}
+
+impl Drop for OffloadSender {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.endpoint_stopped = true;
+        if let Some(waker) = state.reader.take() {
+            drop(state);
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct OffloadState {
+    /// Transmits handed over by the endpoint and yet to be received by the application, with the
+    /// address of the socket they were queued for
+    queue: VecDeque<(SocketAddr, Transmit)>,
+    /// Transmits handed over and not yet reported complete, including those in `queue`
+    outstanding: usize,
+    capacity: usize,
+    /// Woken when transmits are queued or the endpoint stops
+    reader: Option<Waker>,
+    /// Woken when capacity is freed or the `TransmitOffload` is dropped
+    driver: Option<Waker>,
+    endpoint_stopped: bool,
+    abandoned: bool,
+}
+
+/// A source of received UDP datagrams, read by an endpoint in place of its sockets
+///
+/// Set with [`Endpoint::set_datagram_source()`](crate::Endpoint::set_datagram_source). Lets
+/// datagrams be received through a kernel-bypass mechanism such as an AF_XDP ring or io_uring,
+/// while the endpoint keeps routing them to connections and handling events as usual. Responses
+/// the endpoint sends on its own, such as stateless resets, go out on the primary socket, or to a
+/// [`TransmitOffload`] if there is one.
+pub trait DatagramSource: Send + Debug + 'static {
+    /// Receive UDP datagrams, or register to be woken if receiving may succeed in the future
+    ///
+    /// Like [`AsyncUdpSocket::poll_recv()`](crate::AsyncUdpSocket::poll_recv), each datagram, or
+    /// batch of equally-sized datagrams, is written to the start of a separate element of `bufs`,
+    /// and described by the element of `meta` at the same index. Returns the number of elements
+    /// filled. For each, the source must set:
+    ///
+    /// - [`addr`](RecvMeta::addr): the address the datagram was sent from
+    /// - [`len`](RecvMeta::len): the number of bytes written to the buffer
+    /// - [`stride`](RecvMeta::stride): the size of each datagram in the buffer, which is `len`
+    ///   unless several datagrams were coalesced into it
+    /// - [`ecn`](RecvMeta::ecn): the ECN codepoint of the IP header, or `None` if unknown
+    /// - [`dst_ip`](RecvMeta::dst_ip): the local address the datagram was sent to, or `None` if
+    ///   unknown, which is only needed by servers listening on multiple addresses
+    ///
+    /// An error stops the endpoint's driver, except for
+    /// [`ConnectionReset`](io::ErrorKind::ConnectionReset), which is ignored.
+    fn poll_recv(
+        &mut self,
+        cx: &mut Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>>;
+}
@@ -3,6 +3,7 @@ use std::{
     io,
     pin::Pin,
     task::{Context, Poll},
+    time::Instant,
 };
 
 use bytes::Bytes;
@@ -12,6 +13,7 @@ use tokio::io::ReadBuf;
 
 use crate::{
     connection::{ConnectionRef, UnknownStream},
+    runtime::AsyncTimer,
     VarInt,
 };
 
@@ -30,6 +32,8 @@ pub struct RecvStream {
     is_0rtt: bool,
     all_data_read: bool,
     reset: Option<VarInt>,
+    /// The error code the peer reset the stream with, once a read has reported it
+    reset_code: Option<VarInt>,
 }
 
 impl RecvStream {
@@ -40,6 +44,7 @@ impl RecvStream {
             is_0rtt,
             all_data_read: false,
             reset: None,
+            reset_code: None,
         }
     }
 
@@ -106,7 +111,27 @@ impl RecvStream {
     /// stream, but require the application to manage reassembling the original data.
     ///
     /// Slightly more efficient than `read` due to not copying. Chunk boundaries do not correspond
-    /// to peer writes, and hence cannot be used as framing.
+    /// to peer writes, and hence cannot be used as framing: data from several writes may be
+    /// coalesced into a single packet, and a single write may be split across packets, each of
+    /// which may be retransmitted with different boundaries. Applications exchanging discrete
+    /// messages on a stream must delimit them explicitly, for example with a length prefix:
+    ///
+    /// ```
+    /// # async fn f(send: &mut quinn::SendStream, recv: &mut quinn::RecvStream)
+    /// #     -> Result<(), Box<dyn std::error::Error>> {
+    /// // Sender
+    /// let message = b"hello";
+    /// send.write_all(&(message.len() as u32).to_be_bytes()).await?;
+    /// send.write_all(message).await?;
+    ///
+    /// // Receiver
+    /// let mut len = [0; 4];
+    /// recv.read_exact(&mut len).await?;
+    /// let mut message = vec![0; u32::from_be_bytes(len) as usize];
+    /// recv.read_exact(&mut message).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
     pub async fn read_chunk(
         &mut self,
         max_length: usize,
@@ -120,6 +145,50 @@ impl RecvStream {
         .await
     }
 
+    /// Read the next segment of data, giving up at `deadline`
+    ///
+    /// Behaves like [`read_chunk()`](Self::read_chunk), except that `Ok(None)` is yielded if no
+    /// data arrives before `deadline`, and the result of a completed read is wrapped in `Some`.
+    /// Timing out has no effect on the stream, which may continue to be read from afterwards. This
+    /// suits protocols where a quiet peer should be probed, e.g. with a heartbeat, rather than
+    /// abandoned.
+    pub async fn read_chunk_timeout(
+        &mut self,
+        max_length: usize,
+        ordered: bool,
+        deadline: Instant,
+    ) -> Result<Option<Option<Chunk>>, ReadError> {
+        let timer = self
+            .conn
+            .state
+            .lock("RecvStream::read_chunk_timeout")
+            .runtime
+            .new_timer(deadline);
+        ReadChunkTimeout {
+            stream: self,
+            max_length,
+            ordered,
+            timer,
+        }
+        .await
+    }
+
+    /// Read whatever data has arrived, regardless of gaps in the stream
+    ///
+    /// Yields `None` if the stream was finished. Otherwise, yields a segment of data and its offset
+    /// in the stream, which is the same as [`read_chunk(usize::MAX, false)`](Self::read_chunk).
+    /// Data is delivered as soon as it's received rather than once everything before it has
+    /// arrived, so a lost packet doesn't hold up delivery of the data that follows it. In exchange,
+    /// segments may arrive in any order and the application is responsible for reassembling them
+    /// using their offsets. Each byte is yielded exactly once. Once an unordered read has been
+    /// made, ordered reads fail with [`ReadError::IllegalOrderedRead`].
+    pub async fn read_unordered(&mut self) -> Result<Option<(Bytes, u64)>, ReadError> {
+        Ok(self
+            .read_chunk(usize::MAX, false)
+            .await?
+            .map(|chunk| (chunk.bytes, chunk.offset)))
+    }
+
     /// Foundation of [`Self::read_chunk`]
     fn poll_read_chunk(
         &mut self,
@@ -210,6 +279,32 @@ impl RecvStream {
         Ok(())
     }
 
+    /// Get the number of received bytes which can be read without blocking
+    ///
+    /// Only data contiguous with what's already been read counts, unless unordered reads have been
+    /// made. An amount which keeps growing indicates that the application is reading more slowly
+    /// than the peer sends, up to the limit set by the stream's flow control window.
+    pub fn available(&self) -> u64 {
+        let mut conn = self.conn.state.lock("RecvStream::available");
+        conn.inner.recv_stream(self.stream).available()
+    }
+
+    /// The error code the peer reset this stream with, if it has
+    ///
+    /// Distinguishes a stream reset by the peer from one which was finished cleanly. When the
+    /// reset arrives, a pending read is woken immediately and fails with [`ReadError::Reset`]
+    /// carrying the same code.
+    pub fn reset_code(&self) -> Option<VarInt> {
+        if self.reset_code.is_some() {
+            return self.reset_code;
+        }
+        let mut conn = self.conn.state.lock("RecvStream::reset_code");
+        if self.is_0rtt && conn.check_0rtt().is_err() {
+            return None;
+        }
+        conn.inner.recv_stream(self.stream).reset_code()
+    }
+
     /// Check if this stream has been opened during 0-RTT.
     ///
     /// In which case any non-idempotent request should be considered dangerous at the application
@@ -223,6 +318,19 @@ impl RecvStream {
         self.stream
     }
 
+    /// Whether this stream was opened by this side of the connection, rather than the peer
+    ///
+    /// See [`StreamId::initiator()`] and [`StreamId::dir()`] for more about the stream's origin.
+    pub fn is_locally_initiated(&self) -> bool {
+        let side = self
+            .conn
+            .state
+            .lock("RecvStream::is_locally_initiated")
+            .inner
+            .side();
+        self.stream.is_initiated_by(side)
+    }
+
     /// Handle common logic related to reading out of a receive stream
     ///
     /// This takes an `FnMut` closure that takes care of the actual reading process, matching
@@ -279,16 +387,19 @@ impl RecvStream {
                     Poll::Pending
                 }
             },
-            ReadStatus::Failed(read, Reset(error_code)) => match read {
-                None => {
-                    self.all_data_read = true;
-                    Poll::Ready(Err(ReadError::Reset(error_code)))
-                }
-                done => {
-                    self.reset = Some(error_code);
-                    Poll::Ready(Ok(done))
+            ReadStatus::Failed(read, Reset(error_code)) => {
+                self.reset_code = Some(error_code);
+                match read {
+                    None => {
+                        self.all_data_read = true;
+                        Poll::Ready(Err(ReadError::Reset(error_code)))
+                    }
+                    done => {
+                        self.reset = Some(error_code);
+                        Poll::Ready(Ok(done))
+                    }
                 }
-            },
+            }
         }
     }
 }
@@ -529,6 +640,30 @@ impl<'a> Future for ReadChunk<'a> {
     }
 }
 
+/// Future produced by [`RecvStream::read_chunk_timeout()`].
+///
+/// [`RecvStream::read_chunk_timeout()`]: crate::RecvStream::read_chunk_timeout
+#[must_use = "futures/streams/sinks do nothing unless you `.await` or poll them"]
+struct ReadChunkTimeout<'a> {
+    stream: &'a mut RecvStream,
+    max_length: usize,
+    ordered: bool,
+    timer: Pin<Box<dyn AsyncTimer>>,
+}
+
+impl<'a> Future for ReadChunkTimeout<'a> {
+    type Output = Result<Option<Option<Chunk>>, ReadError>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let (max_length, ordered) = (self.max_length, self.ordered);
+        // Check for data first so that nothing which has already arrived is reported as a timeout
+        if let Poll::Ready(result) = self.stream.poll_read_chunk(cx, max_length, ordered) {
+            return Poll::Ready(result.map(Some));
+        }
+        ready!(self.timer.as_mut().poll(cx));
+        Poll::Ready(Ok(None))
+    }
+}
+
 /// Future produced by [`RecvStream::read_chunks()`].
 ///
 /// [`RecvStream::read_chunks()`]: crate::RecvStream::read_chunks
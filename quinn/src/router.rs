@@ -0,0 +1,151 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use bytes::BytesMut;
+use proto::{CidChange, ConnectionId, EcnCodepoint};
+use rustc_hash::FxHashMap;
+use tokio::sync::mpsc;
+
+/// Routing table shared by endpoints whose sockets receive datagrams for each other's connections
+///
+/// When several endpoints are bound to the same address with `SO_REUSEPORT`, the kernel picks the
+/// receiving socket by hashing the peer's address, so a connection's datagrams may be delivered to
+/// the wrong endpoint after the peer migrates to a new address or its NAT rebinds. Endpoints that
+/// have [joined](crate::Endpoint::join_router) the same router record the connection IDs they
+/// issue in it, and forward datagrams addressed to another member's connection ID to that member
+/// rather than treating them as belonging to an unknown connection.
+///
+/// This costs a lock of the shared table and a hash lookup for every received datagram, plus a
+/// channel send and an extra wakeup for each forwarded one, so it should only be used when
+/// multiple endpoints actually share a port. All members must issue connection IDs of the same
+/// length, since the table is keyed by the destination connection ID of received packets.
+/// Endpoints using zero-length connection IDs can't be routed this way.
+#[derive(Debug, Default)]
+pub struct ConnectionRouter {
+    state: Mutex<RouterState>,
+}
+
+impl ConnectionRouter {
+    /// Create an empty routing table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn join(self: &Arc<Self>) -> RouterMembership {
+        let (sender, forwarded) = mpsc::unbounded_channel();
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_member;
+        state.next_member += 1;
+        state.members.insert(id, sender);
+        RouterMembership {
+            router: self.clone(),
+            id,
+            forwarded,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RouterState {
+    next_member: u64,
+    members: FxHashMap<u64, mpsc::UnboundedSender<ForwardedDatagram>>,
+    /// The member which issued each connection ID
+    cids: FxHashMap<ConnectionId, u64>,
+}
+
+/// A datagram received by one member of a [`ConnectionRouter`] on behalf of another
+#[derive(Debug)]
+pub(crate) struct ForwardedDatagram {
+    pub(crate) remote: SocketAddr,
+    pub(crate) local_ip: Option<IpAddr>,
+    pub(crate) ecn: Option<EcnCodepoint>,
+    pub(crate) data: BytesMut,
+}
+
+/// An endpoint's registration with a [`ConnectionRouter`], removed from the router on drop
+#[derive(Debug)]
+pub(crate) struct RouterMembership {
+    router: Arc<ConnectionRouter>,
+    id: u64,
+    forwarded: mpsc::UnboundedReceiver<ForwardedDatagram>,
+}
+
+impl RouterMembership {
+    /// Record changes to the connection IDs issued by this member
+    pub(crate) fn update(&self, changes: impl Iterator<Item = CidChange>) {
+        let mut changes = changes.peekable();
+        if changes.peek().is_none() {
+            return;
+        }
+        let mut state = self.router.state.lock().unwrap();
+        for change in changes {
+            match change {
+                CidChange::Issued(cid) => {
+                    state.cids.insert(cid, self.id);
+                }
+                CidChange::Retired(cid) => {
+                    if state.cids.get(&cid) == Some(&self.id) {
+                        state.cids.remove(&cid);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Forward `datagram` to the member owning its destination connection ID, if that's another
+    /// member
+    ///
+    /// Returns the datagram if it should be handled locally instead.
+    pub(crate) fn route(
+        &self,
+        local_cid_len: usize,
+        datagram: ForwardedDatagram,
+    ) -> Option<ForwardedDatagram> {
+        let cid = match dst_cid(&datagram.data, local_cid_len) {
+            Some(cid) => cid,
+            None => return Some(datagram),
+        };
+        let state = self.router.state.lock().unwrap();
+        let owner = match state.cids.get(&cid) {
+            Some(&owner) if owner != self.id => owner,
+            _ => return Some(datagram),
+        };
+        match state.members.get(&owner) {
+            Some(sender) => sender.send(datagram).err().map(|e| e.0),
+            None => Some(datagram),
+        }
+    }
+
+    pub(crate) fn poll_forwarded(&mut self, cx: &mut Context) -> Poll<Option<ForwardedDatagram>> {
+        self.forwarded.poll_recv(cx)
+    }
+}
+
+impl Drop for RouterMembership {
+    fn drop(&mut self) {
+        let mut state = self.router.state.lock().unwrap();
+        state.members.remove(&self.id);
+        let id = self.id;
+        state.cids.retain(|_, owner| *owner != id);
+    }
+}
+
+/// Maximum length of a connection ID in QUIC version 1
+const MAX_CID_SIZE: usize = 20;
+
+/// Extract the destination connection ID of the first packet in `data`
+fn dst_cid(data: &[u8], local_cid_len: usize) -> Option<ConnectionId> {
+    let first = *data.first()?;
+    let (start, len) = if first & 0x80 == 0 {
+        (1, local_cid_len)
+    } else {
+        (6, usize::from(*data.get(5)?))
+    };
+    if len == 0 || len > MAX_CID_SIZE {
+        return None;
+    }
+    Some(ConnectionId::new(data.get(start..start + len)?))
+}
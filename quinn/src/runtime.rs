@@ -34,6 +34,10 @@ pub trait AsyncTimer: Send + Debug + 'static {
 pub trait AsyncUdpSocket: Send + Debug + 'static {
     /// Send UDP datagrams from `transmits`, or register to be woken if sending may succeed in the
     /// future
+    ///
+    /// Implementations may interpret [`Transmit::metadata`], which is set through
+    /// [`Connection::set_transmit_metadata()`](crate::Connection::set_transmit_metadata), and are
+    /// free to ignore it.
     fn poll_send(
         &mut self,
         state: &UdpState,
@@ -25,9 +25,11 @@ impl Runtime for AsyncStdRuntime {
 
     fn wrap_udp_socket(&self, sock: std::net::UdpSocket) -> io::Result<Box<dyn AsyncUdpSocket>> {
         udp::UdpSocketState::configure((&sock).into())?;
+        let mut inner = udp::UdpSocketState::new();
+        inner.set_connected(sock.peer_addr().is_ok());
         Ok(Box::new(UdpSocket {
             io: Async::new(sock)?,
-            inner: udp::UdpSocketState::new(),
+            inner,
         }))
     }
 }
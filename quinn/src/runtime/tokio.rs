@@ -28,9 +28,11 @@ impl Runtime for TokioRuntime {
 
     fn wrap_udp_socket(&self, sock: std::net::UdpSocket) -> io::Result<Box<dyn AsyncUdpSocket>> {
         udp::UdpSocketState::configure((&sock).into())?;
+        let mut inner = udp::UdpSocketState::new();
+        inner.set_connected(sock.peer_addr().is_ok());
         Ok(Box::new(UdpSocket {
             io: tokio::net::UdpSocket::from_std(sock)?,
-            inner: udp::UdpSocketState::new(),
+            inner,
         }))
     }
 }
@@ -6,7 +6,7 @@ use std::{
 };
 
 use bytes::Bytes;
-use proto::{ConnectionError, FinishError, StreamId, Written};
+use proto::{ConnectionError, FinishError, StreamId, StreamProgress, Written};
 use thiserror::Error;
 use tokio::sync::oneshot;
 
@@ -27,6 +27,7 @@ pub struct SendStream {
     stream: StreamId,
     is_0rtt: bool,
     finishing: Option<oneshot::Receiver<Option<WriteError>>>,
+    nodelay: bool,
 }
 
 impl SendStream {
@@ -36,6 +37,7 @@ impl SendStream {
             stream,
             is_0rtt,
             finishing: None,
+            nodelay: true,
         }
     }
 
@@ -108,7 +110,7 @@ impl SendStream {
             }
         };
 
-        conn.wake();
+        conn.wake_for_data(self.nodelay);
         Poll::Ready(Ok(result))
     }
 
@@ -194,6 +196,57 @@ impl SendStream {
         Ok(conn.inner.send_stream(self.stream).priority()?)
     }
 
+    /// Get the amount of data written to the stream, sent, and acknowledged by the peer
+    ///
+    /// Suitable for reporting upload progress, since a completed write only means the data was
+    /// buffered. Fails once the stream has been reset, or finished and fully acknowledged.
+    pub fn progress(&self) -> Result<StreamProgress, UnknownStream> {
+        let mut conn = self.conn.state.lock("SendStream::progress");
+        Ok(conn.inner.send_stream(self.stream).progress()?)
+    }
+
+    /// Get the number of bytes written to the stream that are still waiting to be transmitted
+    ///
+    /// Unlike data in flight, this only grows when writes outpace what the connection is able to
+    /// send, making it a useful signal for pacing producers that generate data faster than the
+    /// link can carry it.
+    pub fn buffered(&self) -> u64 {
+        let mut conn = self.conn.state.lock("SendStream::buffered");
+        conn.inner.send_stream(self.stream).buffered()
+    }
+
+    /// Check whether the peer's flow control limit for this particular stream has been reached
+    ///
+    /// Distinguishes a stream held back by its own receive window from one waiting on the
+    /// connection-level window or congestion control, which don't affect this result. A write
+    /// blocked on stream-level flow control is woken once the peer raises the limit with a
+    /// MAX_STREAM_DATA frame.
+    pub fn is_flow_control_blocked(&self) -> bool {
+        let mut conn = self.conn.state.lock("SendStream::is_flow_control_blocked");
+        conn.inner
+            .send_stream(self.stream)
+            .is_flow_control_blocked()
+    }
+
+    /// Set whether written data is transmitted without delay
+    ///
+    /// When `true`, which is the default, each write is handed to the connection for immediate
+    /// transmission. When `false`, the connection may wait briefly for further writes so that
+    /// they can share packets, trading a small amount of latency for fewer packets when many small
+    /// writes are made in quick succession. Finishing or resetting the stream is never delayed.
+    /// See [`Connection::set_coalesce_timeout()`](crate::Connection::set_coalesce_timeout) to
+    /// coalesce writes across all of a connection's streams.
+    pub fn set_nodelay(&mut self, nodelay: bool) {
+        self.nodelay = nodelay;
+    }
+
+    /// Whether written data is transmitted without delay
+    ///
+    /// See [`set_nodelay()`](Self::set_nodelay).
+    pub fn nodelay(&self) -> bool {
+        self.nodelay
+    }
+
     /// Completes if/when the peer stops the stream, yielding the error code
     pub async fn stopped(&mut self) -> Result<VarInt, StoppedError> {
         Stopped { stream: self }.await
@@ -222,6 +275,19 @@ impl SendStream {
     pub fn id(&self) -> StreamId {
         self.stream
     }
+
+    /// Whether this stream was opened by this side of the connection, rather than the peer
+    ///
+    /// See [`StreamId::initiator()`] and [`StreamId::dir()`] for more about the stream's origin.
+    pub fn is_locally_initiated(&self) -> bool {
+        let side = self
+            .conn
+            .state
+            .lock("SendStream::is_locally_initiated")
+            .inner
+            .side();
+        self.stream.is_initiated_by(side)
+    }
 }
 
 #[cfg(feature = "futures-io")]
@@ -432,7 +498,9 @@ pub enum WriteError {
     /// This was a 0-RTT stream and the server rejected it
     ///
     /// Can only occur on clients for 0-RTT streams, which can be opened using
-    /// [`Connecting::into_0rtt()`].
+    /// [`Connecting::into_0rtt()`], unless
+    /// [`TransportConfig::retransmit_rejected_0rtt()`](crate::TransportConfig::retransmit_rejected_0rtt)
+    /// lets them survive the rejection.
     ///
     /// [`Connecting::into_0rtt()`]: crate::Connecting::into_0rtt()
     #[error("0-RTT rejected")]
@@ -451,7 +519,9 @@ pub enum StoppedError {
     /// This was a 0-RTT stream and the server rejected it
     ///
     /// Can only occur on clients for 0-RTT streams, which can be opened using
-    /// [`Connecting::into_0rtt()`].
+    /// [`Connecting::into_0rtt()`], unless
+    /// [`TransportConfig::retransmit_rejected_0rtt()`](crate::TransportConfig::retransmit_rejected_0rtt)
+    /// lets them survive the rejection.
     ///
     /// [`Connecting::into_0rtt()`]: crate::Connecting::into_0rtt()
     #[error("0-RTT rejected")]
@@ -5,10 +5,14 @@ use std::{
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
     str,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
 };
 
-use crate::runtime::TokioRuntime;
+use crate::runtime::{AsyncUdpSocket, Runtime as _, TokioRuntime};
 use bytes::Bytes;
 use rand::{rngs::StdRng, RngCore, SeedableRng};
 use tokio::{
@@ -19,7 +23,10 @@ use tracing::{info, info_span};
 use tracing_futures::Instrument as _;
 use tracing_subscriber::EnvFilter;
 
-use super::{ClientConfig, Endpoint, RecvStream, SendStream, TransportConfig};
+use super::{
+    BufferPool, ClientConfig, Connecting, Connection, ConnectionRouter, Endpoint, EndpointConfig,
+    RecvStream, SendDatagramError, SendStream, TransportConfig,
+};
 
 #[test]
 fn handshake_timeout() {
@@ -59,6 +66,35 @@ fn handshake_timeout() {
     assert!(dt > IDLE_TIMEOUT && dt < 2 * IDLE_TIMEOUT);
 }
 
+#[tokio::test]
+async fn handshake_timeout_stats() {
+    let _guard = subscribe();
+    let client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let mut client_config =
+        crate::ClientConfig::with_root_certificates(rustls::RootCertStore::empty());
+    let mut transport_config = crate::TransportConfig::default();
+    transport_config
+        .max_idle_timeout(Some(Duration::from_millis(200).try_into().unwrap()))
+        .initial_rtt(Duration::from_millis(10));
+    client_config.transport_config(Arc::new(transport_config));
+
+    let result = client
+        .connect_with(
+            client_config,
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1),
+            "localhost",
+        )
+        .unwrap()
+        .await;
+    assert!(matches!(result, Err(crate::ConnectionError::TimedOut)));
+    client.wait_idle().await;
+
+    let handshakes = client.stats().handshakes;
+    assert_eq!(handshakes.timed_out, 1);
+    assert_eq!(handshakes.in_progress, 0);
+    assert_eq!(client.stats().closes.timed_out, 1);
+}
+
 #[tokio::test]
 async fn close_endpoint() {
     let _guard = subscribe();
@@ -95,6 +131,388 @@ async fn close_endpoint() {
     }
 }
 
+#[tokio::test]
+async fn outgoing_handshake_limit() {
+    let _guard = subscribe();
+    let mut config = EndpointConfig::default();
+    config.max_concurrent_outgoing_handshakes(Some(1));
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let mut endpoint = Endpoint::new(config, None, socket, TokioRuntime).unwrap();
+    endpoint.set_default_client_config(ClientConfig::with_root_certificates(
+        rustls::RootCertStore::empty(),
+    ));
+
+    let unreachable = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+    let first = endpoint.connect(unreachable, "localhost").unwrap();
+    let second = endpoint.connect(unreachable, "localhost").unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(endpoint.stats().outgoing_handshakes, 1);
+
+    // Abandoning the first attempt lets the second one begin
+    drop(first);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(endpoint.stats().outgoing_handshakes, 1);
+
+    drop(second);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(endpoint.stats().outgoing_handshakes, 0);
+
+    // Attempts abandoned while waiting don't hold on to their turn once it comes
+    let first = endpoint.connect(unreachable, "localhost").unwrap();
+    let second = endpoint.connect(unreachable, "localhost").unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    drop(second);
+    drop(first);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(endpoint.stats().outgoing_handshakes, 0);
+}
+
+#[tokio::test]
+async fn transmit_scheduling() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let mut config = EndpointConfig::default();
+    config.transmit_scheduling(crate::TransmitScheduling::WeightedFair);
+    let client = client_with_config(&server, config);
+
+    const SIZE: usize = 256 * 1024;
+    let server_addr = server.local_addr().unwrap();
+    let send = |weight| {
+        let connecting = client.connect(server_addr, "localhost").unwrap();
+        async move {
+            let conn = connecting.await.expect("connect");
+            conn.set_transmit_weight(weight);
+            let mut stream = conn.open_uni().await.unwrap();
+            stream.write_all(&vec![0xAB; SIZE]).await.unwrap();
+            stream.finish().await.unwrap();
+            conn
+        }
+    };
+    let receive = async {
+        for _ in 0..2 {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            tokio::spawn(async move {
+                let stream = conn.accept_uni().await.unwrap();
+                assert_eq!(stream.read_to_end(SIZE).await.unwrap().len(), SIZE);
+                conn.closed().await;
+            });
+        }
+    };
+    // Both connections make progress regardless of their weights
+    let (_, a, b) = tokio::join!(receive, send(1), send(4));
+    drop((a, b));
+}
+
+#[tokio::test]
+async fn per_datagram_recv_limit() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let mut config = EndpointConfig::default();
+    config.per_datagram_recv_limit(true);
+    let client = client_with_config(&server, config);
+
+    const SIZE: usize = 1024 * 1024;
+    let (client_conn, server_conn) = connected_to(&client, &server).await;
+    // Every datagram is handled, even if a batch is split across passes
+    assert_eq!(transfer(&server_conn, &client_conn, SIZE).await, SIZE);
+}
+
+/// Sends at most one datagram per call, checking that the rest of each batch is offered again
+#[derive(Debug)]
+struct PartialSendSocket {
+    inner: Box<dyn AsyncUdpSocket>,
+    /// Contents of the first transmit that was offered but not sent
+    unsent: Option<Vec<u8>>,
+    /// Number of calls which sent fewer datagrams than offered
+    partial: Arc<AtomicUsize>,
+}
+
+impl AsyncUdpSocket for PartialSendSocket {
+    fn poll_send(
+        &mut self,
+        state: &udp::UdpState,
+        cx: &mut Context,
+        transmits: &[proto::Transmit],
+    ) -> Poll<Result<usize, io::Error>> {
+        if let Some(unsent) = self.unsent.take() {
+            assert_eq!(transmits[0].contents, unsent, "transmit lost or reordered");
+        }
+        let n = ready!(self.inner.poll_send(state, cx, &transmits[..1]))?;
+        if n == 1 && transmits.len() > 1 {
+            self.unsent = Some(transmits[1].contents.clone());
+            self.partial.fetch_add(1, Ordering::Relaxed);
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [io::IoSliceMut<'_>],
+        meta: &mut [udp::RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_recv(cx, bufs, meta)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[tokio::test]
+async fn partial_send_batches() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let partial = Arc::new(AtomicUsize::new(0));
+    let socket = PartialSendSocket {
+        inner: TokioRuntime.wrap_udp_socket(socket).unwrap(),
+        unsent: None,
+        partial: partial.clone(),
+    };
+    let mut client =
+        Endpoint::new_with_abstract_socket(EndpointConfig::default(), None, socket, TokioRuntime)
+            .unwrap();
+    client.set_default_client_config(server.default_client_config.clone().unwrap());
+
+    const SIZE: usize = 1024 * 1024;
+    let (client_conn, server_conn) = connected_to(&client, &server).await;
+    assert_eq!(transfer(&client_conn, &server_conn, SIZE).await, SIZE);
+    assert!(partial.load(Ordering::Relaxed) > 0);
+}
+
+/// Recycles released buffers, counting how many were handed out and returned
+#[derive(Default)]
+struct RecyclingPool {
+    free: std::sync::Mutex<Vec<Vec<u8>>>,
+    allocated: AtomicUsize,
+    released: AtomicUsize,
+}
+
+impl BufferPool for RecyclingPool {
+    fn allocate(&self, capacity: usize) -> Vec<u8> {
+        self.allocated.fetch_add(1, Ordering::Relaxed);
+        // Recycled buffers are handed out with stale contents, which the endpoint must discard
+        let mut buf = self.free.lock().unwrap().pop().unwrap_or_default();
+        buf.reserve(capacity);
+        buf
+    }
+
+    fn release(&self, buf: Vec<u8>) {
+        self.released.fetch_add(1, Ordering::Relaxed);
+        self.free.lock().unwrap().push(buf);
+    }
+}
+
+#[tokio::test]
+async fn buffer_pool() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let pool = Arc::new(RecyclingPool::default());
+    let mut config = EndpointConfig::default();
+    config.buffer_pool(pool.clone());
+    let client = client_with_config(&server, config);
+    // The receive buffer
+    assert_eq!(pool.allocated.load(Ordering::Relaxed), 1);
+
+    const SIZE: usize = 256 * 1024;
+    let (client_conn, server_conn) = connected_to(&client, &server).await;
+    assert_eq!(transfer(&client_conn, &server_conn, SIZE).await, SIZE);
+    // Transmit buffers were drawn from the pool and returned after sending
+    assert!(pool.allocated.load(Ordering::Relaxed) > 1);
+    assert!(pool.released.load(Ordering::Relaxed) > 0);
+}
+
+#[tokio::test]
+async fn max_buffered_datagrams() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let mut config = EndpointConfig::default();
+    config.max_buffered_datagrams(Some(2));
+    let client = client_with_config(&server, config);
+
+    let (client_conn, server_conn) = connected_to(&client, &server).await;
+
+    for i in 0..5u8 {
+        server_conn.send_datagram(vec![i].into()).unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while client.stats().datagrams_dropped < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("datagrams dropped");
+    let stats = client.stats();
+    assert_eq!(stats.datagrams_dropped, 3);
+    assert_eq!(stats.buffered_datagrams, 2);
+
+    // The oldest datagrams were dropped
+    for i in 3..5u8 {
+        assert_eq!(client_conn.read_datagram().await.unwrap(), vec![i]);
+    }
+    assert_eq!(client.stats().buffered_datagrams, 0);
+}
+
+#[tokio::test]
+async fn offload_transmits() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let sender = socket.try_clone().unwrap();
+    let mut client = Endpoint::new(EndpointConfig::default(), None, socket, TokioRuntime).unwrap();
+    client.set_default_client_config(server.default_client_config.clone().unwrap());
+
+    // Send the client's datagrams from another task, one at a time
+    let offload = client.offload_transmits(1);
+    let local_addr = client.local_addr().unwrap();
+    let send = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let mut sent = 0;
+        while offload.recv(&mut buf).await.is_some() {
+            for (addr, transmit) in buf.drain(..) {
+                assert_eq!(addr, local_addr);
+                let segment_size = transmit.segment_size.unwrap_or(transmit.contents.len());
+                for datagram in transmit.contents.chunks(segment_size) {
+                    sender.send_to(datagram, transmit.destination).unwrap();
+                }
+                sent += 1;
+                offload.complete(1);
+            }
+        }
+        sent
+    });
+
+    const MSG: &[u8] = b"offloaded";
+    let (client_conn, server_conn) = connected_to(&client, &server).await;
+    let mut stream = client_conn.open_uni().await.unwrap();
+    stream.write_all(MSG).await.unwrap();
+    stream.finish().await.unwrap();
+    let stream = server_conn.accept_uni().await.unwrap();
+    assert_eq!(stream.read_to_end(MSG.len()).await.unwrap(), MSG);
+
+    client_conn.close(0u32.into(), b"done");
+    drop(client_conn);
+    client.wait_idle().await;
+    drop(client);
+    // The offload ends with the endpoint's driver
+    let sent = send.await.unwrap();
+    assert!(sent > 0);
+    assert!(server.stats().recv_datagrams >= sent);
+}
+
+#[tokio::test]
+async fn offload_transmits_added_socket() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let added = socket.local_addr().unwrap();
+    let sender = socket.try_clone().unwrap();
+    server.add_socket(socket).unwrap();
+
+    let offload = server.offload_transmits(16);
+    let send = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let mut sent = 0;
+        while offload.recv(&mut buf).await.is_some() {
+            let n = buf.len();
+            for (addr, transmit) in buf.drain(..) {
+                // The client connects to the added socket, so must be answered from it
+                assert_eq!(addr, added);
+                let segment_size = transmit.segment_size.unwrap_or(transmit.contents.len());
+                for datagram in transmit.contents.chunks(segment_size) {
+                    sender.send_to(datagram, transmit.destination).unwrap();
+                }
+                sent += 1;
+            }
+            offload.complete(n);
+        }
+        sent
+    });
+
+    let mut client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    client.set_default_client_config(server.default_client_config.clone().unwrap());
+    let (client_conn, _server_conn) =
+        establish(client.connect(added, "localhost").unwrap(), &server).await;
+    assert_eq!(client_conn.remote_address(), added);
+
+    drop(client_conn);
+    server.close(0u32.into(), b"");
+    server.wait_idle().await;
+    drop(server);
+    assert!(send.await.unwrap() > 0);
+}
+
+#[tokio::test]
+async fn datagram_source() {
+    use std::{
+        io::IoSliceMut,
+        sync::atomic::{AtomicUsize, Ordering},
+        task::{Context, Poll},
+    };
+
+    /// Reads a clone of the endpoint's socket, counting the datagrams received
+    #[derive(Debug)]
+    struct CountingSource {
+        socket: tokio::net::UdpSocket,
+        received: Arc<AtomicUsize>,
+    }
+
+    impl crate::DatagramSource for CountingSource {
+        fn poll_recv(
+            &mut self,
+            cx: &mut Context,
+            bufs: &mut [IoSliceMut<'_>],
+            meta: &mut [udp::RecvMeta],
+        ) -> Poll<io::Result<usize>> {
+            let mut buf = tokio::io::ReadBuf::new(&mut bufs[0]);
+            let addr = match self.socket.poll_recv_from(cx, &mut buf) {
+                Poll::Ready(Ok(addr)) => addr,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            let len = buf.filled().len();
+            meta[0] = udp::RecvMeta {
+                addr,
+                len,
+                stride: len,
+                ecn: None,
+                dst_ip: None,
+            };
+            self.received.fetch_add(1, Ordering::Relaxed);
+            Poll::Ready(Ok(1))
+        }
+    }
+
+    let _guard = subscribe();
+    let server = endpoint();
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let source_socket = socket.try_clone().unwrap();
+    source_socket.set_nonblocking(true).unwrap();
+    let source_socket = tokio::net::UdpSocket::from_std(source_socket).unwrap();
+    let mut client = Endpoint::new(EndpointConfig::default(), None, socket, TokioRuntime).unwrap();
+    client.set_default_client_config(server.default_client_config.clone().unwrap());
+    let received = Arc::new(AtomicUsize::new(0));
+    client.set_datagram_source(Some(Box::new(CountingSource {
+        socket: source_socket,
+        received: received.clone(),
+    })));
+
+    establish(
+        client
+            .connect(server.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        &server,
+    )
+    .await;
+    // Everything the client received came through the source
+    assert!(received.load(Ordering::Relaxed) > 0);
+    assert_eq!(
+        client.stats().recv_datagrams,
+        received.load(Ordering::Relaxed) as u64
+    );
+}
+
 #[test]
 fn local_addr() {
     let socket = UdpSocket::bind("[::1]:0").unwrap();
@@ -111,6 +529,33 @@ fn local_addr() {
     );
 }
 
+#[test]
+#[cfg(unix)]
+fn local_addresses() {
+    let runtime = rt_basic();
+    let _guard = runtime.enter();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    let ep = Endpoint::new(Default::default(), None, socket, TokioRuntime).unwrap();
+    assert_eq!(ep.local_addresses().unwrap(), vec![addr]);
+
+    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    let ep = Endpoint::new(Default::default(), None, socket, TokioRuntime).unwrap();
+    let addrs = ep.local_addresses().unwrap();
+    assert!(addrs.contains(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)));
+    assert!(addrs.iter().all(|x| x.is_ipv4() && x.port() == port));
+
+    // Additional sockets are reported after the primary one
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let added = socket.local_addr().unwrap();
+    ep.add_socket(socket).unwrap();
+    let addrs = ep.local_addresses().unwrap();
+    assert_eq!(addrs.last(), Some(&added));
+    assert!(addrs[..addrs.len() - 1].iter().all(|x| x.port() == port));
+}
+
 #[test]
 fn read_after_close() {
     let _guard = subscribe();
@@ -208,18 +653,913 @@ async fn accept_after_close() {
         .await
         .expect("endpoint")
         .await
-        .expect("connection");
+        .expect("connection");
+
+    // ...and read what was sent.
+    let stream = receiver.accept_uni().await.expect("incoming streams");
+    let msg = stream
+        .read_to_end(usize::max_value())
+        .await
+        .expect("read_to_end");
+    assert_eq!(msg, MSG);
+
+    // But it's still definitely closed.
+    assert!(receiver.open_uni().await.is_err());
+}
+
+#[tokio::test]
+async fn close_stats() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    sender.close(0u32.into(), b"");
+    receiver.closed().await;
+    drop((sender, receiver));
+    endpoint.wait_idle().await;
+
+    let closes = endpoint.stats().closes;
+    assert_eq!(closes.locally_closed, 1);
+    assert_eq!(closes.peer_application_closed, 1);
+    assert_eq!(closes.timed_out, 0);
+}
+
+#[tokio::test]
+async fn close_stats_timeout() {
+    let _guard = subscribe();
+    let mut transport_config = TransportConfig::default();
+    transport_config.max_idle_timeout(Some(Duration::from_millis(100).try_into().unwrap()));
+    let endpoint = endpoint_with_config(transport_config);
+
+    let (client, server) = connected_pair(&endpoint).await;
+    // Idle timeouts drain the connection at once, without a closing period
+    assert!(matches!(
+        client.closed().await,
+        crate::ConnectionError::TimedOut
+    ));
+    assert!(matches!(
+        server.closed().await,
+        crate::ConnectionError::TimedOut
+    ));
+    drop((client, server));
+    endpoint.wait_idle().await;
+
+    let closes = endpoint.stats().closes;
+    assert_eq!(closes.timed_out, 2);
+    assert_eq!(closes.locally_closed, 0);
+}
+
+#[tokio::test]
+async fn handshake_stats() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let addr = endpoint.local_addr().unwrap();
+
+    let (client, server) = establish(endpoint.connect(addr, "localhost").unwrap(), &endpoint).await;
+    assert_eq!(endpoint.stats().handshakes.in_progress, 0);
+    // Both sides of the connection belong to the endpoint
+    assert_eq!(endpoint.stats().handshakes.established, 2);
+
+    // The certificate isn't valid for this name. The client's alert reaches the server before
+    // 1-RTT keys are available, so it's stripped of its error code.
+    let (client_err, server_err) =
+        tokio::join!(endpoint.connect(addr, "example.com").unwrap(), async {
+            endpoint.accept().await.unwrap().await
+        });
+    client_err.unwrap_err();
+    server_err.unwrap_err();
+
+    drop((client, server));
+    endpoint.wait_idle().await;
+    let handshakes = endpoint.stats().handshakes;
+    assert_eq!(handshakes.succeeded, 2);
+    assert_eq!(handshakes.crypto_error, 1);
+    assert_eq!(handshakes.timed_out, 0);
+    assert_eq!(handshakes.other_failure, 1);
+    assert_eq!(handshakes.in_progress, 0);
+    assert_eq!(handshakes.established, 0);
+}
+
+#[tokio::test]
+async fn read_chunk_timeout() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    let mut s = sender.open_uni().await.unwrap();
+    s.write_all(b"a").await.unwrap();
+    let mut stream = receiver.accept_uni().await.expect("incoming streams");
+    let chunk = stream.read_chunk(usize::MAX, true).await.unwrap().unwrap();
+    assert_eq!(&chunk.bytes[..], b"a");
+
+    // Nothing more has been sent, so the read times out...
+    let deadline = std::time::Instant::now() + Duration::from_millis(50);
+    let res = stream.read_chunk_timeout(usize::MAX, true, deadline).await;
+    assert!(matches!(res, Ok(None)));
+
+    // ...but the stream remains usable
+    s.write_all(b"b").await.unwrap();
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    let chunk = stream
+        .read_chunk_timeout(usize::MAX, true, deadline)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(&chunk.bytes[..], b"b");
+}
+
+#[tokio::test]
+async fn keep_alive_override() {
+    let _guard = subscribe();
+    let mut transport_config = TransportConfig::default();
+    transport_config.max_idle_timeout(Some(Duration::from_millis(200).try_into().unwrap()));
+    let endpoint = endpoint_with_config(transport_config);
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    sender.set_keep_alive_interval(Some(Duration::from_millis(50)));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(sender.close_reason().is_none());
+    assert!(receiver.close_reason().is_none());
+
+    sender.set_keep_alive_interval(None);
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert!(matches!(
+        sender.close_reason(),
+        Some(crate::ConnectionError::TimedOut)
+    ));
+}
+
+#[tokio::test]
+async fn close_connections() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    let closed = endpoint.close_connections(&[sender.handle()], 7u32.into(), b"shed");
+    assert_eq!(closed, 1);
+    assert!(matches!(
+        receiver.closed().await,
+        crate::ConnectionError::ApplicationClosed(ref close) if close.error_code == 7u32.into()
+    ));
+}
+
+#[tokio::test]
+async fn drained() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    sender.close(0u32.into(), b"");
+    assert!(matches!(
+        receiver.closed().await,
+        crate::ConnectionError::ApplicationClosed(_)
+    ));
+    tokio::time::timeout(Duration::from_secs(5), receiver.drained())
+        .await
+        .expect("receiver drained");
+    tokio::time::timeout(Duration::from_secs(5), sender.drained())
+        .await
+        .expect("sender drained");
+}
+
+#[tokio::test]
+async fn blocking_socket() {
+    let _guard = subscribe();
+    let server = endpoint();
+
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    socket.set_nonblocking(false).unwrap();
+    let client = Endpoint::new(Default::default(), None, socket, TokioRuntime).unwrap();
+    let config = server.default_client_config.clone().unwrap();
+
+    let connecting = client
+        .connect_with(config, server.local_addr().unwrap(), "localhost")
+        .unwrap();
+    establish(connecting, &server).await;
+}
+
+#[tokio::test]
+async fn can_reach() {
+    let _guard = subscribe();
+    let endpoint = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    assert!(endpoint.can_reach(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 4433)));
+    assert!(!endpoint.can_reach(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)));
+    assert!(!endpoint.can_reach(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 4433)));
+}
+
+#[tokio::test]
+async fn connected_socket() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let server_addr = server.local_addr().unwrap();
+
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    socket.connect(server_addr).unwrap();
+    let client = Endpoint::new(Default::default(), None, socket, TokioRuntime).unwrap();
+    let config = server.default_client_config.clone().unwrap();
+
+    let elsewhere = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), server_addr.port() + 1);
+    assert!(client.can_reach(server_addr));
+    assert!(!client.can_reach(elsewhere));
+    assert!(matches!(
+        client.connect_with(config.clone(), elsewhere, "localhost"),
+        Err(crate::ConnectError::InvalidRemoteAddress(_))
+    ));
+
+    let connecting = client
+        .connect_with(config, server_addr, "localhost")
+        .unwrap();
+    let (client_conn, server_conn) = establish(connecting, &server).await;
+
+    const MSG: &[u8] = b"hello";
+    let mut stream = server_conn.open_uni().await.unwrap();
+    stream.write_all(MSG).await.unwrap();
+    stream.finish().await.unwrap();
+    let stream = client_conn.accept_uni().await.unwrap();
+    assert_eq!(stream.read_to_end(MSG.len()).await.unwrap(), MSG);
+}
+
+#[tokio::test]
+async fn additional_socket() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let extra_addr = socket.local_addr().unwrap();
+    server.add_socket(socket).unwrap();
+
+    let client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let config = server.default_client_config.clone().unwrap();
+    let connecting = client
+        .connect_with(config, extra_addr, "localhost")
+        .unwrap();
+    let (client_conn, server_conn) = establish(connecting, &server).await;
+
+    // The client discards anything not sent from the address it connected to
+    const MSG: &[u8] = b"hello";
+    let mut stream = server_conn.open_uni().await.unwrap();
+    stream.write_all(MSG).await.unwrap();
+    stream.finish().await.unwrap();
+    let stream = client_conn.accept_uni().await.unwrap();
+    assert_eq!(stream.read_to_end(MSG.len()).await.unwrap(), MSG);
+    assert_ne!(server.local_addr().unwrap(), extra_addr);
+}
+
+/// Stops sending once `blocked` is set, never waking the endpoint again
+#[derive(Debug)]
+struct BlockableSocket {
+    inner: Box<dyn AsyncUdpSocket>,
+    blocked: Arc<AtomicBool>,
+    /// Largest batch of transmits offered while blocked
+    max_blocked_batch: Arc<AtomicUsize>,
+}
+
+impl AsyncUdpSocket for BlockableSocket {
+    fn poll_send(
+        &mut self,
+        state: &udp::UdpState,
+        cx: &mut Context,
+        transmits: &[proto::Transmit],
+    ) -> Poll<Result<usize, io::Error>> {
+        if self.blocked.load(Ordering::Relaxed) {
+            self.max_blocked_batch
+                .fetch_max(transmits.len(), Ordering::Relaxed);
+            return Poll::Pending;
+        }
+        self.inner.poll_send(state, cx, transmits)
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [io::IoSliceMut<'_>],
+        meta: &mut [udp::RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_recv(cx, bufs, meta)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[tokio::test]
+async fn blocked_socket_spares_others() {
+    let _guard = subscribe();
+    // Let the blocked connection queue plenty of transmits without waiting for acknowledgements
+    let mut congestion = crate::congestion::NewRenoConfig::default();
+    congestion.initial_window(16 * 1024 * 1024);
+    let mut transport_config = TransportConfig::default();
+    transport_config
+        .congestion_controller_factory(Arc::new(congestion))
+        .send_window(16 * 1024 * 1024);
+    let (server_config, client_config) = configs(transport_config);
+    let blocked = Arc::new(AtomicBool::new(false));
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let socket = BlockableSocket {
+        inner: TokioRuntime.wrap_udp_socket(socket).unwrap(),
+        blocked: blocked.clone(),
+        max_blocked_batch: Arc::new(AtomicUsize::new(0)),
+    };
+    let server = Endpoint::new_with_abstract_socket(
+        EndpointConfig::default(),
+        Some(server_config),
+        socket,
+        TokioRuntime,
+    )
+    .unwrap();
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let extra_addr = socket.local_addr().unwrap();
+    server.add_socket(socket).unwrap();
+    let mut client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    client.set_default_client_config(client_config);
+
+    let (_client_conn, server_conn) = establish(
+        client
+            .connect(server.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        &server,
+    )
+    .await;
+    blocked.store(true, Ordering::Relaxed);
+    // Fill the primary socket's queue with transmits it will never send
+    let mut stream = server_conn.open_uni().await.unwrap();
+    stream.write_all(&vec![0xAB; 1024 * 1024]).await.unwrap();
+
+    let serve = async {
+        let (client_conn, server_conn) =
+            establish(client.connect(extra_addr, "localhost").unwrap(), &server).await;
+        const MSG: &[u8] = b"hello";
+        let mut stream = server_conn.open_uni().await.unwrap();
+        stream.write_all(MSG).await.unwrap();
+        stream.finish().await.unwrap();
+        let stream = client_conn.accept_uni().await.unwrap();
+        assert_eq!(stream.read_to_end(MSG.len()).await.unwrap(), MSG);
+    };
+    tokio::time::timeout(Duration::from_secs(5), serve)
+        .await
+        .expect("other socket starved");
+}
+
+#[tokio::test]
+async fn blocked_sockets_bound_queued_transmits() {
+    let _guard = subscribe();
+    let mut congestion = crate::congestion::NewRenoConfig::default();
+    congestion.initial_window(16 * 1024 * 1024);
+    let mut transport_config = TransportConfig::default();
+    transport_config
+        .congestion_controller_factory(Arc::new(congestion))
+        .send_window(16 * 1024 * 1024);
+    let (server_config, client_config) = configs(transport_config);
+    let blocked = Arc::new(AtomicBool::new(false));
+    let max_blocked_batch = Arc::new(AtomicUsize::new(0));
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let socket = BlockableSocket {
+        inner: TokioRuntime.wrap_udp_socket(socket).unwrap(),
+        blocked: blocked.clone(),
+        max_blocked_batch: max_blocked_batch.clone(),
+    };
+    let server = Endpoint::new_with_abstract_socket(
+        EndpointConfig::default(),
+        Some(server_config),
+        socket,
+        TokioRuntime,
+    )
+    .unwrap();
+    let mut client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    client.set_default_client_config(client_config);
+
+    let (_client_conn, server_conn) = establish(
+        client
+            .connect(server.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        &server,
+    )
+    .await;
+    blocked.store(true, Ordering::Relaxed);
+    // Produce far more transmits than fit in a batch while the only socket is blocked
+    let mut stream = server_conn.open_uni().await.unwrap();
+    stream.write_all(&vec![0xAB; 1024 * 1024]).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(server.is_send_blocked());
+    // The rest stays with the scheduler rather than piling up in front of the socket
+    assert!(max_blocked_batch.load(Ordering::Relaxed) <= udp::BATCH_SIZE);
+}
+
+#[tokio::test]
+async fn connect_deadline() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    // Nobody will answer the handshake
+    let silent = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let config = endpoint.default_client_config.clone().unwrap();
+
+    let deadline = std::time::Instant::now() + Duration::from_millis(100);
+    let connecting = endpoint
+        .connect_with_deadline(config, silent.local_addr().unwrap(), "localhost", deadline)
+        .unwrap();
+    let result = tokio::time::timeout(Duration::from_secs(5), connecting)
+        .await
+        .expect("deadline ignored");
+    assert!(matches!(result, Err(crate::ConnectionError::TimedOut)));
+    tokio::time::timeout(Duration::from_secs(5), endpoint.wait_idle())
+        .await
+        .expect("connection not cleaned up");
+}
+
+#[tokio::test]
+async fn for_each_connection() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    let mut ids = Vec::new();
+    let visited = endpoint.for_each_connection(|conn| ids.push(conn.stable_id()));
+    assert_eq!(visited, 2);
+    ids.sort_unstable();
+    let mut expected = vec![sender.stable_id(), receiver.stable_id()];
+    expected.sort_unstable();
+    assert_eq!(ids, expected);
+
+    sender.close(0u32.into(), b"");
+    receiver.closed().await;
+    assert_eq!(endpoint.for_each_connection(|_| {}), 0);
+}
+
+#[tokio::test]
+async fn connection_attempt_callback() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let attempts = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let attempts2 = attempts.clone();
+    endpoint.set_connection_attempt_callback(Some(Box::new(move |attempt| {
+        attempts2.lock().unwrap().push(attempt.remote);
+    })));
+
+    let _pair = connected_pair(&endpoint).await;
+    assert_eq!(
+        *attempts.lock().unwrap(),
+        vec![endpoint.local_addr().unwrap()]
+    );
+}
+
+#[tokio::test]
+async fn new_connection_callback() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let created = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let created2 = created.clone();
+    endpoint.set_new_connection_callback(Some(Box::new(move |handle, remote| {
+        created2.lock().unwrap().push((handle, remote));
+    })));
+
+    let connecting = endpoint
+        .connect(endpoint.local_addr().unwrap(), "localhost")
+        .unwrap();
+    let incoming = endpoint.accept().await.unwrap();
+    // Reported before the connection could be accepted
+    assert_eq!(
+        *created.lock().unwrap(),
+        vec![(incoming.handle(), endpoint.local_addr().unwrap())]
+    );
+    let (_sender, _receiver) = tokio::join!(connecting, incoming);
+    assert_eq!(created.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn trace_level() {
+    let output = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let output2 = output.clone();
+    let sub = tracing_subscriber::FmtSubscriber::builder()
+        .with_max_level(tracing::Level::INFO)
+        .with_ansi(false)
+        .with_writer(move || CaptureWriter(output2.clone()))
+        .finish();
+    let _guard = tracing::subscriber::set_default(sub);
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+    let verbose = || {
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        output.contains("TRACE")
+    };
+    let transfer = || async {
+        let mut stream = sender.open_uni().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        stream.finish().await.unwrap();
+        let stream = receiver.accept_uni().await.unwrap();
+        stream.read_to_end(usize::MAX).await.unwrap();
+    };
+    assert!(!verbose());
+
+    sender.set_trace_level(Some(tracing::Level::TRACE));
+    transfer().await;
+    assert!(verbose());
+
+    sender.set_trace_level(None);
+    output.lock().unwrap().clear();
+    transfer().await;
+    assert!(!verbose());
+}
+
+struct CaptureWriter(Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn last_activity() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    let before = receiver.last_activity();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let mut stream = sender.open_uni().await.unwrap();
+    stream.write_all(b"hello").await.unwrap();
+    stream.finish().await.unwrap();
+    assert!(receiver.last_activity() > before);
+}
+
+#[tokio::test]
+async fn timer_stats() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    let stats = endpoint.stats();
+    assert_eq!(stats.armed_timers, 2);
+    assert!(stats.timer_updates >= 2);
+    let timers = endpoint.timer_snapshot();
+    let mut handles = timers.iter().map(|&(handle, _)| handle).collect::<Vec<_>>();
+    handles.sort_unstable();
+    let mut expected = vec![sender.handle(), receiver.handle()];
+    expected.sort_unstable();
+    assert_eq!(handles, expected);
+    assert!(timers[0].1 <= timers[1].1);
+
+    sender.close(0u32.into(), b"");
+    receiver.closed().await;
+    drop((sender, receiver));
+    endpoint.wait_idle().await;
+    assert_eq!(endpoint.stats().armed_timers, 0);
+    assert!(endpoint.timer_snapshot().is_empty());
+}
+
+#[tokio::test]
+async fn max_connections_stat() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    assert_eq!(endpoint.stats().max_connections, 0);
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+    assert_eq!(endpoint.stats().max_connections, 2);
+
+    sender.close(0u32.into(), b"");
+    receiver.closed().await;
+    drop((sender, receiver));
+    endpoint.wait_idle().await;
+    assert_eq!(endpoint.stats().max_connections, 2);
+}
+
+#[tokio::test]
+async fn coalescing_stats() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (_sender, _receiver) = connected_pair(&endpoint).await;
+
+    // The server coalesces its Initial and Handshake packets
+    let stats = endpoint.stats();
+    assert_ne!(stats.sent_datagrams, 0);
+    assert!(stats.sent_packets > stats.sent_datagrams);
+}
+
+#[tokio::test]
+async fn reset_stats() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+    sender.close(0u32.into(), b"");
+    receiver.closed().await;
+    drop((sender, receiver));
+    endpoint.wait_idle().await;
+
+    let stats = endpoint.stats();
+    assert_ne!(stats.sent_datagrams, 0);
+    assert_ne!(stats.recv_datagrams, 0);
+    assert_ne!(stats.timer_updates, 0);
+
+    endpoint.reset_stats();
+    let stats = endpoint.stats();
+    assert_eq!(stats.sent_datagrams, 0);
+    assert_eq!(stats.sent_packets, 0);
+    assert_eq!(stats.recv_datagrams, 0);
+    assert_eq!(stats.timer_updates, 0);
+    assert_eq!(stats.max_connections, 2);
+}
+
+#[tokio::test]
+async fn take_incoming() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    assert!(endpoint.take_incoming().is_empty());
+
+    let connecting = endpoint
+        .connect(endpoint.local_addr().unwrap(), "localhost")
+        .unwrap();
+    let incoming = loop {
+        let incoming = endpoint.take_incoming();
+        if !incoming.is_empty() {
+            break incoming;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    assert_eq!(incoming.len(), 1);
+    for conn in incoming {
+        conn.await
+            .expect("connection")
+            .close(42u32.into(), b"restarting");
+    }
+
+    let client = connecting.await.expect("connect");
+    match client.closed().await {
+        crate::ConnectionError::ApplicationClosed(close) => {
+            assert_eq!(close.error_code, 42u32.into());
+            assert_eq!(&close.reason[..], b"restarting");
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[tokio::test]
+async fn connect_after_close() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    endpoint.close(0u32.into(), b"");
+    assert!(matches!(
+        endpoint.connect(endpoint.local_addr().unwrap(), "localhost"),
+        Err(crate::ConnectError::EndpointStopping)
+    ));
+}
+
+#[tokio::test]
+async fn write_without_nodelay() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    let mut s = sender.open_uni().await.unwrap();
+    s.set_nodelay(false);
+    for i in 0..10u8 {
+        s.write_all(&[i]).await.unwrap();
+    }
+
+    let mut stream = receiver.accept_uni().await.expect("incoming streams");
+    let mut buf = [0; 10];
+    stream.read_exact(&mut buf).await.expect("read_exact");
+    assert_eq!(buf, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[tokio::test]
+async fn flush() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    let mut s = sender.open_uni().await.unwrap();
+    s.set_nodelay(false);
+    s.write_all(b"hello").await.unwrap();
+    sender.flush().await.unwrap();
+    assert_eq!(s.progress().unwrap().sent, 5);
+
+    let mut stream = receiver.accept_uni().await.expect("incoming streams");
+    let mut buf = [0; 5];
+    stream.read_exact(&mut buf).await.expect("read_exact");
+    assert_eq!(&buf, b"hello");
+
+    sender.close(0u32.into(), b"done");
+    assert!(matches!(
+        sender.flush().await,
+        Err(crate::ConnectionError::LocallyClosed)
+    ));
+}
+
+#[tokio::test]
+async fn coalesce_timeout() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    sender.set_coalesce_timeout(Some(Duration::from_secs(10)));
+    let mut s = sender.open_uni().await.unwrap();
+    // Let post-handshake traffic, which would also flush held back data, die down
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    s.write_all(b"hello").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(s.progress().unwrap().sent, 0);
+
+    // Disabling coalescing transmits the held back data
+    sender.set_coalesce_timeout(None);
+    let mut stream = receiver.accept_uni().await.expect("incoming streams");
+    let mut buf = [0; 5];
+    stream.read_exact(&mut buf).await.expect("read_exact");
+    assert_eq!(&buf, b"hello");
+}
+
+#[tokio::test]
+async fn drive_connection_now() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+
+    sender.set_coalesce_timeout(Some(Duration::from_secs(10)));
+    let mut s = sender.open_uni().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    s.write_all(b"hello").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(s.progress().unwrap().sent, 0);
+
+    // Driving the connection transmits the held back data
+    assert!(endpoint.drive_connection_now(sender.handle()));
+    let mut buf = [0; 5];
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let mut stream = receiver.accept_uni().await.expect("incoming streams");
+        stream.read_exact(&mut buf).await.expect("read_exact");
+    })
+    .await
+    .expect("timeout");
+    assert_eq!(&buf, b"hello");
+
+    let handle = sender.handle();
+    drop((sender, receiver, s));
+    endpoint.wait_idle().await;
+    assert!(!endpoint.drive_connection_now(handle));
+}
+
+#[tokio::test]
+async fn rebind_family() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let config = server.default_client_config.clone().unwrap();
+    let v6 = || UdpSocket::bind(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 0)).unwrap();
+    assert_eq!(
+        server.rebind_v4(v6()).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(
+        server.rebind_v6(v6()).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    server.add_socket(v6()).unwrap();
+
+    let client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let connecting = client
+        .connect_with(config.clone(), server.local_addr().unwrap(), "localhost")
+        .unwrap();
+    let (v4_conn, _server_conn) = establish(connecting, &server).await;
+
+    // Replacing the IPv6 socket leaves IPv4 connections alone
+    let socket = v6();
+    let v6_addr = socket.local_addr().unwrap();
+    server.rebind_v6(socket).unwrap();
+    assert_eq!(server.local_addr().unwrap().ip(), Ipv4Addr::LOCALHOST);
+
+    let client = Endpoint::client(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 0)).unwrap();
+    let connecting = client.connect_with(config, v6_addr, "localhost").unwrap();
+    establish(connecting, &server).await;
+    assert!(v4_conn.close_reason().is_none());
+}
+
+#[tokio::test]
+async fn wake_stats() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (sender, receiver) = connected_pair(&endpoint).await;
+    assert!(receiver.wake_stats().packets > 0);
+
+    // Let post-handshake traffic die down
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let before = sender.wake_stats();
+    let mut s = sender.open_uni().await.unwrap();
+    s.write_all(b"hello").await.unwrap();
+    s.finish().await.unwrap();
+    let after = sender.wake_stats();
+    assert!(after.application > before.application);
+
+    assert!(after.last.is_some());
+}
+
+#[cfg(feature = "dangerous-configuration")]
+#[tokio::test]
+async fn connect_with_verifier() {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::SystemTime,
+    };
+
+    use rustls::client::{ServerCertVerified, ServerCertVerifier, ServerName};
+
+    /// Counts the certificates it's asked about, accepting them only if `accept` is set
+    struct Verifier {
+        accept: bool,
+        calls: AtomicUsize,
+    }
+
+    impl ServerCertVerifier for Verifier {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &rustls::Certificate,
+            _intermediates: &[rustls::Certificate],
+            _server_name: &ServerName,
+            _scts: &mut dyn Iterator<Item = &[u8]>,
+            _ocsp_response: &[u8],
+            _now: SystemTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            match self.accept {
+                true => Ok(ServerCertVerified::assertion()),
+                false => Err(rustls::Error::General("rejected".into())),
+            }
+        }
+    }
+
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let addr = endpoint.local_addr().unwrap();
+    let server = endpoint.clone();
+    tokio::spawn(async move {
+        while let Some(conn) = server.accept().await {
+            tokio::spawn(conn);
+        }
+    });
+
+    let reject = Arc::new(Verifier {
+        accept: false,
+        calls: AtomicUsize::new(0),
+    });
+    let result = endpoint
+        .connect_with_verifier(addr, "localhost", reject.clone())
+        .unwrap()
+        .await;
+    assert!(result.is_err());
+    assert_eq!(reject.calls.load(Ordering::Relaxed), 1);
+
+    let accept = Arc::new(Verifier {
+        accept: true,
+        calls: AtomicUsize::new(0),
+    });
+    let conn = endpoint
+        .connect_with_verifier(addr, "localhost", accept.clone())
+        .unwrap()
+        .await
+        .unwrap();
+    assert_eq!(accept.calls.load(Ordering::Relaxed), 1);
+    conn.close(0u32.into(), b"done");
 
-    // ...and read what was sent.
-    let stream = receiver.accept_uni().await.expect("incoming streams");
-    let msg = stream
-        .read_to_end(usize::max_value())
+    // The configuration used for other connections is unaffected
+    endpoint
+        .connect(addr, "localhost")
+        .unwrap()
         .await
-        .expect("read_to_end");
-    assert_eq!(msg, MSG);
+        .unwrap()
+        .close(0u32.into(), b"done");
 
-    // But it's still definitely closed.
-    assert!(receiver.open_uni().await.is_err());
+    // Opaque crypto configurations can't be adjusted
+    let opaque = ClientConfig::new(Arc::new(
+        rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(rustls::RootCertStore::empty())
+            .with_no_client_auth(),
+    ));
+    let endpoint = endpoint.with_default_client_config(opaque);
+    assert!(matches!(
+        endpoint.connect_with_verifier(addr, "localhost", accept),
+        Err(crate::ConnectError::OpaqueCryptoConfig)
+    ));
 }
 
 /// Construct an endpoint suitable for connecting to itself
@@ -228,6 +1568,67 @@ fn endpoint() -> Endpoint {
 }
 
 fn endpoint_with_config(transport_config: TransportConfig) -> Endpoint {
+    let (server_config, client_config) = configs(transport_config);
+    let mut endpoint = Endpoint::server(
+        server_config,
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+    )
+    .unwrap();
+    endpoint.set_default_client_config(client_config);
+
+    endpoint
+}
+
+/// Connect `endpoint` to itself, returning the client's and the server's side of the connection
+async fn connected_pair(endpoint: &Endpoint) -> (Connection, Connection) {
+    let connecting = endpoint
+        .connect(endpoint.local_addr().unwrap(), "localhost")
+        .unwrap();
+    establish(connecting, endpoint).await
+}
+
+/// Complete `connecting` along with the connection `server` accepts for it, returning the
+/// client's and the server's side of the connection
+async fn establish(connecting: Connecting, server: &Endpoint) -> (Connection, Connection) {
+    let (client, server) = tokio::join!(connecting, async {
+        server.accept().await.expect("endpoint").await
+    });
+    (client.expect("connect"), server.expect("accept"))
+}
+
+/// Connect `client` to `server`, returning the client's and the server's side of the connection
+async fn connected_to(client: &Endpoint, server: &Endpoint) -> (Connection, Connection) {
+    let connecting = client
+        .connect(server.local_addr().unwrap(), "localhost")
+        .unwrap();
+    establish(connecting, server).await
+}
+
+/// A client endpoint on a new socket using `config`, which trusts `server`
+fn client_with_config(server: &Endpoint, config: EndpointConfig) -> Endpoint {
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let mut client = Endpoint::new(config, None, socket, TokioRuntime).unwrap();
+    client.set_default_client_config(server.default_client_config.clone().unwrap());
+    client
+}
+
+/// Send `size` bytes over a new unidirectional stream, returning how many `receiver` read
+async fn transfer(sender: &Connection, receiver: &Connection, size: usize) -> usize {
+    let send = async {
+        let mut stream = sender.open_uni().await.unwrap();
+        stream.write_all(&vec![0xAB; size]).await.unwrap();
+        stream.finish().await.unwrap();
+    };
+    let receive = async {
+        let stream = receiver.accept_uni().await.unwrap();
+        stream.read_to_end(size).await.unwrap()
+    };
+    let ((), data) = tokio::join!(send, receive);
+    data.len()
+}
+
+/// Server and client configs sharing `transport_config` and a fresh self-signed certificate
+fn configs(transport_config: TransportConfig) -> (crate::ServerConfig, ClientConfig) {
     let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
     let key = rustls::PrivateKey(cert.serialize_private_key_der());
     let cert = rustls::Certificate(cert.serialize_der().unwrap());
@@ -237,16 +1638,10 @@ fn endpoint_with_config(transport_config: TransportConfig) -> Endpoint {
 
     let mut roots = rustls::RootCertStore::empty();
     roots.add(&cert).unwrap();
-    let mut endpoint = Endpoint::server(
-        server_config,
-        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
-    )
-    .unwrap();
     let mut client_config = ClientConfig::with_root_certificates(roots);
     client_config.transport_config(transport_config);
-    endpoint.set_default_client_config(client_config);
 
-    endpoint
+    (server_config, client_config)
 }
 
 #[tokio::test]
@@ -281,6 +1676,7 @@ async fn zero_rtt() {
         .expect("0-RTT succeeded without keys")
         .await
         .expect("connect");
+    assert!(!connection.was_resumed());
 
     tokio::spawn(async move {
         // Buy time for the driver to process the server's NewSessionTicket
@@ -316,6 +1712,7 @@ async fn zero_rtt() {
         .expect("read_to_end");
     assert_eq!(msg, MSG);
     assert!(zero_rtt.await);
+    assert!(connection.was_resumed());
 
     drop(connection);
 
@@ -655,20 +2052,492 @@ async fn rebind_recv() {
 }
 
 #[tokio::test]
-async fn stream_id_flow_control() {
+async fn rebind_quiet() {
     let _guard = subscribe();
-    let mut cfg = TransportConfig::default();
-    cfg.max_concurrent_uni_streams(1u32.into());
-    let endpoint = endpoint_with_config(cfg);
+    let server = endpoint();
+    let client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let config = server.default_client_config.clone().unwrap();
+
+    let connecting = client
+        .connect_with(config, server.local_addr().unwrap(), "localhost")
+        .unwrap();
+    let (client_conn, server_conn) = establish(connecting, &server).await;
+
+    client
+        .rebind_quiet(UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap())
+        .unwrap();
+
+    // The server learns of the new address from the client's next packet
+    const MSG: &[u8] = b"hello";
+    let mut stream = client_conn.open_uni().await.unwrap();
+    stream.write_all(MSG).await.unwrap();
+    stream.finish().await.unwrap();
+    let stream = server_conn.accept_uni().await.unwrap();
+    assert_eq!(stream.read_to_end(MSG.len()).await.unwrap(), MSG);
+    assert_eq!(server_conn.remote_address(), client.local_addr().unwrap());
+}
+
+#[tokio::test]
+async fn rebind_ping_coalescing() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let config = server.default_client_config.clone().unwrap();
+
+    let connecting = client
+        .connect_with(config, server.local_addr().unwrap(), "localhost")
+        .unwrap();
+    let (client_conn, server_conn) = establish(connecting, &server).await;
+    let pings = || client_conn.stats().frame_tx.ping;
+    let rebind = || {
+        client
+            .rebind(UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap())
+            .unwrap()
+    };
+    rebind();
+    rebind();
+    rebind();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // Only the first rebind pings immediately, though the client may also ping in response to
+    // the server validating its new address
+    let immediate = pings();
+    assert!(immediate > 0);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(pings(), immediate);
+
+    // The deferred pings are sent at once when the interval has passed
+    tokio::time::sleep(crate::REBIND_PING_INTERVAL).await;
+    assert!(pings() > immediate);
+    assert_eq!(server_conn.remote_address(), client.local_addr().unwrap());
+}
+
+#[tokio::test]
+async fn connection_router() {
+    let _guard = subscribe();
+    let router = Arc::new(ConnectionRouter::new());
+    let server = endpoint();
+    server.join_router(router.clone());
+    let server_addr = server.local_addr().unwrap();
+    let client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let config = server.default_client_config.clone().unwrap();
+
+    let (client_conn, server_conn) = establish(
+        client
+            .connect_with(config, server_addr, "localhost")
+            .unwrap(),
+        &server,
+    )
+    .await;
+
+    // Hand the server's address to another endpoint, so it receives the client's datagrams
+    server
+        .rebind(UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap())
+        .unwrap();
+    let other = Endpoint::client(server_addr).unwrap();
+    other.join_router(router);
+
+    const MSG: &[u8] = b"hello";
+    client_conn.send_datagram(MSG.into()).unwrap();
+    assert_eq!(server_conn.read_datagram().await.unwrap(), MSG);
+}
+
+#[tokio::test]
+async fn refresh_udp_state() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    endpoint.refresh_udp_state();
+
+    let (client, server) = connected_pair(&endpoint).await;
+    let mut stream = client.open_uni().await.unwrap();
+    stream.write_all(b"hello").await.unwrap();
+    stream.finish().await.unwrap();
+    let stream = server.accept_uni().await.unwrap();
+    assert_eq!(stream.read_to_end(5).await.unwrap(), b"hello");
+}
+
+#[tokio::test]
+async fn connection_stats_by_handle() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = connected_pair(&endpoint).await;
+
+    let handles = [client.handle(), server.handle()];
+    let stats = endpoint.connection_stats(handles[0]).unwrap();
+    assert!(stats.udp_tx.datagrams > 0);
+    assert!(endpoint.connection_stats(handles[1]).is_some());
+
+    client.close(0u32.into(), b"done");
+    drop(client);
+    server.closed().await;
+    drop(server);
+    endpoint.wait_idle().await;
+    for handle in handles {
+        assert!(endpoint.connection_stats(handle).is_none());
+    }
+}
+
+#[tokio::test]
+async fn stats_snapshot() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = connected_pair(&endpoint).await;
+
+    let snapshot = endpoint.stats_snapshot();
+    assert!(snapshot.endpoint.recv_datagrams > 0);
+    let mut handles = [client.handle(), server.handle()];
+    handles.sort_unstable();
+    let snapshot_handles = snapshot
+        .connections
+        .iter()
+        .map(|x| x.handle)
+        .collect::<Vec<_>>();
+    assert_eq!(snapshot_handles, handles);
+    assert!(snapshot
+        .connections
+        .iter()
+        .all(|x| x.stats.udp_tx.datagrams > 0));
+}
+
+#[tokio::test]
+async fn replace_default_client_config() {
+    let _guard = subscribe();
+    let mut endpoint = endpoint();
+    let addr = endpoint.local_addr().unwrap();
+
+    let previous = endpoint.replace_default_client_config(None);
+    assert!(previous.is_some());
+    assert!(matches!(
+        endpoint.connect(addr, "localhost"),
+        Err(crate::ConnectError::NoDefaultClientConfig)
+    ));
+    assert!(endpoint.replace_default_client_config(previous).is_none());
+    assert!(endpoint.connect(addr, "localhost").is_ok());
+}
+
+#[tokio::test]
+#[cfg(target_os = "linux")]
+async fn preferred_local_ip() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    endpoint
+        .rebind(UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)).unwrap())
+        .unwrap();
+    let server_addr = SocketAddr::new(
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        endpoint.local_addr().unwrap().port(),
+    );
+
+    let (client, server) = establish(
+        endpoint.connect(server_addr, "localhost").unwrap(),
+        &endpoint,
+    )
+    .await;
+    assert_eq!(server.remote_address().ip(), Ipv4Addr::LOCALHOST);
+
+    // Any loopback address is local, so the server sees the client migrate to it
+    let preferred = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+    client.set_preferred_local_ip(Some(preferred));
+    let mut send = client.open_uni().await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while server.remote_address().ip() != preferred {
+            send.write_all(b"hello").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("client migrated");
+}
+
+#[tokio::test]
+async fn replace_server_config() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let previous = endpoint.replace_server_config(None).expect("server config");
+    assert!(endpoint.replace_server_config(Some(previous)).is_none());
+
+    connected_pair(&endpoint).await;
+}
+
+#[tokio::test]
+async fn read_unordered() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = connected_pair(&endpoint).await;
+
+    let data = (0..64 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+    let mut send = client.open_uni().await.unwrap();
+    send.write_all(&data).await.unwrap();
+    send.finish().await.unwrap();
+
+    let mut recv = server.accept_uni().await.unwrap();
+    let mut received = vec![0; data.len()];
+    let mut total = 0;
+    while let Some((bytes, offset)) = recv.read_unordered().await.unwrap() {
+        let offset = offset as usize;
+        received[offset..offset + bytes.len()].copy_from_slice(&bytes);
+        total += bytes.len();
+    }
+    assert_eq!(total, data.len());
+    assert_eq!(received, data);
+}
+
+#[tokio::test]
+async fn cwnd_samples() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = connected_pair(&endpoint).await;
+    assert!(client.cwnd_samples().is_empty());
+
+    client.set_cwnd_sampling(8);
+    let mut send = client.open_uni().await.unwrap();
+    send.write_all(&[0xAB; 256 * 1024]).await.unwrap();
+    send.finish().await.unwrap();
+    let recv = server.accept_uni().await.unwrap();
+    recv.read_to_end(usize::MAX).await.unwrap();
+
+    let samples = client.cwnd_samples();
+    assert_eq!(samples.len(), 8);
+    assert!(samples
+        .windows(2)
+        .all(|x| x[0].time <= x[1].time && x[0] != x[1]));
+
+    // Shrinking keeps the newest samples
+    client.set_cwnd_sampling(2);
+    let recent = client.cwnd_samples();
+    assert_eq!(recent.len(), 2);
+    assert!(recent[0].time >= samples[6].time);
+    client.set_cwnd_sampling(0);
+    assert!(client.cwnd_samples().is_empty());
+}
+
+#[tokio::test]
+async fn byte_quota() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = connected_pair(&endpoint).await;
+    assert_eq!(client.remaining_byte_quota(), None);
+
+    client.set_byte_quota(Some(crate::ByteQuota {
+        send: Some(client.bytes_sent() + 16 * 1024),
+        error_code: 42u32.into(),
+        ..Default::default()
+    }));
+    let remaining = client.remaining_byte_quota().unwrap();
+    assert_eq!(remaining.send, Some(16 * 1024));
+    assert_eq!(remaining.total, None);
+
+    let mut stream = client.open_uni().await.unwrap();
+    // Exceeding the quota closes the connection, failing the write
+    let _ = stream.write_all(&[0xab; 64 * 1024]).await;
+    assert!(matches!(
+        client.closed().await,
+        crate::ConnectionError::LocallyClosed
+    ));
+    assert!(matches!(
+        server.closed().await,
+        crate::ConnectionError::ApplicationClosed(ref close) if close.error_code == 42u32.into()
+    ));
+    assert_eq!(client.remaining_byte_quota().unwrap().send, Some(0));
+}
+
+#[tokio::test]
+async fn send_datagram_wait() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = connected_pair(&endpoint).await;
+
+    const MSG: &[u8] = b"datagram";
+    client.send_datagram_wait(MSG.into()).await.unwrap();
+    assert_eq!(server.read_datagram().await.unwrap(), MSG);
+
+    // Waits for the maximum size to grow, which it won't here, until the connection is lost
+    let oversized = Bytes::from(vec![0; client.max_datagram_size().unwrap() + 1]);
+    let (result, ()) = tokio::join!(client.send_datagram_wait(oversized), async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.close(0u32.into(), b"done");
+    });
+    assert!(matches!(result, Err(SendDatagramError::ConnectionLost(_))));
+}
+
+#[tokio::test]
+async fn handshake_info() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
 
     let (client, server) = tokio::join!(
         endpoint
             .connect(endpoint.local_addr().unwrap(), "localhost")
-            .unwrap(),
-        async { endpoint.accept().await.unwrap().await }
+            .unwrap()
+            .handshake(),
+        async { endpoint.accept().await.unwrap().handshake().await }
+    );
+    let (client, client_info) = client.expect("connect");
+    let (_server, server_info) = server.expect("accept");
+
+    assert_eq!(client_info.version, client.version());
+    assert!(client_info.rtt > Duration::ZERO);
+    let handshake_data = client_info
+        .handshake_data
+        .unwrap()
+        .downcast::<crate::crypto::rustls::HandshakeData>()
+        .unwrap();
+    assert!(handshake_data.cipher_suite.is_some());
+    assert!(client_info.peer_identity.is_some());
+    // The server doesn't authenticate clients
+    assert!(server_info.peer_identity.is_none());
+}
+
+#[tokio::test]
+async fn handshake_confirmed() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap()
+            .confirmed(),
+        async { endpoint.accept().await.unwrap().confirmed().await }
     );
-    let client = client.unwrap();
-    let server = server.unwrap();
+    let client = client.expect("connect");
+    let server = server.expect("accept");
+    assert!(client.handshake_confirmed());
+    assert!(server.handshake_confirmed());
+}
+
+#[tokio::test]
+async fn byte_counts() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = connected_pair(&endpoint).await;
+    assert!(client.bytes_sent() > 0);
+    assert!(client.bytes_received() > 0);
+
+    const MSG: &[u8] = &[0xab; 4096];
+    let sent_before = client.bytes_sent();
+    let mut s = client.open_uni().await.unwrap();
+    s.write_all(MSG).await.unwrap();
+    s.finish().await.unwrap();
+    let r = server.accept_uni().await.unwrap();
+    assert_eq!(r.read_to_end(usize::MAX).await.unwrap(), MSG);
+    assert!(client.bytes_sent() >= sent_before + MSG.len() as u64);
+    assert!(server.bytes_received() >= MSG.len() as u64);
+}
+
+#[tokio::test]
+async fn wait_for_connection() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let config = server.default_client_config.clone().unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    let waiter = tokio::spawn({
+        let server = server.clone();
+        async move { server.wait_for_connection().await }
+    });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(!waiter.is_finished());
+
+    let connecting = client
+        .connect_with(config, server_addr, "localhost")
+        .unwrap();
+    tokio::time::timeout(Duration::from_secs(5), waiter)
+        .await
+        .expect("wait_for_connection")
+        .unwrap();
+    // Resolves immediately once a connection exists
+    server.wait_for_connection().await;
+    let _incoming = server.accept().await.unwrap();
+    drop(connecting);
+}
+
+#[tokio::test]
+async fn wait_until_connections_below() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let mut conns = Vec::new();
+    for _ in 0..2 {
+        let (client, server) = connected_pair(&endpoint).await;
+        conns.push((client, server));
+    }
+    // Each connection is counted on both sides
+    endpoint.wait_until_connections_below(5).await;
+    assert!(tokio::time::timeout(
+        Duration::from_millis(50),
+        endpoint.wait_until_connections_below(4)
+    )
+    .await
+    .is_err());
+
+    let (client, _server) = conns.pop().unwrap();
+    client.close(0u32.into(), b"");
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        endpoint.wait_until_connections_below(3),
+    )
+    .await
+    .expect("wait_until_connections_below");
+}
+
+#[tokio::test]
+async fn close_by_priority() {
+    let _guard = subscribe();
+    let server = endpoint();
+    let client = Endpoint::client(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let config = server.default_client_config.clone().unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    let mut conns = Vec::new();
+    for priority in [0, 1] {
+        let (client_conn, server_conn) = establish(
+            client
+                .connect_with(config.clone(), server_addr, "localhost")
+                .unwrap(),
+            &server,
+        )
+        .await;
+        server_conn.set_transmit_priority(priority);
+        conns.push((client_conn, server_conn));
+    }
+
+    let closing = tokio::spawn({
+        let server = server.clone();
+        async move {
+            server
+                .close_by_priority(
+                    0u32.into(),
+                    b"shutdown",
+                    crate::PriorityOrder::HighestFirst,
+                    Some(Duration::from_millis(500)),
+                )
+                .await
+        }
+    });
+    // The higher-priority connection is closed first, while the other one lingers
+    conns[1].0.closed().await;
+    assert!(conns[0].0.close_reason().is_none());
+    closing.await.unwrap();
+    conns[0].0.closed().await;
+    assert!(server.accept().await.is_none());
+}
+
+#[tokio::test]
+async fn stream_id_flow_control() {
+    let _guard = subscribe();
+    let mut cfg = TransportConfig::default();
+    cfg.max_concurrent_uni_streams(1u32.into());
+    let endpoint = endpoint_with_config(cfg);
+
+    let (client, server) = connected_pair(&endpoint).await;
 
     // If `open_uni` doesn't get unblocked when the previous stream is dropped, this will time out.
     tokio::join!(
@@ -687,3 +2556,62 @@ async fn stream_id_flow_control() {
         }
     );
 }
+
+#[tokio::test]
+async fn recv_stream_reset_code() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = connected_pair(&endpoint).await;
+
+    let mut send = client.open_uni().await.unwrap();
+    send.write_all(b"a").await.unwrap();
+    let mut recv = server.accept_uni().await.unwrap();
+    let mut buf = [0; 1];
+    recv.read_exact(&mut buf).await.unwrap();
+    assert_eq!(recv.reset_code(), None);
+
+    // A pending read is woken by the reset
+    let (read, ()) = tokio::join!(recv.read(&mut buf), async {
+        send.reset(7u32.into()).unwrap();
+    });
+    assert_eq!(read, Err(crate::ReadError::Reset(7u32.into())));
+    assert_eq!(recv.reset_code(), Some(7u32.into()));
+}
+
+#[tokio::test]
+async fn stream_initiator() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = connected_pair(&endpoint).await;
+
+    let (mut send, client_recv) = client.open_bi().await.unwrap();
+    send.write_all(b"a").await.unwrap();
+    let (server_send, recv) = server.accept_bi().await.unwrap();
+    assert_eq!(send.id(), recv.id());
+    assert!(send.id().is_client_initiated() && send.id().is_bidi());
+    assert!(send.is_locally_initiated() && client_recv.is_locally_initiated());
+    assert!(!recv.is_locally_initiated() && !server_send.is_locally_initiated());
+
+    let send = server.open_uni().await.unwrap();
+    assert!(send.id().is_server_initiated() && send.id().is_uni());
+    assert!(send.is_locally_initiated());
+}
+
+#[tokio::test]
+async fn uptime_and_duration() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let addr = endpoint.local_addr().unwrap();
+    let connecting = endpoint.connect(addr, "localhost").unwrap();
+    let (client, server) = establish(connecting, &endpoint).await;
+
+    let uptime = endpoint.uptime();
+    let duration = client.duration();
+    assert!(duration <= uptime);
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(endpoint.uptime() >= uptime + Duration::from_millis(10));
+    assert!(client.duration() >= duration + Duration::from_millis(10));
+    assert!(server.duration() >= Duration::from_millis(10));
+}
@@ -0,0 +1,76 @@
+use tracing::{
+    level_filters::LevelFilter,
+    span,
+    subscriber::{Interest, Subscriber},
+    Dispatch, Event, Level, Metadata,
+};
+
+/// Forwards to another subscriber, additionally enabling everything up to a certain level
+///
+/// Installed as the default subscriber while driving a connection with
+/// [`Connection::set_trace_level()`](crate::Connection::set_trace_level), so that its events are
+/// recorded by the subscriber the application configured even when they're more verbose than that
+/// subscriber's own filter allows.
+#[derive(Debug)]
+pub(crate) struct LevelOverride {
+    inner: Dispatch,
+    level: Level,
+}
+
+impl LevelOverride {
+    pub(crate) fn new(inner: Dispatch, level: Level) -> Self {
+        Self { inner, level }
+    }
+}
+
+impl Subscriber for LevelOverride {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        let interest = self.inner.register_callsite(metadata);
+        if *metadata.level() <= self.level && !interest.is_always() {
+            // Whether the callsite is enabled depends on which connection is being driven
+            return Interest::sometimes();
+        }
+        interest
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        // The inner subscriber's hint isn't exposed by `Dispatch`
+        None
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.level || self.inner.enabled(metadata)
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        self.inner.new_span(span)
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        self.inner.record(span, values)
+    }
+
+    fn record_follows_from(&self, span: &span::Id, follows: &span::Id) {
+        self.inner.record_follows_from(span, follows)
+    }
+
+    fn event(&self, event: &Event<'_>) {
+        self.inner.event(event)
+    }
+
+    fn enter(&self, span: &span::Id) {
+        self.inner.enter(span)
+    }
+
+    fn exit(&self, span: &span::Id) {
+        self.inner.exit(span)
+    }
+
+    fn clone_span(&self, id: &span::Id) -> span::Id {
+        self.inner.clone_span(id)
+    }
+
+    fn try_close(&self, id: span::Id) -> bool {
+        self.inner.try_close(id)
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::VecDeque;
+
+use proto::{ConnectionHandle, Transmit, TransmitScheduling};
+use rustc_hash::FxHashMap;
+
+/// Datagrams produced by an endpoint's connections which are waiting to be sent
+///
+/// Determines the order in which connections get to use the socket according to a
+/// [`TransmitScheduling`] strategy.
+#[derive(Debug)]
+pub(crate) struct TransmitQueue {
+    strategy: TransmitScheduling,
+    /// All pending transmits in arrival order, when using `TransmitScheduling::Fifo`
+    fifo: VecDeque<(ConnectionHandle, Transmit)>,
+    /// Per-connection state for the other strategies
+    connections: FxHashMap<ConnectionHandle, ConnectionQueue>,
+    /// Connections with pending transmits, in the order they'll be served
+    active: VecDeque<ConnectionHandle>,
+}
+
+impl TransmitQueue {
+    pub(crate) fn new(strategy: TransmitScheduling) -> Self {
+        Self {
+            strategy,
+            fifo: VecDeque::new(),
+            connections: FxHashMap::default(),
+            active: VecDeque::new(),
+        }
+    }
+
+    /// Queue a datagram produced by the connection `ch`
+    pub(crate) fn push(&mut self, ch: ConnectionHandle, transmit: Transmit) {
+        if self.strategy == TransmitScheduling::Fifo {
+            self.fifo.push_back((ch, transmit));
+            return;
+        }
+        let conn = self.connections.entry(ch).or_default();
+        if conn.transmits.is_empty() {
+            conn.credit = conn.weight;
+            self.active.push_back(ch);
+        }
+        conn.transmits.push_back(transmit);
+    }
+
+    /// Take the datagram which should be sent next, along with the connection which produced it
+    ///
+    /// Connections for which `eligible` returns `false` are passed over, keeping their place.
+    pub(crate) fn pop(
+        &mut self,
+        mut eligible: impl FnMut(ConnectionHandle) -> bool,
+    ) -> Option<(ConnectionHandle, Transmit)> {
+        if self.strategy == TransmitScheduling::Fifo {
+            let index = self.fifo.iter().position(|&(ch, _)| eligible(ch))?;
+            return self.fifo.remove(index);
+        }
+        let mut candidates = self
+            .active
+            .iter()
+            .enumerate()
+            .filter(|&(_, &ch)| eligible(ch));
+        let (index, &ch) = match self.strategy {
+            TransmitScheduling::StrictPriority => {
+                // Prefer the connection which has been waiting longest among those of the highest
+                // priority
+                let connections = &self.connections;
+                candidates
+                    .rev()
+                    .max_by_key(|(_, ch)| connections[ch].priority)?
+            }
+            _ => candidates.next()?,
+        };
+        let conn = self.connections.get_mut(&ch).unwrap();
+        let transmit = conn.transmits.pop_front().unwrap();
+        conn.credit = conn.credit.saturating_sub(1);
+        if conn.transmits.is_empty() {
+            self.active.remove(index);
+            if conn.forgotten {
+                self.connections.remove(&ch);
+            }
+        } else if self.strategy != TransmitScheduling::WeightedFair || conn.credit == 0 {
+            // Give the next connection a turn
+            conn.credit = conn.weight;
+            self.active.remove(index);
+            self.active.push_back(ch);
+        }
+        Some((ch, transmit))
+    }
+
+    /// Set the scheduling parameters of the connection `ch`
+    pub(crate) fn configure(&mut self, ch: ConnectionHandle, weight: u32, priority: i32) {
+        if self.strategy == TransmitScheduling::Fifo {
+            return;
+        }
+        let conn = self.connections.entry(ch).or_default();
+        conn.weight = weight.max(1);
+        conn.priority = priority;
+        conn.forgotten = false;
+    }
+
+    /// Discard the state of the connection `ch` once its pending transmits have been sent
+    pub(crate) fn forget(&mut self, ch: ConnectionHandle) {
+        if let Some(conn) = self.connections.get_mut(&ch) {
+            if conn.transmits.is_empty() {
+                self.connections.remove(&ch);
+            } else {
+                // The handle may be reused before the remaining transmits are sent
+                conn.weight = 1;
+                conn.priority = 0;
+                conn.forgotten = true;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ConnectionQueue {
+    transmits: VecDeque<Transmit>,
+    /// Number of transmits taken per turn under `TransmitScheduling::WeightedFair`
+    weight: u32,
+    /// Number of transmits remaining in the current turn
+    credit: u32,
+    priority: i32,
+    /// Whether the connection has been drained, so this state should be discarded once empty
+    forgotten: bool,
+}
+
+impl Default for ConnectionQueue {
+    fn default() -> Self {
+        Self {
+            transmits: VecDeque::new(),
+            weight: 1,
+            credit: 1,
+            priority: 0,
+            forgotten: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    fn transmit(tag: u8) -> Transmit {
+        Transmit {
+            destination: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 4433),
+            ecn: None,
+            contents: vec![tag],
+            segment_size: None,
+            src_ip: None,
+            metadata: None,
+            priority: None,
+        }
+    }
+
+    /// Queue `count` transmits for each of `chs`, all from the first connection first
+    fn fill(queue: &mut TransmitQueue, chs: &[ConnectionHandle], count: usize) {
+        for &ch in chs {
+            for _ in 0..count {
+                queue.push(ch, transmit(ch.0 as u8));
+            }
+        }
+    }
+
+    fn drain(queue: &mut TransmitQueue) -> Vec<u8> {
+        std::iter::from_fn(|| queue.pop(|_| true))
+            .map(|(ch, t)| {
+                assert_eq!(ch.0 as u8, t.contents[0]);
+                t.contents[0]
+            })
+            .collect()
+    }
+
+    const CHS: [ConnectionHandle; 3] = [
+        ConnectionHandle(0),
+        ConnectionHandle(1),
+        ConnectionHandle(2),
+    ];
+
+    #[test]
+    fn fifo() {
+        let mut queue = TransmitQueue::new(TransmitScheduling::Fifo);
+        fill(&mut queue, &CHS, 2);
+        assert_eq!(drain(&mut queue), [0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut queue = TransmitQueue::new(TransmitScheduling::RoundRobin);
+        // A busy connection doesn't delay the others
+        fill(&mut queue, &CHS[..1], 4);
+        fill(&mut queue, &CHS[1..], 1);
+        assert_eq!(drain(&mut queue), [0, 1, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn weighted_fair() {
+        let mut queue = TransmitQueue::new(TransmitScheduling::WeightedFair);
+        queue.configure(CHS[0], 3, 0);
+        fill(&mut queue, &CHS[..2], 6);
+        assert_eq!(drain(&mut queue), [0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn strict_priority() {
+        let mut queue = TransmitQueue::new(TransmitScheduling::StrictPriority);
+        queue.configure(CHS[2], 1, 1);
+        fill(&mut queue, &CHS, 2);
+        assert_eq!(drain(&mut queue), [2, 2, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn forget() {
+        let mut queue = TransmitQueue::new(TransmitScheduling::RoundRobin);
+        fill(&mut queue, &CHS[..1], 2);
+        // Pending transmits are still sent after the connection is drained
+        queue.forget(CHS[0]);
+        assert_eq!(drain(&mut queue), [0, 0]);
+        assert!(queue.connections.is_empty());
+    }
+
+    #[test]
+    fn ineligible() {
+        for &(strategy, rest) in &[
+            (TransmitScheduling::Fifo, [0, 0, 1]),
+            (TransmitScheduling::RoundRobin, [0, 1, 0]),
+            (TransmitScheduling::StrictPriority, [0, 0, 1]),
+        ] {
+            let mut queue = TransmitQueue::new(strategy);
+            queue.configure(CHS[0], 1, 1);
+            fill(&mut queue, &CHS[..2], 2);
+            // Passing over a connection doesn't cost it its place
+            let (ch, _) = queue.pop(|ch| ch != CHS[0]).unwrap();
+            assert_eq!(ch, CHS[1]);
+            assert_eq!(drain(&mut queue), rest);
+        }
+    }
+}